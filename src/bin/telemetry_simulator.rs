@@ -0,0 +1,183 @@
+//! Posts a generated GPS track for one vehicle to the telemetry ingest endpoint.
+//!
+//!     cargo run --bin telemetry_simulator -- --car <car_id> [--points 120] [--interval-secs 5]
+//!         [--batch 10] [--start 14.5995,120.9842] [--seed 42] [--backfill] [--url http://localhost:5000]
+//!
+//! The tracker key comes from TELEMETRY_INGEST_KEY (or --key). With --backfill the track ends now
+//! and is sent as fast as possible; otherwise points are sent in real time.
+
+use std::{env, time::Duration};
+
+use chrono::{DateTime, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::json;
+
+struct Options {
+    url: String,
+    key: String,
+    car_id: String,
+    points: usize,
+    interval_secs: i64,
+    batch: usize,
+    start: (f64, f64),
+    seed: u64,
+    backfill: bool,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: telemetry_simulator --car <car_id> [--points N] [--interval-secs S] [--batch N] \
+         [--start LAT,LON] [--seed N] [--backfill] [--url URL] [--key KEY]"
+    );
+    std::process::exit(2);
+}
+
+fn parse_options() -> Options {
+    let mut options = Options {
+        url: env::var("SIMULATOR_API_URL").unwrap_or_else(|_| "http://localhost:5000".to_string()),
+        key: env::var("TELEMETRY_INGEST_KEY").unwrap_or_default(),
+        car_id: String::new(),
+        points: 120,
+        interval_secs: 5,
+        batch: 10,
+        start: (14.5995, 120.9842),
+        seed: rand::random(),
+        backfill: false,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--url" => options.url = value(),
+            "--key" => options.key = value(),
+            "--car" => options.car_id = value(),
+            "--points" => options.points = value().parse().unwrap_or_else(|_| usage()),
+            "--interval-secs" => options.interval_secs = value().parse().unwrap_or_else(|_| usage()),
+            "--batch" => options.batch = value().parse().unwrap_or_else(|_| usage()),
+            "--seed" => options.seed = value().parse().unwrap_or_else(|_| usage()),
+            "--start" => {
+                let start = value();
+                let (lat, lon) = start.split_once(',').unwrap_or_else(|| usage());
+                options.start = (
+                    lat.trim().parse().unwrap_or_else(|_| usage()),
+                    lon.trim().parse().unwrap_or_else(|_| usage()),
+                );
+            }
+            "--backfill" => options.backfill = true,
+            _ => usage(),
+        }
+    }
+
+    if options.car_id.is_empty() || options.points == 0 || options.batch == 0 || options.interval_secs <= 0 {
+        usage();
+    }
+    options
+}
+
+/// A drive through town: speed drifts between stops, heading wanders like a street grid.
+struct Vehicle {
+    lat: f64,
+    lon: f64,
+    speed_kph: f64,
+    heading: f64,
+    stopped_for: i64,
+}
+
+impl Vehicle {
+    fn step(&mut self, rng: &mut StdRng, secs: i64) {
+        if self.stopped_for > 0 {
+            self.stopped_for -= secs;
+            self.speed_kph = 0.0;
+            return;
+        }
+        if rng.gen_bool(0.03) {
+            // Traffic light or checkpoint
+            self.stopped_for = rng.gen_range(20..120);
+            self.speed_kph = 0.0;
+            return;
+        }
+
+        self.speed_kph = (self.speed_kph + rng.gen_range(-8.0..8.0)).clamp(15.0, 70.0);
+        if rng.gen_bool(0.08) {
+            self.heading += if rng.gen_bool(0.5) { 90.0 } else { -90.0 };
+        }
+        self.heading = (self.heading + rng.gen_range(-5.0..5.0)).rem_euclid(360.0);
+
+        let km = self.speed_kph * secs as f64 / 3600.0;
+        let heading = self.heading.to_radians();
+        self.lat += km * heading.cos() / 111.32;
+        self.lon += km * heading.sin() / (111.32 * self.lat.to_radians().cos());
+    }
+
+    fn point(&self, recorded_at: DateTime<Utc>) -> serde_json::Value {
+        json!({
+            "lat": self.lat,
+            "lon": self.lon,
+            "speedKph": (self.speed_kph * 10.0).round() / 10.0,
+            "heading": (self.heading * 10.0).round() / 10.0,
+            "recordedAt": recorded_at,
+        })
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv::dotenv().ok();
+    let options = parse_options();
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let client = reqwest::Client::new();
+    let endpoint = format!("{}/api/telemetry/ingest", options.url.trim_end_matches('/'));
+
+    let mut vehicle = Vehicle {
+        lat: options.start.0,
+        lon: options.start.1,
+        speed_kph: 30.0,
+        heading: rng.gen_range(0.0..360.0),
+        stopped_for: 0,
+    };
+    let interval = chrono::Duration::seconds(options.interval_secs);
+    let mut recorded_at = if options.backfill {
+        Utc::now() - interval * options.points as i32
+    } else {
+        Utc::now()
+    };
+
+    println!(
+        "Simulating {} points for car {} (seed {}) -> {}",
+        options.points, options.car_id, options.seed, endpoint
+    );
+
+    let mut sent = 0;
+    while sent < options.points {
+        let size = options.batch.min(options.points - sent);
+        let mut points = Vec::with_capacity(size);
+        for _ in 0..size {
+            points.push(vehicle.point(recorded_at));
+            vehicle.step(&mut rng, options.interval_secs);
+            recorded_at += interval;
+        }
+
+        if !options.backfill {
+            // Real time: a tracker uploads a batch once its last point has been recorded
+            tokio::time::sleep(Duration::from_secs(options.interval_secs as u64 * size as u64)).await;
+        }
+
+        let response = client
+            .post(&endpoint)
+            .header("X-Tracker-Key", &options.key)
+            .json(&json!({ "carId": options.car_id, "points": points }))
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(format!("ingest failed with {}: {}", status, body).into());
+        }
+        sent += size;
+        println!("sent {}/{}: {}", sent, options.points, body);
+    }
+
+    println!("Done; last position {:.5},{:.5}", vehicle.lat, vehicle.lon);
+    Ok(())
+}
@@ -7,6 +7,8 @@ pub struct Config {
     pub gmail_user: String,
     pub gmail_password: String,
     pub admin_code: String,
+    pub scheduler_enabled: bool,
+    pub scheduler_tick_secs: u64,
 }
 
 impl Config {
@@ -28,6 +30,15 @@ impl Config {
             gmail_user: env::var("GMAIL_USER").unwrap_or_else(|_| "no-reply@example.com".to_string()),
             gmail_password: env::var("GMAIL_PASSWORD").unwrap_or_else(|_| "dummy-password".to_string()),
             admin_code: env::var("ADMIN_CODE").unwrap_or_else(|_| "122601".to_string()),
+            // Every instance may run the scheduler; the per-job lock in `scheduled_jobs`
+            // ensures only one of them executes a given job at a time.
+            scheduler_enabled: env::var("SCHEDULER_ENABLED")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            scheduler_tick_secs: env::var("SCHEDULER_TICK_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
         })
     }
 }
@@ -331,6 +331,18 @@ pub async fn run_migrations(pool: &PgPool) -> AppResult<()> {
         "ALTER TABLE trips ADD COLUMN IF NOT EXISTS destination VARCHAR(500)",
         "ALTER TABLE trips ALTER COLUMN start_location DROP NOT NULL",
         "ALTER TABLE armored_cars ADD COLUMN IF NOT EXISTS passenger_capacity INTEGER DEFAULT 4",
+        "ALTER TABLE shifts ADD COLUMN IF NOT EXISTS grace_period_minutes INTEGER DEFAULT 15",
+        "ALTER TABLE shifts ADD COLUMN IF NOT EXISTS replacement_status VARCHAR(50) DEFAULT 'not_needed'",
+        "ALTER TABLE firearm_allocations ADD COLUMN IF NOT EXISTS expected_return_date TIMESTAMP WITH TIME ZONE",
+        "ALTER TABLE firearm_allocations ADD COLUMN IF NOT EXISTS notes TEXT",
+        "ALTER TABLE firearm_allocations ADD COLUMN IF NOT EXISTS issued_by VARCHAR(36)",
+        "ALTER TABLE firearm_allocations ADD COLUMN IF NOT EXISTS overdue_notified_at TIMESTAMP WITH TIME ZONE",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS home_latitude DOUBLE PRECISION",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS home_longitude DOUBLE PRECISION",
+        "ALTER TABLE shifts ADD COLUMN IF NOT EXISTS original_guard_id VARCHAR(36)",
+        "ALTER TABLE shifts ADD COLUMN IF NOT EXISTS replacement_wave INTEGER DEFAULT 0",
+        "ALTER TABLE shifts ADD COLUMN IF NOT EXISTS replacement_escalated_at TIMESTAMP WITH TIME ZONE",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS hourly_rate DOUBLE PRECISION",
     ] {
         sqlx::query(migration)
             .execute(pool)
@@ -495,5 +507,1641 @@ pub async fn run_migrations(pool: &PgPool) -> AppResult<()> {
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to create firearm_maintenance table: {}", e)))?;
 
+    // Create training_courses table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS training_courses (
+            id VARCHAR(36) PRIMARY KEY,
+            code VARCHAR(50) NOT NULL UNIQUE,
+            name VARCHAR(255) NOT NULL,
+            description TEXT,
+            training_type VARCHAR(100) NOT NULL,
+            validity_months INTEGER,
+            duration_hours DOUBLE PRECISION,
+            is_active BOOLEAN NOT NULL DEFAULT true,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create training_courses table: {}", e)))?;
+
+    // Create training_sessions table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS training_sessions (
+            id VARCHAR(36) PRIMARY KEY,
+            course_id VARCHAR(36) NOT NULL,
+            instructor_id VARCHAR(36),
+            instructor_name VARCHAR(255),
+            location VARCHAR(500),
+            start_time TIMESTAMP WITH TIME ZONE NOT NULL,
+            end_time TIMESTAMP WITH TIME ZONE NOT NULL,
+            capacity INTEGER NOT NULL,
+            status VARCHAR(50) NOT NULL DEFAULT 'scheduled',
+            completed_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (course_id) REFERENCES training_courses(id) ON DELETE CASCADE,
+            FOREIGN KEY (instructor_id) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create training_sessions table: {}", e)))?;
+
+    // Create training_enrollments table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS training_enrollments (
+            id VARCHAR(36) PRIMARY KEY,
+            session_id VARCHAR(36) NOT NULL,
+            guard_id VARCHAR(36) NOT NULL,
+            status VARCHAR(50) NOT NULL DEFAULT 'enrolled',
+            waitlist_position INTEGER,
+            source VARCHAR(50) NOT NULL DEFAULT 'manual',
+            attendance_marked_at TIMESTAMP WITH TIME ZONE,
+            training_record_id VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (session_id, guard_id),
+            FOREIGN KEY (session_id) REFERENCES training_sessions(id) ON DELETE CASCADE,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (training_record_id) REFERENCES training_records(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create training_enrollments table: {}", e)))?;
+
+    // Create scheduled_jobs table (one row per background job; the row doubles as its run lock)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS scheduled_jobs (
+            name VARCHAR(100) PRIMARY KEY,
+            description TEXT,
+            interval_seconds INTEGER NOT NULL,
+            enabled BOOLEAN NOT NULL DEFAULT true,
+            next_run_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            locked_by VARCHAR(255),
+            locked_until TIMESTAMP WITH TIME ZONE,
+            last_started_at TIMESTAMP WITH TIME ZONE,
+            last_finished_at TIMESTAMP WITH TIME ZONE,
+            last_status VARCHAR(50),
+            last_result TEXT,
+            last_error TEXT,
+            consecutive_failures INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create scheduled_jobs table: {}", e)))?;
+
+    // Create scheduled_job_runs table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS scheduled_job_runs (
+            id VARCHAR(36) PRIMARY KEY,
+            job_name VARCHAR(100) NOT NULL,
+            trigger VARCHAR(50) NOT NULL DEFAULT 'schedule',
+            instance_id VARCHAR(255) NOT NULL,
+            status VARCHAR(50) NOT NULL DEFAULT 'running',
+            result TEXT,
+            error TEXT,
+            started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            finished_at TIMESTAMP WITH TIME ZONE,
+            duration_ms BIGINT,
+            FOREIGN KEY (job_name) REFERENCES scheduled_jobs(name) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create scheduled_job_runs table: {}", e)))?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_scheduled_job_runs_job_started ON scheduled_job_runs(job_name, started_at DESC)",
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create scheduled_job_runs index: {}", e)))?;
+
+    // Create client_sites table (named sites referenced by shifts.client_site, with coordinates)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS client_sites (
+            id VARCHAR(36) PRIMARY KEY,
+            name VARCHAR(255) NOT NULL UNIQUE,
+            address VARCHAR(500),
+            latitude DOUBLE PRECISION,
+            longitude DOUBLE PRECISION,
+            supervisor_id VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (supervisor_id) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create client_sites table: {}", e)))?;
+
+    // Create replacement_offers table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS replacement_offers (
+            id VARCHAR(36) PRIMARY KEY,
+            shift_id VARCHAR(36) NOT NULL,
+            guard_id VARCHAR(36) NOT NULL,
+            wave INTEGER NOT NULL,
+            rank_in_wave INTEGER NOT NULL,
+            merit_score DOUBLE PRECISION,
+            distance_km DOUBLE PRECISION,
+            status VARCHAR(50) NOT NULL DEFAULT 'pending',
+            notification_id VARCHAR(36),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            responded_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id) ON DELETE CASCADE,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            UNIQUE(shift_id, guard_id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create replacement_offers table: {}", e)))?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_replacement_offers_status_expires ON replacement_offers(status, expires_at)",
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create replacement_offers index: {}", e)))?;
+
+    // Create guard_weekly_availability table (recurring windows in the scheduling timezone;
+    // day_of_week follows Postgres DOW, 0 = Sunday; end_time <= start_time means overnight)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS guard_weekly_availability (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            day_of_week SMALLINT NOT NULL CHECK (day_of_week BETWEEN 0 AND 6),
+            start_time TIME NOT NULL,
+            end_time TIME NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create guard_weekly_availability table: {}", e)))?;
+
+    // Create guard_leave_requests table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS guard_leave_requests (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            leave_type VARCHAR(50) NOT NULL,
+            start_date TIMESTAMP WITH TIME ZONE NOT NULL,
+            end_date TIMESTAMP WITH TIME ZONE NOT NULL,
+            reason TEXT,
+            status VARCHAR(50) NOT NULL DEFAULT 'pending',
+            reviewed_by VARCHAR(36),
+            reviewed_at TIMESTAMP WITH TIME ZONE,
+            review_notes TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (reviewed_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create guard_leave_requests table: {}", e)))?;
+
+    // Create guard_blackout_dates table (one-off unavailability that needs no approval)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS guard_blackout_dates (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            start_date TIMESTAMP WITH TIME ZONE NOT NULL,
+            end_date TIMESTAMP WITH TIME ZONE NOT NULL,
+            reason TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create guard_blackout_dates table: {}", e)))?;
+
+    // Create disciplinary_records table (warnings and suspensions; created here because
+    // guard_is_available() below checks suspensions)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS disciplinary_records (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            action_type VARCHAR(50) NOT NULL,
+            severity VARCHAR(20) NOT NULL,
+            infraction VARCHAR(255) NOT NULL,
+            description TEXT,
+            evidence_urls TEXT[] NOT NULL DEFAULT '{}',
+            incident_id VARCHAR(36),
+            issued_by VARCHAR(36),
+            effective_from TIMESTAMP WITH TIME ZONE NOT NULL,
+            effective_until TIMESTAMP WITH TIME ZONE,
+            appeal_status VARCHAR(20) NOT NULL DEFAULT 'none',
+            appeal_reason TEXT,
+            appealed_at TIMESTAMP WITH TIME ZONE,
+            appeal_reviewed_by VARCHAR(36),
+            appeal_notes TEXT,
+            appeal_resolved_at TIMESTAMP WITH TIME ZONE,
+            lifted_by VARCHAR(36),
+            lifted_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (issued_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create disciplinary_records table: {}", e)))?;
+
+    // guard_is_available(): single source of truth for scheduling, replacement offers and
+    // mission staffing. Checks the availability toggle, approved leave, blackout dates, active
+    // suspensions and, if the guard has any weekly windows, that the whole period fits inside one of them.
+    sqlx::query(
+        r#"
+        CREATE OR REPLACE FUNCTION guard_is_available(
+            p_guard_id VARCHAR, p_start TIMESTAMPTZ, p_end TIMESTAMPTZ, p_tz TEXT
+        ) RETURNS BOOLEAN LANGUAGE sql STABLE AS $$
+            SELECT NOT EXISTS (
+                    SELECT 1 FROM guard_availability ga
+                    WHERE ga.guard_id = p_guard_id AND ga.available = false
+                    AND (ga.available_from IS NULL OR ga.available_from < p_end)
+                    AND (ga.available_to IS NULL OR ga.available_to > p_start)
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM guard_leave_requests lr
+                    WHERE lr.guard_id = p_guard_id AND lr.status = 'approved'
+                    AND lr.start_date < p_end AND lr.end_date > p_start
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM guard_blackout_dates b
+                    WHERE b.guard_id = p_guard_id
+                    AND b.start_date < p_end AND b.end_date > p_start
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM disciplinary_records d
+                    WHERE d.guard_id = p_guard_id AND d.action_type = 'suspension'
+                    AND d.appeal_status <> 'overturned'
+                    AND d.effective_from < p_end
+                    AND (d.effective_until IS NULL OR d.effective_until > p_start)
+                )
+                AND (
+                    NOT EXISTS (SELECT 1 FROM guard_weekly_availability w WHERE w.guard_id = p_guard_id)
+                    OR EXISTS (
+                        SELECT 1 FROM guard_weekly_availability w
+                        WHERE w.guard_id = p_guard_id
+                        AND w.day_of_week = EXTRACT(DOW FROM p_start AT TIME ZONE p_tz)
+                        AND (p_start AT TIME ZONE p_tz)::time >= w.start_time
+                        AND (p_end AT TIME ZONE p_tz) <= (p_start AT TIME ZONE p_tz)::date
+                            + CASE WHEN w.end_time > w.start_time THEN 0 ELSE 1 END
+                            + w.end_time
+                    )
+                    OR EXISTS (
+                        -- tail of the previous day's overnight window
+                        SELECT 1 FROM guard_weekly_availability w
+                        WHERE w.guard_id = p_guard_id
+                        AND w.end_time <= w.start_time
+                        AND w.day_of_week = EXTRACT(DOW FROM (p_start AT TIME ZONE p_tz) - INTERVAL '1 day')
+                        AND (p_end AT TIME ZONE p_tz) <= (p_start AT TIME ZONE p_tz)::date + w.end_time
+                    )
+                )
+        $$
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create guard_is_available function: {}", e)))?;
+
+    // Create shift_swaps table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS shift_swaps (
+            id VARCHAR(36) PRIMARY KEY,
+            shift_id VARCHAR(36) NOT NULL,
+            posted_by VARCHAR(36) NOT NULL,
+            swap_type VARCHAR(50) NOT NULL,
+            status VARCHAR(50) NOT NULL DEFAULT 'open',
+            notes TEXT,
+            claimed_by VARCHAR(36),
+            claim_shift_id VARCHAR(36),
+            claimed_at TIMESTAMP WITH TIME ZONE,
+            reviewed_by VARCHAR(36),
+            reviewed_at TIMESTAMP WITH TIME ZONE,
+            review_notes TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id) ON DELETE CASCADE,
+            FOREIGN KEY (posted_by) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (claimed_by) REFERENCES users(id) ON DELETE SET NULL,
+            FOREIGN KEY (claim_shift_id) REFERENCES shifts(id) ON DELETE SET NULL,
+            FOREIGN KEY (reviewed_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create shift_swaps table: {}", e)))?;
+
+    // Create shift_swap_events table (audit trail of every step in a trade)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS shift_swap_events (
+            id VARCHAR(36) PRIMARY KEY,
+            swap_id VARCHAR(36) NOT NULL,
+            actor_id VARCHAR(36) NOT NULL,
+            action VARCHAR(50) NOT NULL,
+            details TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (swap_id) REFERENCES shift_swaps(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create shift_swap_events table: {}", e)))?;
+
+    // Create pay_rate_rules table (the newest active row is used for timesheets)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS pay_rate_rules (
+            id VARCHAR(36) PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            base_hourly_rate DOUBLE PRECISION NOT NULL,
+            currency VARCHAR(10) NOT NULL DEFAULT 'PHP',
+            regular_hours_per_day DOUBLE PRECISION NOT NULL DEFAULT 8,
+            overtime_multiplier DOUBLE PRECISION NOT NULL DEFAULT 1.25,
+            night_diff_start TIME NOT NULL DEFAULT '22:00',
+            night_diff_end TIME NOT NULL DEFAULT '06:00',
+            night_diff_premium DOUBLE PRECISION NOT NULL DEFAULT 0.10,
+            regular_holiday_multiplier DOUBLE PRECISION NOT NULL DEFAULT 2.0,
+            special_holiday_multiplier DOUBLE PRECISION NOT NULL DEFAULT 1.3,
+            active BOOLEAN NOT NULL DEFAULT true,
+            created_by VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create pay_rate_rules table: {}", e)))?;
+
+    // Seed a default rule so timesheets can be generated before one is configured
+    sqlx::query(
+        r#"
+        INSERT INTO pay_rate_rules (id, name, base_hourly_rate)
+        SELECT 'default-pay-rule', 'Default', 80.0
+        WHERE NOT EXISTS (SELECT 1 FROM pay_rate_rules)
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to seed pay_rate_rules: {}", e)))?;
+
+    // Create holidays table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS holidays (
+            id VARCHAR(36) PRIMARY KEY,
+            holiday_date DATE NOT NULL UNIQUE,
+            name VARCHAR(255) NOT NULL,
+            holiday_type VARCHAR(50) NOT NULL DEFAULT 'regular',
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create holidays table: {}", e)))?;
+
+    // Create timesheets table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS timesheets (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            period_start DATE NOT NULL,
+            period_end DATE NOT NULL,
+            rate_rule_id VARCHAR(36) NOT NULL,
+            hourly_rate DOUBLE PRECISION NOT NULL,
+            total_hours DOUBLE PRECISION NOT NULL DEFAULT 0,
+            regular_hours DOUBLE PRECISION NOT NULL DEFAULT 0,
+            overtime_hours DOUBLE PRECISION NOT NULL DEFAULT 0,
+            night_diff_hours DOUBLE PRECISION NOT NULL DEFAULT 0,
+            holiday_hours DOUBLE PRECISION NOT NULL DEFAULT 0,
+            regular_pay DOUBLE PRECISION NOT NULL DEFAULT 0,
+            overtime_pay DOUBLE PRECISION NOT NULL DEFAULT 0,
+            night_diff_pay DOUBLE PRECISION NOT NULL DEFAULT 0,
+            holiday_pay DOUBLE PRECISION NOT NULL DEFAULT 0,
+            gross_pay DOUBLE PRECISION NOT NULL DEFAULT 0,
+            status VARCHAR(50) NOT NULL DEFAULT 'pending_approval',
+            reviewed_by VARCHAR(36),
+            reviewed_at TIMESTAMP WITH TIME ZONE,
+            review_notes TEXT,
+            generated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (rate_rule_id) REFERENCES pay_rate_rules(id),
+            UNIQUE(guard_id, period_start, period_end)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create timesheets table: {}", e)))?;
+
+    // Create timesheet_entries table (one row per attendance record)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS timesheet_entries (
+            id VARCHAR(36) PRIMARY KEY,
+            timesheet_id VARCHAR(36) NOT NULL,
+            attendance_id VARCHAR(36) NOT NULL,
+            shift_id VARCHAR(36) NOT NULL,
+            work_date DATE NOT NULL,
+            check_in TIMESTAMP NOT NULL,
+            check_out TIMESTAMP NOT NULL,
+            hours DOUBLE PRECISION NOT NULL,
+            regular_hours DOUBLE PRECISION NOT NULL,
+            overtime_hours DOUBLE PRECISION NOT NULL,
+            night_diff_hours DOUBLE PRECISION NOT NULL,
+            holiday_hours DOUBLE PRECISION NOT NULL,
+            holiday_type VARCHAR(50),
+            FOREIGN KEY (timesheet_id) REFERENCES timesheets(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create timesheet_entries table: {}", e)))?;
+
+    // Create clients table (billing entities; car_allocations.client_id refers to clients.id)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS clients (
+            id VARCHAR(36) PRIMARY KEY,
+            name VARCHAR(255) NOT NULL UNIQUE,
+            billing_email VARCHAR(255),
+            billing_address VARCHAR(500),
+            contact_phone VARCHAR(50),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create clients table: {}", e)))?;
+
+    sqlx::query("ALTER TABLE client_sites ADD COLUMN IF NOT EXISTS client_id VARCHAR(36) REFERENCES clients(id) ON DELETE SET NULL")
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to add client_sites.client_id: {}", e)))?;
+
+    // Create client_contracts table (billing rates, minimums and surcharges per client)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS client_contracts (
+            id VARCHAR(36) PRIMARY KEY,
+            client_id VARCHAR(36) NOT NULL,
+            name VARCHAR(255) NOT NULL,
+            currency VARCHAR(10) NOT NULL DEFAULT 'PHP',
+            guard_hourly_rate DOUBLE PRECISION NOT NULL,
+            trip_rate DOUBLE PRECISION NOT NULL DEFAULT 0,
+            per_km_rate DOUBLE PRECISION NOT NULL DEFAULT 0,
+            minimum_hours_per_shift DOUBLE PRECISION NOT NULL DEFAULT 0,
+            minimum_invoice_amount DOUBLE PRECISION NOT NULL DEFAULT 0,
+            night_surcharge_pct DOUBLE PRECISION NOT NULL DEFAULT 0,
+            holiday_surcharge_pct DOUBLE PRECISION NOT NULL DEFAULT 0,
+            payment_terms_days INTEGER NOT NULL DEFAULT 30,
+            start_date DATE NOT NULL,
+            end_date DATE,
+            status VARCHAR(50) NOT NULL DEFAULT 'active',
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (client_id) REFERENCES clients(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create client_contracts table: {}", e)))?;
+
+    // Invoice numbers are only assigned when an invoice is issued, so drafts leave no gaps
+    sqlx::query("CREATE SEQUENCE IF NOT EXISTS invoice_number_seq")
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to create invoice_number_seq: {}", e)))?;
+
+    // Create invoices table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS invoices (
+            id VARCHAR(36) PRIMARY KEY,
+            invoice_number VARCHAR(50) UNIQUE,
+            client_id VARCHAR(36) NOT NULL,
+            contract_id VARCHAR(36) NOT NULL,
+            period_start DATE NOT NULL,
+            period_end DATE NOT NULL,
+            currency VARCHAR(10) NOT NULL,
+            subtotal DOUBLE PRECISION NOT NULL DEFAULT 0,
+            total DOUBLE PRECISION NOT NULL DEFAULT 0,
+            status VARCHAR(50) NOT NULL DEFAULT 'draft',
+            issued_at TIMESTAMP WITH TIME ZONE,
+            due_date DATE,
+            paid_at TIMESTAMP WITH TIME ZONE,
+            payment_reference VARCHAR(255),
+            notes VARCHAR(1000),
+            created_by VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (client_id) REFERENCES clients(id) ON DELETE RESTRICT,
+            FOREIGN KEY (contract_id) REFERENCES client_contracts(id) ON DELETE RESTRICT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create invoices table: {}", e)))?;
+
+    // Create invoice_line_items table (each billable line points back at its attendance or trip)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS invoice_line_items (
+            id VARCHAR(36) PRIMARY KEY,
+            invoice_id VARCHAR(36) NOT NULL,
+            line_type VARCHAR(50) NOT NULL,
+            description VARCHAR(500) NOT NULL,
+            service_date DATE,
+            quantity DOUBLE PRECISION NOT NULL,
+            unit_price DOUBLE PRECISION NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            attendance_id VARCHAR(36),
+            shift_id VARCHAR(36),
+            trip_id VARCHAR(36),
+            guard_id VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE,
+            FOREIGN KEY (attendance_id) REFERENCES attendance(id) ON DELETE RESTRICT,
+            FOREIGN KEY (trip_id) REFERENCES trips(id) ON DELETE RESTRICT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create invoice_line_items table: {}", e)))?;
+
+    // A record can only be billed once per line type
+    for statement in [
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_invoice_lines_attendance ON invoice_line_items(attendance_id, line_type) WHERE attendance_id IS NOT NULL",
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_invoice_lines_trip ON invoice_line_items(trip_id, line_type) WHERE trip_id IS NOT NULL",
+        "CREATE INDEX IF NOT EXISTS idx_invoice_lines_invoice ON invoice_line_items(invoice_id)",
+    ] {
+        sqlx::query(statement)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to create invoice line index: {}", e)))?;
+    }
+
+    // Client portal accounts are users with role 'client' tied to a client organisation;
+    // portal evaluations record the submitting account instead of a free-text name
+    for migration in [
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS client_id VARCHAR(36) REFERENCES clients(id) ON DELETE CASCADE",
+        "ALTER TABLE client_evaluations ADD COLUMN IF NOT EXISTS client_user_id VARCHAR(36) REFERENCES users(id) ON DELETE SET NULL",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to run client portal migration: {}", e)))?;
+    }
+
+    // Create incidents table (site incidents reported by guards or supervisors)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS incidents (
+            id VARCHAR(36) PRIMARY KEY,
+            client_site VARCHAR(255) NOT NULL,
+            shift_id VARCHAR(36),
+            reported_by VARCHAR(36) NOT NULL,
+            severity VARCHAR(20) NOT NULL DEFAULT 'low',
+            title VARCHAR(255) NOT NULL,
+            description TEXT,
+            status VARCHAR(50) NOT NULL DEFAULT 'open',
+            occurred_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            resolved_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id) ON DELETE SET NULL,
+            FOREIGN KEY (reported_by) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create incidents table: {}", e)))?;
+
+    // Create service_requests table (client requests for ad-hoc guarding or missions)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS service_requests (
+            id VARCHAR(36) PRIMARY KEY,
+            client_id VARCHAR(36) NOT NULL,
+            requested_by VARCHAR(36) NOT NULL,
+            client_site_id VARCHAR(36),
+            request_type VARCHAR(50) NOT NULL,
+            title VARCHAR(255) NOT NULL,
+            description TEXT,
+            location VARCHAR(500),
+            requested_start TIMESTAMP WITH TIME ZONE NOT NULL,
+            requested_end TIMESTAMP WITH TIME ZONE NOT NULL,
+            guards_required INTEGER NOT NULL DEFAULT 1,
+            vehicles_required INTEGER NOT NULL DEFAULT 0,
+            priority VARCHAR(20) NOT NULL DEFAULT 'normal',
+            status VARCHAR(50) NOT NULL DEFAULT 'pending',
+            dispatcher_id VARCHAR(36),
+            dispatcher_notes TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (client_id) REFERENCES clients(id) ON DELETE CASCADE,
+            FOREIGN KEY (requested_by) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (client_site_id) REFERENCES client_sites(id) ON DELETE SET NULL,
+            FOREIGN KEY (dispatcher_id) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create service_requests table: {}", e)))?;
+
+    // Evaluation integrity: evaluations submitted by a portal account or a one-time link are
+    // `verified`, and each completed shift or mission can carry at most one verified rating
+    for migration in [
+        "ALTER TABLE client_evaluations ADD COLUMN IF NOT EXISTS verified BOOLEAN NOT NULL DEFAULT false",
+        "ALTER TABLE client_evaluations ADD COLUMN IF NOT EXISTS status VARCHAR(50) NOT NULL DEFAULT 'active'",
+        "ALTER TABLE client_evaluations ADD COLUMN IF NOT EXISTS client_id VARCHAR(36)",
+        "ALTER TABLE client_evaluations ADD COLUMN IF NOT EXISTS evaluation_link_id VARCHAR(36)",
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_client_evaluations_shift_once ON client_evaluations(shift_id) WHERE verified AND shift_id IS NOT NULL",
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_client_evaluations_mission_once ON client_evaluations(mission_id) WHERE verified AND mission_id IS NOT NULL",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to run evaluation migration: {}", e)))?;
+    }
+
+    // Create evaluation_links table (one-time signed links for clients without portal accounts)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS evaluation_links (
+            id VARCHAR(36) PRIMARY KEY,
+            shift_id VARCHAR(36),
+            mission_id VARCHAR(36),
+            client_id VARCHAR(36) NOT NULL,
+            guard_id VARCHAR(36) NOT NULL,
+            evaluator_email VARCHAR(255),
+            created_by VARCHAR(36),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            used_at TIMESTAMP WITH TIME ZONE,
+            evaluation_id VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (client_id) REFERENCES clients(id) ON DELETE CASCADE,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create evaluation_links table: {}", e)))?;
+
+    // Create evaluation_disputes table (a guard may appeal each rating once)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS evaluation_disputes (
+            id VARCHAR(36) PRIMARY KEY,
+            evaluation_id VARCHAR(36) NOT NULL UNIQUE,
+            guard_id VARCHAR(36) NOT NULL,
+            reason TEXT NOT NULL,
+            status VARCHAR(50) NOT NULL DEFAULT 'open',
+            reviewed_by VARCHAR(36),
+            review_notes TEXT,
+            resolved_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (evaluation_id) REFERENCES client_evaluations(id) ON DELETE CASCADE,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create evaluation_disputes table: {}", e)))?;
+
+    // Create merit_models table (versioned scoring formulas; weights are percentages summing to 100)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS merit_models (
+            id VARCHAR(36) PRIMARY KEY,
+            version INTEGER NOT NULL UNIQUE,
+            name VARCHAR(255) NOT NULL,
+            attendance_weight DOUBLE PRECISION NOT NULL DEFAULT 0,
+            punctuality_weight DOUBLE PRECISION NOT NULL DEFAULT 0,
+            client_rating_weight DOUBLE PRECISION NOT NULL DEFAULT 0,
+            incident_weight DOUBLE PRECISION NOT NULL DEFAULT 0,
+            patrol_weight DOUBLE PRECISION NOT NULL DEFAULT 0,
+            training_weight DOUBLE PRECISION NOT NULL DEFAULT 0,
+            gold_min DOUBLE PRECISION NOT NULL,
+            silver_min DOUBLE PRECISION NOT NULL,
+            bronze_min DOUBLE PRECISION NOT NULL,
+            is_active BOOLEAN NOT NULL DEFAULT false,
+            notes TEXT,
+            created_by VARCHAR(36),
+            activated_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create merit_models table: {}", e)))?;
+
+    for migration in [
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_merit_models_active ON merit_models(is_active) WHERE is_active",
+        // Version 1 is the original hard-coded formula
+        "INSERT INTO merit_models
+         (id, version, name, attendance_weight, punctuality_weight, client_rating_weight,
+          gold_min, silver_min, bronze_min, is_active, activated_at)
+         VALUES ('merit-model-v1', 1, 'Baseline', 30, 35, 35, 90, 80, 70, true, CURRENT_TIMESTAMP)
+         ON CONFLICT (id) DO NOTHING",
+        "ALTER TABLE guard_merit_scores ADD COLUMN IF NOT EXISTS incident_score DOUBLE PRECISION",
+        "ALTER TABLE guard_merit_scores ADD COLUMN IF NOT EXISTS patrol_score DOUBLE PRECISION",
+        "ALTER TABLE guard_merit_scores ADD COLUMN IF NOT EXISTS training_score DOUBLE PRECISION",
+        "ALTER TABLE guard_merit_scores ADD COLUMN IF NOT EXISTS model_version INTEGER",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Create merit_score_history table (one snapshot per recalculation)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS merit_score_history (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            model_id VARCHAR(36) NOT NULL,
+            model_version INTEGER NOT NULL,
+            attendance_score DOUBLE PRECISION NOT NULL,
+            punctuality_score DOUBLE PRECISION NOT NULL,
+            client_rating DOUBLE PRECISION NOT NULL,
+            incident_score DOUBLE PRECISION NOT NULL,
+            patrol_score DOUBLE PRECISION NOT NULL,
+            training_score DOUBLE PRECISION NOT NULL,
+            overall_score DOUBLE PRECISION NOT NULL,
+            rank VARCHAR(50) NOT NULL,
+            calculated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (model_id) REFERENCES merit_models(id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create merit_score_history table: {}", e)))?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_merit_score_history_guard ON merit_score_history(guard_id, calculated_at DESC)")
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to index merit_score_history: {}", e)))?;
+
+    // Create patrol_rounds table (checkpoint completion per patrol, used by the patrol merit factor)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS patrol_rounds (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            shift_id VARCHAR(36),
+            checkpoints_expected INTEGER NOT NULL,
+            checkpoints_completed INTEGER NOT NULL,
+            started_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            completed_at TIMESTAMP WITH TIME ZONE,
+            notes TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create patrol_rounds table: {}", e)))?;
+
+    // Merit models may decay older events; window scores back the rolling rankings
+    sqlx::query("ALTER TABLE merit_models ADD COLUMN IF NOT EXISTS decay_half_life_days DOUBLE PRECISION")
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+
+    // Create guard_merit_window_scores table (latest score per guard per rolling window)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS guard_merit_window_scores (
+            guard_id VARCHAR(36) NOT NULL,
+            window_days INTEGER NOT NULL,
+            model_version INTEGER NOT NULL,
+            overall_score DOUBLE PRECISION NOT NULL,
+            rank VARCHAR(50) NOT NULL,
+            punctuality_score DOUBLE PRECISION NOT NULL,
+            average_client_rating DOUBLE PRECISION,
+            activity_count INTEGER NOT NULL,
+            previous_score DOUBLE PRECISION,
+            trend VARCHAR(20),
+            calculated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (guard_id, window_days),
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create guard_merit_window_scores table: {}", e)))?;
+
+    // Create merit_recalculation_runs table (progress of batch recalculations; one may run at a time)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS merit_recalculation_runs (
+            id VARCHAR(36) PRIMARY KEY,
+            trigger VARCHAR(50) NOT NULL,
+            requested_by VARCHAR(36),
+            status VARCHAR(50) NOT NULL DEFAULT 'running',
+            model_version INTEGER,
+            total_guards INTEGER NOT NULL DEFAULT 0,
+            processed_guards INTEGER NOT NULL DEFAULT 0,
+            succeeded INTEGER NOT NULL DEFAULT 0,
+            failed INTEGER NOT NULL DEFAULT 0,
+            error TEXT,
+            started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            finished_at TIMESTAMP WITH TIME ZONE,
+            FOREIGN KEY (requested_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create merit_recalculation_runs table: {}", e)))?;
+
+    // Create merit_recalculation_errors table (guards a batch run could not score)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS merit_recalculation_errors (
+            id VARCHAR(36) PRIMARY KEY,
+            run_id VARCHAR(36) NOT NULL,
+            guard_id VARCHAR(36) NOT NULL,
+            error TEXT NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (run_id) REFERENCES merit_recalculation_runs(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create merit_recalculation_errors table: {}", e)))?;
+
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_merit_recalculation_runs_running ON merit_recalculation_runs(status) WHERE status = 'running'",
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to index merit_recalculation_runs: {}", e)))?;
+
+    // Create commendations table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS commendations (
+            id VARCHAR(36) PRIMARY KEY,
+            guard_id VARCHAR(36) NOT NULL,
+            level VARCHAR(20) NOT NULL DEFAULT 'commendation',
+            title VARCHAR(255) NOT NULL,
+            description TEXT,
+            evidence_urls TEXT[] NOT NULL DEFAULT '{}',
+            awarded_by VARCHAR(36),
+            awarded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            expires_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (guard_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (awarded_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create commendations table: {}", e)))?;
+
+    for migration in [
+        "CREATE INDEX IF NOT EXISTS idx_disciplinary_records_guard ON disciplinary_records(guard_id, effective_from)",
+        "CREATE INDEX IF NOT EXISTS idx_commendations_guard ON commendations(guard_id, awarded_at)",
+        // Points added to (commendations) or taken from (disciplinary records) the weighted merit score
+        "ALTER TABLE guard_merit_scores ADD COLUMN IF NOT EXISTS conduct_adjustment DOUBLE PRECISION",
+        "ALTER TABLE merit_score_history ADD COLUMN IF NOT EXISTS conduct_adjustment DOUBLE PRECISION NOT NULL DEFAULT 0",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Push new notifications to listening instances (LISTEN notification_inserted); `seq` gives
+    // streams a stable order to resume from
+    for migration in [
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS seq BIGSERIAL",
+        "CREATE INDEX IF NOT EXISTS idx_notifications_user_seq ON notifications(user_id, seq)",
+        r#"
+        CREATE OR REPLACE FUNCTION notify_notification_inserted() RETURNS trigger
+        LANGUAGE plpgsql AS $$
+        BEGIN
+            PERFORM pg_notify(
+                'notification_inserted',
+                json_build_object('id', NEW.id, 'userId', NEW.user_id)::text
+            );
+            RETURN NEW;
+        END
+        $$
+        "#,
+        "DROP TRIGGER IF EXISTS notifications_notify_insert ON notifications",
+        "CREATE TRIGGER notifications_notify_insert AFTER INSERT ON notifications
+         FOR EACH ROW EXECUTE FUNCTION notify_notification_inserted()",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Create notification_preferences table (channels per user and notification type)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_preferences (
+            user_id VARCHAR(36) NOT NULL,
+            notification_type VARCHAR(50) NOT NULL,
+            email_enabled BOOLEAN NOT NULL,
+            sms_enabled BOOLEAN NOT NULL,
+            push_enabled BOOLEAN NOT NULL,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (user_id, notification_type),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create notification_preferences table: {}", e)))?;
+
+    // Create notification_settings table (quiet hours)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_settings (
+            user_id VARCHAR(36) PRIMARY KEY,
+            quiet_hours_start TIME,
+            quiet_hours_end TIME,
+            timezone VARCHAR(64),
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create notification_settings table: {}", e)))?;
+
+    // Create push_devices table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS push_devices (
+            id VARCHAR(36) PRIMARY KEY,
+            user_id VARCHAR(36) NOT NULL,
+            device_token TEXT NOT NULL UNIQUE,
+            platform VARCHAR(20) NOT NULL DEFAULT 'web',
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            last_seen_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create push_devices table: {}", e)))?;
+
+    // Create notification_deliveries table (one row per notification, channel and destination)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_deliveries (
+            id VARCHAR(36) PRIMARY KEY,
+            notification_id VARCHAR(36) NOT NULL,
+            user_id VARCHAR(36) NOT NULL,
+            channel VARCHAR(20) NOT NULL,
+            destination TEXT,
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            last_error TEXT,
+            provider VARCHAR(50),
+            provider_message_id VARCHAR(255),
+            sent_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (notification_id, channel, destination),
+            FOREIGN KEY (notification_id) REFERENCES notifications(id) ON DELETE CASCADE,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create notification_deliveries table: {}", e)))?;
+
+    for migration in [
+        // Existing notifications count as dispatched; only new ones go out on other channels
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS dispatched_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP",
+        "ALTER TABLE notifications ALTER COLUMN dispatched_at DROP DEFAULT",
+        "CREATE INDEX IF NOT EXISTS idx_notifications_undispatched ON notifications(created_at) WHERE dispatched_at IS NULL",
+        "CREATE INDEX IF NOT EXISTS idx_notification_deliveries_due ON notification_deliveries(next_attempt_at) WHERE status IN ('pending', 'deferred')",
+        "CREATE INDEX IF NOT EXISTS idx_push_devices_user ON push_devices(user_id)",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Notification templates, structured payloads, grouping and expiry. `group_key` merges
+    // repeats into the user's unread notification; expired rows are archived, then purged.
+    for migration in [
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS template_key VARCHAR(100)",
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS entity_type VARCHAR(50)",
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS entity_id VARCHAR(100)",
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS params JSONB",
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS group_key VARCHAR(255)",
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS group_count INTEGER NOT NULL DEFAULT 1",
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE DEFAULT (CURRENT_TIMESTAMP + INTERVAL '30 days')",
+        "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS archived_at TIMESTAMP WITH TIME ZONE",
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_notifications_open_group ON notifications(user_id, group_key)
+         WHERE read = false AND archived_at IS NULL AND group_key IS NOT NULL",
+        "CREATE INDEX IF NOT EXISTS idx_notifications_expires ON notifications(expires_at) WHERE archived_at IS NULL",
+        "CREATE INDEX IF NOT EXISTS idx_notifications_archived ON notifications(archived_at) WHERE archived_at IS NOT NULL",
+        "CREATE INDEX IF NOT EXISTS idx_notifications_entity ON notifications(entity_type, entity_id)",
+        "ALTER TABLE notification_settings ADD COLUMN IF NOT EXISTS locale VARCHAR(10)",
+        // Fill `{name}` placeholders from a JSON object of strings
+        r#"
+        CREATE OR REPLACE FUNCTION render_notification_template(template TEXT, params JSONB) RETURNS TEXT
+        LANGUAGE plpgsql IMMUTABLE AS $$
+        DECLARE
+            param RECORD;
+            rendered TEXT := template;
+        BEGIN
+            FOR param IN SELECT key, value FROM jsonb_each_text(COALESCE(params, '{}'::jsonb)) LOOP
+                rendered := replace(rendered, '{' || param.key || '}', COALESCE(param.value, ''));
+            END LOOP;
+            RETURN rendered;
+        END;
+        $$
+        "#,
+        // A grouped repeat updates the existing row; open streams are told about it the same way
+        "DROP TRIGGER IF EXISTS notifications_notify_group ON notifications",
+        "CREATE TRIGGER notifications_notify_group AFTER UPDATE OF group_count ON notifications
+         FOR EACH ROW EXECUTE FUNCTION notify_notification_inserted()",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Create notification_templates table (title and body per template and locale)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_templates (
+            template_key VARCHAR(100) NOT NULL,
+            locale VARCHAR(10) NOT NULL,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            customized BOOLEAN NOT NULL DEFAULT false,
+            updated_by VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (template_key, locale),
+            FOREIGN KEY (updated_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create notification_templates table: {}", e)))?;
+
+    // Create vehicle_positions table (GPS points from vehicle trackers, partitioned by month;
+    // partitions are created by ensure_vehicle_position_partition as points arrive)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS vehicle_positions (
+            car_id VARCHAR(36) NOT NULL,
+            trip_id VARCHAR(36),
+            recorded_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            latitude DOUBLE PRECISION NOT NULL,
+            longitude DOUBLE PRECISION NOT NULL,
+            speed_kph DOUBLE PRECISION,
+            heading DOUBLE PRECISION,
+            received_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (car_id, recorded_at),
+            FOREIGN KEY (car_id) REFERENCES armored_cars(id) ON DELETE CASCADE
+        ) PARTITION BY RANGE (recorded_at)
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create vehicle_positions table: {}", e)))?;
+
+    // Create vehicle_live_positions table (latest point per vehicle)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS vehicle_live_positions (
+            car_id VARCHAR(36) PRIMARY KEY,
+            trip_id VARCHAR(36),
+            recorded_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            latitude DOUBLE PRECISION NOT NULL,
+            longitude DOUBLE PRECISION NOT NULL,
+            speed_kph DOUBLE PRECISION,
+            heading DOUBLE PRECISION,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (car_id) REFERENCES armored_cars(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create vehicle_live_positions table: {}", e)))?;
+
+    for migration in [
+        "CREATE INDEX IF NOT EXISTS idx_vehicle_positions_trip ON vehicle_positions(trip_id, recorded_at)",
+        // Monthly partition holding `ts`, e.g. vehicle_positions_y2026m10
+        r#"
+        CREATE OR REPLACE FUNCTION ensure_vehicle_position_partition(ts TIMESTAMP WITH TIME ZONE) RETURNS TEXT
+        LANGUAGE plpgsql AS $$
+        DECLARE
+            month_start TIMESTAMP WITH TIME ZONE := date_trunc('month', ts AT TIME ZONE 'UTC') AT TIME ZONE 'UTC';
+            partition_name TEXT := 'vehicle_positions_' || to_char(ts AT TIME ZONE 'UTC', '"y"YYYY"m"MM');
+        BEGIN
+            IF to_regclass(partition_name) IS NULL THEN
+                EXECUTE format(
+                    'CREATE TABLE IF NOT EXISTS %I PARTITION OF vehicle_positions FOR VALUES FROM (%L) TO (%L)',
+                    partition_name, month_start, month_start + INTERVAL '1 month'
+                );
+            END IF;
+            RETURN partition_name;
+        END;
+        $$
+        "#,
+        "SELECT ensure_vehicle_position_partition(CURRENT_TIMESTAMP)",
+        r#"
+        CREATE OR REPLACE FUNCTION haversine_km(lat1 DOUBLE PRECISION, lon1 DOUBLE PRECISION,
+                                                lat2 DOUBLE PRECISION, lon2 DOUBLE PRECISION)
+        RETURNS DOUBLE PRECISION LANGUAGE sql IMMUTABLE AS $$
+            SELECT 6371 * 2 * ASIN(SQRT(
+                POWER(SIN(RADIANS(lat2 - lat1) / 2), 2)
+                + COS(RADIANS(lat1)) * COS(RADIANS(lat2)) * POWER(SIN(RADIANS(lon2 - lon1) / 2), 2)))
+        $$
+        "#,
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Create trip_routes table (planned route per armored trip; waypoints as parallel arrays)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS trip_routes (
+            trip_id VARCHAR(36) PRIMARY KEY,
+            waypoint_latitudes DOUBLE PRECISION[] NOT NULL,
+            waypoint_longitudes DOUBLE PRECISION[] NOT NULL,
+            corridor_meters DOUBLE PRECISION NOT NULL DEFAULT 500,
+            max_stop_minutes INTEGER NOT NULL DEFAULT 10,
+            arrival_radius_meters DOUBLE PRECISION NOT NULL DEFAULT 200,
+            planned_arrival_at TIMESTAMP WITH TIME ZONE,
+            arrived_at TIMESTAMP WITH TIME ZONE,
+            planned_by VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (trip_id) REFERENCES trips(id) ON DELETE CASCADE,
+            FOREIGN KEY (planned_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create trip_routes table: {}", e)))?;
+
+    // Create trip_allowed_stops table (planned stops where a vehicle may wait)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS trip_allowed_stops (
+            id VARCHAR(36) PRIMARY KEY,
+            trip_id VARCHAR(36) NOT NULL,
+            name VARCHAR(255) NOT NULL,
+            latitude DOUBLE PRECISION NOT NULL,
+            longitude DOUBLE PRECISION NOT NULL,
+            radius_meters DOUBLE PRECISION NOT NULL DEFAULT 150,
+            max_minutes INTEGER NOT NULL DEFAULT 30,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (trip_id) REFERENCES trip_routes(trip_id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create trip_allowed_stops table: {}", e)))?;
+
+    // Create trip_alerts table (deviations, long stops and late arrivals, kept for post-mission review)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS trip_alerts (
+            id VARCHAR(36) PRIMARY KEY,
+            trip_id VARCHAR(36) NOT NULL,
+            car_id VARCHAR(36) NOT NULL,
+            alert_type VARCHAR(50) NOT NULL,
+            priority VARCHAR(20) NOT NULL,
+            started_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            ended_at TIMESTAMP WITH TIME ZONE,
+            latitude DOUBLE PRECISION,
+            longitude DOUBLE PRECISION,
+            distance_meters DOUBLE PRECISION,
+            duration_minutes DOUBLE PRECISION,
+            details TEXT,
+            acknowledged_by VARCHAR(36),
+            acknowledged_at TIMESTAMP WITH TIME ZONE,
+            review_notes TEXT,
+            reviewed_by VARCHAR(36),
+            reviewed_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (trip_id) REFERENCES trips(id) ON DELETE CASCADE,
+            FOREIGN KEY (car_id) REFERENCES armored_cars(id) ON DELETE CASCADE,
+            FOREIGN KEY (acknowledged_by) REFERENCES users(id) ON DELETE SET NULL,
+            FOREIGN KEY (reviewed_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create trip_alerts table: {}", e)))?;
+
+    for migration in [
+        // At most one ongoing alert of each type per trip
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_trip_alerts_open ON trip_alerts(trip_id, alert_type) WHERE ended_at IS NULL",
+        "CREATE INDEX IF NOT EXISTS idx_trip_alerts_trip ON trip_alerts(trip_id, started_at)",
+        "CREATE INDEX IF NOT EXISTS idx_trip_allowed_stops_trip ON trip_allowed_stops(trip_id)",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // SOS alerts are incidents raised by a guard in distress, possibly away from any client site
+    for migration in [
+        "ALTER TABLE incidents ALTER COLUMN client_site DROP NOT NULL",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS incident_type VARCHAR(20) NOT NULL DEFAULT 'report'",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS trip_id VARCHAR(36) REFERENCES trips(id) ON DELETE SET NULL",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS latitude DOUBLE PRECISION",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS longitude DOUBLE PRECISION",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS acknowledged_by VARCHAR(36) REFERENCES users(id) ON DELETE SET NULL",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS acknowledged_at TIMESTAMP WITH TIME ZONE",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS escalation_level INTEGER NOT NULL DEFAULT 0",
+        "ALTER TABLE incidents ADD COLUMN IF NOT EXISTS next_escalation_at TIMESTAMP WITH TIME ZONE",
+        "CREATE INDEX IF NOT EXISTS idx_incidents_next_escalation ON incidents(next_escalation_at) WHERE next_escalation_at IS NOT NULL",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to run SOS migration: {}", e)))?;
+    }
+
+    // Create incident_events table (timeline of an incident: raised, notified, escalated, acknowledged, ...)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS incident_events (
+            id VARCHAR(36) PRIMARY KEY,
+            incident_id VARCHAR(36) NOT NULL,
+            event_type VARCHAR(50) NOT NULL,
+            actor_id VARCHAR(36),
+            escalation_level INTEGER,
+            latitude DOUBLE PRECISION,
+            longitude DOUBLE PRECISION,
+            details TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (incident_id) REFERENCES incidents(id) ON DELETE CASCADE,
+            FOREIGN KEY (actor_id) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create incident_events table: {}", e)))?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_incident_events_incident ON incident_events(incident_id, created_at)")
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+
+    // Create vehicle_service_policies table (service intervals per car model; model '*' applies to models without one)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS vehicle_service_policies (
+            id VARCHAR(36) PRIMARY KEY,
+            model VARCHAR(255) NOT NULL UNIQUE,
+            interval_km INTEGER,
+            interval_months INTEGER,
+            lead_km INTEGER NOT NULL DEFAULT 500,
+            lead_days INTEGER NOT NULL DEFAULT 14,
+            maintenance_type VARCHAR(100) NOT NULL DEFAULT 'routine_service',
+            description VARCHAR(1000),
+            updated_by VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            CHECK (interval_km IS NOT NULL OR interval_months IS NOT NULL),
+            FOREIGN KEY (updated_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create vehicle_service_policies table: {}", e)))?;
+
+    for migration in [
+        // Odometer reading at the last completed service
+        "ALTER TABLE armored_cars ADD COLUMN IF NOT EXISTS last_maintenance_mileage INTEGER",
+        "ALTER TABLE car_maintenance ADD COLUMN IF NOT EXISTS policy_id VARCHAR(36) REFERENCES vehicle_service_policies(id) ON DELETE SET NULL",
+        "ALTER TABLE car_maintenance ADD COLUMN IF NOT EXISTS due_mileage INTEGER",
+        "ALTER TABLE car_maintenance ADD COLUMN IF NOT EXISTS auto_generated BOOLEAN NOT NULL DEFAULT false",
+        // At most one open policy service per car
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_car_maintenance_open_auto ON car_maintenance(car_id) WHERE auto_generated AND status = 'scheduled'",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to run vehicle service migration: {}", e)))?;
+    }
+
+    // Car registration and insurance: the fleet manager responsible for renewals, overrides and their audit trail
+    sqlx::query("ALTER TABLE armored_cars ADD COLUMN IF NOT EXISTS fleet_manager_id VARCHAR(36) REFERENCES users(id) ON DELETE SET NULL")
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+
+    // Create vehicle_compliance_overrides table (supervisor permission to dispatch a car with a lapsed document)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS vehicle_compliance_overrides (
+            id VARCHAR(36) PRIMARY KEY,
+            car_id VARCHAR(36) NOT NULL,
+            document VARCHAR(20) NOT NULL,
+            reason TEXT NOT NULL,
+            approved_by VARCHAR(36) NOT NULL,
+            valid_until TIMESTAMP WITH TIME ZONE NOT NULL,
+            revoked_by VARCHAR(36),
+            revoked_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (car_id) REFERENCES armored_cars(id) ON DELETE CASCADE,
+            FOREIGN KEY (approved_by) REFERENCES users(id),
+            FOREIGN KEY (revoked_by) REFERENCES users(id),
+            CHECK (document IN ('registration', 'insurance'))
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create vehicle_compliance_overrides table: {}", e)))?;
+
+    // Create vehicle_compliance_events table (audit of overrides granted, used and revoked)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS vehicle_compliance_events (
+            id VARCHAR(36) PRIMARY KEY,
+            car_id VARCHAR(36) NOT NULL,
+            override_id VARCHAR(36),
+            action VARCHAR(50) NOT NULL,
+            actor_id VARCHAR(36),
+            details TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (car_id) REFERENCES armored_cars(id) ON DELETE CASCADE,
+            FOREIGN KEY (override_id) REFERENCES vehicle_compliance_overrides(id) ON DELETE SET NULL,
+            FOREIGN KEY (actor_id) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create vehicle_compliance_events table: {}", e)))?;
+
+    for migration in [
+        "CREATE INDEX IF NOT EXISTS idx_vehicle_compliance_overrides_car ON vehicle_compliance_overrides(car_id, valid_until)",
+        "CREATE INDEX IF NOT EXISTS idx_vehicle_compliance_events_car ON vehicle_compliance_events(car_id, created_at)",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Create vehicle_status_history table (every armored car status change)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS vehicle_status_history (
+            id VARCHAR(36) PRIMARY KEY,
+            car_id VARCHAR(36) NOT NULL,
+            from_status VARCHAR(50),
+            to_status VARCHAR(50) NOT NULL,
+            reason TEXT NOT NULL,
+            changed_by VARCHAR(36),
+            trip_id VARCHAR(36),
+            allocation_id VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (car_id) REFERENCES armored_cars(id) ON DELETE CASCADE,
+            FOREIGN KEY (changed_by) REFERENCES users(id) ON DELETE SET NULL,
+            FOREIGN KEY (trip_id) REFERENCES trips(id) ON DELETE SET NULL,
+            FOREIGN KEY (allocation_id) REFERENCES car_allocations(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create vehicle_status_history table: {}", e)))?;
+
+    for migration in [
+        // Older handlers wrote 'operational' for an idle car; anything else unrecognised needs a look
+        "UPDATE armored_cars SET status = 'available' WHERE status = 'operational'",
+        "UPDATE armored_cars SET status = 'out_of_service'
+         WHERE status NOT IN ('available', 'allocated', 'deployed', 'maintenance', 'out_of_service')",
+        "ALTER TABLE armored_cars DROP CONSTRAINT IF EXISTS armored_cars_status_check",
+        "ALTER TABLE armored_cars ADD CONSTRAINT armored_cars_status_check
+         CHECK (status IN ('available', 'allocated', 'deployed', 'maintenance', 'out_of_service'))",
+        "CREATE INDEX IF NOT EXISTS idx_vehicle_status_history_car ON vehicle_status_history(car_id, created_at)",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Create trip_crew table (explicit crew manifest per trip, frozen when the trip starts)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS trip_crew (
+            id VARCHAR(36) PRIMARY KEY,
+            trip_id VARCHAR(36) NOT NULL,
+            user_id VARCHAR(36) NOT NULL,
+            crew_role VARCHAR(20) NOT NULL,
+            firearm_allocation_id VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (trip_id) REFERENCES trips(id) ON DELETE CASCADE,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (firearm_allocation_id) REFERENCES firearm_allocations(id) ON DELETE SET NULL,
+            UNIQUE (trip_id, user_id),
+            CHECK (crew_role IN ('driver', 'escort', 'custodian'))
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create trip_crew table: {}", e)))?;
+
+    for migration in [
+        "ALTER TABLE trips ADD COLUMN IF NOT EXISTS crew_frozen_at TIMESTAMP WITH TIME ZONE",
+        "ALTER TABLE trips ADD COLUMN IF NOT EXISTS crew_updated_by VARCHAR(36) REFERENCES users(id) ON DELETE SET NULL",
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_trip_crew_one_driver ON trip_crew(trip_id) WHERE crew_role = 'driver'",
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_trip_crew_one_custodian ON trip_crew(trip_id) WHERE crew_role = 'custodian'",
+        "CREATE INDEX IF NOT EXISTS idx_trip_crew_user ON trip_crew(user_id)",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Create consignments table (cash and valuables carried on an armored trip)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS consignments (
+            id VARCHAR(36) PRIMARY KEY,
+            trip_id VARCHAR(36) NOT NULL,
+            reference VARCHAR(100) NOT NULL,
+            description VARCHAR(1000),
+            declared_value DOUBLE PRECISION NOT NULL,
+            currency VARCHAR(10) NOT NULL DEFAULT 'PHP',
+            seal_numbers TEXT[] NOT NULL,
+            pickup_party VARCHAR(255) NOT NULL,
+            pickup_address VARCHAR(500),
+            dropoff_party VARCHAR(255) NOT NULL,
+            dropoff_address VARCHAR(500),
+            status VARCHAR(20) NOT NULL DEFAULT 'registered',
+            seals_intact BOOLEAN,
+            picked_up_at TIMESTAMP WITH TIME ZONE,
+            delivered_at TIMESTAMP WITH TIME ZONE,
+            created_by VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (trip_id) REFERENCES trips(id) ON DELETE CASCADE,
+            FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL,
+            CHECK (status IN ('registered', 'in_transit', 'delivered'))
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create consignments table: {}", e)))?;
+
+    // Create consignment_handovers table (signed custody changes with the seal check at each)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS consignment_handovers (
+            id VARCHAR(36) PRIMARY KEY,
+            consignment_id VARCHAR(36) NOT NULL,
+            stage VARCHAR(20) NOT NULL,
+            from_party VARCHAR(255) NOT NULL,
+            to_party VARCHAR(255) NOT NULL,
+            signed_by_name VARCHAR(255) NOT NULL,
+            signature TEXT NOT NULL,
+            seals_presented TEXT[] NOT NULL,
+            broken_seals TEXT[] NOT NULL DEFAULT '{}',
+            missing_seals TEXT[] NOT NULL DEFAULT '{}',
+            unexpected_seals TEXT[] NOT NULL DEFAULT '{}',
+            seals_intact BOOLEAN NOT NULL,
+            notes VARCHAR(1000),
+            recorded_by VARCHAR(36),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (consignment_id) REFERENCES consignments(id) ON DELETE CASCADE,
+            FOREIGN KEY (recorded_by) REFERENCES users(id) ON DELETE SET NULL,
+            CHECK (stage IN ('pickup', 'transfer', 'delivery'))
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create consignment_handovers table: {}", e)))?;
+
+    for migration in [
+        "CREATE INDEX IF NOT EXISTS idx_consignments_trip ON consignments(trip_id)",
+        "CREATE INDEX IF NOT EXISTS idx_consignment_handovers_consignment ON consignment_handovers(consignment_id, created_at)",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
+    // Driver qualifications: license classes held, the class a car requires and defensive-driving certification
+    for migration in [
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS license_classes TEXT[] NOT NULL DEFAULT '{}'",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS defensive_driving_cert_number VARCHAR(100)",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS defensive_driving_cert_expiry TIMESTAMP WITH TIME ZONE",
+        "ALTER TABLE armored_cars ADD COLUMN IF NOT EXISTS required_license_class VARCHAR(20)",
+        "CREATE INDEX IF NOT EXISTS idx_trips_driver_start ON trips(driver_id, start_time)",
+    ] {
+        sqlx::query(migration)
+            .execute(pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
+    }
+
     Ok(())
 }
@@ -9,6 +9,7 @@ use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::{trip_crew, trip_management, vehicle_compliance, vehicle_status},
 };
 
 #[derive(Debug, Serialize)]
@@ -298,27 +299,37 @@ pub async fn update_mission_status(
     State(db): State<Arc<PgPool>>,
     Json(payload): Json<UpdateMissionStatusRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
     // Update trip status
-    sqlx::query(
-        "UPDATE trips SET status = $1 WHERE id = $2"
-    )
-    .bind(&payload.status)
-    .bind(&payload.mission_id)
-    .execute(db.as_ref())
-    .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to update mission: {}", e)))?;
+    let (car_id, driver_id) = trip_management::set_trip_status(&mut tx, &payload.mission_id, &payload.status)
+        .await
+        .map_err(|e| match e {
+            AppError::NotFound(_) => AppError::NotFound("Mission not found".to_string()),
+            e => e,
+        })?;
 
-    // If completed, update vehicle status back to operational
-    if payload.status == "completed" {
-        sqlx::query(
-            "UPDATE armored_cars SET status = 'operational' 
-             WHERE id IN (SELECT car_id FROM trips WHERE id = $1)"
-        )
-        .bind(&payload.mission_id)
-        .execute(db.as_ref())
+    if matches!(payload.status.as_str(), "in_progress" | "in_transit") {
+        trip_crew::freeze_crew(&mut tx, &payload.mission_id).await?;
+    }
+    let overridden = vehicle_status::follow_trip(&mut tx, &car_id, &payload.mission_id, &payload.status).await?;
+
+    tx.commit()
         .await
-        .ok();
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    vehicle_compliance::record_override_use(
+        db.as_ref(),
+        &overridden,
+        Some(&driver_id),
+        &format!("Dispatched on trip {}", payload.mission_id),
+    )
+    .await?;
 
+    if payload.status == "completed" {
         // Return allocated firearms
         sqlx::query(
             "UPDATE firearms SET status = 'available'
@@ -9,10 +9,16 @@ use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::{
+        driver_qualifications,
+        trip_crew::{self, CrewManifest},
+        vehicle_compliance, vehicle_maintenance,
+        vehicle_status::{self, StatusChange},
+    },
     models::{
         ArmoredCar, CreateArmoredCarRequest, UpdateArmoredCarRequest, CarAllocation, IssueCarRequest,
         ReturnCarRequest, CarMaintenance, CreateMaintenanceRequest, DriverAssignment,
-        AssignDriverRequest, Trip, CreateTripRequest, EndTripRequest,
+        AssignDriverRequest, Trip, CreateTripRequest, EndTripRequest, VehicleStatus,
     },
     utils,
 };
@@ -31,8 +37,13 @@ pub async fn add_armored_car(
 
     let id = utils::generate_id();
 
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
     sqlx::query(
-        "INSERT INTO armored_cars (id, license_plate, vin, model, manufacturer, capacity_kg, registration_expiry, insurance_expiry, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+        "INSERT INTO armored_cars (id, license_plate, vin, model, manufacturer, capacity_kg, registration_expiry, insurance_expiry, status, required_license_class) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
     )
     .bind(&id)
     .bind(&payload.license_plate)
@@ -42,11 +53,25 @@ pub async fn add_armored_car(
     .bind(payload.capacity_kg)
     .bind(&payload.registration_expiry)
     .bind(&payload.insurance_expiry)
-    .bind("available")
-    .execute(db.as_ref())
+    .bind(VehicleStatus::Available.as_str())
+    .bind(payload.required_license_class.as_deref().and_then(driver_qualifications::normalize_class))
+    .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to create armored car: {}", e)))?;
 
+    vehicle_status::record_change(
+        &mut tx,
+        &id,
+        None,
+        VehicleStatus::Available,
+        &StatusChange { reason: "Added to the fleet", ..Default::default() },
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
     Ok((
         StatusCode::CREATED,
         Json(json!({
@@ -60,7 +85,7 @@ pub async fn get_all_armored_cars(
     State(db): State<Arc<PgPool>>,
 ) -> AppResult<Json<Vec<ArmoredCar>>> {
     let cars = sqlx::query_as::<_, ArmoredCar>(
-        "SELECT id, license_plate, vin, model, manufacturer, capacity_kg, status, registration_expiry, insurance_expiry, last_maintenance_date, mileage, created_at, updated_at FROM armored_cars"
+        "SELECT id, license_plate, vin, model, manufacturer, capacity_kg, status, registration_expiry, insurance_expiry, last_maintenance_date, mileage, created_at, updated_at, fleet_manager_id, required_license_class FROM armored_cars"
     )
     .fetch_all(db.as_ref())
     .await
@@ -74,7 +99,7 @@ pub async fn get_armored_car_by_id(
     Path(id): Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
     let car = sqlx::query_as::<_, ArmoredCar>(
-        "SELECT id, license_plate, vin, model, manufacturer, capacity_kg, status, registration_expiry, insurance_expiry, last_maintenance_date, mileage, created_at, updated_at FROM armored_cars WHERE id = $1"
+        "SELECT id, license_plate, vin, model, manufacturer, capacity_kg, status, registration_expiry, insurance_expiry, last_maintenance_date, mileage, created_at, updated_at, fleet_manager_id, required_license_class FROM armored_cars WHERE id = $1"
     )
     .bind(&id)
     .fetch_optional(db.as_ref())
@@ -102,7 +127,7 @@ pub async fn update_armored_car(
     Json(payload): Json<UpdateArmoredCarRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     let car = sqlx::query_as::<_, ArmoredCar>(
-        "SELECT id, license_plate, vin, model, manufacturer, capacity_kg, status, registration_expiry, insurance_expiry, last_maintenance_date, mileage, created_at, updated_at FROM armored_cars WHERE id = $1"
+        "SELECT id, license_plate, vin, model, manufacturer, capacity_kg, status, registration_expiry, insurance_expiry, last_maintenance_date, mileage, created_at, updated_at, fleet_manager_id, required_license_class FROM armored_cars WHERE id = $1"
     )
     .bind(&id)
     .fetch_optional(db.as_ref())
@@ -110,23 +135,80 @@ pub async fn update_armored_car(
     .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
     .ok_or_else(|| AppError::NotFound("Armored car not found".to_string()))?;
 
-    let status = payload.status.as_deref().unwrap_or(&car.status);
+    let status = payload.status.filter(|status| status.as_str() != car.status);
+    if matches!(status, Some(VehicleStatus::Allocated | VehicleStatus::Deployed)) {
+        return Err(AppError::ValidationError(
+            "allocated and deployed are set by issuing the car and starting a trip".to_string(),
+        ));
+    }
+    let status_change = match status {
+        Some(status) => {
+            let (Some(changed_by), Some(reason)) =
+                (payload.changed_by.as_deref(), payload.reason.as_deref().map(str::trim).filter(|r| !r.is_empty()))
+            else {
+                return Err(AppError::ValidationError(
+                    "changedBy and reason are required to change the status".to_string(),
+                ));
+            };
+            utils::ensure_supervisor(db.as_ref(), changed_by, "change a vehicle's status").await?;
+            Some((status, changed_by, reason))
+        }
+        None => None,
+    };
     let mileage = payload.mileage.unwrap_or(car.mileage);
     let registration_expiry = payload.registration_expiry.or(car.registration_expiry);
     let insurance_expiry = payload.insurance_expiry.or(car.insurance_expiry);
+    let fleet_manager_id = payload.fleet_manager_id.or(car.fleet_manager_id);
+    // An empty class clears the requirement
+    let required_license_class = match &payload.required_license_class {
+        Some(class) => driver_qualifications::normalize_class(class),
+        None => car.required_license_class,
+    };
+
+    if let Some(manager_id) = &fleet_manager_id {
+        let manager_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+            .bind(manager_id)
+            .fetch_one(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+        if !manager_exists {
+            return Err(AppError::NotFound("Fleet manager not found".to_string()));
+        }
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    if let Some((status, changed_by, reason)) = status_change {
+        vehicle_status::transition(
+            &mut tx,
+            &id,
+            status,
+            &StatusChange { reason, changed_by: Some(changed_by), ..Default::default() },
+        )
+        .await?;
+    }
 
     sqlx::query(
-        "UPDATE armored_cars SET status = $1, mileage = $2, registration_expiry = $3, insurance_expiry = $4, updated_at = CURRENT_TIMESTAMP WHERE id = $5"
+        "UPDATE armored_cars SET mileage = $1, registration_expiry = $2, insurance_expiry = $3, fleet_manager_id = $4, required_license_class = $5, updated_at = CURRENT_TIMESTAMP WHERE id = $6"
     )
-    .bind(status)
     .bind(mileage)
     .bind(&registration_expiry)
     .bind(&insurance_expiry)
+    .bind(&fleet_manager_id)
+    .bind(&required_license_class)
     .bind(&id)
-    .execute(db.as_ref())
+    .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to update armored car: {}", e)))?;
 
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
     Ok(Json(json!({ "message": "Armored car updated successfully" })))
 }
 
@@ -149,8 +231,26 @@ pub async fn issue_car(
     State(db): State<Arc<PgPool>>,
     Json(payload): Json<IssueCarRequest>,
 ) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    let client_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM clients WHERE id = $1)")
+        .bind(&payload.client_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if !client_exists {
+        return Err(AppError::NotFound("Client not found".to_string()));
+    }
+
+    vehicle_maintenance::ensure_serviceable(db.as_ref(), &payload.car_id).await?;
+    let overridden = vehicle_compliance::ensure_documents_valid(db.as_ref(), &payload.car_id).await?;
+
     let id = utils::generate_id();
 
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
     sqlx::query(
         "INSERT INTO car_allocations (id, car_id, client_id, expected_return_date, notes, status) VALUES ($1, $2, $3, $4, $5, $6)"
     )
@@ -160,17 +260,33 @@ pub async fn issue_car(
     .bind(&payload.expected_return_date)
     .bind(&payload.notes)
     .bind("active")
-    .execute(db.as_ref())
+    .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to issue car: {}", e)))?;
 
-    // Update car status to allocated
-    sqlx::query("UPDATE armored_cars SET status = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
-        .bind("allocated")
-        .bind(&payload.car_id)
-        .execute(db.as_ref())
+    // Only an idle car can be issued; a deployed car returns to its own allocation after the trip
+    let from = vehicle_status::transition(
+        &mut tx,
+        &payload.car_id,
+        VehicleStatus::Allocated,
+        &StatusChange { reason: "Issued to a client", allocation_id: Some(&id), ..Default::default() },
+    )
+    .await?;
+    if from != VehicleStatus::Available {
+        return Err(AppError::Conflict(format!("Armored car is {} and cannot be issued", from)));
+    }
+
+    tx.commit()
         .await
-        .map_err(|e| AppError::DatabaseError(format!("Failed to update car status: {}", e)))?;
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    vehicle_compliance::record_override_use(
+        db.as_ref(),
+        &overridden,
+        payload.issued_by.as_deref(),
+        &format!("Issued to client {} (allocation {})", payload.client_id, id),
+    )
+    .await?;
 
     Ok((
         StatusCode::CREATED,
@@ -194,22 +310,48 @@ pub async fn return_car(
     .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
     .ok_or_else(|| AppError::NotFound("Allocation not found".to_string()))?;
 
+    if allocation.status != "active" {
+        return Err(AppError::Conflict("Car has already been returned".to_string()));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
     sqlx::query(
         "UPDATE car_allocations SET return_date = CURRENT_TIMESTAMP, status = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"
     )
     .bind("returned")
     .bind(&payload.allocation_id)
-    .execute(db.as_ref())
+    .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to return car: {}", e)))?;
 
-    // Update car status to available
-    sqlx::query("UPDATE armored_cars SET status = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
-        .bind("available")
-        .bind(&allocation.car_id)
-        .execute(db.as_ref())
+    // A car out on a trip must finish it first; one sent to maintenance stays there
+    match vehicle_status::current_status(&mut tx, &allocation.car_id).await? {
+        VehicleStatus::Deployed => {
+            return Err(AppError::Conflict("Car is on a trip; end the trip before returning it".to_string()));
+        }
+        VehicleStatus::Allocated => {
+            vehicle_status::transition(
+                &mut tx,
+                &allocation.car_id,
+                VehicleStatus::Available,
+                &StatusChange {
+                    reason: "Returned by the client",
+                    allocation_id: Some(&payload.allocation_id),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        }
+        _ => {}
+    }
+
+    tx.commit()
         .await
-        .map_err(|e| AppError::DatabaseError(format!("Failed to update car status: {}", e)))?;
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
 
     Ok(Json(json!({ "message": "Car returned successfully" })))
 }
@@ -283,7 +425,7 @@ pub async fn complete_maintenance(
     Path(maintenance_id): Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
     let maintenance = sqlx::query_as::<_, CarMaintenance>(
-        "SELECT id, car_id, maintenance_type, description, cost::FLOAT8 as cost, scheduled_date, completion_date, status, notes, created_at, updated_at FROM car_maintenance WHERE id = $1"
+        "SELECT id, car_id, maintenance_type, description, cost::FLOAT8 as cost, scheduled_date, completion_date, status, notes, created_at, updated_at, policy_id, due_mileage, auto_generated FROM car_maintenance WHERE id = $1"
     )
     .bind(&maintenance_id)
     .fetch_optional(db.as_ref())
@@ -291,22 +433,42 @@ pub async fn complete_maintenance(
     .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
     .ok_or_else(|| AppError::NotFound("Maintenance record not found".to_string()))?;
 
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
     sqlx::query(
         "UPDATE car_maintenance SET completion_date = CURRENT_TIMESTAMP, status = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"
     )
     .bind("completed")
     .bind(&maintenance_id)
-    .execute(db.as_ref())
+    .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to complete maintenance: {}", e)))?;
 
-    // Update car last maintenance date
-    sqlx::query("UPDATE armored_cars SET last_maintenance_date = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1")
+    // Update car last maintenance date; the next service interval counts from this odometer reading
+    sqlx::query("UPDATE armored_cars SET last_maintenance_date = CURRENT_TIMESTAMP, last_maintenance_mileage = COALESCE(mileage, 0), updated_at = CURRENT_TIMESTAMP WHERE id = $1")
         .bind(&maintenance.car_id)
-        .execute(db.as_ref())
+        .execute(&mut *tx)
         .await
         .map_err(|e| AppError::DatabaseError(format!("Failed to update car maintenance date: {}", e)))?;
 
+    if let Some(car_id) = &maintenance.car_id {
+        vehicle_status::transition_if(
+            &mut tx,
+            car_id,
+            VehicleStatus::Maintenance,
+            VehicleStatus::Available,
+            &StatusChange { reason: "Maintenance completed", ..Default::default() },
+        )
+        .await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
     Ok(Json(json!({ "message": "Maintenance completed successfully" })))
 }
 
@@ -315,7 +477,7 @@ pub async fn get_car_maintenance_records(
     Path(car_id): Path<String>,
 ) -> AppResult<Json<Vec<CarMaintenance>>> {
     let records = sqlx::query_as::<_, CarMaintenance>(
-        "SELECT id, car_id, maintenance_type, description, cost::FLOAT8 as cost, scheduled_date, completion_date, status, notes, created_at, updated_at FROM car_maintenance WHERE car_id = $1 ORDER BY scheduled_date DESC"
+        "SELECT id, car_id, maintenance_type, description, cost::FLOAT8 as cost, scheduled_date, completion_date, status, notes, created_at, updated_at, policy_id, due_mileage, auto_generated FROM car_maintenance WHERE car_id = $1 ORDER BY scheduled_date DESC"
     )
     .bind(&car_id)
     .fetch_all(db.as_ref())
@@ -333,6 +495,13 @@ pub async fn assign_driver(
 ) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
     let id = utils::generate_id();
 
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to acquire connection: {}", e)))?;
+
+    driver_qualifications::ensure_licensed(&mut conn, &payload.guard_id, &payload.car_id).await?;
+
     sqlx::query(
         "INSERT INTO driver_assignments (id, car_id, guard_id, status) VALUES ($1, $2, $3, $4)"
     )
@@ -340,7 +509,7 @@ pub async fn assign_driver(
     .bind(&payload.car_id)
     .bind(&payload.guard_id)
     .bind("active")
-    .execute(db.as_ref())
+    .execute(&mut *conn)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to assign driver: {}", e)))?;
 
@@ -390,8 +559,23 @@ pub async fn create_trip(
     State(db): State<Arc<PgPool>>,
     Json(payload): Json<CreateTripRequest>,
 ) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    vehicle_maintenance::ensure_serviceable(db.as_ref(), &payload.car_id).await?;
+    let overridden = vehicle_compliance::ensure_documents_valid(db.as_ref(), &payload.car_id).await?;
+
     let id = utils::generate_id();
 
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let crew = CrewManifest {
+        driver_id: &payload.driver_id,
+        escorts: &payload.escorts,
+        custodian_id: payload.custodian_id.as_deref(),
+    };
+    trip_crew::validate_crew(&mut tx, &payload.car_id, None, &crew).await?;
+
     sqlx::query(
         "INSERT INTO trips (id, car_id, driver_id, allocation_id, start_location, start_time, mission_details, status) VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP, $6, $7)"
     )
@@ -402,10 +586,39 @@ pub async fn create_trip(
     .bind(&payload.start_location)
     .bind(&payload.mission_details)
     .bind("in_transit")
-    .execute(db.as_ref())
+    .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to create trip: {}", e)))?;
 
+    // The trip starts now, so its manifest is frozen straight away
+    trip_crew::write_crew(&mut tx, &id, &crew).await?;
+    trip_crew::freeze_crew(&mut tx, &id).await?;
+
+    vehicle_status::transition(
+        &mut tx,
+        &payload.car_id,
+        VehicleStatus::Deployed,
+        &StatusChange {
+            reason: "Trip started",
+            changed_by: Some(&payload.driver_id),
+            trip_id: Some(&id),
+            allocation_id: payload.allocation_id.as_deref(),
+        },
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    vehicle_compliance::record_override_use(
+        db.as_ref(),
+        &overridden,
+        Some(&payload.driver_id),
+        &format!("Dispatched on trip {}", id),
+    )
+    .await?;
+
     Ok((
         StatusCode::CREATED,
         Json(json!({
@@ -424,18 +637,66 @@ pub async fn end_trip(
         .as_deref()
         .and_then(|s| s.parse::<f64>().ok());
 
-    sqlx::query(
-        "UPDATE trips SET end_location = $1, distance_km = $2, end_time = CURRENT_TIMESTAMP, status = $3, updated_at = CURRENT_TIMESTAMP WHERE id = $4"
+    if distance_km_f64.is_some_and(|km| km < 0.0) {
+        return Err(AppError::ValidationError("distanceKm cannot be negative".to_string()));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    // Only a trip still under way is ended, so its distance is added to the odometer once
+    let car_id = sqlx::query_scalar::<_, String>(
+        "UPDATE trips SET end_location = $1, distance_km = $2, end_time = CURRENT_TIMESTAMP, status = $3, updated_at = CURRENT_TIMESTAMP WHERE id = $4 AND status IN ('in_progress', 'in_transit') RETURNING car_id"
     )
     .bind(&payload.end_location)
     .bind(distance_km_f64)
     .bind("completed")
     .bind(&payload.trip_id)
-    .execute(db.as_ref())
+    .fetch_optional(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to end trip: {}", e)))?;
 
-    Ok(Json(json!({ "message": "Trip completed successfully" })))
+    // Only a trip under way can end; a scheduled or cancelled trip was never driven
+    let Some(car_id) = car_id else {
+        let status = sqlx::query_scalar::<_, String>("SELECT status FROM trips WHERE id = $1")
+            .bind(&payload.trip_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+        return Err(match status {
+            Some(status) => AppError::Conflict(format!("Trip is {} and cannot be ended", status)),
+            None => AppError::NotFound("Trip not found".to_string()),
+        });
+    };
+
+    if let Some(distance_km) = distance_km_f64 {
+        sqlx::query("UPDATE armored_cars SET mileage = COALESCE(mileage, 0) + ROUND($1)::INTEGER, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
+            .bind(distance_km)
+            .bind(&car_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update car mileage: {}", e)))?;
+    }
+
+    vehicle_status::release_after_trip(
+        &mut tx,
+        &car_id,
+        &StatusChange { reason: "Trip completed", trip_id: Some(&payload.trip_id), ..Default::default() },
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    let planned = vehicle_maintenance::plan_maintenance(db.as_ref(), Some(&car_id)).await?;
+
+    Ok(Json(json!({
+        "message": "Trip completed successfully",
+        "maintenancePlanned": planned
+    })))
 }
 
 pub async fn get_car_trips(
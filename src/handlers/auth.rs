@@ -10,6 +10,7 @@ use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::notification_stream::issue_stream_token,
     models::{CreateUserRequest, LoginRequest, VerifyEmailRequest, ResendCodeRequest, UserResponse},
     utils::{self, verify_password},
 };
@@ -287,7 +288,7 @@ pub async fn login(
 
     // Find user by email, username, or phone
     let user = sqlx::query(
-        r#"SELECT id, email, username, password, role, full_name, phone_number, license_number, license_expiry_date, profile_photo, verified, created_at, updated_at FROM users 
+        r#"SELECT id, email, username, password, role, full_name, phone_number, license_number, license_expiry_date, profile_photo, verified, client_id, created_at, updated_at FROM users 
            WHERE email = $1 OR username = $1 OR phone_number = $1"#
     )
     .bind(&payload.identifier)
@@ -324,6 +325,7 @@ pub async fn login(
     let phone_number: Option<String> = user.try_get("phone_number").ok();
     let license_number: Option<String> = user.try_get("license_number").ok();
     let profile_photo: Option<String> = user.try_get("profile_photo").ok();
+    let client_id: Option<String> = user.try_get("client_id").ok().flatten();
 
     Ok(Json(json!({
         "message": "Login successful",
@@ -336,7 +338,10 @@ pub async fn login(
             "phoneNumber": phone_number,
             "licenseNumber": license_number,
             "profilePhoto": profile_photo,
-        }
+            "clientId": client_id,
+        },
+        // Opens GET /api/notifications/stream
+        "streamToken": issue_stream_token(&id),
     })))
 }
 
@@ -0,0 +1,547 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::{Arc, OnceLock};
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::notifications::notify_user,
+    models::{
+        BlackoutDate, CalendarRangeQuery, CancelLeaveRequest, CreateBlackoutDateRequest,
+        CreateLeaveRequest, LeaveRequest, ReviewLeaveRequest, SetWeeklyAvailabilityRequest,
+        Shift, WeeklyAvailabilityWindow,
+    },
+    utils,
+};
+
+const LEAVE_TYPES: &[&str] = &["vacation", "sick", "emergency", "other"];
+
+/// Timezone weekly availability windows are written in (`SCHEDULE_TIMEZONE`, default Asia/Manila).
+pub fn schedule_timezone() -> &'static str {
+    static TIMEZONE: OnceLock<String> = OnceLock::new();
+    TIMEZONE.get_or_init(|| {
+        std::env::var("SCHEDULE_TIMEZONE").unwrap_or_else(|_| "Asia/Manila".to_string())
+    })
+}
+
+/// Explain why a guard cannot work the given period, or `None` if they can.
+pub async fn availability_conflict(
+    db: impl PgExecutor<'_>,
+    guard_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> AppResult<Option<String>> {
+    sqlx::query_scalar::<_, Option<String>>(
+        r#"
+        SELECT CASE
+            WHEN guard_is_available($1, $2, $3, $4) THEN NULL
+            WHEN EXISTS (
+                SELECT 1 FROM disciplinary_records
+                WHERE guard_id = $1 AND action_type = 'suspension' AND appeal_status <> 'overturned'
+                AND effective_from < $3 AND (effective_until IS NULL OR effective_until > $2)
+            ) THEN 'Guard is suspended during this period'
+            WHEN EXISTS (
+                SELECT 1 FROM guard_leave_requests
+                WHERE guard_id = $1 AND status = 'approved' AND start_date < $3 AND end_date > $2
+            ) THEN 'Guard is on approved leave during this period'
+            WHEN EXISTS (
+                SELECT 1 FROM guard_blackout_dates
+                WHERE guard_id = $1 AND start_date < $3 AND end_date > $2
+            ) THEN 'Guard has a blackout date during this period'
+            WHEN EXISTS (
+                SELECT 1 FROM guard_availability WHERE guard_id = $1 AND available = false
+            ) THEN 'Guard is marked unavailable'
+            ELSE 'Period falls outside the guard''s weekly availability'
+        END
+        "#,
+    )
+    .bind(guard_id)
+    .bind(start)
+    .bind(end)
+    .bind(schedule_timezone())
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to check availability: {}", e)))
+}
+
+/// Reject scheduling a guard outside their availability with `AppError::Conflict`.
+pub async fn ensure_guard_available(
+    db: impl PgExecutor<'_>,
+    guard_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> AppResult<()> {
+    match availability_conflict(db, guard_id, start, end).await? {
+        Some(reason) => Err(AppError::Conflict(reason)),
+        None => Ok(()),
+    }
+}
+
+pub fn parse_time_of_day(value: &str) -> AppResult<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M:%S"))
+        .map_err(|_| AppError::ValidationError(format!("Invalid time '{}', expected HH:MM", value)))
+}
+
+// Replace a guard's recurring weekly availability
+pub async fn set_weekly_availability(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+    Json(payload): Json<SetWeeklyAvailabilityRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    sqlx::query("SELECT id FROM users WHERE id = $1")
+        .bind(&guard_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+
+    let mut windows = Vec::with_capacity(payload.windows.len());
+    for window in &payload.windows {
+        if !(0..=6).contains(&window.day_of_week) {
+            return Err(AppError::ValidationError(
+                "dayOfWeek must be between 0 (Sunday) and 6 (Saturday)".to_string(),
+            ));
+        }
+        let start = parse_time_of_day(&window.start_time)?;
+        let end = parse_time_of_day(&window.end_time)?;
+        windows.push((window.day_of_week, start, end));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    sqlx::query("DELETE FROM guard_weekly_availability WHERE guard_id = $1")
+        .bind(&guard_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to clear weekly availability: {}", e)))?;
+
+    for (day_of_week, start, end) in &windows {
+        sqlx::query(
+            "INSERT INTO guard_weekly_availability (id, guard_id, day_of_week, start_time, end_time)
+             VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(utils::generate_id())
+        .bind(&guard_id)
+        .bind(day_of_week)
+        .bind(start)
+        .bind(end)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save weekly availability: {}", e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(json!({
+        "message": "Weekly availability updated successfully",
+        "windows": windows.len(),
+        "timezone": schedule_timezone()
+    })))
+}
+
+// Get a guard's recurring weekly availability
+pub async fn get_weekly_availability(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let windows = sqlx::query_as::<_, WeeklyAvailabilityWindow>(
+        "SELECT * FROM guard_weekly_availability WHERE guard_id = $1 ORDER BY day_of_week, start_time",
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": windows.len(),
+        "timezone": schedule_timezone(),
+        "windows": windows
+    })))
+}
+
+// Submit a leave request for approval
+pub async fn create_leave_request(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateLeaveRequest>,
+) -> AppResult<(StatusCode, Json<LeaveRequest>)> {
+    if !LEAVE_TYPES.contains(&payload.leave_type.as_str()) {
+        return Err(AppError::ValidationError(format!(
+            "leaveType must be one of: {}",
+            LEAVE_TYPES.join(", ")
+        )));
+    }
+    if payload.end_date <= payload.start_date {
+        return Err(AppError::ValidationError("endDate must be after startDate".to_string()));
+    }
+
+    sqlx::query("SELECT id FROM users WHERE id = $1")
+        .bind(&payload.guard_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+
+    let overlapping = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(
+             SELECT 1 FROM guard_leave_requests
+             WHERE guard_id = $1 AND status IN ('pending', 'approved')
+             AND start_date < $3 AND end_date > $2
+         )",
+    )
+    .bind(&payload.guard_id)
+    .bind(payload.start_date)
+    .bind(payload.end_date)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if overlapping {
+        return Err(AppError::Conflict(
+            "Guard already has a pending or approved leave overlapping these dates".to_string(),
+        ));
+    }
+
+    let leave = sqlx::query_as::<_, LeaveRequest>(
+        "INSERT INTO guard_leave_requests (id, guard_id, leave_type, start_date, end_date, reason, status)
+         VALUES ($1, $2, $3, $4, $5, $6, 'pending')
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.guard_id)
+    .bind(&payload.leave_type)
+    .bind(payload.start_date)
+    .bind(payload.end_date)
+    .bind(&payload.reason)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create leave request: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(leave)))
+}
+
+// List all leave requests, pending first (admin view)
+pub async fn get_leave_requests(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let leaves = sqlx::query_as::<_, LeaveRequest>(
+        "SELECT * FROM guard_leave_requests
+         ORDER BY CASE WHEN status = 'pending' THEN 0 ELSE 1 END, start_date DESC
+         LIMIT 200",
+    )
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let pending = leaves.iter().filter(|l| l.status == "pending").count();
+
+    Ok(Json(json!({
+        "total": leaves.len(),
+        "pending": pending,
+        "leaveRequests": leaves
+    })))
+}
+
+// Get a guard's leave requests
+pub async fn get_guard_leave_requests(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let leaves = sqlx::query_as::<_, LeaveRequest>(
+        "SELECT * FROM guard_leave_requests WHERE guard_id = $1 ORDER BY start_date DESC",
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": leaves.len(),
+        "leaveRequests": leaves
+    })))
+}
+
+// Approve or reject a pending leave request
+pub async fn review_leave_request(
+    State(db): State<Arc<PgPool>>,
+    Path(leave_id): Path<String>,
+    Json(payload): Json<ReviewLeaveRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let status = if payload.approved { "approved" } else { "rejected" };
+
+    let leave = sqlx::query_as::<_, LeaveRequest>(
+        "UPDATE guard_leave_requests
+         SET status = $2, reviewed_by = $3, reviewed_at = CURRENT_TIMESTAMP,
+             review_notes = $4, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND status = 'pending'
+         RETURNING *",
+    )
+    .bind(&leave_id)
+    .bind(status)
+    .bind(&payload.reviewer_id)
+    .bind(&payload.notes)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to review leave request: {}", e)))?;
+
+    let leave = match leave {
+        Some(leave) => leave,
+        None => {
+            let current = sqlx::query_scalar::<_, String>(
+                "SELECT status FROM guard_leave_requests WHERE id = $1",
+            )
+            .bind(&leave_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Leave request not found".to_string()))?;
+            return Err(AppError::Conflict(format!("Leave request is already {}", current)));
+        }
+    };
+
+    // Shifts already scheduled inside an approved leave need reassigning
+    let conflicting_shifts = if payload.approved {
+        sqlx::query_as::<_, Shift>(
+            "SELECT id, guard_id, start_time, end_time, client_site, status, created_at, updated_at
+             FROM shifts
+             WHERE guard_id = $1 AND status = 'scheduled'
+             AND start_time < $3 AND end_time > $2
+             ORDER BY start_time",
+        )
+        .bind(&leave.guard_id)
+        .bind(leave.start_date)
+        .bind(leave.end_date)
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    } else {
+        Vec::new()
+    };
+
+    notify_user(
+        db.as_ref(),
+        &leave.guard_id,
+        if payload.approved { "leave_review.approved" } else { "leave_review.rejected" },
+        Some(("leave_request", &leave.id)),
+        json!({
+            "leaveType": leave.leave_type,
+            "startDate": leave.start_date.format("%Y-%m-%d").to_string(),
+            "endDate": leave.end_date.format("%Y-%m-%d").to_string()
+        }),
+    )
+    .await?;
+
+    Ok(Json(json!({
+        "message": format!("Leave request {}", status),
+        "leaveRequest": leave,
+        "conflictingShifts": conflicting_shifts
+    })))
+}
+
+// Cancel a pending or upcoming leave request
+pub async fn cancel_leave_request(
+    State(db): State<Arc<PgPool>>,
+    Path(leave_id): Path<String>,
+    Json(payload): Json<CancelLeaveRequest>,
+) -> AppResult<Json<LeaveRequest>> {
+    let leave = sqlx::query_as::<_, LeaveRequest>("SELECT * FROM guard_leave_requests WHERE id = $1")
+        .bind(&leave_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Leave request not found".to_string()))?;
+
+    if leave.guard_id != payload.guard_id {
+        return Err(AppError::Forbidden("Leave request belongs to another guard".to_string()));
+    }
+    if !matches!(leave.status.as_str(), "pending" | "approved") {
+        return Err(AppError::Conflict(format!("Leave request is already {}", leave.status)));
+    }
+    if leave.start_date <= Utc::now() {
+        return Err(AppError::Conflict("Leave has already started".to_string()));
+    }
+
+    let leave = sqlx::query_as::<_, LeaveRequest>(
+        "UPDATE guard_leave_requests SET status = 'cancelled', updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 RETURNING *",
+    )
+    .bind(&leave_id)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to cancel leave request: {}", e)))?;
+
+    Ok(Json(leave))
+}
+
+// Add a blackout period for a guard
+pub async fn create_blackout_date(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+    Json(payload): Json<CreateBlackoutDateRequest>,
+) -> AppResult<(StatusCode, Json<BlackoutDate>)> {
+    if payload.end_date <= payload.start_date {
+        return Err(AppError::ValidationError("endDate must be after startDate".to_string()));
+    }
+
+    sqlx::query("SELECT id FROM users WHERE id = $1")
+        .bind(&guard_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+
+    let blackout = sqlx::query_as::<_, BlackoutDate>(
+        "INSERT INTO guard_blackout_dates (id, guard_id, start_date, end_date, reason)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&guard_id)
+    .bind(payload.start_date)
+    .bind(payload.end_date)
+    .bind(&payload.reason)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create blackout date: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(blackout)))
+}
+
+// Get a guard's blackout periods
+pub async fn get_blackout_dates(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let blackouts = sqlx::query_as::<_, BlackoutDate>(
+        "SELECT * FROM guard_blackout_dates WHERE guard_id = $1 ORDER BY start_date",
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": blackouts.len(),
+        "blackoutDates": blackouts
+    })))
+}
+
+// Remove a blackout period
+pub async fn delete_blackout_date(
+    State(db): State<Arc<PgPool>>,
+    Path(blackout_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM guard_blackout_dates WHERE id = $1")
+        .bind(&blackout_id)
+        .execute(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to delete blackout date: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Blackout date not found".to_string()));
+    }
+
+    Ok(Json(json!({
+        "message": "Blackout date deleted successfully"
+    })))
+}
+
+// Combined calendar for a guard: weekly windows, leave, blackouts and shifts in range.
+// Defaults to the next 30 days.
+pub async fn get_guard_calendar(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+    Query(range): Query<CalendarRangeQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let from = range.from.unwrap_or_else(Utc::now);
+    let to = range.to.unwrap_or(from + Duration::days(30));
+    if to <= from {
+        return Err(AppError::ValidationError("'to' must be after 'from'".to_string()));
+    }
+
+    let windows = sqlx::query_as::<_, WeeklyAvailabilityWindow>(
+        "SELECT * FROM guard_weekly_availability WHERE guard_id = $1 ORDER BY day_of_week, start_time",
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let leaves = sqlx::query_as::<_, LeaveRequest>(
+        "SELECT * FROM guard_leave_requests
+         WHERE guard_id = $1 AND status IN ('pending', 'approved')
+         AND start_date < $3 AND end_date > $2
+         ORDER BY start_date",
+    )
+    .bind(&guard_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let blackouts = sqlx::query_as::<_, BlackoutDate>(
+        "SELECT * FROM guard_blackout_dates
+         WHERE guard_id = $1 AND start_date < $3 AND end_date > $2
+         ORDER BY start_date",
+    )
+    .bind(&guard_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let shifts = sqlx::query_as::<_, Shift>(
+        "SELECT id, guard_id, start_time, end_time, client_site, status, created_at, updated_at
+         FROM shifts
+         WHERE guard_id = $1 AND start_time < $3 AND end_time > $2
+         ORDER BY start_time",
+    )
+    .bind(&guard_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "guardId": guard_id,
+        "from": from,
+        "to": to,
+        "timezone": schedule_timezone(),
+        "weeklyAvailability": windows,
+        "leaveRequests": leaves,
+        "blackoutDates": blackouts,
+        "shifts": shifts
+    })))
+}
+
+// Check whether a guard can work a given period
+pub async fn check_guard_availability(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+    Query(range): Query<CalendarRangeQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let (Some(from), Some(to)) = (range.from, range.to) else {
+        return Err(AppError::BadRequest("'from' and 'to' are required".to_string()));
+    };
+
+    let conflict = availability_conflict(db.as_ref(), &guard_id, from, to).await?;
+
+    Ok(Json(json!({
+        "guardId": guard_id,
+        "available": conflict.is_none(),
+        "reason": conflict
+    })))
+}
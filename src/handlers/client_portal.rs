@@ -0,0 +1,395 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{
+        evaluations::{submit_account_evaluation, EvaluationTarget},
+        notifications::notify_user,
+    },
+    models::{
+        CalendarRangeQuery, ClientSite, CreateServiceRequest, Incident, IncidentQuery,
+        PortalEvaluationRequest, ServiceRequest, ServiceRequestQuery, Trip,
+    },
+    utils,
+};
+
+const REQUEST_TYPES: &[&str] = &["guard_service", "mission", "escort", "other"];
+const PRIORITIES: &[&str] = &["low", "normal", "high", "urgent"];
+
+/// A client portal account and the organisation it can see.
+pub struct ClientScope {
+    pub user_id: String,
+    pub client_id: String,
+    pub full_name: String,
+}
+
+/// Resolve a portal user to their client organisation. Every portal query is filtered
+/// by the returned `client_id`, so staff and guard accounts are refused here.
+pub async fn client_scope(db: &PgPool, user_id: &str) -> AppResult<ClientScope> {
+    let row = sqlx::query_as::<_, (String, Option<String>, String)>(
+        "SELECT role, client_id, full_name FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    match row {
+        (role, Some(client_id), full_name) if role == "client" => Ok(ClientScope {
+            user_id: user_id.to_string(),
+            client_id,
+            full_name,
+        }),
+        _ => Err(AppError::Forbidden("Not a client portal account".to_string())),
+    }
+}
+
+// Sites owned by the user's client
+pub async fn get_portal_sites(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+
+    let sites = sqlx::query_as::<_, ClientSite>("SELECT * FROM client_sites WHERE client_id = $1 ORDER BY name")
+        .bind(&scope.client_id)
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": sites.len(),
+        "sites": sites
+    })))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct PortalShift {
+    id: String,
+    guard_id: String,
+    guard_name: String,
+    client_site: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    status: String,
+    check_in_time: Option<DateTime<Utc>>,
+    check_out_time: Option<DateTime<Utc>>,
+}
+
+// Shifts at the client's sites (defaults to the past week and next 30 days)
+pub async fn get_portal_shifts(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Query(range): Query<CalendarRangeQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+    let from = range.from.unwrap_or_else(|| Utc::now() - Duration::days(7));
+    let to = range.to.unwrap_or_else(|| Utc::now() + Duration::days(30));
+
+    let shifts = sqlx::query_as::<_, PortalShift>(
+        "SELECT s.id, s.guard_id, u.full_name AS guard_name, s.client_site, s.start_time, s.end_time, s.status,
+                a.check_in_time, a.check_out_time
+         FROM shifts s
+         JOIN client_sites cs ON cs.name = s.client_site
+         JOIN users u ON u.id = s.guard_id
+         LEFT JOIN LATERAL (
+             SELECT check_in_time, check_out_time FROM attendance
+             WHERE shift_id = s.id ORDER BY check_in_time DESC LIMIT 1
+         ) a ON true
+         WHERE cs.client_id = $1 AND s.start_time < $3 AND s.end_time > $2
+         ORDER BY s.start_time",
+    )
+    .bind(&scope.client_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "from": from,
+        "to": to,
+        "total": shifts.len(),
+        "shifts": shifts
+    })))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct PortalGuard {
+    id: String,
+    full_name: String,
+    profile_photo: Option<String>,
+    rank: Option<String>,
+    shifts_at_sites: i64,
+    last_shift_at: Option<DateTime<Utc>>,
+    next_shift_at: Option<DateTime<Utc>>,
+}
+
+// Guards who worked or are scheduled at the client's sites in the last 90 days or ahead
+pub async fn get_portal_guards(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+
+    let guards = sqlx::query_as::<_, PortalGuard>(
+        "SELECT u.id, u.full_name, u.profile_photo, gms.rank,
+                COUNT(s.id) AS shifts_at_sites,
+                MAX(s.start_time) FILTER (WHERE s.start_time <= CURRENT_TIMESTAMP) AS last_shift_at,
+                MIN(s.start_time) FILTER (WHERE s.start_time > CURRENT_TIMESTAMP) AS next_shift_at
+         FROM shifts s
+         JOIN client_sites cs ON cs.name = s.client_site
+         JOIN users u ON u.id = s.guard_id
+         LEFT JOIN guard_merit_scores gms ON gms.guard_id = u.id
+         WHERE cs.client_id = $1 AND s.start_time > CURRENT_TIMESTAMP - INTERVAL '90 days'
+         GROUP BY u.id, u.full_name, u.profile_photo, gms.rank
+         ORDER BY u.full_name",
+    )
+    .bind(&scope.client_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": guards.len(),
+        "guards": guards
+    })))
+}
+
+// Incidents at the client's sites
+pub async fn get_portal_incidents(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Query(query): Query<IncidentQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+
+    let incidents = sqlx::query_as::<_, Incident>(
+        "SELECT i.* FROM incidents i
+         JOIN client_sites cs ON cs.name = i.client_site
+         WHERE cs.client_id = $1
+         AND ($2::varchar IS NULL OR i.client_site = $2)
+         AND ($3::varchar IS NULL OR i.status = $3)
+         ORDER BY i.occurred_at DESC",
+    )
+    .bind(&scope.client_id)
+    .bind(&query.client_site)
+    .bind(&query.status)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": incidents.len(),
+        "incidents": incidents
+    })))
+}
+
+// Armored trips run under the client's car allocations
+pub async fn get_portal_trips(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+
+    let trips = sqlx::query_as::<_, Trip>(
+        "SELECT t.id, t.car_id, t.driver_id, t.allocation_id, t.start_location, t.end_location, t.start_time, t.end_time,
+                t.distance_km::FLOAT8 AS distance_km, t.status, t.mission_details, t.created_at, t.updated_at
+         FROM trips t
+         JOIN car_allocations ca ON ca.id = t.allocation_id
+         WHERE ca.client_id = $1
+         ORDER BY t.start_time DESC",
+    )
+    .bind(&scope.client_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": trips.len(),
+        "trips": trips
+    })))
+}
+
+// Rate the guard on a completed shift or mission for this client
+pub async fn submit_portal_evaluation(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<PortalEvaluationRequest>,
+) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    let target = EvaluationTarget::from_ids(payload.shift_id, payload.mission_id)?;
+    let evaluation =
+        submit_account_evaluation(db.as_ref(), &user_id, target, payload.rating, payload.comment.as_deref()).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "id": evaluation.id,
+            "guardId": evaluation.guard_id,
+            "message": "Evaluation submitted successfully"
+        })),
+    ))
+}
+
+// Request ad-hoc guarding or a mission; lands in the dispatcher queue
+pub async fn create_service_request(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<CreateServiceRequest>,
+) -> AppResult<(StatusCode, Json<ServiceRequest>)> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+
+    if payload.title.trim().is_empty() {
+        return Err(AppError::BadRequest("Title is required".to_string()));
+    }
+    if !REQUEST_TYPES.contains(&payload.request_type.as_str()) {
+        return Err(AppError::ValidationError(format!("requestType must be one of: {}", REQUEST_TYPES.join(", "))));
+    }
+    let priority = payload.priority.as_deref().unwrap_or("normal");
+    if !PRIORITIES.contains(&priority) {
+        return Err(AppError::ValidationError(format!("priority must be one of: {}", PRIORITIES.join(", "))));
+    }
+    if payload.requested_end <= payload.requested_start {
+        return Err(AppError::ValidationError("requestedEnd must be after requestedStart".to_string()));
+    }
+    if payload.requested_start < Utc::now() {
+        return Err(AppError::ValidationError("requestedStart must be in the future".to_string()));
+    }
+    let guards_required = payload.guards_required.unwrap_or(1);
+    let vehicles_required = payload.vehicles_required.unwrap_or(0);
+    if guards_required < 0 || vehicles_required < 0 {
+        return Err(AppError::ValidationError("Resource counts cannot be negative".to_string()));
+    }
+
+    if let Some(site_id) = &payload.client_site_id {
+        let owned = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM client_sites WHERE id = $1 AND client_id = $2)",
+        )
+        .bind(site_id)
+        .bind(&scope.client_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+        if !owned {
+            return Err(AppError::NotFound("Client site not found".to_string()));
+        }
+    } else if payload.location.as_deref().is_none_or(|l| l.trim().is_empty()) {
+        return Err(AppError::BadRequest("Either clientSiteId or location is required".to_string()));
+    }
+
+    let request = sqlx::query_as::<_, ServiceRequest>(
+        "INSERT INTO service_requests
+         (id, client_id, requested_by, client_site_id, request_type, title, description, location,
+          requested_start, requested_end, guards_required, vehicles_required, priority)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&scope.client_id)
+    .bind(&scope.user_id)
+    .bind(&payload.client_site_id)
+    .bind(&payload.request_type)
+    .bind(payload.title.trim())
+    .bind(&payload.description)
+    .bind(&payload.location)
+    .bind(payload.requested_start)
+    .bind(payload.requested_end)
+    .bind(guards_required)
+    .bind(vehicles_required)
+    .bind(priority)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create service request: {}", e)))?;
+
+    let dispatchers: Vec<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE role IN ('admin', 'superadmin')")
+            .fetch_all(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch dispatchers: {}", e)))?;
+
+    for dispatcher_id in dispatchers {
+        notify_user(
+            db.as_ref(),
+            &dispatcher_id,
+            "service_request.created",
+            Some(("service_request", &request.id)),
+            json!({ "requestType": request.request_type, "priority": request.priority, "title": request.title }),
+        )
+        .await?;
+    }
+
+    Ok((StatusCode::CREATED, Json(request)))
+}
+
+// The client's service requests
+pub async fn get_portal_service_requests(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Query(query): Query<ServiceRequestQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+
+    let requests = sqlx::query_as::<_, ServiceRequest>(
+        "SELECT * FROM service_requests
+         WHERE client_id = $1 AND ($2::varchar IS NULL OR status = $2)
+         ORDER BY created_at DESC",
+    )
+    .bind(&scope.client_id)
+    .bind(&query.status)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": requests.len(),
+        "requests": requests
+    })))
+}
+
+// Withdraw a service request that dispatch has not scheduled yet
+pub async fn cancel_service_request(
+    State(db): State<Arc<PgPool>>,
+    Path((user_id, request_id)): Path<(String, String)>,
+) -> AppResult<Json<ServiceRequest>> {
+    let scope = client_scope(db.as_ref(), &user_id).await?;
+
+    let request = sqlx::query_as::<_, ServiceRequest>(
+        "UPDATE service_requests SET status = 'cancelled', updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND client_id = $2 AND status IN ('pending', 'acknowledged')
+         RETURNING *",
+    )
+    .bind(&request_id)
+    .bind(&scope.client_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to cancel service request: {}", e)))?;
+
+    match request {
+        Some(request) => Ok(Json(request)),
+        None => {
+            let status = sqlx::query_scalar::<_, String>(
+                "SELECT status FROM service_requests WHERE id = $1 AND client_id = $2",
+            )
+            .bind(&request_id)
+            .bind(&scope.client_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Service request not found".to_string()))?;
+            Err(AppError::Conflict(format!("Cannot cancel a request that is {}", status)))
+        }
+    }
+}
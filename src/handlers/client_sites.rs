@@ -0,0 +1,133 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{ClientSite, CreateClientSiteRequest, UpdateClientSiteRequest},
+    utils,
+};
+
+fn validate_coordinates(latitude: Option<f64>, longitude: Option<f64>) -> AppResult<()> {
+    if latitude.is_some() != longitude.is_some() {
+        return Err(AppError::ValidationError(
+            "latitude and longitude must be provided together".to_string(),
+        ));
+    }
+    if let (Some(lat), Some(lng)) = (latitude, longitude) {
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+            return Err(AppError::ValidationError("Coordinates out of range".to_string()));
+        }
+    }
+    Ok(())
+}
+
+async fn ensure_client_exists(db: &PgPool, client_id: Option<&str>) -> AppResult<()> {
+    let Some(client_id) = client_id else {
+        return Ok(());
+    };
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM clients WHERE id = $1)")
+        .bind(client_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err(AppError::NotFound("Client not found".to_string()));
+    }
+    Ok(())
+}
+
+// Register a client site
+pub async fn create_client_site(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateClientSiteRequest>,
+) -> AppResult<(StatusCode, Json<ClientSite>)> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Site name is required".to_string()));
+    }
+    validate_coordinates(payload.latitude, payload.longitude)?;
+    ensure_client_exists(db.as_ref(), payload.client_id.as_deref()).await?;
+
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM client_sites WHERE name = $1)")
+        .bind(payload.name.trim())
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if exists {
+        return Err(AppError::Conflict(format!("Client site '{}' already exists", payload.name.trim())));
+    }
+
+    let site = sqlx::query_as::<_, ClientSite>(
+        "INSERT INTO client_sites (id, name, address, latitude, longitude, supervisor_id, client_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(payload.name.trim())
+    .bind(&payload.address)
+    .bind(payload.latitude)
+    .bind(payload.longitude)
+    .bind(&payload.supervisor_id)
+    .bind(&payload.client_id)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create client site: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(site)))
+}
+
+// List client sites
+pub async fn get_client_sites(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let sites = sqlx::query_as::<_, ClientSite>("SELECT * FROM client_sites ORDER BY name")
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": sites.len(),
+        "sites": sites
+    })))
+}
+
+// Update a client site's address, coordinates, supervisor or owning client
+pub async fn update_client_site(
+    State(db): State<Arc<PgPool>>,
+    Path(site_id): Path<String>,
+    Json(payload): Json<UpdateClientSiteRequest>,
+) -> AppResult<Json<ClientSite>> {
+    validate_coordinates(payload.latitude, payload.longitude)?;
+    ensure_client_exists(db.as_ref(), payload.client_id.as_deref()).await?;
+
+    let site = sqlx::query_as::<_, ClientSite>(
+        "UPDATE client_sites
+         SET address = COALESCE($2, address),
+             latitude = COALESCE($3, latitude),
+             longitude = COALESCE($4, longitude),
+             supervisor_id = COALESCE($5, supervisor_id),
+             client_id = COALESCE($6, client_id),
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&site_id)
+    .bind(&payload.address)
+    .bind(payload.latitude)
+    .bind(payload.longitude)
+    .bind(&payload.supervisor_id)
+    .bind(&payload.client_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update client site: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Client site not found".to_string()))?;
+
+    Ok(Json(site))
+}
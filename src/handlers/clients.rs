@@ -0,0 +1,340 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{
+        Client, ClientContract, ClientSite, ClientUser, CreateClientContractRequest, CreateClientRequest,
+        CreateClientUserRequest, UpdateClientContractRequest, UpdateClientRequest,
+    },
+    utils,
+};
+
+fn validate_contract_amounts(values: &[(&str, Option<f64>)]) -> AppResult<()> {
+    for (field, value) in values {
+        if matches!(value, Some(v) if *v < 0.0) {
+            return Err(AppError::ValidationError(format!("{} cannot be negative", field)));
+        }
+    }
+    Ok(())
+}
+
+// Register a billing client
+pub async fn create_client(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateClientRequest>,
+) -> AppResult<(StatusCode, Json<Client>)> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Client name is required".to_string()));
+    }
+
+    let client = sqlx::query_as::<_, Client>(
+        "INSERT INTO clients (id, name, billing_email, billing_address, contact_phone)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (name) DO NOTHING
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(payload.name.trim())
+    .bind(&payload.billing_email)
+    .bind(&payload.billing_address)
+    .bind(&payload.contact_phone)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create client: {}", e)))?
+    .ok_or_else(|| AppError::Conflict(format!("Client '{}' already exists", payload.name.trim())))?;
+
+    Ok((StatusCode::CREATED, Json(client)))
+}
+
+// List clients
+pub async fn get_clients(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let clients = sqlx::query_as::<_, Client>("SELECT * FROM clients ORDER BY name")
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": clients.len(),
+        "clients": clients
+    })))
+}
+
+// Get a client with its sites and contracts
+pub async fn get_client(
+    State(db): State<Arc<PgPool>>,
+    Path(client_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let client = sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE id = $1")
+        .bind(&client_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Client not found".to_string()))?;
+
+    let sites = sqlx::query_as::<_, ClientSite>("SELECT * FROM client_sites WHERE client_id = $1 ORDER BY name")
+        .bind(&client_id)
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let contracts = sqlx::query_as::<_, ClientContract>(
+        "SELECT * FROM client_contracts WHERE client_id = $1 ORDER BY start_date DESC",
+    )
+    .bind(&client_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "client": client,
+        "sites": sites,
+        "contracts": contracts
+    })))
+}
+
+// Update a client's billing details
+pub async fn update_client(
+    State(db): State<Arc<PgPool>>,
+    Path(client_id): Path<String>,
+    Json(payload): Json<UpdateClientRequest>,
+) -> AppResult<Json<Client>> {
+    let client = sqlx::query_as::<_, Client>(
+        "UPDATE clients
+         SET billing_email = COALESCE($2, billing_email),
+             billing_address = COALESCE($3, billing_address),
+             contact_phone = COALESCE($4, contact_phone),
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&client_id)
+    .bind(&payload.billing_email)
+    .bind(&payload.billing_address)
+    .bind(&payload.contact_phone)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update client: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Client not found".to_string()))?;
+
+    Ok(Json(client))
+}
+
+// Create a billing contract for a client
+pub async fn create_contract(
+    State(db): State<Arc<PgPool>>,
+    Path(client_id): Path<String>,
+    Json(payload): Json<CreateClientContractRequest>,
+) -> AppResult<(StatusCode, Json<ClientContract>)> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Contract name is required".to_string()));
+    }
+    validate_contract_amounts(&[
+        ("guardHourlyRate", Some(payload.guard_hourly_rate)),
+        ("tripRate", payload.trip_rate),
+        ("perKmRate", payload.per_km_rate),
+        ("minimumHoursPerShift", payload.minimum_hours_per_shift),
+        ("minimumInvoiceAmount", payload.minimum_invoice_amount),
+        ("nightSurchargePct", payload.night_surcharge_pct),
+        ("holidaySurchargePct", payload.holiday_surcharge_pct),
+    ])?;
+    if matches!(payload.end_date, Some(end) if end < payload.start_date) {
+        return Err(AppError::ValidationError("endDate must not be before startDate".to_string()));
+    }
+
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM clients WHERE id = $1)")
+        .bind(&client_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err(AppError::NotFound("Client not found".to_string()));
+    }
+
+    let contract = sqlx::query_as::<_, ClientContract>(
+        "INSERT INTO client_contracts
+         (id, client_id, name, currency, guard_hourly_rate, trip_rate, per_km_rate,
+          minimum_hours_per_shift, minimum_invoice_amount, night_surcharge_pct, holiday_surcharge_pct,
+          payment_terms_days, start_date, end_date)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&client_id)
+    .bind(payload.name.trim())
+    .bind(payload.currency.as_deref().unwrap_or("PHP"))
+    .bind(payload.guard_hourly_rate)
+    .bind(payload.trip_rate.unwrap_or(0.0))
+    .bind(payload.per_km_rate.unwrap_or(0.0))
+    .bind(payload.minimum_hours_per_shift.unwrap_or(0.0))
+    .bind(payload.minimum_invoice_amount.unwrap_or(0.0))
+    .bind(payload.night_surcharge_pct.unwrap_or(0.0))
+    .bind(payload.holiday_surcharge_pct.unwrap_or(0.0))
+    .bind(payload.payment_terms_days.unwrap_or(30))
+    .bind(payload.start_date)
+    .bind(payload.end_date)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create contract: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(contract)))
+}
+
+// List a client's contracts
+pub async fn get_client_contracts(
+    State(db): State<Arc<PgPool>>,
+    Path(client_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let contracts = sqlx::query_as::<_, ClientContract>(
+        "SELECT * FROM client_contracts WHERE client_id = $1 ORDER BY start_date DESC",
+    )
+    .bind(&client_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": contracts.len(),
+        "contracts": contracts
+    })))
+}
+
+// Update contract rates, terms or status. Rates apply to invoices generated afterwards.
+pub async fn update_contract(
+    State(db): State<Arc<PgPool>>,
+    Path(contract_id): Path<String>,
+    Json(payload): Json<UpdateClientContractRequest>,
+) -> AppResult<Json<ClientContract>> {
+    validate_contract_amounts(&[
+        ("guardHourlyRate", payload.guard_hourly_rate),
+        ("tripRate", payload.trip_rate),
+        ("perKmRate", payload.per_km_rate),
+        ("minimumHoursPerShift", payload.minimum_hours_per_shift),
+        ("minimumInvoiceAmount", payload.minimum_invoice_amount),
+        ("nightSurchargePct", payload.night_surcharge_pct),
+        ("holidaySurchargePct", payload.holiday_surcharge_pct),
+    ])?;
+    if let Some(status) = &payload.status {
+        if !matches!(status.as_str(), "active" | "terminated") {
+            return Err(AppError::ValidationError("status must be 'active' or 'terminated'".to_string()));
+        }
+    }
+
+    let contract = sqlx::query_as::<_, ClientContract>(
+        "UPDATE client_contracts
+         SET guard_hourly_rate = COALESCE($2, guard_hourly_rate),
+             trip_rate = COALESCE($3, trip_rate),
+             per_km_rate = COALESCE($4, per_km_rate),
+             minimum_hours_per_shift = COALESCE($5, minimum_hours_per_shift),
+             minimum_invoice_amount = COALESCE($6, minimum_invoice_amount),
+             night_surcharge_pct = COALESCE($7, night_surcharge_pct),
+             holiday_surcharge_pct = COALESCE($8, holiday_surcharge_pct),
+             payment_terms_days = COALESCE($9, payment_terms_days),
+             end_date = COALESCE($10, end_date),
+             status = COALESCE($11, status),
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&contract_id)
+    .bind(payload.guard_hourly_rate)
+    .bind(payload.trip_rate)
+    .bind(payload.per_km_rate)
+    .bind(payload.minimum_hours_per_shift)
+    .bind(payload.minimum_invoice_amount)
+    .bind(payload.night_surcharge_pct)
+    .bind(payload.holiday_surcharge_pct)
+    .bind(payload.payment_terms_days)
+    .bind(payload.end_date)
+    .bind(&payload.status)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update contract: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Contract not found".to_string()))?;
+
+    Ok(Json(contract))
+}
+
+// Create a client portal account for a client organisation
+pub async fn create_client_user(
+    State(db): State<Arc<PgPool>>,
+    Path(client_id): Path<String>,
+    Json(payload): Json<CreateClientUserRequest>,
+) -> AppResult<(StatusCode, Json<ClientUser>)> {
+    if payload.username.trim().is_empty() || payload.full_name.trim().is_empty() || payload.password.is_empty() {
+        return Err(AppError::BadRequest("Username, full name and password are required".to_string()));
+    }
+    utils::validate_email(&payload.email)?;
+
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM clients WHERE id = $1)")
+        .bind(&client_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err(AppError::NotFound("Client not found".to_string()));
+    }
+
+    let taken = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE email = $1 OR username = $2)")
+        .bind(&payload.email)
+        .bind(payload.username.trim())
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if taken {
+        return Err(AppError::Conflict("Email or username already in use".to_string()));
+    }
+
+    let password_hash = utils::hash_password(&payload.password).await?;
+
+    // Portal accounts are provisioned by staff, so they skip email verification
+    let user = sqlx::query_as::<_, ClientUser>(
+        "INSERT INTO users (id, email, username, password, role, full_name, phone_number, verified, client_id)
+         VALUES ($1, $2, $3, $4, 'client', $5, $6, true, $7)
+         RETURNING id, email, username, full_name, phone_number, client_id, created_at",
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.email)
+    .bind(payload.username.trim())
+    .bind(&password_hash)
+    .bind(payload.full_name.trim())
+    .bind(&payload.phone_number)
+    .bind(&client_id)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create client user: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+// List a client's portal accounts
+pub async fn get_client_users(
+    State(db): State<Arc<PgPool>>,
+    Path(client_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let users = sqlx::query_as::<_, ClientUser>(
+        "SELECT id, email, username, full_name, phone_number, client_id, created_at
+         FROM users WHERE role = 'client' AND client_id = $1
+         ORDER BY full_name",
+    )
+    .bind(&client_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": users.len(),
+        "users": users
+    })))
+}
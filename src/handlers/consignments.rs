@@ -0,0 +1,481 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{notifications::notify_user, trip_crew},
+    models::{Consignment, ConsignmentHandover, CreateConsignmentRequest, RecordHandoverRequest},
+    utils,
+};
+
+const STAGES: &[&str] = &["pickup", "transfer", "delivery"];
+/// Largest signature accepted (a data URL of a signature pad image is well under this).
+const MAX_SIGNATURE_LEN: usize = 200_000;
+
+/// Trimmed, non-empty, distinct seal numbers.
+fn clean_seals(seals: &[String]) -> AppResult<Vec<String>> {
+    let cleaned: Vec<String> = seals.iter().map(|seal| seal.trim().to_string()).collect();
+    if cleaned.iter().any(|seal| seal.is_empty()) {
+        return Err(AppError::ValidationError("Seal numbers cannot be blank".to_string()));
+    }
+    let distinct: BTreeSet<&String> = cleaned.iter().collect();
+    if distinct.len() != cleaned.len() {
+        return Err(AppError::ValidationError("Seal numbers must be distinct".to_string()));
+    }
+    Ok(cleaned)
+}
+
+async fn fetch_consignment(db: &PgPool, consignment_id: &str) -> AppResult<Consignment> {
+    sqlx::query_as::<_, Consignment>("SELECT * FROM consignments WHERE id = $1")
+        .bind(consignment_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Consignment not found".to_string()))
+}
+
+async fn fetch_handovers(db: &PgPool, consignment_ids: &[String]) -> AppResult<Vec<ConsignmentHandover>> {
+    sqlx::query_as::<_, ConsignmentHandover>(
+        "SELECT * FROM consignment_handovers WHERE consignment_id = ANY($1) ORDER BY created_at ASC",
+    )
+    .bind(consignment_ids)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))
+}
+
+/// POST /api/trips/:trip_id/consignments  — register a sealed consignment before it is picked up
+pub async fn create_consignment(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+    Json(payload): Json<CreateConsignmentRequest>,
+) -> AppResult<(StatusCode, Json<Consignment>)> {
+    if payload.reference.trim().is_empty() {
+        return Err(AppError::ValidationError("reference is required".to_string()));
+    }
+    if !payload.declared_value.is_finite() || payload.declared_value <= 0.0 {
+        return Err(AppError::ValidationError("declaredValue must be greater than 0".to_string()));
+    }
+    if payload.pickup_party.trim().is_empty() || payload.dropoff_party.trim().is_empty() {
+        return Err(AppError::ValidationError("pickupParty and dropoffParty are required".to_string()));
+    }
+    let seals = clean_seals(&payload.seal_numbers)?;
+    if seals.is_empty() {
+        return Err(AppError::ValidationError("At least one seal number is required".to_string()));
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.created_by, "register consignments").await?;
+
+    let trip_status = sqlx::query_scalar::<_, String>("SELECT status FROM trips WHERE id = $1")
+        .bind(&trip_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+    if matches!(trip_status.as_str(), "completed" | "cancelled") {
+        return Err(AppError::Conflict(format!("Trip is {}", trip_status)));
+    }
+
+    let consignment = sqlx::query_as::<_, Consignment>(
+        "INSERT INTO consignments (id, trip_id, reference, description, declared_value, currency, seal_numbers,
+                                   pickup_party, pickup_address, dropoff_party, dropoff_address, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&trip_id)
+    .bind(payload.reference.trim())
+    .bind(&payload.description)
+    .bind(payload.declared_value)
+    .bind(payload.currency.as_deref().unwrap_or("PHP"))
+    .bind(&seals)
+    .bind(payload.pickup_party.trim())
+    .bind(&payload.pickup_address)
+    .bind(payload.dropoff_party.trim())
+    .bind(&payload.dropoff_address)
+    .bind(&payload.created_by)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create consignment: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(consignment)))
+}
+
+/// GET /api/trips/:trip_id/consignments
+pub async fn get_trip_consignments(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let consignments = sqlx::query_as::<_, Consignment>(
+        "SELECT * FROM consignments WHERE trip_id = $1 ORDER BY created_at ASC",
+    )
+    .bind(&trip_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let total_declared_value: f64 = consignments.iter().map(|c| c.declared_value).sum();
+
+    Ok(Json(json!({
+        "total": consignments.len(),
+        "totalDeclaredValue": total_declared_value,
+        "consignments": consignments
+    })))
+}
+
+/// GET /api/consignments/:consignment_id  — the consignment and its chain of custody
+pub async fn get_consignment(
+    State(db): State<Arc<PgPool>>,
+    Path(consignment_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let consignment = fetch_consignment(db.as_ref(), &consignment_id).await?;
+    let handovers = fetch_handovers(db.as_ref(), std::slice::from_ref(&consignment.id)).await?;
+
+    Ok(Json(json!({
+        "consignment": consignment,
+        "handovers": handovers
+    })))
+}
+
+/// POST /api/consignments/:consignment_id/handovers  — a signed hand-over with a seal check
+pub async fn record_handover(
+    State(db): State<Arc<PgPool>>,
+    Path(consignment_id): Path<String>,
+    Json(payload): Json<RecordHandoverRequest>,
+) -> AppResult<(StatusCode, Json<ConsignmentHandover>)> {
+    if !STAGES.contains(&payload.stage.as_str()) {
+        return Err(AppError::ValidationError(format!("stage must be one of: {}", STAGES.join(", "))));
+    }
+    if payload.from_party.trim().is_empty() || payload.to_party.trim().is_empty() {
+        return Err(AppError::ValidationError("fromParty and toParty are required".to_string()));
+    }
+    if payload.signed_by_name.trim().is_empty() || payload.signature.trim().is_empty() {
+        return Err(AppError::ValidationError("signedByName and signature are required".to_string()));
+    }
+    if payload.signature.len() > MAX_SIGNATURE_LEN {
+        return Err(AppError::ValidationError("signature is too large".to_string()));
+    }
+    let presented = clean_seals(&payload.seal_numbers)?;
+    let broken = clean_seals(&payload.broken_seals)?;
+    if let Some(seal) = broken.iter().find(|seal| !presented.contains(seal)) {
+        return Err(AppError::ValidationError(format!("Broken seal {} is not among sealNumbers", seal)));
+    }
+
+    let consignment = fetch_consignment(db.as_ref(), &consignment_id).await?;
+
+    // The trip's crew and supervisors record hand-overs
+    if !utils::is_supervisor(db.as_ref(), &payload.recorded_by).await? {
+        let on_crew = trip_crew::crew_members(db.as_ref(), &consignment.trip_id)
+            .await?
+            .iter()
+            .any(|member| member.user_id == payload.recorded_by);
+        if !on_crew {
+            return Err(AppError::Forbidden("Only the trip's crew or a supervisor can record hand-overs".to_string()));
+        }
+    }
+
+    let registered: BTreeSet<&String> = consignment.seal_numbers.iter().collect();
+    let found: BTreeSet<&String> = presented.iter().collect();
+    let missing: Vec<String> = registered.difference(&found).map(|seal| seal.to_string()).collect();
+    let unexpected: Vec<String> = found.difference(&registered).map(|seal| seal.to_string()).collect();
+    let seals_intact = missing.is_empty() && unexpected.is_empty() && broken.is_empty();
+
+    let details = [("missing", &missing), ("unexpected", &unexpected), ("broken", &broken)]
+        .iter()
+        .filter(|(_, seals)| !seals.is_empty())
+        .map(|(label, seals)| format!("{} {}", label, seals.join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    // Seals are checked against the registration when the consignment is collected
+    if payload.stage == "pickup" && !seals_intact {
+        return Err(AppError::ValidationError(format!(
+            "Seals do not match the registered consignment: {}",
+            details
+        )));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let status = sqlx::query_scalar::<_, String>("SELECT status FROM consignments WHERE id = $1 FOR UPDATE")
+        .bind(&consignment_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let expected = match payload.stage.as_str() {
+        "pickup" => "registered",
+        _ => "in_transit",
+    };
+    if status != expected {
+        return Err(AppError::Conflict(format!(
+            "Consignment is {}; a {} hand-over is not expected",
+            status, payload.stage
+        )));
+    }
+
+    let handover = sqlx::query_as::<_, ConsignmentHandover>(
+        "INSERT INTO consignment_handovers (id, consignment_id, stage, from_party, to_party, signed_by_name, signature,
+                                            seals_presented, broken_seals, missing_seals, unexpected_seals, seals_intact,
+                                            notes, recorded_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&consignment_id)
+    .bind(&payload.stage)
+    .bind(payload.from_party.trim())
+    .bind(payload.to_party.trim())
+    .bind(payload.signed_by_name.trim())
+    .bind(&payload.signature)
+    .bind(&presented)
+    .bind(&broken)
+    .bind(&missing)
+    .bind(&unexpected)
+    .bind(seals_intact)
+    .bind(&payload.notes)
+    .bind(&payload.recorded_by)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record hand-over: {}", e)))?;
+
+    let update = match payload.stage.as_str() {
+        "pickup" => Some(
+            "UPDATE consignments SET status = 'in_transit', picked_up_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+        ),
+        "delivery" => Some(
+            "UPDATE consignments SET status = 'delivered', seals_intact = $2, delivered_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+        ),
+        _ => None,
+    };
+    if let Some(update) = update {
+        sqlx::query(update)
+            .bind(&consignment_id)
+            .bind(seals_intact)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update consignment: {}", e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    // The hand-over is recorded; a failed alert is logged rather than reported as a failed request
+    if !seals_intact {
+        let supervisors: Vec<String> =
+            match sqlx::query_scalar("SELECT id FROM users WHERE role IN ('admin', 'superadmin')")
+                .fetch_all(db.as_ref())
+                .await
+            {
+                Ok(supervisors) => supervisors,
+                Err(e) => {
+                    tracing::error!("Failed to fetch supervisors for seal breach on consignment {}: {}", consignment_id, e);
+                    Vec::new()
+                }
+            };
+
+        for supervisor_id in supervisors {
+            if let Err(e) = notify_user(
+                db.as_ref(),
+                &supervisor_id,
+                "consignment.seal_breach",
+                Some(("consignment", &consignment_id)),
+                json!({
+                    "reference": consignment.reference,
+                    "stage": payload.stage,
+                    "details": details
+                }),
+            )
+            .await
+            {
+                tracing::error!(
+                    "Failed to alert {} of seal breach on consignment {}: {}",
+                    supervisor_id, consignment_id, e
+                );
+            }
+        }
+    }
+
+    Ok((StatusCode::CREATED, Json(handover)))
+}
+
+fn render_signature(signature: &str) -> String {
+    if signature.starts_with("data:image/png;base64,") || signature.starts_with("data:image/jpeg;base64,") {
+        format!("<img class=\"sig\" src=\"{}\" alt=\"signature\">", utils::escape_html(signature))
+    } else {
+        format!("<em>{}</em>", utils::escape_html(signature))
+    }
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// GET /api/trips/:trip_id/proof-of-delivery  — printable HTML with the crew, seals and signed hand-overs
+pub async fn get_proof_of_delivery(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let (plate, start_time, end_time, trip_status): (String, DateTime<Utc>, Option<DateTime<Utc>>, String) =
+        sqlx::query_as(
+            "SELECT ac.license_plate, t.start_time, t.end_time, t.status
+             FROM trips t JOIN armored_cars ac ON ac.id = t.car_id
+             WHERE t.id = $1",
+        )
+        .bind(&trip_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+
+    let consignments = sqlx::query_as::<_, Consignment>(
+        "SELECT * FROM consignments WHERE trip_id = $1 ORDER BY created_at ASC",
+    )
+    .bind(&trip_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    if consignments.is_empty() {
+        return Err(AppError::NotFound("Trip has no consignments".to_string()));
+    }
+
+    let ids: Vec<String> = consignments.iter().map(|c| c.id.clone()).collect();
+    let handovers = fetch_handovers(db.as_ref(), &ids).await?;
+    let crew = trip_crew::crew_members(db.as_ref(), &trip_id).await?;
+
+    let crew_rows: String = crew
+        .iter()
+        .map(|member| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                utils::escape_html(&member.crew_role),
+                utils::escape_html(member.full_name.as_deref().unwrap_or(&member.user_id)),
+                utils::escape_html(member.firearm_serial_number.as_deref().unwrap_or("")),
+            )
+        })
+        .collect();
+
+    let sections: String = consignments
+        .iter()
+        .map(|consignment| {
+            let seal_check = match consignment.seals_intact {
+                Some(true) => "<span class=\"ok\">Seals intact</span>",
+                Some(false) => "<span class=\"bad\">SEALS NOT INTACT</span>",
+                None => "<span class=\"pending\">Not delivered</span>",
+            };
+            let chain: String = handovers
+                .iter()
+                .filter(|handover| handover.consignment_id == consignment.id)
+                .map(|handover| {
+                    let mut seals = if handover.seals_intact { "intact".to_string() } else { String::new() };
+                    for (label, list) in [
+                        ("missing", &handover.missing_seals),
+                        ("unexpected", &handover.unexpected_seals),
+                        ("broken", &handover.broken_seals),
+                    ] {
+                        if !list.is_empty() {
+                            seals.push_str(&format!("{} {}<br>", label, utils::escape_html(&list.join(", "))));
+                        }
+                    }
+                    format!(
+                        "<tr><td>{}</td><td>{}</td><td>{} &rarr; {}</td><td>{}<br>{}</td><td>{}</td></tr>\n",
+                        utils::escape_html(&handover.stage),
+                        format_time(Some(handover.created_at)),
+                        utils::escape_html(&handover.from_party),
+                        utils::escape_html(&handover.to_party),
+                        utils::escape_html(&handover.signed_by_name),
+                        render_signature(&handover.signature),
+                        seals,
+                    )
+                })
+                .collect();
+
+            format!(
+                r#"<h2>Consignment {reference}</h2>
+<p>{description}</p>
+<p><strong>Declared value:</strong> {currency} {value:.2}<br>
+<strong>Seals:</strong> {seals}<br>
+<strong>From:</strong> {pickup} {pickup_address}<br>
+<strong>To:</strong> {dropoff} {dropoff_address}<br>
+<strong>Delivered:</strong> {delivered} &mdash; {seal_check}</p>
+<table>
+<thead><tr><th>Stage</th><th>Time</th><th>Custody</th><th>Signed by</th><th>Seals</th></tr></thead>
+<tbody>
+{chain}</tbody>
+</table>
+"#,
+                reference = utils::escape_html(&consignment.reference),
+                description = utils::escape_html(consignment.description.as_deref().unwrap_or("")),
+                currency = utils::escape_html(&consignment.currency),
+                value = consignment.declared_value,
+                seals = utils::escape_html(&consignment.seal_numbers.join(", ")),
+                pickup = utils::escape_html(&consignment.pickup_party),
+                pickup_address = utils::escape_html(consignment.pickup_address.as_deref().unwrap_or("")),
+                dropoff = utils::escape_html(&consignment.dropoff_party),
+                dropoff_address = utils::escape_html(consignment.dropoff_address.as_deref().unwrap_or("")),
+                delivered = format_time(consignment.delivered_at),
+                seal_check = seal_check,
+                chain = chain,
+            )
+        })
+        .collect();
+
+    let delivered = consignments.iter().filter(|c| c.status == "delivered").count();
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Proof of Delivery {number}</title>
+<style>
+body {{ font-family: Arial, sans-serif; margin: 40px; color: #222; }}
+table {{ width: 100%; border-collapse: collapse; margin-top: 12px; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 6px 8px; text-align: left; vertical-align: top; }}
+.sig {{ max-height: 60px; }}
+.ok {{ color: #1a7f37; font-weight: bold; }}
+.bad {{ color: #c00; font-weight: bold; }}
+.pending {{ color: #666; }}
+</style>
+</head>
+<body>
+<h1>Proof of Delivery {number}</h1>
+<p><strong>Vehicle:</strong> {plate}<br>
+<strong>Trip:</strong> {start} to {end} ({status})<br>
+<strong>Delivered:</strong> {delivered} of {total} consignments<br>
+<strong>Generated:</strong> {generated}</p>
+<table>
+<thead><tr><th>Role</th><th>Crew</th><th>Firearm</th></tr></thead>
+<tbody>
+{crew_rows}</tbody>
+</table>
+{sections}
+</body>
+</html>
+"#,
+        number = utils::escape_html(&format!("POD-{}-{}", start_time.format("%Y%m%d"), &trip_id[..trip_id.len().min(8)])),
+        plate = utils::escape_html(&plate),
+        start = format_time(Some(start_time)),
+        end = format_time(end_time),
+        status = utils::escape_html(&trip_status),
+        delivered = delivered,
+        total = consignments.len(),
+        generated = format_time(Some(Utc::now())),
+        crew_rows = crew_rows,
+        sections = sections,
+    );
+
+    Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html))
+}
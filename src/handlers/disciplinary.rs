@@ -0,0 +1,435 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{merit, notifications::notify_user},
+    models::{
+        AppealDisciplinaryRecordRequest, Commendation, CommendationQuery, CreateCommendationRequest,
+        CreateDisciplinaryRecordRequest, DisciplinaryRecord, DisciplinaryRecordQuery, LiftDisciplinaryRecordRequest,
+        ResolveDisciplinaryAppealRequest,
+    },
+    utils,
+};
+
+/// How long a warning stays on a guard's record when no end date is given.
+/// Suspensions have no default: without an end date they last until lifted.
+fn default_duration_days(action_type: &str) -> Option<i64> {
+    match action_type {
+        "verbal_warning" => Some(90),
+        "written_warning" => Some(180),
+        "final_warning" => Some(365),
+        _ => None,
+    }
+}
+
+/// Commendations count toward merit for a year unless given an expiry.
+const COMMENDATION_DEFAULT_DAYS: i64 = 365;
+
+/// The suspension currently in force for a guard, if any. Overturned suspensions never count.
+pub async fn active_suspension(db: impl PgExecutor<'_>, guard_id: &str) -> AppResult<Option<DisciplinaryRecord>> {
+    sqlx::query_as::<_, DisciplinaryRecord>(
+        "SELECT * FROM disciplinary_records
+         WHERE guard_id = $1 AND action_type = 'suspension' AND appeal_status <> 'overturned'
+         AND effective_from <= CURRENT_TIMESTAMP
+         AND (effective_until IS NULL OR effective_until > CURRENT_TIMESTAMP)
+         ORDER BY effective_from DESC
+         LIMIT 1",
+    )
+    .bind(guard_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Suspension check error: {}", e)))
+}
+
+async fn ensure_guard(db: &PgPool, guard_id: &str) -> AppResult<()> {
+    let role = sqlx::query_scalar::<_, String>("SELECT role FROM users WHERE id = $1")
+        .bind(guard_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+
+    if role != "user" {
+        return Err(AppError::BadRequest("Records can only be issued to guards".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_evidence_urls(urls: &[String]) -> AppResult<()> {
+    if urls.iter().any(|url| !(url.starts_with("https://") || url.starts_with("http://"))) {
+        return Err(AppError::ValidationError("Evidence links must be http(s) URLs".to_string()));
+    }
+    Ok(())
+}
+
+fn describe_period(record: &DisciplinaryRecord) -> String {
+    match record.effective_until {
+        Some(until) => format!("until {}", until.format("%Y-%m-%d")),
+        None => "until further notice".to_string(),
+    }
+}
+
+// Supervisor records a warning or suspension against a guard
+pub async fn create_disciplinary_record(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateDisciplinaryRecordRequest>,
+) -> AppResult<(StatusCode, Json<DisciplinaryRecord>)> {
+    if !matches!(
+        payload.action_type.as_str(),
+        "verbal_warning" | "written_warning" | "final_warning" | "suspension"
+    ) {
+        return Err(AppError::ValidationError(
+            "actionType must be verbal_warning, written_warning, final_warning or suspension".to_string(),
+        ));
+    }
+    if !matches!(payload.severity.as_str(), "low" | "medium" | "high" | "critical") {
+        return Err(AppError::ValidationError(
+            "severity must be low, medium, high or critical".to_string(),
+        ));
+    }
+    if payload.infraction.trim().is_empty() {
+        return Err(AppError::BadRequest("An infraction is required".to_string()));
+    }
+    validate_evidence_urls(&payload.evidence_urls)?;
+
+    let effective_from = payload.effective_from.unwrap_or_else(Utc::now);
+    let effective_until: Option<DateTime<Utc>> = payload.effective_until.or_else(|| {
+        default_duration_days(&payload.action_type).map(|days| effective_from + Duration::days(days))
+    });
+    if effective_until.is_some_and(|until| until <= effective_from) {
+        return Err(AppError::ValidationError("effectiveUntil must be after effectiveFrom".to_string()));
+    }
+
+    utils::ensure_supervisor(db.as_ref(), &payload.issued_by, "issue disciplinary records").await?;
+    ensure_guard(db.as_ref(), &payload.guard_id).await?;
+
+    if let Some(incident_id) = &payload.incident_id {
+        let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM incidents WHERE id = $1)")
+            .bind(incident_id)
+            .fetch_one(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+        if !exists {
+            return Err(AppError::NotFound("Incident not found".to_string()));
+        }
+    }
+
+    let record = sqlx::query_as::<_, DisciplinaryRecord>(
+        "INSERT INTO disciplinary_records
+         (id, guard_id, action_type, severity, infraction, description, evidence_urls, incident_id, issued_by,
+          effective_from, effective_until)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.guard_id)
+    .bind(&payload.action_type)
+    .bind(&payload.severity)
+    .bind(payload.infraction.trim())
+    .bind(&payload.description)
+    .bind(&payload.evidence_urls)
+    .bind(&payload.incident_id)
+    .bind(&payload.issued_by)
+    .bind(effective_from)
+    .bind(effective_until)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create disciplinary record: {}", e)))?;
+
+    notify_user(
+        db.as_ref(),
+        &record.guard_id,
+        if record.action_type == "suspension" { "disciplinary.suspension" } else { "disciplinary.warning" },
+        Some(("disciplinary_record", &record.id)),
+        json!({
+            "actionType": record.action_type.replace('_', " "),
+            "infraction": record.infraction,
+            "period": describe_period(&record)
+        }),
+    )
+    .await?;
+
+    merit::recalculate_merit_score(db.as_ref(), &record.guard_id).await?;
+
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+// List disciplinary records, newest first, optionally for one guard or only those in effect
+pub async fn get_disciplinary_records(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<DisciplinaryRecordQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let records = sqlx::query_as::<_, DisciplinaryRecord>(
+        "SELECT * FROM disciplinary_records
+         WHERE ($1::varchar IS NULL OR guard_id = $1)
+         AND (NOT $2 OR (appeal_status <> 'overturned' AND effective_from <= CURRENT_TIMESTAMP
+                         AND (effective_until IS NULL OR effective_until > CURRENT_TIMESTAMP)))
+         ORDER BY effective_from DESC",
+    )
+    .bind(&query.guard_id)
+    .bind(query.active_only.unwrap_or(false))
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": records.len(),
+        "records": records
+    })))
+}
+
+// The guard appeals a record; each record can be appealed once
+pub async fn appeal_disciplinary_record(
+    State(db): State<Arc<PgPool>>,
+    Path(record_id): Path<String>,
+    Json(payload): Json<AppealDisciplinaryRecordRequest>,
+) -> AppResult<Json<DisciplinaryRecord>> {
+    if payload.reason.trim().is_empty() {
+        return Err(AppError::BadRequest("A reason is required".to_string()));
+    }
+
+    let record = sqlx::query_as::<_, DisciplinaryRecord>("SELECT * FROM disciplinary_records WHERE id = $1")
+        .bind(&record_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Disciplinary record not found".to_string()))?;
+
+    if record.guard_id != payload.guard_id {
+        return Err(AppError::Forbidden("Only the guard on the record can appeal it".to_string()));
+    }
+
+    let record = sqlx::query_as::<_, DisciplinaryRecord>(
+        "UPDATE disciplinary_records
+         SET appeal_status = 'pending', appeal_reason = $2, appealed_at = CURRENT_TIMESTAMP,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND appeal_status = 'none'
+         RETURNING *",
+    )
+    .bind(&record_id)
+    .bind(payload.reason.trim())
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to appeal record: {}", e)))?
+    .ok_or_else(|| AppError::Conflict("This record has already been appealed".to_string()))?;
+
+    let supervisors: Vec<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE role IN ('admin', 'superadmin')")
+            .fetch_all(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch supervisors: {}", e)))?;
+
+    for supervisor_id in supervisors {
+        notify_user(
+            db.as_ref(),
+            &supervisor_id,
+            "disciplinary_appeal.filed",
+            Some(("disciplinary_record", &record.id)),
+            json!({ "actionType": record.action_type.replace('_', " "), "infraction": record.infraction }),
+        )
+        .await?;
+    }
+
+    Ok(Json(record))
+}
+
+// Supervisor decides an appeal; an overturned record stops blocking and stops counting against merit
+pub async fn resolve_disciplinary_appeal(
+    State(db): State<Arc<PgPool>>,
+    Path(record_id): Path<String>,
+    Json(payload): Json<ResolveDisciplinaryAppealRequest>,
+) -> AppResult<Json<DisciplinaryRecord>> {
+    if !matches!(payload.outcome.as_str(), "upheld" | "overturned") {
+        return Err(AppError::ValidationError("outcome must be 'upheld' or 'overturned'".to_string()));
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.reviewed_by, "resolve appeals").await?;
+
+    let record = sqlx::query_as::<_, DisciplinaryRecord>(
+        "UPDATE disciplinary_records
+         SET appeal_status = $2, appeal_reviewed_by = $3, appeal_notes = $4,
+             appeal_resolved_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND appeal_status = 'pending'
+         RETURNING *",
+    )
+    .bind(&record_id)
+    .bind(&payload.outcome)
+    .bind(&payload.reviewed_by)
+    .bind(&payload.notes)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to resolve appeal: {}", e)))?;
+
+    let record = match record {
+        Some(record) => record,
+        None => {
+            let status = sqlx::query_scalar::<_, String>(
+                "SELECT appeal_status FROM disciplinary_records WHERE id = $1",
+            )
+            .bind(&record_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Disciplinary record not found".to_string()))?;
+            return Err(AppError::Conflict(match status.as_str() {
+                "none" => "This record has not been appealed".to_string(),
+                _ => format!("Appeal is already {}", status),
+            }));
+        }
+    };
+
+    merit::recalculate_merit_score(db.as_ref(), &record.guard_id).await?;
+
+    notify_user(
+        db.as_ref(),
+        &record.guard_id,
+        if record.appeal_status == "overturned" { "disciplinary_appeal.overturned" } else { "disciplinary_appeal.upheld" },
+        Some(("disciplinary_record", &record.id)),
+        json!({}),
+    )
+    .await?;
+
+    Ok(Json(record))
+}
+
+// Supervisor ends a record early, e.g. reinstating a suspended guard
+pub async fn lift_disciplinary_record(
+    State(db): State<Arc<PgPool>>,
+    Path(record_id): Path<String>,
+    Json(payload): Json<LiftDisciplinaryRecordRequest>,
+) -> AppResult<Json<DisciplinaryRecord>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.lifted_by, "lift disciplinary records").await?;
+
+    let record = sqlx::query_as::<_, DisciplinaryRecord>(
+        "UPDATE disciplinary_records
+         SET effective_until = GREATEST(effective_from, CURRENT_TIMESTAMP), lifted_by = $2,
+             lifted_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND appeal_status <> 'overturned'
+         AND (effective_until IS NULL OR effective_until > CURRENT_TIMESTAMP)
+         RETURNING *",
+    )
+    .bind(&record_id)
+    .bind(&payload.lifted_by)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to lift record: {}", e)))?;
+
+    let record = match record {
+        Some(record) => record,
+        None => {
+            let exists = sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(SELECT 1 FROM disciplinary_records WHERE id = $1)",
+            )
+            .bind(&record_id)
+            .fetch_one(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+            return Err(if exists {
+                AppError::Conflict("Record is no longer in effect".to_string())
+            } else {
+                AppError::NotFound("Disciplinary record not found".to_string())
+            });
+        }
+    };
+
+    merit::recalculate_merit_score(db.as_ref(), &record.guard_id).await?;
+
+    if record.action_type == "suspension" {
+        notify_user(
+            db.as_ref(),
+            &record.guard_id,
+            "disciplinary.suspension_lifted",
+            Some(("disciplinary_record", &record.id)),
+            json!({}),
+        )
+        .await?;
+    }
+
+    Ok(Json(record))
+}
+
+// Supervisor awards a commendation, which adds to the guard's merit score while it is current
+pub async fn create_commendation(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateCommendationRequest>,
+) -> AppResult<(StatusCode, Json<Commendation>)> {
+    let level = payload.level.as_deref().unwrap_or("commendation");
+    if !matches!(level, "commendation" | "award") {
+        return Err(AppError::ValidationError("level must be 'commendation' or 'award'".to_string()));
+    }
+    if payload.title.trim().is_empty() {
+        return Err(AppError::BadRequest("A title is required".to_string()));
+    }
+    validate_evidence_urls(&payload.evidence_urls)?;
+
+    let awarded_at = Utc::now();
+    let expires_at = payload
+        .expires_at
+        .unwrap_or(awarded_at + Duration::days(COMMENDATION_DEFAULT_DAYS));
+    if expires_at <= awarded_at {
+        return Err(AppError::ValidationError("expiresAt must be in the future".to_string()));
+    }
+
+    utils::ensure_supervisor(db.as_ref(), &payload.awarded_by, "award commendations").await?;
+    ensure_guard(db.as_ref(), &payload.guard_id).await?;
+
+    let commendation = sqlx::query_as::<_, Commendation>(
+        "INSERT INTO commendations
+         (id, guard_id, level, title, description, evidence_urls, awarded_by, awarded_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.guard_id)
+    .bind(level)
+    .bind(payload.title.trim())
+    .bind(&payload.description)
+    .bind(&payload.evidence_urls)
+    .bind(&payload.awarded_by)
+    .bind(awarded_at)
+    .bind(expires_at)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create commendation: {}", e)))?;
+
+    notify_user(
+        db.as_ref(),
+        &commendation.guard_id,
+        if level == "award" { "commendation.award" } else { "commendation.commendation" },
+        Some(("commendation", &commendation.id)),
+        json!({ "title": commendation.title }),
+    )
+    .await?;
+
+    merit::recalculate_merit_score(db.as_ref(), &commendation.guard_id).await?;
+
+    Ok((StatusCode::CREATED, Json(commendation)))
+}
+
+// List commendations, newest first, optionally for one guard
+pub async fn get_commendations(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<CommendationQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let commendations = sqlx::query_as::<_, Commendation>(
+        "SELECT * FROM commendations
+         WHERE ($1::varchar IS NULL OR guard_id = $1)
+         ORDER BY awarded_at DESC",
+    )
+    .bind(&query.guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": commendations.len(),
+        "commendations": commendations
+    })))
+}
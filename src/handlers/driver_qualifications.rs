@@ -0,0 +1,443 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{DriverQualifications, HoursOfService, SetDriverQualificationsRequest},
+    utils,
+};
+
+const DEFAULT_MAX_DAILY_HOURS: f64 = 10.0;
+const DEFAULT_MAX_WEEKLY_HOURS: f64 = 60.0;
+const DEFAULT_MIN_REST_HOURS: f64 = 8.0;
+
+const QUALIFICATIONS_SQL: &str = r#"
+    SELECT id AS user_id, full_name, verified, license_number, license_expiry_date, license_classes,
+           defensive_driving_cert_number, defensive_driving_cert_expiry
+    FROM users WHERE id = $1
+"#;
+
+fn hours_from_env(key: &str, default: f64) -> f64 {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|hours: &f64| hours.is_finite() && *hours > 0.0)
+        .unwrap_or(default)
+}
+
+/// A license class as stored: trimmed and upper case. None for an empty class.
+pub fn normalize_class(class: &str) -> Option<String> {
+    let class = class.trim().to_uppercase();
+    (!class.is_empty()).then_some(class)
+}
+
+async fn fetch_qualifications(conn: &mut PgConnection, driver_id: &str) -> AppResult<DriverQualifications> {
+    sqlx::query_as::<_, DriverQualifications>(QUALIFICATIONS_SQL)
+        .bind(driver_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Driver not found".to_string()))
+}
+
+/// Why the driver may not drive (a car needing `required_class`, if given), empty when qualified.
+fn qualification_problems(driver: &DriverQualifications, required_class: Option<&str>) -> Vec<String> {
+    let now = Utc::now();
+    let mut problems = Vec::new();
+
+    if !driver.verified {
+        problems.push("is not verified".to_string());
+    }
+    if driver.license_number.as_deref().is_none_or(|number| number.trim().is_empty()) {
+        problems.push("has no driver's license on file".to_string());
+    }
+    match driver.license_expiry_date {
+        None => problems.push("has no license expiry date on file".to_string()),
+        Some(expiry) if expiry < now => {
+            problems.push(format!("has a driver's license that expired on {}", expiry.format("%Y-%m-%d")))
+        }
+        Some(_) => {}
+    }
+    if let Some(class) = required_class {
+        if !driver.license_classes.iter().any(|held| held == class) {
+            problems.push(format!("does not hold a class {} license", class));
+        }
+    }
+    match driver.defensive_driving_cert_expiry {
+        _ if driver.defensive_driving_cert_number.is_none() => {
+            problems.push("has no defensive-driving certification on file".to_string())
+        }
+        None => problems.push("has no defensive-driving certification expiry on file".to_string()),
+        Some(expiry) if expiry < now => problems.push(format!(
+            "has a defensive-driving certification that expired on {}",
+            expiry.format("%Y-%m-%d")
+        )),
+        Some(_) => {}
+    }
+
+    problems
+}
+
+/// Reject a driver who may not drive this car: unverified, without a valid license of the car's
+/// class, or without current defensive-driving certification.
+pub async fn ensure_licensed(conn: &mut PgConnection, driver_id: &str, car_id: &str) -> AppResult<()> {
+    let required_class = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT required_license_class FROM armored_cars WHERE id = $1",
+    )
+    .bind(car_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Armored car not found".to_string()))?;
+
+    let driver = fetch_qualifications(conn, driver_id).await?;
+    match qualification_problems(&driver, required_class.as_deref()).first() {
+        Some(problem) => Err(AppError::Conflict(format!("{} {}", driver.full_name, problem))),
+        None => Ok(()),
+    }
+}
+
+/// Hours of the trips (start, end) that fall between `from` and `to`.
+fn hours_within(trips: &[(DateTime<Utc>, DateTime<Utc>)], from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    trips
+        .iter()
+        .map(|(start, end)| (*end.min(&to) - *start.max(&from)).num_seconds().max(0))
+        .sum::<i64>() as f64
+        / 3600.0
+}
+
+/// Hours left to drive from `at` until a planned `end_time`, None when there is no end time.
+fn planned_hours(at: DateTime<Utc>, end_time: Option<DateTime<Utc>>) -> Option<f64> {
+    end_time.map(|end| (end - at).num_seconds().max(0) as f64 / 3600.0)
+}
+
+/// Whether driving `planned` more hours after `driven` breaks `limit`. Without a planned
+/// duration the driver must still be under the limit.
+fn over_limit(driven: f64, planned: Option<f64>, limit: f64) -> bool {
+    match planned {
+        Some(planned) => driven + planned > limit,
+        None => driven >= limit,
+    }
+}
+
+fn limit_violation(driven: f64, planned: Option<f64>, limit: f64, period: &str) -> Option<String> {
+    if !over_limit(driven, planned, limit) {
+        return None;
+    }
+    Some(match planned {
+        Some(planned) => format!(
+            "would drive {:.1} hours in {} with this {:.1}-hour trip (limit {})",
+            driven + planned, period, planned, limit
+        ),
+        None => format!("has driven {:.1} hours in the last {} (limit {})", driven, period, limit),
+    })
+}
+
+/// Driving time over the 24 hours and 7 days before `at` from the driver's trips, and the rest
+/// since the last one ended. `exclude_trip` leaves out the trip being assigned, whose
+/// `planned_hours` count toward the limits instead.
+pub async fn hours_of_service(
+    conn: &mut PgConnection,
+    driver_id: &str,
+    exclude_trip: Option<&str>,
+    planned_hours: Option<f64>,
+    at: DateTime<Utc>,
+) -> AppResult<HoursOfService> {
+    let now = Utc::now();
+    let week_ago = at - Duration::days(7);
+
+    // A trip counts once it has started. Trips still under way are counted until now; a trip
+    // closed without an end time contributes nothing.
+    let trips: Vec<(String, DateTime<Utc>, DateTime<Utc>, bool)> = sqlx::query_as(
+        "SELECT id, start_time,
+                COALESCE(end_time, CASE WHEN status IN ('in_progress', 'in_transit') THEN CURRENT_TIMESTAMP ELSE start_time END),
+                end_time IS NULL AND status IN ('in_progress', 'in_transit')
+         FROM trips
+         WHERE driver_id = $1 AND ($2::varchar IS NULL OR id <> $2)
+           AND start_time <= $4
+           AND (status IN ('in_progress', 'in_transit', 'completed') OR (status = 'cancelled' AND crew_frozen_at IS NOT NULL))
+           AND COALESCE(end_time, CURRENT_TIMESTAMP) > $3",
+    )
+    .bind(driver_id)
+    .bind(exclude_trip)
+    .bind(week_ago)
+    .bind(at)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let windows: Vec<(DateTime<Utc>, DateTime<Utc>)> = trips.iter().map(|(_, start, end, _)| (*start, *end)).collect();
+    let hours_last_24h = hours_within(&windows, at - Duration::hours(24), at);
+    let hours_last_7_days = hours_within(&windows, week_ago, at);
+
+    // Looking ahead, a trip under way is assumed to end now
+    let active_trip_id = trips.iter().find(|(_, _, _, active)| *active).map(|(id, _, _, _)| id.clone());
+    let last_trip_ended_at = trips
+        .iter()
+        .filter(|(_, _, _, active)| !active || at > now)
+        .map(|(_, _, end, _)| *end)
+        .max();
+    let rest_hours = last_trip_ended_at.map(|ended| (at - ended).num_seconds().max(0) as f64 / 3600.0);
+
+    let max_daily_hours = hours_from_env("DRIVER_MAX_DAILY_HOURS", DEFAULT_MAX_DAILY_HOURS);
+    let max_weekly_hours = hours_from_env("DRIVER_MAX_WEEKLY_HOURS", DEFAULT_MAX_WEEKLY_HOURS);
+    let min_rest_hours = hours_from_env("DRIVER_MIN_REST_HOURS", DEFAULT_MIN_REST_HOURS);
+
+    let mut violations = Vec::new();
+    if let Some(trip_id) = active_trip_id.as_ref().filter(|_| at <= now) {
+        violations.push(format!("is driving trip {}", trip_id));
+    }
+    violations.extend(limit_violation(hours_last_24h, planned_hours, max_daily_hours, "24 hours"));
+    violations.extend(limit_violation(hours_last_7_days, planned_hours, max_weekly_hours, "7 days"));
+    if let Some(rest) = rest_hours.filter(|rest| *rest < min_rest_hours) {
+        violations.push(format!(
+            "has rested {:.1} hours since the last trip (minimum {})",
+            rest, min_rest_hours
+        ));
+    }
+
+    Ok(HoursOfService {
+        driver_id: driver_id.to_string(),
+        hours_last_24h,
+        hours_last_7_days,
+        last_trip_ended_at,
+        rest_hours,
+        active_trip_id,
+        max_daily_hours,
+        max_weekly_hours,
+        min_rest_hours,
+        planned_trip_hours: planned_hours,
+        violations,
+    })
+}
+
+/// Reject a driver who would break the hours-of-service rules by driving `trip_id`, at its start
+/// time if that is still ahead, or a trip starting now when None. A trip with an end time counts
+/// its planned hours from then on.
+pub async fn ensure_rested(conn: &mut PgConnection, driver_id: &str, trip_id: Option<&str>) -> AppResult<()> {
+    let now = Utc::now();
+    let (start_time, end_time) = match trip_id {
+        Some(trip_id) => sqlx::query_as::<_, (DateTime<Utc>, Option<DateTime<Utc>>)>(
+            "SELECT start_time, end_time FROM trips WHERE id = $1",
+        )
+        .bind(trip_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .unwrap_or((now, None)),
+        None => (now, None),
+    };
+
+    ensure_rested_for(conn, driver_id, trip_id, start_time, end_time).await
+}
+
+/// Reject a driver who would break the hours-of-service rules by driving from `start_time` to
+/// `end_time`, e.g. a trip not created yet. `exclude_trip` is the trip itself once it exists.
+pub async fn ensure_rested_for(
+    conn: &mut PgConnection,
+    driver_id: &str,
+    exclude_trip: Option<&str>,
+    start_time: DateTime<Utc>,
+    end_time: Option<DateTime<Utc>>,
+) -> AppResult<()> {
+    let at = start_time.max(Utc::now());
+    let planned_hours = planned_hours(at, end_time);
+
+    let hours = hours_of_service(conn, driver_id, exclude_trip, planned_hours, at).await?;
+    let Some(violation) = hours.violations.first() else { return Ok(()) };
+
+    let name = sqlx::query_scalar::<_, String>("SELECT full_name FROM users WHERE id = $1")
+        .bind(driver_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .unwrap_or_else(|| driver_id.to_string());
+
+    Err(AppError::Conflict(format!("{} {}", name, violation)))
+}
+
+/// GET /api/users/:user_id/driver-qualifications  — license, certification and hours of service
+pub async fn get_driver_qualifications(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to acquire connection: {}", e)))?;
+
+    let driver = fetch_qualifications(&mut conn, &user_id).await?;
+    let hours = hours_of_service(&mut conn, &user_id, None, None, Utc::now()).await?;
+    let problems = qualification_problems(&driver, None);
+
+    Ok(Json(json!({
+        "qualifications": driver,
+        "problems": problems,
+        "hoursOfService": hours,
+        "canDrive": problems.is_empty() && hours.violations.is_empty()
+    })))
+}
+
+/// PUT /api/users/:user_id/driver-qualifications  — license classes and defensive-driving certification
+pub async fn set_driver_qualifications(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<SetDriverQualificationsRequest>,
+) -> AppResult<Json<DriverQualifications>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.updated_by, "update driver qualifications").await?;
+
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to acquire connection: {}", e)))?;
+    let current = fetch_qualifications(&mut conn, &user_id).await?;
+
+    let license_classes = match &payload.license_classes {
+        Some(classes) => {
+            let mut classes: Vec<String> = classes.iter().filter_map(|class| normalize_class(class)).collect();
+            classes.sort();
+            classes.dedup();
+            classes
+        }
+        None => current.license_classes,
+    };
+    // An empty certificate number clears the certification
+    let (cert_number, cert_expiry) = match payload.defensive_driving_cert_number.as_deref().map(str::trim) {
+        Some("") => (None, None),
+        Some(number) => (
+            Some(number.to_string()),
+            payload.defensive_driving_cert_expiry.or(current.defensive_driving_cert_expiry),
+        ),
+        None => (
+            current.defensive_driving_cert_number,
+            payload.defensive_driving_cert_expiry.or(current.defensive_driving_cert_expiry),
+        ),
+    };
+    if cert_number.is_some() && cert_expiry.is_none() {
+        return Err(AppError::ValidationError(
+            "defensiveDrivingCertExpiry is required with a certification".to_string(),
+        ));
+    }
+
+    sqlx::query(
+        "UPDATE users SET license_classes = $1, defensive_driving_cert_number = $2, defensive_driving_cert_expiry = $3,
+                          updated_at = CURRENT_TIMESTAMP
+         WHERE id = $4",
+    )
+    .bind(&license_classes)
+    .bind(&cert_number)
+    .bind(cert_expiry)
+    .bind(&user_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update driver qualifications: {}", e)))?;
+
+    Ok(Json(fetch_qualifications(&mut conn, &user_id).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qualified_driver() -> DriverQualifications {
+        let next_year = Utc::now() + Duration::days(365);
+        DriverQualifications {
+            user_id: "driver".to_string(),
+            full_name: "Driver".to_string(),
+            verified: true,
+            license_number: Some("N01-23-456789".to_string()),
+            license_expiry_date: Some(next_year),
+            license_classes: vec!["B".to_string(), "C".to_string()],
+            defensive_driving_cert_number: Some("DD-1".to_string()),
+            defensive_driving_cert_expiry: Some(next_year),
+        }
+    }
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        use chrono::TimeZone;
+        Utc.with_ymd_and_hms(2026, 3, 2, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn a_qualified_driver_has_no_problems() {
+        assert!(qualification_problems(&qualified_driver(), Some("C")).is_empty());
+        assert!(qualification_problems(&qualified_driver(), None).is_empty());
+    }
+
+    #[test]
+    fn an_expired_license_is_a_problem() {
+        let mut driver = qualified_driver();
+        driver.license_expiry_date = Some(Utc::now() - Duration::days(1));
+        let problems = qualification_problems(&driver, None);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("has a driver's license that expired on"), "{}", problems[0]);
+    }
+
+    #[test]
+    fn a_missing_license_class_is_a_problem() {
+        assert_eq!(
+            qualification_problems(&qualified_driver(), Some("D")),
+            vec!["does not hold a class D license".to_string()]
+        );
+    }
+
+    #[test]
+    fn an_expired_certification_is_a_problem() {
+        let mut driver = qualified_driver();
+        driver.defensive_driving_cert_expiry = Some(Utc::now() - Duration::days(1));
+        let problems = qualification_problems(&driver, None);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("has a defensive-driving certification that expired"), "{}", problems[0]);
+    }
+
+    #[test]
+    fn hours_are_clipped_to_the_window() {
+        let trips = [(at(1), at(3)), (at(5), at(9)), (at(10), at(12))];
+        assert_eq!(hours_within(&trips, at(0), at(12)), 8.0);
+        assert_eq!(hours_within(&trips, at(2), at(6)), 2.0);
+        assert_eq!(hours_within(&trips, at(3), at(5)), 0.0);
+        assert_eq!(hours_within(&[], at(0), at(12)), 0.0);
+    }
+
+    #[test]
+    fn a_planned_trip_may_reach_the_limit_but_not_pass_it() {
+        assert!(!over_limit(6.0, Some(4.0), 10.0));
+        assert!(over_limit(6.0, Some(4.5), 10.0));
+        assert!(over_limit(0.0, Some(10.5), 10.0));
+        assert!(!over_limit(56.0, Some(4.0), 60.0));
+        assert!(over_limit(56.5, Some(4.0), 60.0));
+    }
+
+    #[test]
+    fn a_trip_counts_the_hours_left_from_when_it_starts() {
+        assert_eq!(planned_hours(at(8), Some(at(18))), Some(10.0));
+        assert_eq!(planned_hours(at(12), Some(at(18))), Some(6.0));
+        assert_eq!(planned_hours(at(19), Some(at(18))), Some(0.0));
+        assert_eq!(planned_hours(at(8), None), None);
+    }
+
+    #[test]
+    fn without_a_planned_end_the_driver_must_be_under_the_limit() {
+        assert!(!over_limit(9.9, None, 10.0));
+        assert!(over_limit(10.0, None, 10.0));
+    }
+
+    #[test]
+    fn the_violation_counts_the_planned_trip() {
+        assert_eq!(limit_violation(6.0, Some(4.0), 10.0, "24 hours"), None);
+        assert_eq!(
+            limit_violation(7.0, Some(4.0), 10.0, "24 hours").as_deref(),
+            Some("would drive 11.0 hours in 24 hours with this 4.0-hour trip (limit 10)")
+        );
+        assert_eq!(
+            limit_violation(10.0, None, 10.0, "24 hours").as_deref(),
+            Some("has driven 10.0 hours in the last 24 hours (limit 10)")
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{client_portal::client_scope, merit, notifications::notify_user},
+    models::{
+        ClientEvaluation, CreateEvaluationDisputeRequest, CreateEvaluationLinkRequest, EvaluationDispute,
+        EvaluationDisputeQuery, EvaluationLink, ResolveEvaluationDisputeRequest, SubmitLinkEvaluationRequest,
+    },
+    utils,
+};
+
+const DEFAULT_LINK_TTL_HOURS: i64 = 72;
+const MAX_LINK_TTL_HOURS: i64 = 24 * 30;
+/// Guards can contest a rating for this long after it was submitted.
+const DISPUTE_WINDOW_DAYS: i64 = 30;
+
+const EVALUATION_COLUMNS: &str = "id, guard_id, shift_id, mission_id, evaluator_name, evaluator_role,
+    CAST(rating AS FLOAT8) AS rating, comment, created_at, client_user_id, verified, status";
+
+/// What is being rated: a shift, or a mission (armored trip).
+pub enum EvaluationTarget {
+    Shift(String),
+    Mission(String),
+}
+
+impl EvaluationTarget {
+    pub fn from_ids(shift_id: Option<String>, mission_id: Option<String>) -> AppResult<Self> {
+        match (shift_id, mission_id) {
+            (Some(shift_id), None) => Ok(Self::Shift(shift_id)),
+            (None, Some(mission_id)) => Ok(Self::Mission(mission_id)),
+            _ => Err(AppError::BadRequest("Provide exactly one of shiftId or missionId".to_string())),
+        }
+    }
+
+    fn ids(&self) -> (Option<&str>, Option<&str>) {
+        match self {
+            Self::Shift(id) => (Some(id), None),
+            Self::Mission(id) => (None, Some(id)),
+        }
+    }
+}
+
+struct EligibleTarget {
+    client_id: String,
+    guard_id: String,
+}
+
+pub fn validate_rating(rating: f64) -> AppResult<()> {
+    if !(1.0..=5.0).contains(&rating) {
+        return Err(AppError::ValidationError("Rating must be between 1 and 5".to_string()));
+    }
+    Ok(())
+}
+
+fn link_secret() -> AppResult<String> {
+    ["EVALUATION_LINK_SECRET", "JWT_SECRET"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|s| !s.is_empty())
+        .ok_or_else(|| AppError::InternalServerError("EVALUATION_LINK_SECRET is not configured".to_string()))
+}
+
+fn link_mac(link: &EvaluationLink) -> AppResult<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(link_secret()?.as_bytes())
+        .map_err(|e| AppError::InternalServerError(format!("Invalid link secret: {}", e)))?;
+    let target = link.shift_id.as_deref().or(link.mission_id.as_deref()).unwrap_or_default();
+    mac.update(format!("{}|{}|{}|{}", link.id, target, link.guard_id, link.expires_at.timestamp()).as_bytes());
+    Ok(mac)
+}
+
+fn sign_link(link: &EvaluationLink) -> AppResult<String> {
+    let signature = link_mac(link)?.finalize().into_bytes();
+    Ok(format!("{}.{}", link.id, hex::encode(signature)))
+}
+
+/// Check a token's signature against the stored link, in constant time.
+fn verify_link_signature(link: &EvaluationLink, signature: &str) -> AppResult<()> {
+    let signature = hex::decode(signature).map_err(|_| AppError::Unauthorized("Invalid evaluation link".to_string()))?;
+    link_mac(link)?
+        .verify_slice(&signature)
+        .map_err(|_| AppError::Unauthorized("Invalid evaluation link".to_string()))
+}
+
+/// Only completed shifts (checked out, and ended) or completed missions can be rated,
+/// and only by the client whose site or allocation they were for.
+async fn eligible_target(
+    conn: &mut PgConnection,
+    target: &EvaluationTarget,
+    client_id: Option<&str>,
+) -> AppResult<EligibleTarget> {
+    let (owner, guard_id, completed) = match target {
+        EvaluationTarget::Shift(shift_id) => sqlx::query_as::<_, (Option<String>, String, bool)>(
+            "SELECT cs.client_id, s.guard_id,
+                    s.end_time <= CURRENT_TIMESTAMP AND EXISTS(
+                        SELECT 1 FROM attendance a WHERE a.shift_id = s.id AND a.check_out_time IS NOT NULL
+                    )
+             FROM shifts s
+             LEFT JOIN client_sites cs ON cs.name = s.client_site
+             WHERE s.id = $1",
+        )
+        .bind(shift_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?,
+        EvaluationTarget::Mission(trip_id) => sqlx::query_as::<_, (Option<String>, String, bool)>(
+            "SELECT ca.client_id, t.driver_id, t.status = 'completed'
+             FROM trips t
+             LEFT JOIN car_allocations ca ON ca.id = t.allocation_id
+             WHERE t.id = $1",
+        )
+        .bind(trip_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Mission not found".to_string()))?,
+    };
+
+    let owner = owner.ok_or_else(|| AppError::BadRequest("This assignment is not linked to a client".to_string()))?;
+    if client_id.is_some_and(|client_id| client_id != owner) {
+        return Err(AppError::Forbidden("This assignment was not at your site".to_string()));
+    }
+    if !completed {
+        return Err(AppError::BadRequest("Only completed shifts or missions can be evaluated".to_string()));
+    }
+
+    Ok(EligibleTarget { client_id: owner, guard_id })
+}
+
+async fn ensure_not_rated(conn: &mut PgConnection, target: &EvaluationTarget) -> AppResult<()> {
+    let (shift_id, mission_id) = target.ids();
+    let rated = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM client_evaluations
+                       WHERE verified AND (shift_id = $1 OR mission_id = $2))",
+    )
+    .bind(shift_id)
+    .bind(mission_id)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if rated {
+        return Err(AppError::Conflict("This assignment has already been evaluated".to_string()));
+    }
+    Ok(())
+}
+
+struct Evaluator<'a> {
+    name: &'a str,
+    client_user_id: Option<&'a str>,
+    link_id: Option<&'a str>,
+}
+
+async fn insert_evaluation(
+    conn: &mut PgConnection,
+    target: &EvaluationTarget,
+    eligible: &EligibleTarget,
+    evaluator: Evaluator<'_>,
+    rating: f64,
+    comment: Option<&str>,
+) -> AppResult<ClientEvaluation> {
+    let (shift_id, mission_id) = target.ids();
+    sqlx::query_as::<_, ClientEvaluation>(&format!(
+        "INSERT INTO client_evaluations
+         (id, guard_id, shift_id, mission_id, evaluator_name, evaluator_role, rating, comment,
+          client_user_id, client_id, evaluation_link_id, verified)
+         VALUES ($1, $2, $3, $4, $5, 'client', $6, $7, $8, $9, $10, true)
+         RETURNING {}",
+        EVALUATION_COLUMNS
+    ))
+    .bind(utils::generate_id())
+    .bind(&eligible.guard_id)
+    .bind(shift_id)
+    .bind(mission_id)
+    .bind(evaluator.name)
+    .bind(rating)
+    .bind(comment)
+    .bind(evaluator.client_user_id)
+    .bind(&eligible.client_id)
+    .bind(evaluator.link_id)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| {
+        let unique_violation = e
+            .as_database_error()
+            .and_then(|d| d.code())
+            .is_some_and(|code| code == "23505");
+        if unique_violation {
+            AppError::Conflict("This assignment has already been evaluated".to_string())
+        } else {
+            AppError::DatabaseError(format!("Failed to create evaluation: {}", e))
+        }
+    })
+}
+
+/// Record an evaluation from an authenticated client portal account.
+pub async fn submit_account_evaluation(
+    db: &PgPool,
+    client_user_id: &str,
+    target: EvaluationTarget,
+    rating: f64,
+    comment: Option<&str>,
+) -> AppResult<ClientEvaluation> {
+    validate_rating(rating)?;
+    let scope = client_scope(db, client_user_id).await?;
+
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to acquire connection: {}", e)))?;
+
+    let eligible = eligible_target(&mut conn, &target, Some(&scope.client_id)).await?;
+    ensure_not_rated(&mut conn, &target).await?;
+
+    insert_evaluation(
+        &mut conn,
+        &target,
+        &eligible,
+        Evaluator {
+            name: &scope.full_name,
+            client_user_id: Some(&scope.user_id),
+            link_id: None,
+        },
+        rating,
+        comment,
+    )
+    .await
+}
+
+// Staff issue a one-time signed evaluation link for a completed shift or mission
+pub async fn create_evaluation_link(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateEvaluationLinkRequest>,
+) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    utils::ensure_supervisor(db.as_ref(), &payload.created_by, "issue evaluation links").await?;
+    let ttl_hours = payload.expires_in_hours.unwrap_or(DEFAULT_LINK_TTL_HOURS);
+    if !(1..=MAX_LINK_TTL_HOURS).contains(&ttl_hours) {
+        return Err(AppError::ValidationError(format!("expiresInHours must be between 1 and {}", MAX_LINK_TTL_HOURS)));
+    }
+    if let Some(email) = &payload.evaluator_email {
+        utils::validate_email(email)?;
+    }
+    let target = EvaluationTarget::from_ids(payload.shift_id, payload.mission_id)?;
+    // Fail before writing anything if links cannot be signed
+    link_secret()?;
+
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to acquire connection: {}", e)))?;
+
+    let eligible = eligible_target(&mut conn, &target, None).await?;
+    ensure_not_rated(&mut conn, &target).await?;
+
+    let (shift_id, mission_id) = target.ids();
+    // Truncate to whole seconds so the signed expiry matches what is stored
+    let expires_at = DateTime::<Utc>::from_timestamp((Utc::now() + Duration::hours(ttl_hours)).timestamp(), 0)
+        .unwrap_or_else(Utc::now);
+
+    let link = sqlx::query_as::<_, EvaluationLink>(
+        "INSERT INTO evaluation_links (id, shift_id, mission_id, client_id, guard_id, evaluator_email, created_by, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(shift_id)
+    .bind(mission_id)
+    .bind(&eligible.client_id)
+    .bind(&eligible.guard_id)
+    .bind(&payload.evaluator_email)
+    .bind(&payload.created_by)
+    .bind(expires_at)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create evaluation link: {}", e)))?;
+
+    let token = sign_link(&link)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "link": link,
+            "token": token
+        })),
+    ))
+}
+
+async fn load_link(conn: &mut PgConnection, token: &str, for_update: bool) -> AppResult<EvaluationLink> {
+    let (link_id, signature) = token
+        .split_once('.')
+        .ok_or_else(|| AppError::Unauthorized("Invalid evaluation link".to_string()))?;
+
+    let query = if for_update {
+        "SELECT * FROM evaluation_links WHERE id = $1 FOR UPDATE"
+    } else {
+        "SELECT * FROM evaluation_links WHERE id = $1"
+    };
+    let link = sqlx::query_as::<_, EvaluationLink>(query)
+        .bind(link_id)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::Unauthorized("Invalid evaluation link".to_string()))?;
+
+    verify_link_signature(&link, signature)?;
+
+    if link.used_at.is_some() {
+        return Err(AppError::Conflict("This evaluation link has already been used".to_string()));
+    }
+    if link.expires_at <= Utc::now() {
+        return Err(AppError::Conflict("This evaluation link has expired".to_string()));
+    }
+    Ok(link)
+}
+
+// What an evaluation link is for, so the form can show the guard and assignment
+pub async fn get_evaluation_link(
+    State(db): State<Arc<PgPool>>,
+    Path(token): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to acquire connection: {}", e)))?;
+    let link = load_link(&mut conn, &token, false).await?;
+
+    let (guard_name, client_name) = sqlx::query_as::<_, (String, String)>(
+        "SELECT u.full_name, c.name FROM users u, clients c WHERE u.id = $1 AND c.id = $2",
+    )
+    .bind(&link.guard_id)
+    .bind(&link.client_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "guardName": guard_name,
+        "clientName": client_name,
+        "shiftId": link.shift_id,
+        "missionId": link.mission_id,
+        "expiresAt": link.expires_at
+    })))
+}
+
+// Submit an evaluation through a one-time link
+pub async fn submit_link_evaluation(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<SubmitLinkEvaluationRequest>,
+) -> AppResult<(StatusCode, Json<ClientEvaluation>)> {
+    validate_rating(payload.rating)?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let link = load_link(&mut tx, &payload.token, true).await?;
+    let target = EvaluationTarget::from_ids(link.shift_id.clone(), link.mission_id.clone())?;
+    let eligible = eligible_target(&mut tx, &target, Some(&link.client_id)).await?;
+    ensure_not_rated(&mut tx, &target).await?;
+
+    let name = payload
+        .evaluator_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .or(link.evaluator_email.as_deref())
+        .unwrap_or("Client");
+
+    let evaluation = insert_evaluation(
+        &mut tx,
+        &target,
+        &eligible,
+        Evaluator {
+            name,
+            client_user_id: None,
+            link_id: Some(&link.id),
+        },
+        payload.rating,
+        payload.comment.as_deref(),
+    )
+    .await?;
+
+    sqlx::query("UPDATE evaluation_links SET used_at = CURRENT_TIMESTAMP, evaluation_id = $2 WHERE id = $1")
+        .bind(&link.id)
+        .bind(&evaluation.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to consume evaluation link: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(evaluation)))
+}
+
+// A guard contests a rating
+pub async fn create_evaluation_dispute(
+    State(db): State<Arc<PgPool>>,
+    Path(evaluation_id): Path<String>,
+    Json(payload): Json<CreateEvaluationDisputeRequest>,
+) -> AppResult<(StatusCode, Json<EvaluationDispute>)> {
+    if payload.reason.trim().is_empty() {
+        return Err(AppError::BadRequest("A reason is required".to_string()));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let evaluation = sqlx::query_as::<_, ClientEvaluation>(&format!(
+        "SELECT {} FROM client_evaluations WHERE id = $1 FOR UPDATE",
+        EVALUATION_COLUMNS
+    ))
+    .bind(&evaluation_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Evaluation not found".to_string()))?;
+
+    if evaluation.guard_id != payload.guard_id {
+        return Err(AppError::Forbidden("Only the rated guard can dispute this evaluation".to_string()));
+    }
+    if evaluation.status != "active" {
+        return Err(AppError::Conflict(format!("Evaluation is already {}", evaluation.status)));
+    }
+    if evaluation
+        .created_at
+        .is_some_and(|created| created < Utc::now() - Duration::days(DISPUTE_WINDOW_DAYS))
+    {
+        return Err(AppError::Conflict(format!(
+            "Evaluations can only be disputed within {} days",
+            DISPUTE_WINDOW_DAYS
+        )));
+    }
+
+    let dispute = sqlx::query_as::<_, EvaluationDispute>(
+        "INSERT INTO evaluation_disputes (id, evaluation_id, guard_id, reason)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (evaluation_id) DO NOTHING
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&evaluation_id)
+    .bind(&payload.guard_id)
+    .bind(payload.reason.trim())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create dispute: {}", e)))?
+    .ok_or_else(|| AppError::Conflict("This evaluation has already been disputed".to_string()))?;
+
+    sqlx::query("UPDATE client_evaluations SET status = 'disputed' WHERE id = $1")
+        .bind(&evaluation_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update evaluation: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    let supervisors: Vec<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE role IN ('admin', 'superadmin')")
+            .fetch_all(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch supervisors: {}", e)))?;
+
+    for supervisor_id in supervisors {
+        notify_user(
+            db.as_ref(),
+            &supervisor_id,
+            "evaluation_dispute.filed",
+            Some(("evaluation_dispute", &dispute.id)),
+            json!({
+                "rating": format!("{:.1}", evaluation.rating),
+                "evaluatorName": evaluation.evaluator_name,
+                "shiftId": evaluation.shift_id
+            }),
+        )
+        .await?;
+    }
+
+    Ok((StatusCode::CREATED, Json(dispute)))
+}
+
+// List disputes (open by default)
+pub async fn get_evaluation_disputes(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<EvaluationDisputeQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let disputes = sqlx::query_as::<_, EvaluationDispute>(
+        "SELECT * FROM evaluation_disputes WHERE status = $1 ORDER BY created_at",
+    )
+    .bind(query.status.as_deref().unwrap_or("open"))
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": disputes.len(),
+        "disputes": disputes
+    })))
+}
+
+// Supervisor resolves a dispute; an accepted dispute removes the rating from merit scoring
+pub async fn resolve_evaluation_dispute(
+    State(db): State<Arc<PgPool>>,
+    Path(dispute_id): Path<String>,
+    Json(payload): Json<ResolveEvaluationDisputeRequest>,
+) -> AppResult<Json<EvaluationDispute>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.reviewer_id, "resolve disputes").await?;
+
+    let evaluation_status = match payload.outcome.as_str() {
+        "accepted" => "overturned",
+        "rejected" => "active",
+        _ => return Err(AppError::ValidationError("outcome must be 'accepted' or 'rejected'".to_string())),
+    };
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let dispute = sqlx::query_as::<_, EvaluationDispute>(
+        "UPDATE evaluation_disputes
+         SET status = $2, reviewed_by = $3, review_notes = $4, resolved_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND status = 'open'
+         RETURNING *",
+    )
+    .bind(&dispute_id)
+    .bind(&payload.outcome)
+    .bind(&payload.reviewer_id)
+    .bind(&payload.notes)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to resolve dispute: {}", e)))?;
+
+    let dispute = match dispute {
+        Some(dispute) => dispute,
+        None => {
+            let status = sqlx::query_scalar::<_, String>("SELECT status FROM evaluation_disputes WHERE id = $1")
+                .bind(&dispute_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+                .ok_or_else(|| AppError::NotFound("Dispute not found".to_string()))?;
+            return Err(AppError::Conflict(format!("Dispute is already {}", status)));
+        }
+    };
+
+    sqlx::query("UPDATE client_evaluations SET status = $2 WHERE id = $1")
+        .bind(&dispute.evaluation_id)
+        .bind(evaluation_status)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update evaluation: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    merit::recalculate_merit_score(db.as_ref(), &dispute.guard_id).await?;
+
+    notify_user(
+        db.as_ref(),
+        &dispute.guard_id,
+        if dispute.status == "accepted" { "evaluation_dispute.accepted" } else { "evaluation_dispute.rejected" },
+        Some(("evaluation_dispute", &dispute.id)),
+        json!({}),
+    )
+    .await?;
+
+    Ok(Json(dispute))
+}
@@ -9,6 +9,7 @@ use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::{disciplinary::active_suspension, notifications::notify_user},
     models::{FirearmAllocation, GuardAllocationView, IssueFirearmRequest, ReturnFirearmRequest},
     utils,
 };
@@ -49,7 +50,15 @@ pub async fn issue_firearm(
         .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
         .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
 
-    // ── 3. Authorization: valid permit check ─────────────────────────────────
+    // ── 3. Suspended guards cannot draw firearms, even with force ────────────
+    if let Some(suspension) = active_suspension(db.as_ref(), &payload.guard_id).await? {
+        return Err(AppError::Forbidden(format!(
+            "Guard is suspended ({}) and cannot be issued a firearm",
+            suspension.infraction
+        )));
+    }
+
+    // ── 4. Authorization: valid permit check ─────────────────────────────────
     if !force {
         let permit = sqlx::query(
             r#"SELECT id FROM guard_firearm_permits
@@ -71,7 +80,7 @@ pub async fn issue_firearm(
         }
     }
 
-    // ── 4. Authorization: firearms_handling training check ───────────────────
+    // ── 5. Authorization: firearms_handling training check ───────────────────
     if !force {
         let training = sqlx::query(
             r#"SELECT id FROM training_records
@@ -94,7 +103,7 @@ pub async fn issue_firearm(
         }
     }
 
-    // ── 5. Create allocation ─────────────────────────────────────────────────
+    // ── 6. Create allocation ─────────────────────────────────────────────────
     let allocation_id = utils::generate_id();
 
     sqlx::query(
@@ -279,3 +288,69 @@ pub async fn get_overdue_allocations(
 }
 
 
+
+/// Notify guards (and admins) about overdue firearm returns, at most once a day per allocation.
+/// Run periodically by the background scheduler.
+pub async fn notify_overdue_allocations(db: &PgPool) -> AppResult<serde_json::Value> {
+    #[derive(sqlx::FromRow)]
+    struct OverdueAllocation {
+        id: String,
+        guard_id: String,
+        guard_name: Option<String>,
+        firearm_model: String,
+        firearm_serial_number: String,
+        expected_return_date: chrono::DateTime<chrono::Utc>,
+    }
+
+    let overdue = sqlx::query_as::<_, OverdueAllocation>(
+        r#"
+        SELECT fa.id, fa.guard_id, u.full_name AS guard_name,
+               f.model AS firearm_model, f.serial_number AS firearm_serial_number,
+               fa.expected_return_date
+        FROM firearm_allocations fa
+        JOIN users u ON u.id = fa.guard_id
+        JOIN firearms f ON f.id = fa.firearm_id
+        WHERE fa.status = 'active'
+          AND fa.expected_return_date IS NOT NULL
+          AND fa.expected_return_date < NOW()
+          AND (fa.overdue_notified_at IS NULL OR fa.overdue_notified_at < NOW() - INTERVAL '1 day')
+        "#,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to query overdue allocations: {}", e)))?;
+
+    let admin_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM users WHERE role IN ('admin', 'superadmin')",
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    for allocation in &overdue {
+        let entity = Some(("firearm_allocation", allocation.id.as_str()));
+        let params = json!({
+            "guardName": allocation.guard_name.as_deref().unwrap_or(&allocation.guard_id),
+            "firearmModel": allocation.firearm_model,
+            "serialNumber": allocation.firearm_serial_number,
+            "dueAt": allocation.expected_return_date.format("%Y-%m-%d %I:%M %p").to_string()
+        });
+
+        notify_user(db, &allocation.guard_id, "overdue_allocation.guard", entity, params.clone()).await?;
+
+        for admin_id in &admin_ids {
+            notify_user(db, admin_id, "overdue_allocation.supervisor", entity, params.clone()).await?;
+        }
+
+        sqlx::query("UPDATE firearm_allocations SET overdue_notified_at = NOW() WHERE id = $1")
+            .bind(&allocation.id)
+            .execute(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    }
+
+    Ok(json!({
+        "overdueNotified": overdue.len(),
+        "allocationIds": overdue.iter().map(|a| a.id.clone()).collect::<Vec<_>>()
+    }))
+}
@@ -9,6 +9,7 @@ use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::{availability, replacement_offers},
     models::{
         Attendance, CheckInRequest, CheckOutRequest, CreateShiftRequest, RequestReplacementRequest,
         SetAvailabilityRequest, Shift,
@@ -48,6 +49,8 @@ pub async fn create_shift(
         .map(|dt| dt.with_timezone(&chrono::Utc))
         .ok_or_else(|| AppError::BadRequest("Invalid end_time format".to_string()))?;
 
+    availability::ensure_guard_available(db.as_ref(), &payload.guard_id, start_time, end_time).await?;
+
     sqlx::query(
         "INSERT INTO shifts (id, guard_id, start_time, end_time, client_site, status) VALUES ($1, $2, $3, $4, $5, 'scheduled')"
     )
@@ -163,7 +166,14 @@ pub async fn check_out(
 pub async fn detect_no_shows(
     State(db): State<Arc<PgPool>>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Enhanced no-show detection with grace period and automatic notifications
+    let summary = run_no_show_detection(db.as_ref()).await?;
+    Ok(Json(summary))
+}
+
+/// No-show detection with grace period. Each no-show is recorded against the
+/// original guard and starts the replacement offer workflow.
+/// Shared by the detect-no-shows endpoint and the background scheduler.
+pub async fn run_no_show_detection(db: &PgPool) -> AppResult<serde_json::Value> {
     
     // Step 1: Find shifts that have passed their grace period without check-in
     #[derive(sqlx::FromRow)]
@@ -171,15 +181,10 @@ pub async fn detect_no_shows(
         id: String,
         guard_id: String,
         start_time: chrono::DateTime<chrono::Utc>,
-        end_time: chrono::DateTime<chrono::Utc>,
-        client_site: String,
-        grace_period_minutes: Option<i32>,
-        replacement_status: Option<String>,
     }
     
     let no_show_shifts = sqlx::query_as::<_, NoShowShift>(
-        "SELECT s.id, s.guard_id, s.start_time, s.end_time, s.client_site, 
-                s.grace_period_minutes, s.replacement_status
+        "SELECT s.id, s.guard_id, s.start_time
          FROM shifts s 
          LEFT JOIN attendance a ON s.id = a.shift_id 
          WHERE a.id IS NULL 
@@ -187,96 +192,60 @@ pub async fn detect_no_shows(
          AND (s.start_time + INTERVAL '1 minute' * COALESCE(s.grace_period_minutes, 15)) <= CURRENT_TIMESTAMP
          AND COALESCE(s.replacement_status, 'not_needed') = 'not_needed'"
     )
-    .fetch_all(db.as_ref())
+    .fetch_all(db)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to detect no-shows: {}", e)))?;
 
-    let mut notified_guards = Vec::new();
+    let mut replacements = Vec::new();
     
-    // Step 2: For each no-show, find available substitutes and notify them
     for shift in &no_show_shifts {
-        // Update shift status to searching
+        // Step 2: Record the no-show against the original guard for merit scoring
         sqlx::query(
-            "UPDATE shifts SET replacement_status = 'searching', updated_at = CURRENT_TIMESTAMP WHERE id = $1"
+            "INSERT INTO punctuality_records (id, guard_id, shift_id, scheduled_start_time, actual_check_in_time, minutes_late, is_on_time, status)
+             SELECT $1, $2, $3, $4, NULL, NULL, false, 'no_show'
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM punctuality_records WHERE shift_id = $3 AND guard_id = $2
+             )"
         )
+        .bind(utils::generate_id())
+        .bind(&shift.guard_id)
         .bind(&shift.id)
-        .execute(db.as_ref())
+        .bind(shift.start_time)
+        .execute(db)
         .await
-        .map_err(|e| AppError::DatabaseError(format!("Failed to update shift status: {}", e)))?;
-        
-        // Find available guards (not the original guard, verified users, with role 'user')
-        #[derive(sqlx::FromRow)]
-        struct AvailableGuard {
-            id: String,
-            username: String,
-            full_name: Option<String>,
-        }
-        
-        let available_guards = sqlx::query_as::<_, AvailableGuard>(
-            "SELECT DISTINCT u.id, u.username, u.full_name 
-             FROM users u
-             LEFT JOIN guard_availability ga ON u.id = ga.guard_id
-             WHERE u.id != $1 
-             AND u.role = 'user' 
-             AND u.verified = true
-             AND (ga.available IS NULL OR ga.available = true)
-             AND NOT EXISTS (
-                 SELECT 1 FROM shifts s2 
-                 WHERE s2.guard_id = u.id 
-                 AND s2.status IN ('scheduled', 'in_progress')
-                 AND s2.start_time <= $2 
-                 AND s2.end_time >= $3
-             )
-             LIMIT 20"
+        .map_err(|e| AppError::DatabaseError(format!("Failed to record no-show: {}", e)))?;
+
+        // Step 3: Open the replacement search and send the first wave of offers
+        sqlx::query(
+            "UPDATE shifts 
+             SET replacement_status = 'searching', original_guard_id = COALESCE(original_guard_id, guard_id),
+                 replacement_wave = 0, updated_at = CURRENT_TIMESTAMP 
+             WHERE id = $1"
         )
-        .bind(&shift.guard_id)
-        .bind(&shift.end_time)
-        .bind(&shift.start_time)
-        .fetch_all(db.as_ref())
+        .bind(&shift.id)
+        .execute(db)
         .await
-        .map_err(|e| AppError::DatabaseError(format!("Failed to find available guards: {}", e)))?;
-        
-        // Step 3: Create notifications for available guards
-        for guard in &available_guards {
-            let notification_id = utils::generate_id();
-            let guard_name = guard.full_name.as_ref().unwrap_or(&guard.username);
-            
-            sqlx::query(
-                "INSERT INTO notifications (id, user_id, title, message, type, related_shift_id, read) 
-                 VALUES ($1, $2, $3, $4, 'replacement_request', $5, false)"
-            )
-            .bind(&notification_id)
-            .bind(&guard.id)
-            .bind("Replacement Needed - Urgent")
-            .bind(format!(
-                "Guard no-show detected at {}. Can you cover this shift from {} to {}? Click to accept.",
-                shift.client_site,
-                shift.start_time.format("%I:%M %p"),
-                shift.end_time.format("%I:%M %p")
-            ))
-            .bind(&shift.id)
-            .execute(db.as_ref())
-            .await
-            .map_err(|e| AppError::DatabaseError(format!("Failed to create notification: {}", e)))?;
-            
-            notified_guards.push(json!({
-                "guardId": guard.id,
-                "guardName": guard_name,
-                "shiftId": shift.id
-            }));
-        }
-        
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update shift status: {}", e)))?;
+
+        let outcome = replacement_offers::advance_replacement(db, &shift.id).await?;
+
         tracing::info!(
-            "No-show detected for shift {} (Guard: {}). Notified {} available guards.",
-            shift.id, shift.guard_id, available_guards.len()
+            "No-show detected for shift {} (Guard: {}). Replacement: {}.",
+            shift.id, shift.guard_id, outcome
         );
+
+        replacements.push(json!({
+            "shiftId": shift.id,
+            "originalGuardId": shift.guard_id,
+            "outcome": outcome
+        }));
     }
 
-    Ok(Json(json!({
+    Ok(json!({
         "message": "No-show detection completed",
         "noShowsCount": no_show_shifts.len(),
-        "notifiedGuards": notified_guards
-    })))
+        "replacements": replacements
+    }))
 }
 
 pub async fn request_replacement(
@@ -305,12 +274,15 @@ pub async fn request_replacement(
         .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
         .ok_or_else(|| AppError::NotFound("Replacement guard not found".to_string()))?;
 
-    sqlx::query("SELECT id FROM shifts WHERE id = $1")
-        .bind(&payload.shift_id)
-        .fetch_optional(db.as_ref())
-        .await
-        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
-        .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+    let (start_time, end_time): (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) =
+        sqlx::query_as("SELECT start_time, end_time FROM shifts WHERE id = $1")
+            .bind(&payload.shift_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+    availability::ensure_guard_available(db.as_ref(), &payload.replacement_guard_id, start_time, end_time).await?;
 
     // Update shift to use replacement guard
     sqlx::query(
@@ -385,7 +357,7 @@ pub async fn set_availability(
     })))
 }
 
-// Accept a replacement shift
+// Accept a replacement shift. Only guards holding an open offer for the shift can claim it.
 pub async fn accept_replacement(
     State(db): State<Arc<PgPool>>,
     Json(payload): Json<serde_json::Value>,
@@ -397,71 +369,24 @@ pub async fn accept_replacement(
     let shift_id = payload.get("shiftId")
         .and_then(|v| v.as_str())
         .ok_or_else(|| AppError::BadRequest("Shift ID is required".to_string()))?;
-    
-    let notification_id = payload.get("notificationId")
-        .and_then(|v| v.as_str());
-
-    // Verify guard exists
-    sqlx::query("SELECT id FROM users WHERE id = $1")
-        .bind(guard_id)
-        .fetch_optional(db.as_ref())
-        .await
-        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
-        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
 
-    // Verify shift exists and needs replacement
-    let shift = sqlx::query(
-        "SELECT id, replacement_status FROM shifts WHERE id = $1"
+    let offer_id = sqlx::query_scalar::<_, String>(
+        "SELECT id FROM replacement_offers WHERE shift_id = $1 AND guard_id = $2"
     )
     .bind(shift_id)
+    .bind(guard_id)
     .fetch_optional(db.as_ref())
     .await
     .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
-    .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+    .ok_or_else(|| AppError::Forbidden("No replacement offer was made to this guard for this shift".to_string()))?;
 
-    // Update shift with new guard and mark as accepted
-    sqlx::query(
-        "UPDATE shifts 
-         SET guard_id = $1, replacement_status = 'accepted', updated_at = CURRENT_TIMESTAMP 
-         WHERE id = $2"
-    )
-    .bind(guard_id)
-    .bind(shift_id)
-    .execute(db.as_ref())
-    .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to accept replacement: {}", e)))?;
-
-    // Mark the notification as read if provided
-    if let Some(notif_id) = notification_id {
-        sqlx::query(
-            "UPDATE notifications SET read = true, updated_at = CURRENT_TIMESTAMP WHERE id = $1"
-        )
-        .bind(notif_id)
-        .execute(db.as_ref())
-        .await
-        .ok(); // Ignore errors on notification update
-    }
-
-    // Cancel other pending notifications for this shift
-    sqlx::query(
-        "UPDATE notifications 
-         SET read = true, updated_at = CURRENT_TIMESTAMP 
-         WHERE related_shift_id = $1 AND type = 'replacement_request' AND read = false"
-    )
-    .bind(shift_id)
-    .execute(db.as_ref())
-    .await
-    .ok(); // Ignore errors
-
-    tracing::info!(
-        "Replacement accepted: Guard {} accepted shift {}",
-        guard_id, shift_id
-    );
+    replacement_offers::accept_offer(db.as_ref(), &offer_id, guard_id).await?;
 
     Ok(Json(json!({
         "message": "Replacement shift accepted successfully",
         "shiftId": shift_id,
-        "guardId": guard_id
+        "guardId": guard_id,
+        "offerId": offer_id
     })))
 }
 
@@ -597,6 +522,8 @@ pub async fn update_shift(
         .map(|dt| dt.with_timezone(&chrono::Utc))
         .ok_or_else(|| AppError::BadRequest("Invalid end_time format".to_string()))?;
 
+    availability::ensure_guard_available(db.as_ref(), &payload.guard_id, start_time, end_time).await?;
+
     sqlx::query(
         "UPDATE shifts SET guard_id = $1, start_time = $2, end_time = $3, client_site = $4, updated_at = CURRENT_TIMESTAMP WHERE id = $5"
     )
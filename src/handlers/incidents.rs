@@ -0,0 +1,206 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{CreateIncidentRequest, Incident, IncidentEvent, IncidentQuery, UpdateIncidentStatusRequest},
+    utils,
+};
+
+const SEVERITIES: &[&str] = &["low", "medium", "high", "critical"];
+const STATUSES: &[&str] = &["open", "investigating", "resolved"];
+const INCIDENT_TYPES: &[&str] = &["report", "sos"];
+
+/// Append an entry to an incident's timeline.
+pub async fn record_event(
+    db: impl PgExecutor<'_>,
+    incident_id: &str,
+    event_type: &str,
+    actor_id: Option<&str>,
+    escalation_level: Option<i32>,
+    position: Option<(f64, f64)>,
+    details: Option<&str>,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO incident_events (id, incident_id, event_type, actor_id, escalation_level, latitude, longitude, details)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(utils::generate_id())
+    .bind(incident_id)
+    .bind(event_type)
+    .bind(actor_id)
+    .bind(escalation_level)
+    .bind(position.map(|p| p.0))
+    .bind(position.map(|p| p.1))
+    .bind(details)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record incident event: {}", e)))?;
+
+    Ok(())
+}
+
+// Report an incident at a client site
+pub async fn create_incident(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateIncidentRequest>,
+) -> AppResult<(StatusCode, Json<Incident>)> {
+    if payload.title.trim().is_empty() {
+        return Err(AppError::BadRequest("Incident title is required".to_string()));
+    }
+    let severity = payload.severity.as_deref().unwrap_or("low");
+    if !SEVERITIES.contains(&severity) {
+        return Err(AppError::ValidationError(format!("severity must be one of: {}", SEVERITIES.join(", "))));
+    }
+
+    let site_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM client_sites WHERE name = $1)")
+        .bind(&payload.client_site)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if !site_exists {
+        return Err(AppError::NotFound("Client site not found".to_string()));
+    }
+
+    if let Some(shift_id) = &payload.shift_id {
+        let shift_site = sqlx::query_scalar::<_, String>("SELECT client_site FROM shifts WHERE id = $1")
+            .bind(shift_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+        if shift_site != payload.client_site {
+            return Err(AppError::BadRequest("Shift is not at this client site".to_string()));
+        }
+    }
+
+    let incident = sqlx::query_as::<_, Incident>(
+        "INSERT INTO incidents (id, client_site, shift_id, reported_by, severity, title, description, occurred_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, COALESCE($8, CURRENT_TIMESTAMP))
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.client_site)
+    .bind(&payload.shift_id)
+    .bind(&payload.reported_by)
+    .bind(severity)
+    .bind(payload.title.trim())
+    .bind(&payload.description)
+    .bind(payload.occurred_at)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create incident: {}", e)))?;
+
+    record_event(db.as_ref(), &incident.id, "reported", Some(&payload.reported_by), None, None, None).await?;
+
+    Ok((StatusCode::CREATED, Json(incident)))
+}
+
+// List incidents, filterable by site, status and type
+pub async fn get_incidents(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<IncidentQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    if let Some(incident_type) = &query.incident_type {
+        if !INCIDENT_TYPES.contains(&incident_type.as_str()) {
+            return Err(AppError::ValidationError(format!(
+                "incidentType must be one of: {}",
+                INCIDENT_TYPES.join(", ")
+            )));
+        }
+    }
+
+    let incidents = sqlx::query_as::<_, Incident>(
+        "SELECT * FROM incidents
+         WHERE ($1::varchar IS NULL OR client_site = $1)
+         AND ($2::varchar IS NULL OR status = $2)
+         AND ($3::varchar IS NULL OR incident_type = $3)
+         ORDER BY occurred_at DESC",
+    )
+    .bind(&query.client_site)
+    .bind(&query.status)
+    .bind(&query.incident_type)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": incidents.len(),
+        "incidents": incidents
+    })))
+}
+
+// Move an incident through investigation and resolution
+pub async fn update_incident_status(
+    State(db): State<Arc<PgPool>>,
+    Path(incident_id): Path<String>,
+    Json(payload): Json<UpdateIncidentStatusRequest>,
+) -> AppResult<Json<Incident>> {
+    if !STATUSES.contains(&payload.status.as_str()) {
+        return Err(AppError::ValidationError(format!("status must be one of: {}", STATUSES.join(", "))));
+    }
+
+    // A resolved SOS stops escalating
+    let incident = sqlx::query_as::<_, Incident>(
+        "UPDATE incidents
+         SET status = $2,
+             resolved_at = CASE WHEN $2 = 'resolved' THEN CURRENT_TIMESTAMP ELSE NULL END,
+             next_escalation_at = CASE WHEN $2 = 'resolved' THEN NULL ELSE next_escalation_at END,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&incident_id)
+    .bind(&payload.status)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update incident: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Incident not found".to_string()))?;
+
+    record_event(
+        db.as_ref(),
+        &incident_id,
+        "status_changed",
+        payload.updated_by.as_deref(),
+        None,
+        None,
+        Some(&payload.status),
+    )
+    .await?;
+
+    Ok(Json(incident))
+}
+
+// Get an incident with its full timeline
+pub async fn get_incident_timeline(
+    State(db): State<Arc<PgPool>>,
+    Path(incident_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let incident = sqlx::query_as::<_, Incident>("SELECT * FROM incidents WHERE id = $1")
+        .bind(&incident_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Incident not found".to_string()))?;
+
+    let events = sqlx::query_as::<_, IncidentEvent>(
+        "SELECT * FROM incident_events WHERE incident_id = $1 ORDER BY created_at, id",
+    )
+    .bind(&incident_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "incident": incident,
+        "events": events
+    })))
+}
@@ -0,0 +1,545 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{
+        availability::schedule_timezone,
+        timesheets::{daily_window_hours, fetch_holidays, holiday_hours, round2},
+    },
+    models::{
+        Client, ClientContract, GenerateInvoiceRequest, Invoice, InvoiceLineItem, InvoiceQuery,
+        MarkInvoicePaidRequest,
+    },
+    utils,
+};
+
+/// Contract night surcharges apply to guard hours worked in this window.
+const NIGHT_START: NaiveTime = NaiveTime::from_hms_opt(22, 0, 0).unwrap();
+const NIGHT_END: NaiveTime = NaiveTime::from_hms_opt(6, 0, 0).unwrap();
+
+#[derive(sqlx::FromRow)]
+struct BillableAttendance {
+    id: String,
+    shift_id: String,
+    guard_id: String,
+    guard_name: String,
+    client_site: String,
+    check_in: NaiveDateTime,
+    check_out: NaiveDateTime,
+}
+
+#[derive(sqlx::FromRow)]
+struct BillableTrip {
+    id: String,
+    start_location: Option<String>,
+    end_location: Option<String>,
+    distance_km: Option<f64>,
+    completed_on: NaiveDate,
+}
+
+struct NewLine {
+    line_type: &'static str,
+    description: String,
+    service_date: Option<NaiveDate>,
+    quantity: f64,
+    unit_price: f64,
+    attendance_id: Option<String>,
+    shift_id: Option<String>,
+    trip_id: Option<String>,
+    guard_id: Option<String>,
+}
+
+impl NewLine {
+    fn amount(&self) -> f64 {
+        round2(self.quantity * self.unit_price)
+    }
+}
+
+fn billing_error(context: &str, e: sqlx::Error) -> AppError {
+    let unique_violation = e
+        .as_database_error()
+        .and_then(|d| d.code())
+        .is_some_and(|code| code == "23505");
+    if unique_violation {
+        AppError::Conflict("Some records in this period were billed concurrently; retry".to_string())
+    } else {
+        AppError::DatabaseError(format!("{}: {}", context, e))
+    }
+}
+
+fn attendance_lines(
+    contract: &ClientContract,
+    holidays: &std::collections::HashMap<NaiveDate, String>,
+    record: &BillableAttendance,
+) -> Vec<NewLine> {
+    let (start, end) = (record.check_in, record.check_out);
+    let worked = (end - start).num_seconds().max(0) as f64 / 3600.0;
+    let billed = worked.max(contract.minimum_hours_per_shift);
+    let rate = contract.guard_hourly_rate;
+
+    let mut description = format!(
+        "Guard services - {} at {} ({:.2} h worked)",
+        record.guard_name, record.client_site, worked
+    );
+    if billed > worked {
+        description.push_str(&format!(", {:.2} h shift minimum", contract.minimum_hours_per_shift));
+    }
+
+    let line = |line_type, description, quantity, unit_price| NewLine {
+        line_type,
+        description,
+        service_date: Some(start.date()),
+        quantity: round2(quantity),
+        unit_price,
+        attendance_id: Some(record.id.clone()),
+        shift_id: Some(record.shift_id.clone()),
+        trip_id: None,
+        guard_id: Some(record.guard_id.clone()),
+    };
+
+    let mut lines = vec![line("guard_hours", description, billed, rate)];
+
+    let night = daily_window_hours(NIGHT_START, NIGHT_END, start, end);
+    if night > 0.0 && contract.night_surcharge_pct > 0.0 {
+        lines.push(line(
+            "night_surcharge",
+            format!("Night surcharge {}% - {}", contract.night_surcharge_pct, record.guard_name),
+            night,
+            round2(rate * contract.night_surcharge_pct / 100.0),
+        ));
+    }
+
+    let (holiday, _) = holiday_hours(holidays, start, end);
+    if holiday > 0.0 && contract.holiday_surcharge_pct > 0.0 {
+        lines.push(line(
+            "holiday_surcharge",
+            format!("Holiday surcharge {}% - {}", contract.holiday_surcharge_pct, record.guard_name),
+            holiday,
+            round2(rate * contract.holiday_surcharge_pct / 100.0),
+        ));
+    }
+
+    lines
+}
+
+fn trip_line(contract: &ClientContract, trip: &BillableTrip) -> NewLine {
+    let distance = trip.distance_km.unwrap_or(0.0);
+    NewLine {
+        line_type: "trip",
+        description: format!(
+            "Armored trip {} to {} ({:.1} km)",
+            trip.start_location.as_deref().unwrap_or("-"),
+            trip.end_location.as_deref().unwrap_or("-"),
+            distance
+        ),
+        service_date: Some(trip.completed_on),
+        quantity: 1.0,
+        unit_price: round2(contract.trip_rate + contract.per_km_rate * distance),
+        attendance_id: None,
+        shift_id: None,
+        trip_id: Some(trip.id.clone()),
+        guard_id: None,
+    }
+}
+
+async fn fetch_invoice(db: impl PgExecutor<'_>, invoice_id: &str) -> AppResult<Invoice> {
+    sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE id = $1")
+        .bind(invoice_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+}
+
+async fn fetch_line_items(db: &PgPool, invoice_id: &str) -> AppResult<Vec<InvoiceLineItem>> {
+    sqlx::query_as::<_, InvoiceLineItem>(
+        "SELECT * FROM invoice_line_items WHERE invoice_id = $1
+         ORDER BY service_date NULLS LAST, line_type, created_at",
+    )
+    .bind(invoice_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))
+}
+
+/// Build a draft invoice for a contract from attendance and trips in the period that
+/// have not been billed yet. Each line keeps the attendance/shift or trip it came from.
+pub async fn generate_invoice_for_contract(
+    db: &PgPool,
+    payload: &GenerateInvoiceRequest,
+) -> AppResult<Invoice> {
+    if payload.period_end < payload.period_start {
+        return Err(AppError::ValidationError("periodEnd must not be before periodStart".to_string()));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    // Serialises generation per contract
+    let contract = sqlx::query_as::<_, ClientContract>("SELECT * FROM client_contracts WHERE id = $1 FOR UPDATE")
+        .bind(&payload.contract_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Contract not found".to_string()))?;
+
+    if contract.status != "active" {
+        return Err(AppError::Conflict("Contract is not active".to_string()));
+    }
+
+    // Only bill the part of the period the contract covers
+    let from = payload.period_start.max(contract.start_date);
+    let to = contract.end_date.map_or(payload.period_end, |end| payload.period_end.min(end));
+    if to < from {
+        return Err(AppError::ValidationError("Period is outside the contract term".to_string()));
+    }
+
+    let tz = schedule_timezone();
+    let holidays = fetch_holidays(&mut *tx, from, to + Duration::days(1)).await?;
+
+    let attendance = sqlx::query_as::<_, BillableAttendance>(
+        "SELECT a.id, a.shift_id, a.guard_id, u.full_name AS guard_name, s.client_site,
+                (a.check_in_time AT TIME ZONE $4) AS check_in,
+                (a.check_out_time AT TIME ZONE $4) AS check_out
+         FROM attendance a
+         JOIN shifts s ON s.id = a.shift_id
+         JOIN client_sites cs ON cs.name = s.client_site
+         JOIN users u ON u.id = a.guard_id
+         WHERE cs.client_id = $1
+         AND a.check_out_time IS NOT NULL
+         AND a.check_out_time > a.check_in_time
+         AND (a.check_in_time AT TIME ZONE $4)::date BETWEEN $2 AND $3
+         AND NOT EXISTS (
+             SELECT 1 FROM invoice_line_items li
+             WHERE li.attendance_id = a.id AND li.line_type = 'guard_hours'
+         )
+         ORDER BY a.check_in_time",
+    )
+    .bind(&contract.client_id)
+    .bind(from)
+    .bind(to)
+    .bind(tz)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch billable attendance: {}", e)))?;
+
+    let trips = sqlx::query_as::<_, BillableTrip>(
+        "SELECT t.id, t.start_location, t.end_location, t.distance_km::FLOAT8 AS distance_km,
+                (t.end_time AT TIME ZONE $4)::date AS completed_on
+         FROM trips t
+         JOIN car_allocations ca ON ca.id = t.allocation_id
+         WHERE ca.client_id = $1
+         AND t.status = 'completed'
+         AND t.end_time IS NOT NULL
+         AND (t.end_time AT TIME ZONE $4)::date BETWEEN $2 AND $3
+         AND NOT EXISTS (
+             SELECT 1 FROM invoice_line_items li
+             WHERE li.trip_id = t.id AND li.line_type = 'trip'
+         )
+         ORDER BY t.end_time",
+    )
+    .bind(&contract.client_id)
+    .bind(from)
+    .bind(to)
+    .bind(tz)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch billable trips: {}", e)))?;
+
+    let mut lines: Vec<NewLine> = attendance
+        .iter()
+        .flat_map(|record| attendance_lines(&contract, &holidays, record))
+        .chain(trips.iter().map(|trip| trip_line(&contract, trip)))
+        .collect();
+
+    if lines.is_empty() {
+        return Err(AppError::BadRequest("No unbilled attendance or trips in this period".to_string()));
+    }
+
+    let subtotal = round2(lines.iter().map(NewLine::amount).sum());
+
+    if subtotal < contract.minimum_invoice_amount {
+        lines.push(NewLine {
+            line_type: "minimum_charge",
+            description: format!("Minimum invoice amount ({:.2})", contract.minimum_invoice_amount),
+            service_date: None,
+            quantity: 1.0,
+            unit_price: round2(contract.minimum_invoice_amount - subtotal),
+            attendance_id: None,
+            shift_id: None,
+            trip_id: None,
+            guard_id: None,
+        });
+    }
+
+    let total = round2(lines.iter().map(NewLine::amount).sum());
+
+    let invoice = sqlx::query_as::<_, Invoice>(
+        "INSERT INTO invoices
+         (id, client_id, contract_id, period_start, period_end, currency, subtotal, total, status, notes, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'draft', $9, $10)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&contract.client_id)
+    .bind(&contract.id)
+    .bind(from)
+    .bind(to)
+    .bind(&contract.currency)
+    .bind(subtotal)
+    .bind(total)
+    .bind(&payload.notes)
+    .bind(&payload.created_by)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create invoice: {}", e)))?;
+
+    for line in &lines {
+        sqlx::query(
+            "INSERT INTO invoice_line_items
+             (id, invoice_id, line_type, description, service_date, quantity, unit_price, amount,
+              attendance_id, shift_id, trip_id, guard_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+        )
+        .bind(utils::generate_id())
+        .bind(&invoice.id)
+        .bind(line.line_type)
+        .bind(&line.description)
+        .bind(line.service_date)
+        .bind(line.quantity)
+        .bind(line.unit_price)
+        .bind(line.amount())
+        .bind(&line.attendance_id)
+        .bind(&line.shift_id)
+        .bind(&line.trip_id)
+        .bind(&line.guard_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| billing_error("Failed to create invoice line", e))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| billing_error("Failed to commit transaction", e))?;
+
+    Ok(invoice)
+}
+
+// Generate a draft invoice for a contract and period
+pub async fn generate_invoice(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<GenerateInvoiceRequest>,
+) -> AppResult<(StatusCode, Json<Invoice>)> {
+    let invoice = generate_invoice_for_contract(db.as_ref(), &payload).await?;
+    Ok((StatusCode::CREATED, Json(invoice)))
+}
+
+// List invoices, filterable by client and status
+pub async fn get_invoices(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<InvoiceQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let invoices = sqlx::query_as::<_, Invoice>(
+        "SELECT * FROM invoices
+         WHERE ($1::varchar IS NULL OR client_id = $1)
+         AND ($2::varchar IS NULL OR status = $2)
+         ORDER BY created_at DESC",
+    )
+    .bind(&query.client_id)
+    .bind(&query.status)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": invoices.len(),
+        "invoices": invoices
+    })))
+}
+
+// Get an invoice with its line items
+pub async fn get_invoice(
+    State(db): State<Arc<PgPool>>,
+    Path(invoice_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let invoice = fetch_invoice(db.as_ref(), &invoice_id).await?;
+    let line_items = fetch_line_items(db.as_ref(), &invoice_id).await?;
+
+    Ok(Json(json!({
+        "invoice": invoice,
+        "lineItems": line_items
+    })))
+}
+
+async fn invoice_transition_error(db: &PgPool, invoice_id: &str, action: &str) -> AppError {
+    match fetch_invoice(db, invoice_id).await {
+        Ok(invoice) => AppError::Conflict(format!("Cannot {} an invoice that is {}", action, invoice.status)),
+        Err(e) => e,
+    }
+}
+
+// Issue a draft invoice: assigns its number and due date
+pub async fn issue_invoice(
+    State(db): State<Arc<PgPool>>,
+    Path(invoice_id): Path<String>,
+) -> AppResult<Json<Invoice>> {
+    let invoice = sqlx::query_as::<_, Invoice>(
+        "UPDATE invoices i
+         SET status = 'issued',
+             invoice_number = 'INV-' || to_char(CURRENT_DATE, 'YYYY') || '-' || lpad(nextval('invoice_number_seq')::text, 6, '0'),
+             issued_at = CURRENT_TIMESTAMP,
+             due_date = CURRENT_DATE + c.payment_terms_days,
+             updated_at = CURRENT_TIMESTAMP
+         FROM client_contracts c
+         WHERE i.id = $1 AND i.status = 'draft' AND c.id = i.contract_id
+         RETURNING i.*",
+    )
+    .bind(&invoice_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to issue invoice: {}", e)))?;
+
+    match invoice {
+        Some(invoice) => Ok(Json(invoice)),
+        None => Err(invoice_transition_error(db.as_ref(), &invoice_id, "issue").await),
+    }
+}
+
+// Record payment of an issued invoice
+pub async fn mark_invoice_paid(
+    State(db): State<Arc<PgPool>>,
+    Path(invoice_id): Path<String>,
+    Json(payload): Json<MarkInvoicePaidRequest>,
+) -> AppResult<Json<Invoice>> {
+    let invoice = sqlx::query_as::<_, Invoice>(
+        "UPDATE invoices
+         SET status = 'paid', paid_at = CURRENT_TIMESTAMP, payment_reference = $2, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND status = 'issued'
+         RETURNING *",
+    )
+    .bind(&invoice_id)
+    .bind(&payload.payment_reference)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to mark invoice paid: {}", e)))?;
+
+    match invoice {
+        Some(invoice) => Ok(Json(invoice)),
+        None => Err(invoice_transition_error(db.as_ref(), &invoice_id, "pay").await),
+    }
+}
+
+// Delete a draft invoice; its attendance and trips become billable again
+pub async fn delete_invoice(
+    State(db): State<Arc<PgPool>>,
+    Path(invoice_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM invoices WHERE id = $1 AND status = 'draft'")
+        .bind(&invoice_id)
+        .execute(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to delete invoice: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(invoice_transition_error(db.as_ref(), &invoice_id, "delete").await);
+    }
+
+    Ok(Json(json!({
+        "message": "Draft invoice deleted successfully"
+    })))
+}
+
+// Render an invoice as a printable HTML document
+pub async fn render_invoice_html(
+    State(db): State<Arc<PgPool>>,
+    Path(invoice_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let invoice = fetch_invoice(db.as_ref(), &invoice_id).await?;
+
+    let client = sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE id = $1")
+        .bind(&invoice.client_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let line_items = fetch_line_items(db.as_ref(), &invoice_id).await?;
+
+    let number = invoice.invoice_number.clone().unwrap_or_else(|| "DRAFT".to_string());
+    let rows: String = line_items
+        .iter()
+        .map(|line| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td class=\"num\">{:.2}</td><td class=\"num\">{:.2}</td><td class=\"num\">{:.2}</td></tr>\n",
+                line.service_date.map(|d| d.to_string()).unwrap_or_default(),
+                utils::escape_html(&line.description),
+                line.quantity,
+                line.unit_price,
+                line.amount
+            )
+        })
+        .collect();
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Invoice {number}</title>
+<style>
+body {{ font-family: Arial, sans-serif; margin: 40px; color: #222; }}
+table {{ width: 100%; border-collapse: collapse; margin-top: 24px; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 6px 8px; text-align: left; }}
+.num {{ text-align: right; }}
+.total td {{ font-weight: bold; border-top: 2px solid #222; }}
+.status {{ text-transform: uppercase; color: #666; }}
+</style>
+</head>
+<body>
+<h1>Invoice {number}</h1>
+<p class="status">{status}</p>
+<p><strong>Bill to:</strong> {client}<br>{address}<br>{email}</p>
+<p><strong>Service period:</strong> {period_start} to {period_end}<br>
+<strong>Issued:</strong> {issued}<br>
+<strong>Due:</strong> {due}</p>
+<table>
+<thead><tr><th>Date</th><th>Description</th><th class="num">Qty</th><th class="num">Unit price</th><th class="num">Amount</th></tr></thead>
+<tbody>
+{rows}<tr class="total"><td></td><td>Total ({currency})</td><td></td><td></td><td class="num">{total:.2}</td></tr>
+</tbody>
+</table>
+{notes}
+</body>
+</html>
+"#,
+        number = utils::escape_html(&number),
+        status = utils::escape_html(&invoice.status),
+        client = utils::escape_html(&client.name),
+        address = utils::escape_html(client.billing_address.as_deref().unwrap_or("")),
+        email = utils::escape_html(client.billing_email.as_deref().unwrap_or("")),
+        period_start = invoice.period_start,
+        period_end = invoice.period_end,
+        issued = invoice.issued_at.map(|d| d.date_naive().to_string()).unwrap_or_else(|| "-".to_string()),
+        due = invoice.due_date.map(|d| d.to_string()).unwrap_or_else(|| "-".to_string()),
+        rows = rows,
+        currency = utils::escape_html(&invoice.currency),
+        total = invoice.total,
+        notes = invoice
+            .notes
+            .as_deref()
+            .map(|n| format!("<p>{}</p>", utils::escape_html(n)))
+            .unwrap_or_default(),
+    );
+
+    Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html))
+}
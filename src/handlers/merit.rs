@@ -1,17 +1,23 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
+use chrono::{DateTime, Duration, Utc};
 use serde_json::json;
-use sqlx::PgPool;
-use std::sync::Arc;
+use sqlx::{PgConnection, PgPool};
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::{
+        evaluations::{submit_account_evaluation, EvaluationTarget},
+        merit_models::active_merit_model,
+    },
     models::{
         GuardMeritScore, ClientEvaluation, CreateClientEvaluationRequest, 
-        CalculateMeritScoreRequest, MeritScoreResponse, RankedGuardResponse, MeritStats
+        CalculateMeritScoreRequest, MeritScoreResponse, RankedGuardResponse, MeritStats,
+        MeritModel, MeritModelSpec, MeritRankingQuery, MeritScoreSnapshot, MeritWindowScore,
     },
     utils,
 };
@@ -21,193 +27,579 @@ pub async fn calculate_merit_score(
     State(db): State<Arc<PgPool>>,
     Json(payload): Json<CalculateMeritScoreRequest>,
 ) -> AppResult<(StatusCode, Json<MeritScoreResponse>)> {
-    let guard_id = &payload.guard_id;
+    let response = recalculate_merit_score(db.as_ref(), &payload.guard_id).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
 
-    // 1. Calculate Attendance Score (% of shifts attended)
-    let attendance_result = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(CASE WHEN status = 'completed' THEN 1 END)::int8 as completed 
-         FROM attendance 
-         WHERE guard_id = $1"
-    )
-    .bind(guard_id)
-    .fetch_one(db.as_ref())
-    .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to query attendance: {}", e)))?;
+/// Severity penalties for incidents on a guard's shifts (incident factor starts at 100).
+const INCIDENT_PENALTY_SQL: &str =
+    "CASE i.severity WHEN 'critical' THEN 40 WHEN 'high' THEN 20 WHEN 'medium' THEN 10 ELSE 5 END";
+/// Points taken off the weighted score for each disciplinary record active at the end of the period.
+const DISCIPLINARY_PENALTY_SQL: &str = "CASE d.action_type WHEN 'suspension' THEN 15 WHEN 'final_warning' THEN 10 \
+     WHEN 'written_warning' THEN 5 ELSE 2 END";
+/// Points added for each commendation active at the end of the period.
+const COMMENDATION_BONUS_SQL: &str = "CASE c.level WHEN 'award' THEN 5 ELSE 3 END";
+/// Without a window, only incidents from the last year count against a guard.
+const INCIDENT_LOOKBACK_DAYS: i64 = 365;
+/// Rolling windows kept alongside the all-time score.
+pub const MERIT_WINDOWS: [i32; 3] = [30, 90, 365];
+/// Score change (in points) against the preceding window before a trend counts as up or down.
+const TREND_THRESHOLD: f64 = 2.0;
+
+/// The slice of history merit factors are computed over.
+#[derive(Debug, Clone, Copy)]
+pub struct MeritPeriod {
+    pub from: Option<DateTime<Utc>>,
+    pub to: DateTime<Utc>,
+    /// An event this many days before `to` counts half as much
+    pub half_life_days: Option<f64>,
+}
 
-    let total_shifts = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*)::int8 FROM shifts WHERE guard_id = $1"
-    )
-    .bind(guard_id)
-    .fetch_one(db.as_ref())
-    .await
-    .unwrap_or(0);
+impl MeritPeriod {
+    pub fn all_time(half_life_days: Option<f64>) -> Self {
+        MeritPeriod { from: None, to: Utc::now(), half_life_days }
+    }
 
-    let attendance_score = if total_shifts > 0 {
-        (attendance_result as f64 / total_shifts as f64) * 100.0
-    } else {
-        0.0
-    };
+    pub fn rolling(days: i32, half_life_days: Option<f64>) -> Self {
+        let to = Utc::now();
+        MeritPeriod { from: Some(to - Duration::days(days as i64)), to, half_life_days }
+    }
 
-    // 2. Calculate Punctuality Score (% of on-time check-ins)
-    let on_time_count = sqlx::query_scalar::<_, Option<i64>>(
-        "SELECT COUNT(*) FROM punctuality_records WHERE guard_id = $1 AND is_on_time = true"
-    )
-    .bind(guard_id)
-    .fetch_one(db.as_ref())
-    .await
-    .unwrap_or(None)
-    .unwrap_or(0) as i32;
+    /// The window of the same length immediately before this one.
+    pub fn preceding(&self) -> Option<Self> {
+        let from = self.from?;
+        Some(MeritPeriod { from: Some(from - (self.to - from)), to: from, half_life_days: self.half_life_days })
+    }
+}
 
-    let total_punctuality_records = sqlx::query_scalar::<_, Option<i64>>(
-        "SELECT COUNT(*) FROM punctuality_records WHERE guard_id = $1"
+/// SQL weight for an event at `column`: 1, or exponential decay by age when a half-life is bound to $4.
+/// Queries bind $2 = period start, $3 = period end, $4 = half-life in days.
+fn decay_weight(column: &str) -> String {
+    format!(
+        "CASE WHEN $4::float8 IS NULL THEN 1.0
+              ELSE EXP(-LN(2) * GREATEST(EXTRACT(EPOCH FROM ($3 - {c}))::float8, 0) / 86400.0 / $4::float8) END",
+        c = column
     )
-    .bind(guard_id)
-    .fetch_one(db.as_ref())
-    .await
-    .unwrap_or(None)
-    .unwrap_or(0);
+}
 
-    let punctuality_score = if total_punctuality_records > 0 {
-        (on_time_count as f64 / total_punctuality_records as f64) * 100.0
+fn in_period(column: &str) -> String {
+    format!("($2::timestamptz IS NULL OR {c} >= $2) AND {c} < $3", c = column)
+}
+
+/// A guard's factor scores (each 0-100) and the counts behind them, before weighting.
+#[derive(Debug, Clone)]
+pub struct MeritFactors {
+    pub attendance: f64,
+    pub punctuality: f64,
+    pub client_rating: f64,
+    pub incident: f64,
+    pub patrol: f64,
+    pub training: f64,
+    pub total_shifts: i64,
+    pub on_time_count: i32,
+    pub late_count: i32,
+    pub no_show_count: i32,
+    pub evaluation_count: i32,
+    pub average_rating: Option<f64>,
+    /// Shifts, punctuality records, evaluations and patrol rounds in the period
+    pub activity_count: i64,
+    /// Commendation bonus minus disciplinary penalties, added after weighting
+    pub conduct_adjustment: f64,
+}
+
+/// Weighted overall score and rank for a set of factors under a model.
+pub fn apply_merit_model(spec: &MeritModelSpec, factors: &MeritFactors) -> (f64, &'static str) {
+    let overall = (factors.attendance * spec.attendance_weight
+        + factors.punctuality * spec.punctuality_weight
+        + factors.client_rating * spec.client_rating_weight
+        + factors.incident * spec.incident_weight
+        + factors.patrol * spec.patrol_weight
+        + factors.training * spec.training_weight)
+        / 100.0
+        + factors.conduct_adjustment;
+    let overall = overall.clamp(0.0, 100.0);
+
+    let rank = match overall {
+        score if score >= spec.gold_min => "Gold",
+        score if score >= spec.silver_min => "Silver",
+        score if score >= spec.bronze_min => "Bronze",
+        _ => "Standard",
+    };
+    (overall, rank)
+}
+
+fn ratio_score(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 {
+        (part / whole) * 100.0
     } else {
         0.0
-    };
+    }
+}
 
-    // 3. Calculate Client Rating (average of all evaluations)
-    let (avg_rating, eval_count): (Option<f64>, Option<i64>) = sqlx::query_as(
-        "SELECT CAST(AVG(rating) AS FLOAT8), COUNT(*)::int8 FROM client_evaluations WHERE guard_id = $1"
-    )
-    .bind(guard_id)
-    .fetch_one(db.as_ref())
+#[derive(sqlx::FromRow)]
+struct MeritFactorRow {
+    guard_id: String,
+    attended: f64,
+    scheduled: f64,
+    total_shifts: i64,
+    on_time_weight: f64,
+    punctuality_weight: f64,
+    on_time_count: i64,
+    late_count: i64,
+    no_show_count: i64,
+    punctuality_records: i64,
+    average_rating: Option<f64>,
+    evaluation_count: i64,
+    incident_penalty: f64,
+    checkpoints_completed: f64,
+    checkpoints_expected: f64,
+    patrol_rounds: i64,
+    current_training_types: i64,
+    required_training_types: i64,
+    disciplinary_penalty: f64,
+    commendation_bonus: f64,
+}
+
+impl From<MeritFactorRow> for MeritFactors {
+    fn from(row: MeritFactorRow) -> Self {
+        let training = if row.required_training_types > 0 {
+            (row.current_training_types as f64 / row.required_training_types as f64) * 100.0
+        } else {
+            100.0
+        };
+        let client_rating = match row.average_rating {
+            Some(avg) => (avg / 5.0) * 100.0, // Convert 0-5 to 0-100
+            None => 0.0,
+        };
+
+        MeritFactors {
+            attendance: ratio_score(row.attended, row.scheduled).clamp(0.0, 100.0),
+            punctuality: ratio_score(row.on_time_weight, row.punctuality_weight).clamp(0.0, 100.0),
+            client_rating: client_rating.clamp(0.0, 100.0),
+            incident: (100.0 - row.incident_penalty).clamp(0.0, 100.0),
+            patrol: ratio_score(row.checkpoints_completed, row.checkpoints_expected).clamp(0.0, 100.0),
+            training: training.clamp(0.0, 100.0),
+            total_shifts: row.total_shifts,
+            on_time_count: row.on_time_count as i32,
+            late_count: row.late_count as i32,
+            no_show_count: row.no_show_count as i32,
+            evaluation_count: row.evaluation_count as i32,
+            average_rating: row.average_rating,
+            activity_count: row.total_shifts + row.punctuality_records + row.evaluation_count + row.patrol_rounds,
+            conduct_adjustment: row.commendation_bonus - row.disciplinary_penalty,
+        }
+    }
+}
+
+/// Gather every factor the merit models can weigh, for many guards at once over a period.
+/// One set-based query; guards with no data still get a row.
+pub async fn compute_merit_factors_for(
+    db: &PgPool,
+    guard_ids: &[String],
+    period: &MeritPeriod,
+) -> AppResult<HashMap<String, MeritFactors>> {
+    let incident_from = period
+        .from
+        .unwrap_or(period.to - Duration::days(INCIDENT_LOOKBACK_DAYS));
+
+    let rows = sqlx::query_as::<_, MeritFactorRow>(&format!(
+        "WITH guards AS (SELECT UNNEST($1::varchar[]) AS guard_id),
+         attendance_factor AS (
+             SELECT guard_id, SUM(w) FILTER (WHERE attended) AS attended, SUM(w) AS scheduled, COUNT(*) AS shifts
+             FROM (
+                 SELECT s.guard_id, {shift_weight} AS w, EXISTS(
+                     SELECT 1 FROM attendance a
+                     WHERE a.shift_id = s.id AND a.guard_id = s.guard_id AND a.check_out_time IS NOT NULL
+                 ) AS attended
+                 FROM shifts s
+                 WHERE s.guard_id = ANY($1) AND {shift_period}
+             ) x
+             GROUP BY guard_id
+         ),
+         punctuality_factor AS (
+             SELECT guard_id,
+                    SUM(w) FILTER (WHERE is_on_time) AS on_time_weight, SUM(w) AS total_weight,
+                    COUNT(*) FILTER (WHERE is_on_time) AS on_time,
+                    COUNT(*) FILTER (WHERE status = 'late') AS late,
+                    COUNT(*) FILTER (WHERE status = 'no_show') AS no_show,
+                    COUNT(*) AS records
+             FROM (
+                 SELECT p.guard_id, {punctuality_weight} AS w, p.is_on_time, p.status
+                 FROM punctuality_records p
+                 WHERE p.guard_id = ANY($1) AND {punctuality_period}
+             ) x
+             GROUP BY guard_id
+         ),
+         rating_factor AS (
+             SELECT guard_id, SUM(w * rating) / NULLIF(SUM(w), 0) AS average_rating, COUNT(*) AS evaluations
+             FROM (
+                 SELECT e.guard_id, {evaluation_weight} AS w, e.rating
+                 FROM client_evaluations e
+                 WHERE e.guard_id = ANY($1) AND e.status <> 'overturned' AND {evaluation_period}
+             ) x
+             GROUP BY guard_id
+         ),
+         incident_factor AS (
+             SELECT s.guard_id, SUM({incident_penalty} * {incident_weight}) AS penalty
+             FROM incidents i
+             JOIN shifts s ON s.id = i.shift_id
+             WHERE s.guard_id = ANY($1) AND i.occurred_at >= $5 AND i.occurred_at < $3
+             GROUP BY s.guard_id
+         ),
+         patrol_factor AS (
+             SELECT guard_id, SUM(w * LEAST(checkpoints_completed, checkpoints_expected)) AS completed,
+                    SUM(w * checkpoints_expected) AS expected, COUNT(*) AS rounds
+             FROM (
+                 SELECT r.guard_id, {patrol_weight} AS w, r.checkpoints_completed, r.checkpoints_expected
+                 FROM patrol_rounds r
+                 WHERE r.guard_id = ANY($1) AND {patrol_period}
+             ) x
+             GROUP BY guard_id
+         ),
+         required_training AS (
+             SELECT DISTINCT training_type FROM training_courses WHERE is_active
+         ),
+         training_factor AS (
+             SELECT tr.guard_id, COUNT(DISTINCT tr.training_type) AS current_types
+             FROM training_records tr
+             JOIN required_training rt ON rt.training_type = tr.training_type
+             WHERE tr.guard_id = ANY($1) AND tr.status = 'valid'
+             AND (tr.expiry_date IS NULL OR tr.expiry_date > CURRENT_TIMESTAMP)
+             GROUP BY tr.guard_id
+         ),
+         disciplinary_factor AS (
+             SELECT d.guard_id, SUM({disciplinary_penalty}) AS penalty
+             FROM disciplinary_records d
+             WHERE d.guard_id = ANY($1) AND d.appeal_status <> 'overturned'
+             AND d.effective_from <= $3 AND (d.effective_until IS NULL OR d.effective_until > $3)
+             GROUP BY d.guard_id
+         ),
+         commendation_factor AS (
+             SELECT c.guard_id, SUM({commendation_bonus}) AS bonus
+             FROM commendations c
+             WHERE c.guard_id = ANY($1) AND c.awarded_at <= $3 AND (c.expires_at IS NULL OR c.expires_at > $3)
+             GROUP BY c.guard_id
+         )
+         SELECT g.guard_id,
+                COALESCE(af.attended, 0)::float8 AS attended,
+                COALESCE(af.scheduled, 0)::float8 AS scheduled,
+                COALESCE(af.shifts, 0)::int8 AS total_shifts,
+                COALESCE(pf.on_time_weight, 0)::float8 AS on_time_weight,
+                COALESCE(pf.total_weight, 0)::float8 AS punctuality_weight,
+                COALESCE(pf.on_time, 0)::int8 AS on_time_count,
+                COALESCE(pf.late, 0)::int8 AS late_count,
+                COALESCE(pf.no_show, 0)::int8 AS no_show_count,
+                COALESCE(pf.records, 0)::int8 AS punctuality_records,
+                rf.average_rating::float8 AS average_rating,
+                COALESCE(rf.evaluations, 0)::int8 AS evaluation_count,
+                COALESCE(inf.penalty, 0)::float8 AS incident_penalty,
+                COALESCE(ptf.completed, 0)::float8 AS checkpoints_completed,
+                COALESCE(ptf.expected, 0)::float8 AS checkpoints_expected,
+                COALESCE(ptf.rounds, 0)::int8 AS patrol_rounds,
+                COALESCE(tf.current_types, 0)::int8 AS current_training_types,
+                (SELECT COUNT(*) FROM required_training)::int8 AS required_training_types,
+                COALESCE(df.penalty, 0)::float8 AS disciplinary_penalty,
+                COALESCE(cf.bonus, 0)::float8 AS commendation_bonus
+         FROM guards g
+         LEFT JOIN attendance_factor af ON af.guard_id = g.guard_id
+         LEFT JOIN punctuality_factor pf ON pf.guard_id = g.guard_id
+         LEFT JOIN rating_factor rf ON rf.guard_id = g.guard_id
+         LEFT JOIN incident_factor inf ON inf.guard_id = g.guard_id
+         LEFT JOIN patrol_factor ptf ON ptf.guard_id = g.guard_id
+         LEFT JOIN training_factor tf ON tf.guard_id = g.guard_id
+         LEFT JOIN disciplinary_factor df ON df.guard_id = g.guard_id
+         LEFT JOIN commendation_factor cf ON cf.guard_id = g.guard_id",
+        shift_weight = decay_weight("s.start_time"),
+        shift_period = in_period("s.end_time"),
+        punctuality_weight = decay_weight("p.scheduled_start_time"),
+        punctuality_period = in_period("p.scheduled_start_time"),
+        evaluation_weight = decay_weight("e.created_at"),
+        evaluation_period = in_period("e.created_at"),
+        incident_penalty = INCIDENT_PENALTY_SQL,
+        incident_weight = decay_weight("i.occurred_at"),
+        patrol_weight = decay_weight("r.started_at"),
+        patrol_period = in_period("r.started_at"),
+        disciplinary_penalty = DISCIPLINARY_PENALTY_SQL,
+        commendation_bonus = COMMENDATION_BONUS_SQL,
+    ))
+    .bind(guard_ids)
+    .bind(period.from)
+    .bind(period.to)
+    .bind(period.half_life_days)
+    .bind(incident_from)
+    .fetch_all(db)
     .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to query evaluations: {}", e)))?;
+    .map_err(|e| AppError::DatabaseError(format!("Failed to compute merit factors: {}", e)))?;
 
-    let client_rating = match avg_rating {
-        Some(avg) => (avg / 5.0) * 100.0, // Convert 0-5 to 0-100
-        None => 0.0,
-    };
-    let eval_count = eval_count.unwrap_or(0) as i32;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.guard_id.clone(), MeritFactors::from(row)))
+        .collect())
+}
 
-    // 4. Calculate Overall Score (weighted average)
-    // Attendance: 30%, Punctuality: 35%, Client Rating: 35%
-    let overall_score = (attendance_score * 0.30) + (punctuality_score * 0.35) + (client_rating * 0.35);
+/// Gather every factor the merit models can weigh for one guard over a period.
+pub async fn compute_merit_factors(db: &PgPool, guard_id: &str, period: &MeritPeriod) -> AppResult<MeritFactors> {
+    compute_merit_factors_for(db, &[guard_id.to_string()], period)
+        .await?
+        .remove(guard_id)
+        .ok_or_else(|| AppError::InternalServerError("Merit factors missing for guard".to_string()))
+}
 
-    // 5. Determine Rank based on score
-    let rank = match overall_score {
-        score if score >= 90.0 => "Gold",
-        score if score >= 80.0 => "Silver",
-        score if score >= 70.0 => "Bronze",
-        _ => "Standard",
-    };
+/// One rolling window's score, compared with the window before it.
+pub struct MeritWindowResult {
+    pub days: i32,
+    pub factors: MeritFactors,
+    pub score: f64,
+    pub rank: &'static str,
+    pub previous_score: Option<f64>,
+    pub trend: Option<&'static str>,
+}
+
+/// Everything a recalculation stores for one guard.
+pub struct GuardMeritResult {
+    pub factors: MeritFactors,
+    pub score: f64,
+    pub rank: &'static str,
+    pub windows: Vec<MeritWindowResult>,
+}
+
+/// Score a set of guards under a model: all-time plus each rolling window and its predecessor.
+/// Runs one set-based factor query per period, regardless of how many guards there are.
+pub async fn score_guards(
+    db: &PgPool,
+    spec: &MeritModelSpec,
+    guard_ids: &[String],
+) -> AppResult<HashMap<String, GuardMeritResult>> {
+    let mut results: HashMap<String, GuardMeritResult> =
+        compute_merit_factors_for(db, guard_ids, &MeritPeriod::all_time(spec.decay_half_life_days))
+            .await?
+            .into_iter()
+            .map(|(guard_id, factors)| {
+                let (score, rank) = apply_merit_model(spec, &factors);
+                (guard_id, GuardMeritResult { factors, score, rank, windows: Vec::new() })
+            })
+            .collect();
+
+    for days in MERIT_WINDOWS {
+        let period = MeritPeriod::rolling(days, spec.decay_half_life_days);
+        let mut current = compute_merit_factors_for(db, guard_ids, &period).await?;
+        let mut previous = match period.preceding() {
+            Some(preceding) => compute_merit_factors_for(db, guard_ids, &preceding).await?,
+            None => HashMap::new(),
+        };
+
+        for (guard_id, result) in results.iter_mut() {
+            let Some(factors) = current.remove(guard_id) else { continue };
+            let (score, rank) = apply_merit_model(spec, &factors);
+            let (previous_score, trend) = match previous.remove(guard_id) {
+                Some(prior) if prior.activity_count > 0 => {
+                    let (previous_score, _) = apply_merit_model(spec, &prior);
+                    let trend = match score - previous_score {
+                        delta if delta >= TREND_THRESHOLD => "up",
+                        delta if delta <= -TREND_THRESHOLD => "down",
+                        _ => "steady",
+                    };
+                    (Some(previous_score), Some(trend))
+                }
+                _ => (None, None),
+            };
+            result.windows.push(MeritWindowResult { days, factors, score, rank, previous_score, trend });
+        }
+    }
+
+    Ok(results)
+}
 
-    // 6. Get late and no-show counts
-    let late_count = sqlx::query_scalar::<_, Option<i64>>(
-        "SELECT COUNT(*) FROM punctuality_records WHERE guard_id = $1 AND status = 'late'"
+/// Persist scored guards in bulk: current scores, a history snapshot each, and window scores.
+pub async fn save_merit_results(
+    conn: &mut PgConnection,
+    model: &MeritModel,
+    results: &[(&String, &GuardMeritResult)],
+    calculated_at: DateTime<Utc>,
+) -> AppResult<()> {
+    let guard_ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+    let score_ids: Vec<String> = results.iter().map(|_| utils::generate_id()).collect();
+    let history_ids: Vec<String> = results.iter().map(|_| utils::generate_id()).collect();
+    let factor = |f: fn(&GuardMeritResult) -> f64| results.iter().map(|(_, r)| f(r)).collect::<Vec<f64>>();
+    let count = |f: fn(&GuardMeritResult) -> i32| results.iter().map(|(_, r)| f(r)).collect::<Vec<i32>>();
+    let ranks: Vec<&str> = results.iter().map(|(_, r)| r.rank).collect();
+
+    sqlx::query(
+        "INSERT INTO guard_merit_scores 
+         (id, guard_id, attendance_score, punctuality_score, client_rating, overall_score, rank, 
+          total_shifts_completed, on_time_count, late_count, no_show_count, average_client_rating, evaluation_count,
+          incident_score, patrol_score, training_score, conduct_adjustment, model_version, last_calculated_at)
+         SELECT *, $18::int4, $19::timestamptz
+         FROM UNNEST($1::varchar[], $2::varchar[], $3::float8[], $4::float8[], $5::float8[], $6::float8[],
+                     $7::varchar[], $8::int4[], $9::int4[], $10::int4[], $11::int4[], $12::float8[], $13::int4[],
+                     $14::float8[], $15::float8[], $16::float8[], $17::float8[])
+         ON CONFLICT (guard_id) DO UPDATE
+         SET attendance_score = EXCLUDED.attendance_score, punctuality_score = EXCLUDED.punctuality_score,
+             client_rating = EXCLUDED.client_rating, overall_score = EXCLUDED.overall_score, rank = EXCLUDED.rank,
+             total_shifts_completed = EXCLUDED.total_shifts_completed, on_time_count = EXCLUDED.on_time_count,
+             late_count = EXCLUDED.late_count, no_show_count = EXCLUDED.no_show_count,
+             average_client_rating = EXCLUDED.average_client_rating, evaluation_count = EXCLUDED.evaluation_count,
+             incident_score = EXCLUDED.incident_score, patrol_score = EXCLUDED.patrol_score,
+             training_score = EXCLUDED.training_score, conduct_adjustment = EXCLUDED.conduct_adjustment,
+             model_version = EXCLUDED.model_version,
+             last_calculated_at = EXCLUDED.last_calculated_at, updated_at = CURRENT_TIMESTAMP"
     )
-    .bind(guard_id)
-    .fetch_one(db.as_ref())
+    .bind(&score_ids)
+    .bind(&guard_ids)
+    .bind(factor(|r| r.factors.attendance))
+    .bind(factor(|r| r.factors.punctuality))
+    .bind(factor(|r| r.factors.client_rating))
+    .bind(factor(|r| r.score))
+    .bind(&ranks)
+    .bind(count(|r| r.factors.total_shifts as i32))
+    .bind(count(|r| r.factors.on_time_count))
+    .bind(count(|r| r.factors.late_count))
+    .bind(count(|r| r.factors.no_show_count))
+    .bind(factor(|r| r.factors.client_rating / 100.0 * 5.0)) // Convert back to 0-5
+    .bind(count(|r| r.factors.evaluation_count))
+    .bind(factor(|r| r.factors.incident))
+    .bind(factor(|r| r.factors.patrol))
+    .bind(factor(|r| r.factors.training))
+    .bind(factor(|r| r.factors.conduct_adjustment))
+    .bind(model.version)
+    .bind(calculated_at)
+    .execute(&mut *conn)
     .await
-    .unwrap_or(None)
-    .unwrap_or(0) as i32;
+    .map_err(|e| AppError::DatabaseError(format!("Failed to save merit scores: {}", e)))?;
 
-    let no_show_count = sqlx::query_scalar::<_, Option<i64>>(
-        "SELECT COUNT(*) FROM punctuality_records WHERE guard_id = $1 AND status = 'no_show'"
+    sqlx::query(
+        "INSERT INTO merit_score_history
+         (id, guard_id, attendance_score, punctuality_score, client_rating,
+          incident_score, patrol_score, training_score, overall_score, rank, conduct_adjustment,
+          model_id, model_version, calculated_at)
+         SELECT *, $12::varchar, $13::int4, $14::timestamptz
+         FROM UNNEST($1::varchar[], $2::varchar[], $3::float8[], $4::float8[], $5::float8[],
+                     $6::float8[], $7::float8[], $8::float8[], $9::float8[], $10::varchar[], $11::float8[])"
     )
-    .bind(guard_id)
-    .fetch_one(db.as_ref())
+    .bind(&history_ids)
+    .bind(&guard_ids)
+    .bind(factor(|r| r.factors.attendance))
+    .bind(factor(|r| r.factors.punctuality))
+    .bind(factor(|r| r.factors.client_rating))
+    .bind(factor(|r| r.factors.incident))
+    .bind(factor(|r| r.factors.patrol))
+    .bind(factor(|r| r.factors.training))
+    .bind(factor(|r| r.score))
+    .bind(&ranks)
+    .bind(factor(|r| r.factors.conduct_adjustment))
+    .bind(&model.id)
+    .bind(model.version)
+    .bind(calculated_at)
+    .execute(&mut *conn)
     .await
-    .unwrap_or(None)
-    .unwrap_or(0) as i32;
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record merit history: {}", e)))?;
+
+    let windows: Vec<(&str, &MeritWindowResult)> = results
+        .iter()
+        .flat_map(|(guard_id, r)| r.windows.iter().map(move |w| (guard_id.as_str(), w)))
+        .collect();
 
-    // 7. Update or create merit score record
-    let merit_score_id = sqlx::query_scalar::<_, Option<String>>(
-        "SELECT id FROM guard_merit_scores WHERE guard_id = $1"
+    sqlx::query(
+        "INSERT INTO guard_merit_window_scores
+         (guard_id, window_days, overall_score, rank, punctuality_score, average_client_rating,
+          activity_count, previous_score, trend, model_version, calculated_at)
+         SELECT *, $10::int4, $11::timestamptz
+         FROM UNNEST($1::varchar[], $2::int4[], $3::float8[], $4::varchar[], $5::float8[], $6::float8[],
+                     $7::int4[], $8::float8[], $9::varchar[])
+         ON CONFLICT (guard_id, window_days) DO UPDATE
+         SET model_version = EXCLUDED.model_version, overall_score = EXCLUDED.overall_score,
+             rank = EXCLUDED.rank, punctuality_score = EXCLUDED.punctuality_score,
+             average_client_rating = EXCLUDED.average_client_rating, activity_count = EXCLUDED.activity_count,
+             previous_score = EXCLUDED.previous_score, trend = EXCLUDED.trend,
+             calculated_at = EXCLUDED.calculated_at"
     )
-    .bind(guard_id)
-    .fetch_optional(db.as_ref())
+    .bind(windows.iter().map(|(id, _)| *id).collect::<Vec<&str>>())
+    .bind(windows.iter().map(|(_, w)| w.days).collect::<Vec<i32>>())
+    .bind(windows.iter().map(|(_, w)| w.score).collect::<Vec<f64>>())
+    .bind(windows.iter().map(|(_, w)| w.rank).collect::<Vec<&str>>())
+    .bind(windows.iter().map(|(_, w)| w.factors.punctuality).collect::<Vec<f64>>())
+    .bind(windows.iter().map(|(_, w)| w.factors.average_rating).collect::<Vec<Option<f64>>>())
+    .bind(windows.iter().map(|(_, w)| w.factors.activity_count as i32).collect::<Vec<i32>>())
+    .bind(windows.iter().map(|(_, w)| w.previous_score).collect::<Vec<Option<f64>>>())
+    .bind(windows.iter().map(|(_, w)| w.trend).collect::<Vec<Option<&str>>>())
+    .bind(model.version)
+    .bind(calculated_at)
+    .execute(&mut *conn)
     .await
-    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
-    .flatten();
+    .map_err(|e| AppError::DatabaseError(format!("Failed to save merit window scores: {}", e)))?;
 
-    if let Some(_id) = merit_score_id {
-        // Update existing record
-        sqlx::query(
-            "UPDATE guard_merit_scores 
-             SET attendance_score = $2, punctuality_score = $3, client_rating = $4, 
-                 overall_score = $5, rank = $6, total_shifts_completed = $7,
-                 on_time_count = $8, late_count = $9, no_show_count = $10,
-                 average_client_rating = $11, evaluation_count = $12, last_calculated_at = CURRENT_TIMESTAMP
-             WHERE guard_id = $1"
-        )
-        .bind(guard_id)
-        .bind(attendance_score)
-        .bind(punctuality_score)
-        .bind(client_rating)
-        .bind(overall_score)
-        .bind(rank)
-        .bind(total_shifts as i32)
-        .bind(on_time_count)
-        .bind(late_count)
-        .bind(no_show_count)
-        .bind(client_rating / 100.0 * 5.0) // Convert back to 0-5
-        .bind(eval_count)
-        .execute(db.as_ref())
+    Ok(())
+}
+
+/// Recompute and persist one guard's merit score under the active model,
+/// recording a history snapshot and the rolling window scores.
+pub async fn recalculate_merit_score(db: &PgPool, guard_id: &str) -> AppResult<MeritScoreResponse> {
+    let model = active_merit_model(db).await?;
+    let guard_ids = [guard_id.to_string()];
+    let mut results = score_guards(db, &MeritModelSpec::from(&model), &guard_ids).await?;
+    let result = results
+        .remove(guard_id)
+        .ok_or_else(|| AppError::InternalServerError("Merit factors missing for guard".to_string()))?;
+    let calculated_at = Utc::now();
+
+    let mut tx = db
+        .begin()
         .await
-        .map_err(|e| AppError::DatabaseError(format!("Failed to update merit score: {}", e)))?;
-    } else {
-        // Create new record
-        let id = utils::generate_id();
-        sqlx::query(
-            "INSERT INTO guard_merit_scores 
-             (id, guard_id, attendance_score, punctuality_score, client_rating, overall_score, rank, 
-              total_shifts_completed, on_time_count, late_count, no_show_count, average_client_rating, evaluation_count)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"
-        )
-        .bind(&id)
-        .bind(guard_id)
-        .bind(attendance_score)
-        .bind(punctuality_score)
-        .bind(client_rating)
-        .bind(overall_score)
-        .bind(rank)
-        .bind(total_shifts as i32)
-        .bind(on_time_count)
-        .bind(late_count)
-        .bind(no_show_count)
-        .bind(client_rating / 100.0 * 5.0)
-        .bind(eval_count)
-        .execute(db.as_ref())
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+    save_merit_results(&mut tx, &model, &[(&guard_ids[0], &result)], calculated_at).await?;
+    tx.commit()
         .await
-        .map_err(|e| AppError::DatabaseError(format!("Failed to create merit score: {}", e)))?;
-    }
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
 
     // Get guard name
     let guard_name: Option<String> = sqlx::query_scalar(
         "SELECT full_name FROM users WHERE id = $1"
     )
     .bind(guard_id)
-    .fetch_optional(db.as_ref())
+    .fetch_optional(db)
     .await
     .ok()
     .flatten();
 
-    Ok((StatusCode::OK, Json(MeritScoreResponse {
-        guard_id: guard_id.clone(),
+    let factors = &result.factors;
+    Ok(MeritScoreResponse {
+        guard_id: guard_id.to_string(),
         guard_name,
-        overall_score: overall_score.min(100.0).max(0.0),
-        rank: Some(rank.to_string()),
-        attendance_score: attendance_score.min(100.0).max(0.0),
-        punctuality_score: punctuality_score.min(100.0).max(0.0),
-        client_rating: client_rating.min(100.0).max(0.0),
+        overall_score: result.score,
+        rank: Some(result.rank.to_string()),
+        attendance_score: factors.attendance,
+        punctuality_score: factors.punctuality,
+        client_rating: factors.client_rating,
+        incident_score: factors.incident,
+        patrol_score: factors.patrol,
+        training_score: factors.training,
+        conduct_adjustment: factors.conduct_adjustment,
+        model_version: Some(model.version),
         stats: MeritStats {
-            total_shifts: total_shifts as i32,
-            on_time_count,
-            late_count,
-            no_show_count,
-            evaluations: eval_count,
-            average_rating: avg_rating.unwrap_or(0.0),
+            total_shifts: factors.total_shifts as i32,
+            on_time_count: factors.on_time_count,
+            late_count: factors.late_count,
+            no_show_count: factors.no_show_count,
+            evaluations: factors.evaluation_count,
+            average_rating: factors.average_rating.unwrap_or(0.0),
         },
-    })))
+        windows: result
+            .windows
+            .iter()
+            .map(|w| MeritWindowScore {
+                window_days: w.days,
+                model_version: model.version,
+                overall_score: w.score,
+                rank: w.rank.to_string(),
+                punctuality_score: w.factors.punctuality,
+                average_client_rating: w.factors.average_rating,
+                activity_count: w.factors.activity_count as i32,
+                previous_score: w.previous_score,
+                trend: w.trend.map(str::to_string),
+                calculated_at,
+            })
+            .collect(),
+    })
 }
 
 // Get merit score for a specific guard
@@ -220,7 +612,8 @@ pub async fn get_guard_merit_score(
                 CAST(client_rating AS FLOAT8), CAST(overall_score AS FLOAT8), rank, 
                 total_shifts_completed, on_time_count, late_count, no_show_count, 
                 CAST(average_client_rating AS FLOAT8), evaluation_count, last_calculated_at, 
-                created_at, updated_at
+                created_at, updated_at, incident_score, patrol_score, training_score, model_version,
+                conduct_adjustment
          FROM guard_merit_scores WHERE guard_id = $1"
     )
     .bind(&guard_id)
@@ -238,6 +631,16 @@ pub async fn get_guard_merit_score(
     .ok()
     .flatten();
 
+    let windows = sqlx::query_as::<_, MeritWindowScore>(
+        "SELECT window_days, model_version, overall_score, rank, punctuality_score, average_client_rating,
+                activity_count, previous_score, trend, calculated_at
+         FROM guard_merit_window_scores WHERE guard_id = $1 ORDER BY window_days"
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
     Ok(Json(MeritScoreResponse {
         guard_id,
         guard_name,
@@ -246,6 +649,11 @@ pub async fn get_guard_merit_score(
         attendance_score: merit_score.attendance_score,
         punctuality_score: merit_score.punctuality_score,
         client_rating: merit_score.client_rating,
+        incident_score: merit_score.incident_score.unwrap_or(0.0),
+        patrol_score: merit_score.patrol_score.unwrap_or(0.0),
+        training_score: merit_score.training_score.unwrap_or(0.0),
+        conduct_adjustment: merit_score.conduct_adjustment.unwrap_or(0.0),
+        model_version: merit_score.model_version,
         stats: MeritStats {
             total_shifts: merit_score.total_shifts_completed.unwrap_or(0),
             on_time_count: merit_score.on_time_count.unwrap_or(0),
@@ -254,88 +662,117 @@ pub async fn get_guard_merit_score(
             evaluations: merit_score.evaluation_count.unwrap_or(0),
             average_rating: merit_score.average_client_rating.unwrap_or(0.0),
         },
+        windows,
     }))
 }
 
-// Get all guards ranked by merit score
+// Get all guards ranked by merit score, optionally over a rolling window, at a site or in one tier
 pub async fn get_ranked_guards(
     State(db): State<Arc<PgPool>>,
+    Query(query): Query<MeritRankingQuery>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let guards = sqlx::query_as::<_, (String, String, f64, Option<String>, i32, i32, f64)>(
-        "SELECT gms.guard_id, u.full_name, CAST(gms.overall_score AS FLOAT8), gms.rank, 
-                gms.on_time_count, 
-                COALESCE(gms.on_time_count + gms.late_count + gms.no_show_count, 0) as total_tracked,
-                CAST(gms.average_client_rating AS FLOAT8)
-         FROM guard_merit_scores gms
-         JOIN users u ON gms.guard_id = u.id
-         WHERE u.role = 'user'
-         ORDER BY gms.overall_score DESC, u.full_name"
-    )
-    .fetch_all(db.as_ref())
-    .await
+    if let Some(window) = query.window {
+        if !MERIT_WINDOWS.contains(&window) {
+            return Err(AppError::BadRequest("window must be 30, 90 or 365".to_string()));
+        }
+    }
+    if let Some(tier) = &query.tier {
+        if !["gold", "silver", "bronze", "standard"].contains(&tier.to_lowercase().as_str()) {
+            return Err(AppError::BadRequest("tier must be Gold, Silver, Bronze or Standard".to_string()));
+        }
+    }
+
+    // Site filter: guards with a shift at the site (inside the window, when one is given)
+    let guards = match query.window {
+        None => sqlx::query_as::<_, (String, String, f64, Option<String>, f64, Option<f64>, Option<String>)>(
+            "SELECT gms.guard_id, u.full_name, CAST(gms.overall_score AS FLOAT8), gms.rank,
+                    CASE WHEN COALESCE(gms.on_time_count + gms.late_count + gms.no_show_count, 0) > 0
+                         THEN gms.on_time_count * 100.0 / (gms.on_time_count + gms.late_count + gms.no_show_count)
+                         ELSE 0 END::float8,
+                    CAST(gms.average_client_rating AS FLOAT8), NULL::varchar
+             FROM guard_merit_scores gms
+             JOIN users u ON gms.guard_id = u.id
+             WHERE u.role = 'user'
+             AND ($1::varchar IS NULL OR EXISTS(
+                 SELECT 1 FROM shifts s WHERE s.guard_id = gms.guard_id AND s.client_site = $1
+             ))
+             AND ($2::varchar IS NULL OR LOWER(gms.rank) = LOWER($2))
+             ORDER BY gms.overall_score DESC, u.full_name"
+        )
+        .bind(&query.site)
+        .bind(&query.tier)
+        .fetch_all(db.as_ref())
+        .await,
+        Some(window) => sqlx::query_as::<_, (String, String, f64, Option<String>, f64, Option<f64>, Option<String>)>(
+            "SELECT w.guard_id, u.full_name, w.overall_score, w.rank, w.punctuality_score,
+                    w.average_client_rating, w.trend
+             FROM guard_merit_window_scores w
+             JOIN users u ON w.guard_id = u.id
+             WHERE u.role = 'user' AND w.window_days = $1 AND w.activity_count > 0
+             AND ($2::varchar IS NULL OR EXISTS(
+                 SELECT 1 FROM shifts s
+                 WHERE s.guard_id = w.guard_id AND s.client_site = $2
+                 AND s.start_time >= CURRENT_TIMESTAMP - make_interval(days => $1)
+             ))
+             AND ($3::varchar IS NULL OR LOWER(w.rank) = LOWER($3))
+             ORDER BY w.overall_score DESC, u.full_name"
+        )
+        .bind(window)
+        .bind(&query.site)
+        .bind(&query.tier)
+        .fetch_all(db.as_ref())
+        .await,
+    }
     .map_err(|e| AppError::DatabaseError(format!("Failed to query guards: {}", e)))?;
 
     let ranked: Vec<RankedGuardResponse> = guards
         .into_iter()
         .enumerate()
-        .map(|(idx, (guard_id, guard_name, score, rank, on_time, total, rating))| {
-            let on_time_pct = if total > 0 {
-                (on_time as f64 / total as f64) * 100.0
-            } else {
-                0.0
-            };
-
-            RankedGuardResponse {
-                rank: (idx + 1) as i32,
-                guard_id,
-                guard_name: Some(guard_name),
-                overall_score: score,
-                merit_rank: rank,
-                on_time_percentage: on_time_pct,
-                client_rating: rating,
-            }
+        .map(|(idx, (guard_id, guard_name, score, rank, on_time_pct, rating, trend))| RankedGuardResponse {
+            rank: (idx + 1) as i32,
+            guard_id,
+            guard_name: Some(guard_name),
+            overall_score: score,
+            merit_rank: rank,
+            on_time_percentage: on_time_pct,
+            client_rating: rating.unwrap_or(0.0),
+            trend,
         })
         .collect();
 
     Ok(Json(json!({
+        "window": query.window,
         "total": ranked.len(),
         "rankings": ranked
     })))
 }
 
-// Submit client evaluation for a guard
+// Submit client evaluation from a client account; must reference a completed shift or mission
 pub async fn submit_client_evaluation(
     State(db): State<Arc<PgPool>>,
     Json(payload): Json<CreateClientEvaluationRequest>,
 ) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
-    if payload.rating < 0.0 || payload.rating > 5.0 {
-        return Err(AppError::BadRequest(
-            "Rating must be between 0 and 5".to_string(),
-        ));
-    }
-
-    let id = utils::generate_id();
-    sqlx::query(
-        "INSERT INTO client_evaluations 
-         (id, guard_id, shift_id, mission_id, evaluator_name, evaluator_role, rating, comment)
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+    let client_user_id = payload.client_user_id.ok_or_else(|| {
+        AppError::Unauthorized(
+            "Evaluations must be submitted from a client account or an evaluation link".to_string(),
+        )
+    })?;
+    let target = EvaluationTarget::from_ids(payload.shift_id, payload.mission_id)?;
+
+    let evaluation = submit_account_evaluation(
+        db.as_ref(),
+        &client_user_id,
+        target,
+        payload.rating,
+        payload.comment.as_deref(),
     )
-    .bind(&id)
-    .bind(&payload.guard_id)
-    .bind(&payload.shift_id)
-    .bind(&payload.mission_id)
-    .bind(&payload.evaluator_name)
-    .bind(&payload.evaluator_role)
-    .bind(payload.rating)
-    .bind(&payload.comment)
-    .execute(db.as_ref())
-    .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to create evaluation: {}", e)))?;
+    .await?;
 
     Ok((
         StatusCode::CREATED,
         Json(json!({
-            "id": id,
+            "id": evaluation.id,
+            "guardId": evaluation.guard_id,
             "message": "Evaluation submitted successfully"
         })),
     ))
@@ -348,7 +785,7 @@ pub async fn get_guard_evaluations(
 ) -> AppResult<Json<serde_json::Value>> {
     let evaluations = sqlx::query_as::<_, ClientEvaluation>(
         "SELECT id, guard_id, shift_id, mission_id, evaluator_name, evaluator_role, 
-                CAST(rating AS FLOAT8) AS rating, comment, created_at 
+                CAST(rating AS FLOAT8) AS rating, comment, created_at, client_user_id, verified, status
          FROM client_evaluations 
          WHERE guard_id = $1
          ORDER BY created_at DESC"
@@ -358,8 +795,10 @@ pub async fn get_guard_evaluations(
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to query evaluations: {}", e)))?;
 
-    let avg_rating = evaluations.iter().map(|e| e.rating).sum::<f64>()
-        / if evaluations.is_empty() { 1.0 } else { evaluations.len() as f64 };
+    // Overturned ratings stay visible but no longer count
+    let counted: Vec<f64> = evaluations.iter().filter(|e| e.status != "overturned").map(|e| e.rating).collect();
+    let avg_rating = counted.iter().sum::<f64>()
+        / if counted.is_empty() { 1.0 } else { counted.len() as f64 };
 
     Ok(Json(json!({
         "total": evaluations.len(),
@@ -397,3 +836,22 @@ pub async fn get_overtime_candidates(
         }).collect::<Vec<_>>()
     })))
 }
+
+// Merit score snapshots for a guard, newest first
+pub async fn get_merit_history(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let history = sqlx::query_as::<_, MeritScoreSnapshot>(
+        "SELECT * FROM merit_score_history WHERE guard_id = $1 ORDER BY calculated_at DESC LIMIT 500"
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to query merit history: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": history.len(),
+        "history": history
+    })))
+}
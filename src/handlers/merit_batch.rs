@@ -0,0 +1,274 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{
+        merit::{save_merit_results, score_guards},
+        merit_models::active_merit_model,
+    },
+    models::{
+        MeritModel, MeritModelSpec, MeritRecalculationError, MeritRecalculationRun, StartMeritRecalculationRequest,
+    },
+    utils,
+};
+
+/// Guards scored and saved per pass; progress is written after each chunk.
+const BATCH_SIZE: usize = 500;
+/// A run still marked running after this long is assumed to have died with its instance.
+const STALE_RUN_MINUTES: i32 = 60;
+/// Per-guard errors echoed in the run summary (all of them are stored).
+const SUMMARY_ERROR_LIMIT: usize = 50;
+
+/// Open a recalculation run for every guard. Only one run may be in progress at a time.
+pub async fn start_merit_recalculation(
+    db: &PgPool,
+    trigger: &str,
+    requested_by: Option<&str>,
+) -> AppResult<MeritRecalculationRun> {
+    sqlx::query(
+        "UPDATE merit_recalculation_runs
+         SET status = 'failed', error = 'Abandoned: the instance running it stopped', finished_at = CURRENT_TIMESTAMP
+         WHERE status = 'running' AND started_at < CURRENT_TIMESTAMP - make_interval(mins => $1)",
+    )
+    .bind(STALE_RUN_MINUTES)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to clear stale merit runs: {}", e)))?;
+
+    sqlx::query_as::<_, MeritRecalculationRun>(
+        "INSERT INTO merit_recalculation_runs (id, trigger, requested_by, total_guards)
+         SELECT $1, $2, $3, COUNT(*) FROM users WHERE role = 'user'
+         ON CONFLICT DO NOTHING
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(trigger)
+    .bind(requested_by)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to start merit recalculation: {}", e)))?
+    .ok_or_else(|| AppError::Conflict("A merit recalculation is already running".to_string()))
+}
+
+async fn record_guard_errors(db: &PgPool, run_id: &str, errors: &[(String, String)]) -> AppResult<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO merit_recalculation_errors (id, run_id, guard_id, error)
+         SELECT id, $1, guard_id, error FROM UNNEST($2::varchar[], $3::varchar[], $4::text[]) AS e(id, guard_id, error)",
+    )
+    .bind(run_id)
+    .bind(errors.iter().map(|_| utils::generate_id()).collect::<Vec<String>>())
+    .bind(errors.iter().map(|(guard_id, _)| guard_id.as_str()).collect::<Vec<&str>>())
+    .bind(errors.iter().map(|(_, error)| error.as_str()).collect::<Vec<&str>>())
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record merit errors: {}", e)))?;
+    Ok(())
+}
+
+/// Score and save one chunk of guards. The chunk is written in one transaction; if that
+/// fails, each guard is retried on its own so one bad row only fails that guard.
+async fn process_chunk(
+    db: &PgPool,
+    model: &MeritModel,
+    spec: &MeritModelSpec,
+    guard_ids: &[String],
+) -> (usize, Vec<(String, String)>) {
+    let results = match score_guards(db, spec, guard_ids).await {
+        Ok(results) => results,
+        Err(e) => return (0, guard_ids.iter().map(|id| (id.clone(), e.to_string())).collect()),
+    };
+    let scored: Vec<_> = guard_ids.iter().filter_map(|id| results.get(id).map(|r| (id, r))).collect();
+    let calculated_at = Utc::now();
+
+    let bulk = async {
+        let mut tx = db
+            .begin()
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+        save_merit_results(&mut tx, model, &scored, calculated_at).await?;
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))
+    };
+    if bulk.await.is_ok() {
+        return (scored.len(), Vec::new());
+    }
+
+    let mut saved = 0;
+    let mut errors = Vec::new();
+    for entry in &scored {
+        let single = async {
+            let mut tx = db
+                .begin()
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+            save_merit_results(&mut tx, model, std::slice::from_ref(entry), calculated_at).await?;
+            tx.commit()
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))
+        };
+        match single.await {
+            Ok(()) => saved += 1,
+            Err(e) => errors.push((entry.0.clone(), e.to_string())),
+        }
+    }
+    (saved, errors)
+}
+
+/// Carry out a started run: score every guard in set-based chunks, writing progress
+/// and per-guard errors as it goes. Returns the run summary.
+pub async fn run_merit_recalculation(db: &PgPool, run_id: &str) -> AppResult<serde_json::Value> {
+    let outcome = async {
+        let model = active_merit_model(db).await?;
+        let spec = MeritModelSpec::from(&model);
+        let guard_ids: Vec<String> = sqlx::query_scalar("SELECT id FROM users WHERE role = 'user' ORDER BY id")
+            .fetch_all(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch guards: {}", e)))?;
+
+        sqlx::query("UPDATE merit_recalculation_runs SET model_version = $2, total_guards = $3 WHERE id = $1")
+            .bind(run_id)
+            .bind(model.version)
+            .bind(guard_ids.len() as i32)
+            .execute(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update merit run: {}", e)))?;
+
+        let mut summary_errors = Vec::new();
+        for chunk in guard_ids.chunks(BATCH_SIZE) {
+            let (saved, errors) = process_chunk(db, &model, &spec, chunk).await;
+            record_guard_errors(db, run_id, &errors).await?;
+
+            sqlx::query(
+                "UPDATE merit_recalculation_runs
+                 SET processed_guards = processed_guards + $2, succeeded = succeeded + $3, failed = failed + $4
+                 WHERE id = $1",
+            )
+            .bind(run_id)
+            .bind(chunk.len() as i32)
+            .bind(saved as i32)
+            .bind(errors.len() as i32)
+            .execute(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update merit run: {}", e)))?;
+
+            for (guard_id, error) in errors {
+                if summary_errors.len() < SUMMARY_ERROR_LIMIT {
+                    summary_errors.push(json!({ "guardId": guard_id, "error": error }));
+                }
+            }
+        }
+        Ok::<_, AppError>(summary_errors)
+    }
+    .await;
+
+    let (status, error) = match &outcome {
+        Ok(_) => ("completed", None),
+        Err(e) => ("failed", Some(e.to_string())),
+    };
+    let run = sqlx::query_as::<_, MeritRecalculationRun>(
+        "UPDATE merit_recalculation_runs SET status = $2, error = $3, finished_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(run_id)
+    .bind(status)
+    .bind(&error)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to finish merit run: {}", e)))?;
+
+    let errors = outcome?;
+    Ok(json!({
+        "runId": run.id,
+        "modelVersion": run.model_version,
+        "guards": run.total_guards,
+        "updated": run.succeeded,
+        "failed": run.failed,
+        "errors": errors,
+    }))
+}
+
+/// Recompute merit scores for every guard in one run. Used by the background scheduler.
+pub async fn recalculate_all_merit_scores(db: &PgPool) -> AppResult<serde_json::Value> {
+    let run = start_merit_recalculation(db, "schedule", None).await?;
+    run_merit_recalculation(db, &run.id).await
+}
+
+// Start a batch recalculation in the background; poll the run for progress
+pub async fn start_batch_recalculation(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<StartMeritRecalculationRequest>,
+) -> AppResult<(StatusCode, Json<MeritRecalculationRun>)> {
+    utils::ensure_supervisor(db.as_ref(), &payload.requested_by, "recalculate all merit scores").await?;
+
+    let run = start_merit_recalculation(db.as_ref(), "manual", Some(&payload.requested_by)).await?;
+
+    let run_id = run.id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_merit_recalculation(db.as_ref(), &run_id).await {
+            tracing::error!("Merit recalculation {} failed: {}", run_id, e);
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+// Recent batch recalculation runs, newest first
+pub async fn get_recalculation_runs(State(db): State<Arc<PgPool>>) -> AppResult<Json<serde_json::Value>> {
+    let runs = sqlx::query_as::<_, MeritRecalculationRun>(
+        "SELECT * FROM merit_recalculation_runs ORDER BY started_at DESC LIMIT 20",
+    )
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": runs.len(),
+        "runs": runs
+    })))
+}
+
+// Progress of one run and the guards it could not score
+pub async fn get_recalculation_run(
+    State(db): State<Arc<PgPool>>,
+    Path(run_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let run = sqlx::query_as::<_, MeritRecalculationRun>("SELECT * FROM merit_recalculation_runs WHERE id = $1")
+        .bind(&run_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Recalculation run not found".to_string()))?;
+
+    let errors = sqlx::query_as::<_, MeritRecalculationError>(
+        "SELECT * FROM merit_recalculation_errors WHERE run_id = $1 ORDER BY created_at",
+    )
+    .bind(&run_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let progress = if run.total_guards > 0 {
+        run.processed_guards as f64 / run.total_guards as f64 * 100.0
+    } else {
+        100.0
+    };
+
+    Ok(Json(json!({
+        "run": run,
+        "progressPercent": progress,
+        "errors": errors
+    })))
+}
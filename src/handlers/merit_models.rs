@@ -0,0 +1,239 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::merit::{apply_merit_model, compute_merit_factors, MeritPeriod},
+    models::{ActivateMeritModelRequest, CreateMeritModelRequest, MeritModel, MeritModelSpec, MeritWhatIfRequest},
+    utils,
+};
+
+/// The model new merit scores are calculated with.
+pub async fn active_merit_model(db: impl PgExecutor<'_>) -> AppResult<MeritModel> {
+    sqlx::query_as::<_, MeritModel>("SELECT * FROM merit_models WHERE is_active")
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to load merit model: {}", e)))?
+        .ok_or_else(|| AppError::InternalServerError("No active merit model".to_string()))
+}
+
+fn validate_spec(spec: &MeritModelSpec) -> AppResult<()> {
+    let weights = [
+        spec.attendance_weight,
+        spec.punctuality_weight,
+        spec.client_rating_weight,
+        spec.incident_weight,
+        spec.patrol_weight,
+        spec.training_weight,
+    ];
+    if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+        return Err(AppError::ValidationError("Weights cannot be negative".to_string()));
+    }
+    if (weights.iter().sum::<f64>() - 100.0).abs() > 0.01 {
+        return Err(AppError::ValidationError("Weights must add up to 100".to_string()));
+    }
+    if spec.decay_half_life_days.is_some_and(|days| !days.is_finite() || days <= 0.0) {
+        return Err(AppError::ValidationError("decayHalfLifeDays must be positive".to_string()));
+    }
+    if !(0.0 <= spec.bronze_min && spec.bronze_min < spec.silver_min && spec.silver_min < spec.gold_min && spec.gold_min <= 100.0) {
+        return Err(AppError::ValidationError(
+            "Rank cutoffs must satisfy 0 <= bronzeMin < silverMin < goldMin <= 100".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// List merit model versions, newest first
+pub async fn get_merit_models(State(db): State<Arc<PgPool>>) -> AppResult<Json<serde_json::Value>> {
+    let models = sqlx::query_as::<_, MeritModel>("SELECT * FROM merit_models ORDER BY version DESC")
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": models.len(),
+        "models": models
+    })))
+}
+
+// Publish a new merit model version, optionally activating it
+pub async fn create_merit_model(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateMeritModelRequest>,
+) -> AppResult<(StatusCode, Json<MeritModel>)> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Model name is required".to_string()));
+    }
+    validate_spec(&payload.spec)?;
+    utils::ensure_supervisor(db.as_ref(), &payload.created_by, "manage merit models").await?;
+
+    let activate = payload.activate.unwrap_or(false);
+    let spec = &payload.spec;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    if activate {
+        sqlx::query("UPDATE merit_models SET is_active = false WHERE is_active")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to deactivate merit model: {}", e)))?;
+    }
+
+    let model = sqlx::query_as::<_, MeritModel>(
+        "INSERT INTO merit_models
+         (id, version, name, attendance_weight, punctuality_weight, client_rating_weight, incident_weight,
+          patrol_weight, training_weight, gold_min, silver_min, bronze_min, is_active, notes, created_by, activated_at,
+          decay_half_life_days)
+         SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14,
+                CASE WHEN $12 THEN CURRENT_TIMESTAMP END, $15
+         FROM merit_models
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(payload.name.trim())
+    .bind(spec.attendance_weight)
+    .bind(spec.punctuality_weight)
+    .bind(spec.client_rating_weight)
+    .bind(spec.incident_weight)
+    .bind(spec.patrol_weight)
+    .bind(spec.training_weight)
+    .bind(spec.gold_min)
+    .bind(spec.silver_min)
+    .bind(spec.bronze_min)
+    .bind(activate)
+    .bind(&payload.notes)
+    .bind(&payload.created_by)
+    .bind(spec.decay_half_life_days)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        let unique_violation = e
+            .as_database_error()
+            .and_then(|d| d.code())
+            .is_some_and(|code| code == "23505");
+        if unique_violation {
+            AppError::Conflict("Another merit model was published at the same time; retry".to_string())
+        } else {
+            AppError::DatabaseError(format!("Failed to create merit model: {}", e))
+        }
+    })?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(model)))
+}
+
+// Switch scoring to an existing model version; scores pick it up on their next recalculation
+pub async fn activate_merit_model(
+    State(db): State<Arc<PgPool>>,
+    Path(model_id): Path<String>,
+    Json(payload): Json<ActivateMeritModelRequest>,
+) -> AppResult<Json<MeritModel>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.activated_by, "manage merit models").await?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let is_active = sqlx::query_scalar::<_, bool>("SELECT is_active FROM merit_models WHERE id = $1 FOR UPDATE")
+        .bind(&model_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Merit model not found".to_string()))?;
+
+    if is_active {
+        return Err(AppError::Conflict("Merit model is already active".to_string()));
+    }
+
+    sqlx::query("UPDATE merit_models SET is_active = false WHERE is_active")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to deactivate merit model: {}", e)))?;
+
+    let model = sqlx::query_as::<_, MeritModel>(
+        "UPDATE merit_models SET is_active = true, activated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+    )
+    .bind(&model_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to activate merit model: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(model))
+}
+
+// Rescore guards under a proposed model without saving anything
+pub async fn merit_what_if(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<MeritWhatIfRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    validate_spec(&payload.spec)?;
+    let current = active_merit_model(db.as_ref()).await?;
+    let current_spec = MeritModelSpec::from(&current);
+
+    let guards = sqlx::query_as::<_, (String, String)>(
+        "SELECT id, full_name FROM users
+         WHERE role = 'user' AND ($1::varchar[] IS NULL OR id = ANY($1))
+         ORDER BY full_name",
+    )
+    .bind(&payload.guard_ids)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch guards: {}", e)))?;
+
+    let mut results = Vec::with_capacity(guards.len());
+    for (guard_id, guard_name) in guards {
+        let current_factors =
+            compute_merit_factors(db.as_ref(), &guard_id, &MeritPeriod::all_time(current_spec.decay_half_life_days))
+                .await?;
+        let (current_score, current_rank) = apply_merit_model(&current_spec, &current_factors);
+        let proposed_factors = if payload.spec.decay_half_life_days == current_spec.decay_half_life_days {
+            current_factors
+        } else {
+            compute_merit_factors(db.as_ref(), &guard_id, &MeritPeriod::all_time(payload.spec.decay_half_life_days))
+                .await?
+        };
+        let (proposed_score, proposed_rank) = apply_merit_model(&payload.spec, &proposed_factors);
+        results.push((guard_id, guard_name, current_score, current_rank, proposed_score, proposed_rank));
+    }
+    results.sort_by(|a, b| b.4.total_cmp(&a.4).then_with(|| a.1.cmp(&b.1)));
+
+    let rank_changes = results.iter().filter(|r| r.3 != r.5).count();
+    let guards: Vec<serde_json::Value> = results
+        .into_iter()
+        .map(|(guard_id, guard_name, current_score, current_rank, proposed_score, proposed_rank)| {
+            json!({
+                "guardId": guard_id,
+                "guardName": guard_name,
+                "currentScore": current_score,
+                "currentRank": current_rank,
+                "proposedScore": proposed_score,
+                "proposedRank": proposed_rank,
+                "change": proposed_score - current_score,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "currentModelVersion": current.version,
+        "total": guards.len(),
+        "rankChanges": rank_changes,
+        "guards": guards
+    })))
+}
@@ -3,13 +3,21 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::{
+        availability, driver_qualifications,
+        trip_crew::{self, CrewManifest},
+        vehicle_compliance, vehicle_maintenance,
+        vehicle_status::{self, StatusChange},
+    },
+    models::{CrewEscortInput, VehicleStatus},
     utils,
 };
 
@@ -73,6 +81,73 @@ pub struct MissionDetails {
     pub estimated_duration_hours: f64,
 }
 
+/// An assigned driver of a candidate car, and why they cannot drive it on the mission, if so.
+struct DriverCandidate {
+    car_id: String,
+    driver_id: String,
+    problem: Option<String>,
+}
+
+/// Why a driver may not drive the car from `start_time` to `end_time`: license, certification or
+/// hours of service. None when they may.
+async fn driver_problem(
+    conn: &mut PgConnection,
+    driver_id: &str,
+    car_id: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> AppResult<Option<String>> {
+    let checked = match driver_qualifications::ensure_licensed(conn, driver_id, car_id).await {
+        Ok(()) => driver_qualifications::ensure_rested_for(conn, driver_id, None, start_time, Some(end_time)).await,
+        Err(e) => Err(e),
+    };
+    match checked {
+        Ok(()) => Ok(None),
+        Err(AppError::Conflict(problem)) => Ok(Some(problem)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Pick `needed` of the cars (id, plate), in order, each with the first of its assigned drivers who
+/// may drive it and is not driving another. Returns (car index, driver id) pairs, or why too few
+/// cars have a driver.
+fn choose_drivers(
+    cars: &[(String, String)],
+    candidates: &[DriverCandidate],
+    needed: usize,
+) -> Result<Vec<(usize, String)>, String> {
+    let mut chosen: Vec<(usize, String)> = Vec::new();
+    let mut skipped = Vec::new();
+    for (car, (car_id, plate)) in cars.iter().enumerate() {
+        if chosen.len() == needed {
+            break;
+        }
+        let assigned: Vec<&DriverCandidate> = candidates.iter().filter(|c| c.car_id == *car_id).collect();
+        let driver = assigned
+            .iter()
+            .find(|c| c.problem.is_none() && !chosen.iter().any(|(_, id)| *id == c.driver_id));
+        match driver {
+            Some(driver) => chosen.push((car, driver.driver_id.clone())),
+            None if assigned.is_empty() => skipped.push(format!("{} has no assigned driver free for the mission", plate)),
+            None => skipped.push(format!(
+                "{}: {}",
+                plate,
+                assigned.iter().map(|c| c.problem.as_deref().unwrap_or("driver is on another vehicle")).collect::<Vec<_>>().join("; ")
+            )),
+        }
+    }
+
+    if chosen.len() < needed {
+        return Err(format!(
+            "Only {} of {} vehicles have a qualified driver ({})",
+            chosen.len(),
+            needed,
+            skipped.join(", ")
+        ));
+    }
+    Ok(chosen)
+}
+
 // Integrated mission assignment endpoint
 pub async fn assign_mission(
     State(db): State<Arc<PgPool>>,
@@ -94,33 +169,116 @@ pub async fn assign_mission(
 
     let duration = (end_time - start_time).num_hours() as f64;
 
-    // 1. Find available guards
+    if payload.guards_required < payload.vehicles_required {
+        return Err(AppError::BadRequest(format!(
+            "Each vehicle needs a guard to drive it. Guards: {}, Vehicles: {}",
+            payload.guards_required, payload.vehicles_required
+        )));
+    }
+
     #[derive(sqlx::FromRow)]
     struct GuardRow {
         id: String,
         full_name: Option<String>,
         username: String,
     }
-    
-    let guards = sqlx::query_as::<_, GuardRow>(
+
+    // 1. Find available vehicles, each with the assigned drivers free for the mission window
+    #[derive(sqlx::FromRow)]
+    struct VehicleRow {
+        id: String,
+        license_plate: String,
+        model: Option<String>,
+        passenger_capacity: Option<i32>,
+        driver_ids: Vec<String>,
+    }
+
+    let candidates = sqlx::query_as::<_, VehicleRow>(
+        "SELECT ac.id, ac.license_plate, ac.model, ac.passenger_capacity,
+                ARRAY(SELECT da.guard_id FROM driver_assignments da
+                      JOIN users u ON u.id = da.guard_id
+                      WHERE da.car_id = ac.id AND da.status = 'active'
+                        AND u.verified = true AND guard_is_available(u.id, $2, $3, $4)
+                      ORDER BY da.assignment_date) AS driver_ids
+         FROM armored_cars ac
+         WHERE ac.status = $1
+         ORDER BY ac.license_plate"
+    )
+    .bind(VehicleStatus::Available.as_str())
+    .bind(start_time)
+    .bind(end_time)
+    .bind(availability::schedule_timezone())
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to query vehicles: {}", e)))?;
+
+    if candidates.len() < payload.vehicles_required as usize {
+        return Err(AppError::BadRequest(format!(
+            "Insufficient vehicles available. Requested: {}, Available: {}",
+            payload.vehicles_required, candidates.len()
+        )));
+    }
+
+    // 2. Drive each vehicle with an assigned driver who holds the licenses and has the hours for it
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to acquire connection: {}", e)))?;
+    let mut driver_candidates = Vec::new();
+    for vehicle in &candidates {
+        for driver_id in &vehicle.driver_ids {
+            let problem = driver_problem(&mut conn, driver_id, &vehicle.id, start_time, end_time).await?;
+            driver_candidates.push(DriverCandidate { car_id: vehicle.id.clone(), driver_id: driver_id.clone(), problem });
+        }
+    }
+    drop(conn);
+
+    let cars: Vec<(String, String)> = candidates.iter().map(|v| (v.id.clone(), v.license_plate.clone())).collect();
+    let chosen = choose_drivers(&cars, &driver_candidates, payload.vehicles_required as usize)
+        .map_err(AppError::Conflict)?;
+    let vehicles: Vec<&VehicleRow> = chosen.iter().map(|(car, _)| &candidates[*car]).collect();
+    let driver_ids: Vec<String> = chosen.into_iter().map(|(_, driver_id)| driver_id).collect();
+
+    let mut overridden = Vec::new();
+    for vehicle in &vehicles {
+        vehicle_maintenance::ensure_serviceable(db.as_ref(), &vehicle.id).await?;
+        overridden.push(vehicle_compliance::ensure_documents_valid(db.as_ref(), &vehicle.id).await?);
+    }
+
+    // 3. The rest of the guards, from those whose availability calendar covers the mission window
+    let mut drivers = sqlx::query_as::<_, GuardRow>("SELECT id, full_name, username FROM users WHERE id = ANY($1)")
+        .bind(&driver_ids)
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query guards: {}", e)))?;
+    drivers.sort_by_key(|guard| driver_ids.iter().position(|id| *id == guard.id));
+
+    let escorts_required = (payload.guards_required - payload.vehicles_required) as usize;
+    let escorts = sqlx::query_as::<_, GuardRow>(
         "SELECT id, full_name, username FROM users 
          WHERE role = 'user' 
          AND verified = true 
+         AND NOT (id = ANY($5))
+         AND guard_is_available(id, $2, $3, $4)
          LIMIT $1"
     )
-    .bind(payload.guards_required as i64)
+    .bind(escorts_required as i64)
+    .bind(start_time)
+    .bind(end_time)
+    .bind(availability::schedule_timezone())
+    .bind(&driver_ids)
     .fetch_all(db.as_ref())
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to query guards: {}", e)))?;
 
-    if guards.len() < payload.guards_required as usize {
+    if escorts.len() < escorts_required {
         return Err(AppError::BadRequest(format!(
             "Insufficient guards available. Requested: {}, Available: {}",
-            payload.guards_required, guards.len()
+            payload.guards_required, drivers.len() + escorts.len()
         )));
     }
 
-    // 2. Find available firearms
+    // 4. Find available firearms
     #[derive(sqlx::FromRow)]
     struct FirearmRow {
         id: String,
@@ -145,37 +303,18 @@ pub async fn assign_mission(
         )));
     }
 
-    // 3. Find available vehicles
-    #[derive(sqlx::FromRow)]
-    struct VehicleRow {
-        id: String,
-        model: Option<String>,
-        passenger_capacity: Option<i32>,
-    }
-    
-    let vehicles = sqlx::query_as::<_, VehicleRow>(
-        "SELECT id, model, passenger_capacity FROM armored_cars 
-         WHERE status = 'operational' 
-         LIMIT $1"
-    )
-    .bind(payload.vehicles_required as i64)
-    .fetch_all(db.as_ref())
-    .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to query vehicles: {}", e)))?;
-
-    if vehicles.len() < payload.vehicles_required as usize {
-        return Err(AppError::BadRequest(format!(
-            "Insufficient vehicles available. Requested: {}, Available: {}",
-            payload.vehicles_required, vehicles.len()
-        )));
-    }
-
     let mission_id = format!("MISSION_{}_{}", 
         start_time.format("%Y%m%d"),
         utils::generate_id().split('-').next().unwrap_or("001")
     );
 
-    // 4. Create shifts for guards
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    // 5. Create shifts for guards; escorts are armed before drivers
+    let guards: Vec<&GuardRow> = escorts.iter().chain(&drivers).collect();
     let mut guard_assignments = Vec::new();
     for guard in &guards {
         let shift_id = utils::generate_id();
@@ -188,7 +327,7 @@ pub async fn assign_mission(
         .bind(start_time)
         .bind(end_time)
         .bind(&payload.destination)
-        .execute(db.as_ref())
+        .execute(&mut *tx)
         .await
         .map_err(|e| AppError::DatabaseError(format!("Failed to create shift: {}", e)))?;
 
@@ -199,8 +338,9 @@ pub async fn assign_mission(
         });
     }
 
-    // 5. Allocate firearms to guards
+    // 6. Allocate firearms to guards
     let mut firearm_assignments = Vec::new();
+    let mut allocation_ids: Vec<Option<String>> = vec![None; guards.len()];
     for (i, firearm) in firearms.iter().enumerate() {
         if let Some(guard) = guards.get(i) {
             let allocation_id = utils::generate_id();
@@ -212,14 +352,15 @@ pub async fn assign_mission(
             .bind(&guard.id)
             .bind(&firearm.id)
             .bind(start_time)
-            .execute(db.as_ref())
+            .execute(&mut *tx)
             .await
             .map_err(|e| AppError::DatabaseError(format!("Failed to allocate firearm: {}", e)))?;
+            allocation_ids[i] = Some(allocation_id);
 
             // Update firearm status
             sqlx::query("UPDATE firearms SET status = 'allocated' WHERE id = $1")
                 .bind(&firearm.id)
-                .execute(db.as_ref())
+                .execute(&mut *tx)
                 .await
                 .map_err(|e| AppError::DatabaseError(format!("Failed to update firearm: {}", e)))?;
 
@@ -231,9 +372,21 @@ pub async fn assign_mission(
         }
     }
 
-    // 6. Allocate vehicles
+    // 7. Allocate vehicles
     let mut vehicle_assignments = Vec::new();
-    for vehicle in &vehicles {
+    let reason = format!("Reserved for mission {}", mission_id);
+    let mut dispatches = Vec::new();
+    for (v, (vehicle, overridden)) in vehicles.iter().zip(&overridden).enumerate() {
+        // Escorts are shared out among the vehicles in turn
+        let crew_escorts: Vec<CrewEscortInput> = (v..escorts.len())
+            .step_by(vehicles.len())
+            .map(|e| CrewEscortInput {
+                user_id: escorts[e].id.clone(),
+                firearm_allocation_id: allocation_ids[e].clone(),
+            })
+            .collect();
+        let manifest = CrewManifest { driver_id: &driver_ids[v], escorts: &crew_escorts, custodian_id: None };
+
         let trip_id = utils::generate_id();
         sqlx::query(
             "INSERT INTO trips (id, car_id, start_time, end_time, destination, driver_id, status) 
@@ -244,11 +397,24 @@ pub async fn assign_mission(
         .bind(start_time)
         .bind(end_time)
         .bind(&payload.destination)
-        .bind(&guards.first().map(|g| g.id.clone()).unwrap_or_default())
-        .execute(db.as_ref())
+        .bind(manifest.driver_id)
+        .execute(&mut *tx)
         .await
         .map_err(|e| AppError::DatabaseError(format!("Failed to create trip: {}", e)))?;
 
+        trip_crew::validate_crew(&mut tx, &vehicle.id, Some(&trip_id), &manifest).await?;
+        trip_crew::write_crew(&mut tx, &trip_id, &manifest).await?;
+
+        // Reserve the car until its trip starts; a car taken meanwhile is a conflict
+        vehicle_status::transition(
+            &mut tx,
+            &vehicle.id,
+            VehicleStatus::Allocated,
+            &StatusChange { reason: &reason, trip_id: Some(&trip_id), ..Default::default() },
+        )
+        .await?;
+        dispatches.push((trip_id, manifest.driver_id.to_string(), overridden));
+
         vehicle_assignments.push(VehicleAssignment {
             id: vehicle.id.clone(),
             r#type: vehicle.model.clone().unwrap_or_else(|| "Armored Vehicle".to_string()),
@@ -257,6 +423,20 @@ pub async fn assign_mission(
         });
     }
 
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    for (trip_id, driver_id, overridden) in dispatches {
+        vehicle_compliance::record_override_use(
+            db.as_ref(),
+            overridden,
+            Some(&driver_id),
+            &format!("Assigned to mission {} (trip {})", mission_id, trip_id),
+        )
+        .await?;
+    }
+
     let response = MissionAssignmentResponse {
         mission_id: mission_id.clone(),
         status: "allocated".to_string(),
@@ -311,3 +491,60 @@ pub async fn get_missions(
         "missions": missions
     })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cars(plates: &[&str]) -> Vec<(String, String)> {
+        plates.iter().map(|plate| (format!("car-{}", plate), plate.to_string())).collect()
+    }
+
+    fn candidate(plate: &str, driver_id: &str, problem: Option<&str>) -> DriverCandidate {
+        DriverCandidate {
+            car_id: format!("car-{}", plate),
+            driver_id: driver_id.to_string(),
+            problem: problem.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn an_assigned_licensed_driver_is_dispatched() {
+        let candidates = [candidate("A1", "ana", None)];
+        assert_eq!(choose_drivers(&cars(&["A1"]), &candidates, 1), Ok(vec![(0, "ana".to_string())]));
+    }
+
+    #[test]
+    fn an_over_hours_driver_is_rejected_for_the_next_qualified_one() {
+        let candidates = [
+            candidate("A1", "ana", Some("Ana would drive 11.0h within 24h (limit 10h)")),
+            candidate("A1", "ben", None),
+        ];
+        assert_eq!(choose_drivers(&cars(&["A1"]), &candidates, 1), Ok(vec![(0, "ben".to_string())]));
+    }
+
+    #[test]
+    fn a_car_without_a_qualified_driver_is_skipped() {
+        let candidates = [
+            candidate("A1", "ana", Some("Ana would drive 11.0h within 24h (limit 10h)")),
+            candidate("B2", "ben", None),
+        ];
+        assert_eq!(choose_drivers(&cars(&["A1", "B2"]), &candidates, 1), Ok(vec![(1, "ben".to_string())]));
+    }
+
+    #[test]
+    fn a_driver_drives_only_one_car() {
+        let candidates = [candidate("A1", "ana", None), candidate("B2", "ana", None)];
+        let err = choose_drivers(&cars(&["A1", "B2"]), &candidates, 2).unwrap_err();
+        assert!(err.contains("Only 1 of 2"), "{}", err);
+        assert!(err.contains("B2: driver is on another vehicle"), "{}", err);
+    }
+
+    #[test]
+    fn too_few_qualified_drivers_names_each_car() {
+        let candidates = [candidate("A1", "ana", Some("Ana has no license"))];
+        let err = choose_drivers(&cars(&["A1", "B2"]), &candidates, 1).unwrap_err();
+        assert!(err.contains("A1: Ana has no license"), "{}", err);
+        assert!(err.contains("B2 has no assigned driver free for the mission"), "{}", err);
+    }
+}
@@ -14,3 +14,33 @@ pub mod notifications;
 pub mod merit;
 pub mod firearm_maintenance;
 pub mod training;
+pub mod training_catalog;
+pub mod scheduled_jobs;
+pub mod replacement_offers;
+pub mod client_sites;
+pub mod availability;
+pub mod shift_swaps;
+pub mod timesheets;
+pub mod clients;
+pub mod invoices;
+pub mod incidents;
+pub mod client_portal;
+pub mod service_requests;
+pub mod evaluations;
+pub mod merit_models;
+pub mod patrols;
+pub mod merit_batch;
+pub mod disciplinary;
+pub mod notification_stream;
+pub mod notification_providers;
+pub mod notification_dispatch;
+pub mod notification_templates;
+pub mod telemetry;
+pub mod trip_monitoring;
+pub mod sos;
+pub mod vehicle_maintenance;
+pub mod vehicle_compliance;
+pub mod vehicle_status;
+pub mod trip_crew;
+pub mod consignments;
+pub mod driver_qualifications;
@@ -0,0 +1,553 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{
+        availability::{parse_time_of_day, schedule_timezone},
+        notification_providers::{delivery_providers, OutboundMessage},
+        notification_stream::{NotificationHub, NotificationSignal},
+        notification_templates,
+    },
+    models::{
+        NotificationDelivery, NotificationPreference, PushDevice, RegisterPushDeviceRequest,
+        UpdateNotificationPreferencesRequest,
+    },
+    utils,
+};
+
+/// Types that are delivered during quiet hours and, by default, on every channel.
+pub const CRITICAL_TYPES: &[&str] = &["replacement_request", "replacement_escalated", "permit_revoked", "trip_alert", "sos", "consignment"];
+/// Attempts per delivery before it is marked failed.
+const MAX_ATTEMPTS: i32 = 5;
+/// First retry delay; doubles with each failed attempt.
+const RETRY_BASE_SECS: i32 = 30;
+/// A delivery still 'sending' after this long was abandoned by a stopped instance.
+const STALE_SENDING_MINUTES: i32 = 10;
+/// Notifications planned and deliveries sent per job run.
+const BATCH_LIMIT: i64 = 200;
+
+fn is_critical(notification_type: &str) -> bool {
+    CRITICAL_TYPES.contains(&notification_type)
+}
+
+/// Channels used when the user has no preference for a type: push only, or every
+/// channel for critical types.
+fn default_preference(notification_type: &str) -> NotificationPreference {
+    let critical = is_critical(notification_type);
+    NotificationPreference {
+        notification_type: notification_type.to_string(),
+        email_enabled: critical,
+        sms_enabled: critical,
+        push_enabled: true,
+    }
+}
+
+/// When the user's quiet hours end, if they are in them now.
+async fn quiet_hours_end(db: &PgPool, user_id: &str) -> AppResult<Option<DateTime<Utc>>> {
+    let until = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        "SELECT CASE
+                    WHEN (qs < qe AND lt >= qs AND lt < qe) OR (qs > qe AND (lt >= qs OR lt < qe))
+                    THEN (local_now::date + qe
+                          + CASE WHEN qe <= lt THEN INTERVAL '1 day' ELSE INTERVAL '0 days' END) AT TIME ZONE tz
+                END
+         FROM (
+             SELECT quiet_hours_start AS qs, quiet_hours_end AS qe, tz,
+                    NOW() AT TIME ZONE tz AS local_now, (NOW() AT TIME ZONE tz)::time AS lt
+             FROM (
+                 SELECT quiet_hours_start, quiet_hours_end, COALESCE(timezone, $2) AS tz
+                 FROM notification_settings
+                 WHERE user_id = $1 AND quiet_hours_start IS NOT NULL AND quiet_hours_end IS NOT NULL
+             ) s
+         ) q",
+    )
+    .bind(user_id)
+    .bind(schedule_timezone())
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to check quiet hours: {}", e)))?;
+    Ok(until.flatten())
+}
+
+/// Plan a notification's email/SMS/push deliveries from the user's preferences and send
+/// those that are due. Each notification is planned once, by whichever instance claims it.
+pub async fn dispatch_notification(db: &PgPool, notification_id: &str) -> AppResult<usize> {
+    let claimed = sqlx::query_as::<_, (String, String)>(
+        "UPDATE notifications SET dispatched_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND dispatched_at IS NULL
+         RETURNING user_id, type",
+    )
+    .bind(notification_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to claim notification: {}", e)))?;
+    let Some((user_id, notification_type)) = claimed else { return Ok(0) };
+
+    let (email, phone_number) = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT email, phone_number FROM users WHERE id = $1",
+    )
+    .bind(&user_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let preference = sqlx::query_as::<_, NotificationPreference>(
+        "SELECT notification_type, email_enabled, sms_enabled, push_enabled
+         FROM notification_preferences WHERE user_id = $1 AND notification_type = $2",
+    )
+    .bind(&user_id)
+    .bind(&notification_type)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .unwrap_or_else(|| default_preference(&notification_type));
+
+    let mut targets: Vec<(&str, Option<String>, &str)> = Vec::new();
+    if preference.email_enabled {
+        targets.push(("email", Some(email), "No email address on file"));
+    }
+    if preference.sms_enabled {
+        targets.push(("sms", phone_number.filter(|p| !p.is_empty()), "No phone number on file"));
+    }
+    if preference.push_enabled {
+        let devices: Vec<String> =
+            sqlx::query_scalar("SELECT device_token FROM push_devices WHERE user_id = $1 ORDER BY created_at")
+                .bind(&user_id)
+                .fetch_all(db)
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+        if devices.is_empty() {
+            targets.push(("push", None, "No push devices registered"));
+        }
+        for device in devices {
+            targets.push(("push", Some(device), ""));
+        }
+    }
+    if targets.is_empty() {
+        return Ok(0);
+    }
+
+    let quiet_until = if is_critical(&notification_type) {
+        None
+    } else {
+        quiet_hours_end(db, &user_id).await?
+    };
+
+    let providers = delivery_providers();
+    let mut due = Vec::new();
+    for (channel, destination, missing_reason) in targets {
+        let (status, error) = match (&destination, providers.for_channel(channel)) {
+            (None, _) => ("skipped", Some(missing_reason.to_string())),
+            (Some(_), None) => ("skipped", Some(format!("No {} provider configured", channel))),
+            (Some(_), Some(_)) if quiet_until.is_some() => ("deferred", None),
+            (Some(_), Some(_)) => ("pending", None),
+        };
+        let delivery_id = utils::generate_id();
+        sqlx::query(
+            "INSERT INTO notification_deliveries
+             (id, notification_id, user_id, channel, destination, status, last_error, next_attempt_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, COALESCE($8, CURRENT_TIMESTAMP))
+             ON CONFLICT (notification_id, channel, destination) DO NOTHING",
+        )
+        .bind(&delivery_id)
+        .bind(notification_id)
+        .bind(&user_id)
+        .bind(channel)
+        .bind(&destination)
+        .bind(status)
+        .bind(&error)
+        .bind(quiet_until)
+        .execute(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to plan delivery: {}", e)))?;
+
+        if status == "pending" {
+            due.push(delivery_id);
+        }
+    }
+
+    for delivery_id in &due {
+        send_delivery(db, delivery_id).await?;
+    }
+    Ok(due.len())
+}
+
+/// Make one attempt at a due delivery. Failures are retried with exponential backoff
+/// until MAX_ATTEMPTS. Returns the resulting status, or `None` if it was not due.
+pub async fn send_delivery(db: &PgPool, delivery_id: &str) -> AppResult<Option<String>> {
+    let delivery = sqlx::query_as::<_, NotificationDelivery>(
+        "UPDATE notification_deliveries
+         SET status = 'sending', attempts = attempts + 1, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND status IN ('pending', 'deferred') AND next_attempt_at <= CURRENT_TIMESTAMP
+         RETURNING *",
+    )
+    .bind(delivery_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to claim delivery: {}", e)))?;
+    let Some(delivery) = delivery else { return Ok(None) };
+
+    let (title, message) = sqlx::query_as::<_, (String, String)>(
+        "SELECT title, message FROM notifications WHERE id = $1",
+    )
+    .bind(&delivery.notification_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let provider = delivery_providers().for_channel(&delivery.channel);
+    let outcome = match (provider, delivery.destination.as_deref()) {
+        (Some(provider), Some(destination)) => provider
+            .send(&OutboundMessage { destination, title: &title, body: &message })
+            .await
+            .map(|message_id| (provider.name(), message_id)),
+        (None, _) => Err(format!("No {} provider configured", delivery.channel)),
+        (_, None) => Err("No destination".to_string()),
+    };
+
+    let status = match outcome {
+        Ok((provider_name, message_id)) => {
+            sqlx::query(
+                "UPDATE notification_deliveries
+                 SET status = 'sent', provider = $2, provider_message_id = $3, last_error = NULL,
+                     sent_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+                 WHERE id = $1",
+            )
+            .bind(&delivery.id)
+            .bind(provider_name)
+            .bind(&message_id)
+            .execute(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to record delivery: {}", e)))?;
+            "sent"
+        }
+        Err(error) => {
+            let status = if delivery.attempts >= MAX_ATTEMPTS { "failed" } else { "pending" };
+            tracing::warn!(
+                "{} delivery {} attempt {} failed: {}",
+                delivery.channel,
+                delivery.id,
+                delivery.attempts,
+                error
+            );
+            sqlx::query(
+                "UPDATE notification_deliveries
+                 SET status = $2, last_error = $3, provider = $4, updated_at = CURRENT_TIMESTAMP,
+                     next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $5 * POWER(2, attempts - 1))
+                 WHERE id = $1",
+            )
+            .bind(&delivery.id)
+            .bind(status)
+            .bind(&error)
+            .bind(provider.map(|p| p.name()))
+            .bind(RETRY_BASE_SECS as f64)
+            .execute(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to record delivery: {}", e)))?;
+            status
+        }
+    };
+    Ok(Some(status.to_string()))
+}
+
+/// Plan notifications the live dispatcher missed and send every delivery that is due:
+/// retries, and messages held back by quiet hours. Used by the background scheduler.
+pub async fn process_notification_deliveries(db: &PgPool) -> AppResult<serde_json::Value> {
+    let reclaimed = sqlx::query(
+        "UPDATE notification_deliveries SET status = 'pending', updated_at = CURRENT_TIMESTAMP
+         WHERE status = 'sending' AND updated_at < CURRENT_TIMESTAMP - make_interval(mins => $1)",
+    )
+    .bind(STALE_SENDING_MINUTES)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to reclaim deliveries: {}", e)))?
+    .rows_affected();
+
+    let undispatched: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM notifications WHERE dispatched_at IS NULL ORDER BY created_at LIMIT $1",
+    )
+    .bind(BATCH_LIMIT)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch notifications: {}", e)))?;
+
+    let mut planned = 0;
+    for notification_id in &undispatched {
+        match dispatch_notification(db, notification_id).await {
+            Ok(_) => planned += 1,
+            Err(e) => tracing::error!("Failed to dispatch notification {}: {}", notification_id, e),
+        }
+    }
+
+    let due: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM notification_deliveries
+         WHERE status IN ('pending', 'deferred') AND next_attempt_at <= CURRENT_TIMESTAMP
+         ORDER BY next_attempt_at
+         LIMIT $1",
+    )
+    .bind(BATCH_LIMIT)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch due deliveries: {}", e)))?;
+
+    let (mut sent, mut retrying, mut failed) = (0, 0, 0);
+    for delivery_id in &due {
+        match send_delivery(db, delivery_id).await?.as_deref() {
+            Some("sent") => sent += 1,
+            Some("pending") => retrying += 1,
+            Some("failed") => failed += 1,
+            _ => {}
+        }
+    }
+
+    Ok(json!({
+        "reclaimed": reclaimed,
+        "planned": planned,
+        "sent": sent,
+        "retrying": retrying,
+        "failed": failed,
+    }))
+}
+
+/// Dispatch each notification as soon as the listener reports it. Anything missed here
+/// (lagging, restarts) is picked up by the `notification_delivery` job.
+pub fn start(db: Arc<PgPool>, hub: &NotificationHub) {
+    let mut receiver = hub.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(NotificationSignal::Inserted { id, .. }) => {
+                    if let Err(e) = dispatch_notification(db.as_ref(), &id).await {
+                        tracing::error!("Failed to dispatch notification {}: {}", id, e);
+                    }
+                }
+                Ok(NotificationSignal::Resync) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+async fn ensure_user(db: &PgPool, user_id: &str) -> AppResult<()> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+        .bind(user_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    if !exists {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+    Ok(())
+}
+
+// A user's channel preferences, quiet hours and push devices
+pub async fn get_notification_preferences(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    ensure_user(db.as_ref(), &user_id).await?;
+
+    type Settings = (Option<chrono::NaiveTime>, Option<chrono::NaiveTime>, Option<String>, Option<String>);
+    let settings = sqlx::query_as::<_, Settings>(
+        "SELECT quiet_hours_start, quiet_hours_end, timezone, locale FROM notification_settings WHERE user_id = $1",
+    )
+    .bind(&user_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .unwrap_or((None, None, None, None));
+
+    let preferences = sqlx::query_as::<_, NotificationPreference>(
+        "SELECT notification_type, email_enabled, sms_enabled, push_enabled
+         FROM notification_preferences WHERE user_id = $1 ORDER BY notification_type",
+    )
+    .bind(&user_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let devices = sqlx::query_as::<_, PushDevice>(
+        "SELECT * FROM push_devices WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(&user_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "userId": user_id,
+        "quietHoursStart": settings.0.map(|t| t.format("%H:%M").to_string()),
+        "quietHoursEnd": settings.1.map(|t| t.format("%H:%M").to_string()),
+        "timezone": settings.2.as_deref().unwrap_or(schedule_timezone()),
+        "locale": settings.3.as_deref().unwrap_or(notification_templates::DEFAULT_LOCALE),
+        "criticalTypes": CRITICAL_TYPES,
+        "defaults": {
+            "standard": { "email": false, "sms": false, "push": true },
+            "critical": { "email": true, "sms": true, "push": true },
+        },
+        "preferences": preferences,
+        "pushDevices": devices,
+    })))
+}
+
+// Set quiet hours and per-type channel preferences (types not listed keep their current setting)
+pub async fn update_notification_preferences(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<UpdateNotificationPreferencesRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    ensure_user(db.as_ref(), &user_id).await?;
+
+    let (quiet_start, quiet_end) = match (&payload.quiet_hours_start, &payload.quiet_hours_end) {
+        (Some(start), Some(end)) => {
+            let (start, end) = (parse_time_of_day(start)?, parse_time_of_day(end)?);
+            if start == end {
+                return Err(AppError::ValidationError(
+                    "quietHoursStart and quietHoursEnd must differ".to_string(),
+                ));
+            }
+            (Some(start), Some(end))
+        }
+        (None, None) => (None, None),
+        _ => {
+            return Err(AppError::ValidationError(
+                "quietHoursStart and quietHoursEnd must be set together".to_string(),
+            ))
+        }
+    };
+    if let Some(timezone) = &payload.timezone {
+        sqlx::query("SELECT NOW() AT TIME ZONE $1")
+            .bind(timezone)
+            .execute(db.as_ref())
+            .await
+            .map_err(|_| AppError::ValidationError(format!("Unknown timezone '{}'", timezone)))?;
+    }
+    if let Some(locale) = &payload.locale {
+        notification_templates::validate_locale(locale)?;
+    }
+    if payload.preferences.iter().any(|p| p.notification_type.trim().is_empty()) {
+        return Err(AppError::ValidationError("notificationType is required".to_string()));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    sqlx::query(
+        "INSERT INTO notification_settings (user_id, quiet_hours_start, quiet_hours_end, timezone, locale)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (user_id) DO UPDATE
+         SET quiet_hours_start = EXCLUDED.quiet_hours_start, quiet_hours_end = EXCLUDED.quiet_hours_end,
+             timezone = EXCLUDED.timezone, locale = EXCLUDED.locale, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(&user_id)
+    .bind(quiet_start)
+    .bind(quiet_end)
+    .bind(&payload.timezone)
+    .bind(&payload.locale)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to save quiet hours: {}", e)))?;
+
+    for preference in &payload.preferences {
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, notification_type, email_enabled, sms_enabled, push_enabled)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (user_id, notification_type) DO UPDATE
+             SET email_enabled = EXCLUDED.email_enabled, sms_enabled = EXCLUDED.sms_enabled,
+                 push_enabled = EXCLUDED.push_enabled, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(&user_id)
+        .bind(preference.notification_type.trim())
+        .bind(preference.email_enabled)
+        .bind(preference.sms_enabled)
+        .bind(preference.push_enabled)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save preference: {}", e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(json!({ "message": "Notification preferences updated" })))
+}
+
+// Register (or move to this user) a device for web push
+pub async fn register_push_device(
+    State(db): State<Arc<PgPool>>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<RegisterPushDeviceRequest>,
+) -> AppResult<(StatusCode, Json<PushDevice>)> {
+    if payload.device_token.trim().is_empty() {
+        return Err(AppError::BadRequest("deviceToken is required".to_string()));
+    }
+    let platform = payload.platform.as_deref().unwrap_or("web");
+    if !matches!(platform, "web" | "android" | "ios") {
+        return Err(AppError::ValidationError("platform must be web, android or ios".to_string()));
+    }
+    ensure_user(db.as_ref(), &user_id).await?;
+
+    let device = sqlx::query_as::<_, PushDevice>(
+        "INSERT INTO push_devices (id, user_id, device_token, platform)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (device_token) DO UPDATE
+         SET user_id = EXCLUDED.user_id, platform = EXCLUDED.platform, last_seen_at = CURRENT_TIMESTAMP
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&user_id)
+    .bind(payload.device_token.trim())
+    .bind(platform)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to register device: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(device)))
+}
+
+// Stop pushing to a device
+pub async fn delete_push_device(
+    State(db): State<Arc<PgPool>>,
+    Path((user_id, device_id)): Path<(String, String)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM push_devices WHERE id = $1 AND user_id = $2")
+        .bind(&device_id)
+        .bind(&user_id)
+        .execute(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Push device not found".to_string()));
+    }
+    Ok(Json(json!({ "message": "Push device removed" })))
+}
+
+// Per-channel delivery status of a notification
+pub async fn get_notification_deliveries(
+    State(db): State<Arc<PgPool>>,
+    Path(notification_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let deliveries = sqlx::query_as::<_, NotificationDelivery>(
+        "SELECT * FROM notification_deliveries WHERE notification_id = $1 ORDER BY channel, created_at",
+    )
+    .bind(&notification_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": deliveries.len(),
+        "deliveries": deliveries
+    })))
+}
@@ -0,0 +1,218 @@
+use futures_util::future::BoxFuture;
+use std::sync::OnceLock;
+
+use crate::utils;
+
+/// One message to one destination (an email address, phone number or push device token).
+pub struct OutboundMessage<'a> {
+    pub destination: &'a str,
+    pub title: &'a str,
+    pub body: &'a str,
+}
+
+/// Something that can hand a message to an external channel.
+/// Returns the provider's message id, or an error worth retrying.
+pub trait DeliveryProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn send<'a>(&'a self, message: &'a OutboundMessage<'a>) -> BoxFuture<'a, Result<String, String>>;
+}
+
+/// Logs messages instead of sending them. Destinations starting with `fail` are rejected,
+/// so retry handling can be exercised locally.
+pub struct MockProvider {
+    channel: &'static str,
+}
+
+impl DeliveryProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn send<'a>(&'a self, message: &'a OutboundMessage<'a>) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            if message.destination.starts_with("fail") {
+                return Err(format!("Mock {} provider rejected {}", self.channel, message.destination));
+            }
+            tracing::info!(
+                "[mock {}] to {}: {} - {}",
+                self.channel,
+                message.destination,
+                message.title,
+                message.body
+            );
+            Ok(format!("mock-{}", utils::generate_id()))
+        })
+    }
+}
+
+/// Email through the Resend API (same account as verification emails).
+pub struct ResendEmailProvider {
+    api_key: String,
+    from: String,
+}
+
+impl DeliveryProvider for ResendEmailProvider {
+    fn name(&self) -> &'static str {
+        "resend"
+    }
+
+    fn send<'a>(&'a self, message: &'a OutboundMessage<'a>) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let response = reqwest::Client::new()
+                .post("https://api.resend.com/emails")
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .json(&serde_json::json!({
+                    "from": self.from,
+                    "to": [message.destination],
+                    "subject": message.title,
+                    "text": message.body
+                }))
+                .send()
+                .await
+                .map_err(|e| format!("Failed to reach email API: {}", e))?;
+
+            let status = response.status();
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            if !status.is_success() {
+                return Err(format!("Email API error {}: {}", status, body));
+            }
+            Ok(body["id"].as_str().unwrap_or_default().to_string())
+        })
+    }
+}
+
+/// SMS through Twilio's Messages API.
+pub struct TwilioSmsProvider {
+    account_sid: String,
+    auth_token: String,
+    from_number: String,
+}
+
+impl DeliveryProvider for TwilioSmsProvider {
+    fn name(&self) -> &'static str {
+        "twilio"
+    }
+
+    fn send<'a>(&'a self, message: &'a OutboundMessage<'a>) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let text = format!("{}: {}", message.title, message.body);
+            let response = reqwest::Client::new()
+                .post(format!(
+                    "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+                    self.account_sid
+                ))
+                .basic_auth(&self.account_sid, Some(&self.auth_token))
+                .form(&[("To", message.destination), ("From", &self.from_number), ("Body", &text)])
+                .send()
+                .await
+                .map_err(|e| format!("Failed to reach SMS API: {}", e))?;
+
+            let status = response.status();
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            if !status.is_success() {
+                return Err(format!("SMS API error {}: {}", status, body["message"]));
+            }
+            Ok(body["sid"].as_str().unwrap_or_default().to_string())
+        })
+    }
+}
+
+/// Web push through a push gateway that takes a device token, title and body as JSON.
+pub struct PushGatewayProvider {
+    url: String,
+    api_key: Option<String>,
+}
+
+impl DeliveryProvider for PushGatewayProvider {
+    fn name(&self) -> &'static str {
+        "push_gateway"
+    }
+
+    fn send<'a>(&'a self, message: &'a OutboundMessage<'a>) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let mut request = reqwest::Client::new().post(&self.url).json(&serde_json::json!({
+                "token": message.destination,
+                "title": message.title,
+                "body": message.body
+            }));
+            if let Some(api_key) = &self.api_key {
+                request = request.header("Authorization", format!("Bearer {}", api_key));
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Failed to reach push gateway: {}", e))?;
+
+            let status = response.status();
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            if !status.is_success() {
+                return Err(format!("Push gateway error {}: {}", status, body));
+            }
+            Ok(body["id"].as_str().unwrap_or_default().to_string())
+        })
+    }
+}
+
+/// The provider behind each channel; a channel without one is not configured.
+pub struct DeliveryProviders {
+    pub email: Option<Box<dyn DeliveryProvider>>,
+    pub sms: Option<Box<dyn DeliveryProvider>>,
+    pub push: Option<Box<dyn DeliveryProvider>>,
+}
+
+impl DeliveryProviders {
+    pub fn for_channel(&self, channel: &str) -> Option<&dyn DeliveryProvider> {
+        match channel {
+            "email" => self.email.as_deref(),
+            "sms" => self.sms.as_deref(),
+            "push" => self.push.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+fn env_value(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+/// Providers configured from the environment. `NOTIFICATION_PROVIDER=mock` puts every
+/// channel on the mock provider.
+pub fn delivery_providers() -> &'static DeliveryProviders {
+    static PROVIDERS: OnceLock<DeliveryProviders> = OnceLock::new();
+    PROVIDERS.get_or_init(|| {
+        if env_value("NOTIFICATION_PROVIDER").as_deref() == Some("mock") {
+            tracing::info!("Notification channels use the mock provider");
+            return DeliveryProviders {
+                email: Some(Box::new(MockProvider { channel: "email" })),
+                sms: Some(Box::new(MockProvider { channel: "sms" })),
+                push: Some(Box::new(MockProvider { channel: "push" })),
+            };
+        }
+
+        DeliveryProviders {
+            email: env_value("RESEND_API_KEY").map(|api_key| {
+                Box::new(ResendEmailProvider {
+                    api_key,
+                    from: env_value("NOTIFICATION_EMAIL_FROM")
+                        .unwrap_or_else(|| "Sentinel DASIA <noreply@dasiasentinel.xyz>".to_string()),
+                }) as Box<dyn DeliveryProvider>
+            }),
+            sms: match (
+                env_value("TWILIO_ACCOUNT_SID"),
+                env_value("TWILIO_AUTH_TOKEN"),
+                env_value("TWILIO_FROM_NUMBER"),
+            ) {
+                (Some(account_sid), Some(auth_token), Some(from_number)) => Some(Box::new(TwilioSmsProvider {
+                    account_sid,
+                    auth_token,
+                    from_number,
+                })),
+                _ => None,
+            },
+            push: env_value("PUSH_GATEWAY_URL").map(|url| {
+                Box::new(PushGatewayProvider { url, api_key: env_value("PUSH_GATEWAY_API_KEY") })
+                    as Box<dyn DeliveryProvider>
+            }),
+        }
+    })
+}
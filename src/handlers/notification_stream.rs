@@ -0,0 +1,308 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    response::sse::{Event, KeepAlive, Sse},
+    Extension,
+};
+use chrono::{Duration, Utc};
+use futures_util::stream::{self, Stream};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{postgres::PgListener, PgPool};
+use std::{
+    collections::{HashSet, VecDeque},
+    convert::Infallible,
+    sync::Arc,
+};
+use tokio::sync::broadcast;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::notifications::NOTIFICATION_COLUMNS,
+    models::{Notification, NotificationStreamQuery},
+};
+
+/// Channel the notifications insert trigger publishes on.
+const NOTIFICATION_CHANNEL: &str = "notification_inserted";
+/// Signals buffered per instance before slow streams start missing them (and are told to resync).
+const HUB_CAPACITY: usize = 1024;
+/// Most notifications replayed when a stream resumes; past this the client is told to resync.
+const RESUME_LIMIT: i64 = 200;
+/// How long the client should wait before reconnecting a dropped stream.
+const RECONNECT_DELAY_MS: u64 = 3000;
+
+/// What the listener tells open streams.
+#[derive(Debug, Clone)]
+pub enum NotificationSignal {
+    Inserted { id: String, user_id: String },
+    /// Notifications may have been missed (listener reconnected); clients should refetch.
+    Resync,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InsertedPayload {
+    id: String,
+    user_id: String,
+}
+
+/// Fans database notifications out to the streams open on this instance.
+#[derive(Clone)]
+pub struct NotificationHub {
+    sender: broadcast::Sender<NotificationSignal>,
+}
+
+impl NotificationHub {
+    pub fn subscribe(&self) -> broadcast::Receiver<NotificationSignal> {
+        self.sender.subscribe()
+    }
+}
+
+/// LISTEN for inserted notifications on a dedicated connection and forward them to the hub.
+/// Every instance runs its own listener, so a notification inserted anywhere reaches every stream.
+pub fn start_listener(db: Arc<PgPool>) -> NotificationHub {
+    let (sender, _) = broadcast::channel(HUB_CAPACITY);
+    let hub = NotificationHub { sender: sender.clone() };
+
+    tokio::spawn(async move {
+        loop {
+            let mut listener = match PgListener::connect_with(db.as_ref()).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    tracing::error!("Notification listener failed to connect: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            if let Err(e) = listener.listen(NOTIFICATION_CHANNEL).await {
+                tracing::error!("Notification listener failed to LISTEN: {}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                continue;
+            }
+            tracing::info!("✓ Listening for notifications");
+
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => {
+                        match serde_json::from_str::<InsertedPayload>(notification.payload()) {
+                            Ok(payload) => {
+                                let _ = sender.send(NotificationSignal::Inserted {
+                                    id: payload.id,
+                                    user_id: payload.user_id,
+                                });
+                            }
+                            Err(e) => tracing::warn!("Ignoring malformed notification payload: {}", e),
+                        }
+                    }
+                    // Connection dropped; the listener reconnects on the next call but anything
+                    // sent in between is gone, so open streams must refetch
+                    Ok(None) => {
+                        tracing::warn!("Notification listener lost its connection; reconnecting");
+                        let _ = sender.send(NotificationSignal::Resync);
+                    }
+                    Err(e) => {
+                        tracing::error!("Notification listener error: {}", e);
+                        let _ = sender.send(NotificationSignal::Resync);
+                        break;
+                    }
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    hub
+}
+
+fn stream_secret() -> Option<String> {
+    std::env::var("JWT_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+fn token_mac(secret: &str, user_id: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("stream|{}|{}", user_id, expires).as_bytes());
+    mac
+}
+
+/// Signed token a client presents to open its notification stream, returned at login.
+/// `None` when JWT_SECRET is not configured.
+pub fn issue_stream_token(user_id: &str) -> Option<String> {
+    let secret = stream_secret()?;
+    let hours = std::env::var("JWT_EXPIRATION_HOURS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(24);
+    let expires = (Utc::now() + Duration::hours(hours)).timestamp();
+    let signature = hex::encode(token_mac(&secret, user_id, expires).finalize().into_bytes());
+    Some(format!("{}.{}.{}", user_id, expires, signature))
+}
+
+/// The user a stream token was issued to, if it is genuine and unexpired.
+fn verify_stream_token(token: &str) -> AppResult<String> {
+    let secret = stream_secret()
+        .ok_or_else(|| AppError::InternalServerError("Notification streaming is not configured".to_string()))?;
+    let invalid = || AppError::Unauthorized("Invalid stream token".to_string());
+
+    let mut parts = token.rsplitn(3, '.');
+    let (Some(signature), Some(expires), Some(user_id)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let expires: i64 = expires.parse().map_err(|_| invalid())?;
+    let signature = hex::decode(signature).map_err(|_| invalid())?;
+    token_mac(&secret, user_id, expires)
+        .verify_slice(&signature)
+        .map_err(|_| invalid())?;
+
+    if expires < Utc::now().timestamp() {
+        return Err(AppError::Unauthorized("Stream token has expired; log in again".to_string()));
+    }
+    Ok(user_id.to_string())
+}
+
+fn notification_event(notification: &Notification) -> Event {
+    Event::default()
+        .event("notification")
+        .id(notification.id.clone())
+        .data(serde_json::to_string(notification).unwrap_or_default())
+}
+
+fn resync_event(reason: &str) -> Event {
+    Event::default()
+        .event("resync")
+        .data(json!({ "reason": reason }).to_string())
+}
+
+/// Notifications for a user inserted after `last_event_id`, oldest first.
+/// `None` when the id is not one of the user's notifications (e.g. it was deleted).
+async fn notifications_after(db: &PgPool, user_id: &str, last_event_id: &str) -> AppResult<Option<Vec<Notification>>> {
+    let seq = sqlx::query_scalar::<_, i64>("SELECT seq FROM notifications WHERE id = $1 AND user_id = $2")
+        .bind(last_event_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    let Some(seq) = seq else { return Ok(None) };
+
+    let notifications = sqlx::query_as::<_, Notification>(&format!(
+        "SELECT {} FROM notifications WHERE user_id = $1 AND seq > $2 ORDER BY seq LIMIT $3",
+        NOTIFICATION_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(seq)
+    .bind(RESUME_LIMIT + 1)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to load missed notifications: {}", e)))?;
+    Ok(Some(notifications))
+}
+
+struct StreamState {
+    db: Arc<PgPool>,
+    user_id: String,
+    receiver: broadcast::Receiver<NotificationSignal>,
+    pending: VecDeque<Event>,
+    /// Sent from the resume backlog; their live signals are skipped
+    replayed: HashSet<String>,
+}
+
+async fn next_event(mut state: StreamState) -> Option<(Result<Event, Infallible>, StreamState)> {
+    loop {
+        if let Some(event) = state.pending.pop_front() {
+            return Some((Ok(event), state));
+        }
+        match state.receiver.recv().await {
+            Ok(NotificationSignal::Inserted { id, user_id }) => {
+                if user_id != state.user_id || state.replayed.remove(&id) {
+                    continue;
+                }
+                let notification = sqlx::query_as::<_, Notification>(&format!(
+                    "SELECT {} FROM notifications WHERE id = $1",
+                    NOTIFICATION_COLUMNS
+                ))
+                .bind(&id)
+                .fetch_optional(state.db.as_ref())
+                .await;
+                match notification {
+                    Ok(Some(notification)) => return Some((Ok(notification_event(&notification)), state)),
+                    Ok(None) => continue, // deleted before we got to it
+                    Err(e) => {
+                        tracing::warn!("Failed to load notification {} for stream: {}", id, e);
+                        return Some((Ok(resync_event("missed")), state));
+                    }
+                }
+            }
+            Ok(NotificationSignal::Resync) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                return Some((Ok(resync_event("missed")), state));
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+// Server-Sent Events stream of a user's new notifications. A grouped repeat re-sends the existing
+// notification (same id, higher `groupCount`). Reconnects resume after the `Last-Event-ID`
+// header (or `lastEventId`); a `resync` event means the client should refetch the list.
+pub async fn stream_notifications(
+    State(db): State<Arc<PgPool>>,
+    Extension(hub): Extension<NotificationHub>,
+    Query(query): Query<NotificationStreamQuery>,
+    headers: HeaderMap,
+) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let token = bearer
+        .or(query.token.as_deref())
+        .ok_or_else(|| AppError::Unauthorized("A stream token is required".to_string()))?;
+    let user_id = verify_stream_token(token)?;
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or(query.last_event_id)
+        .filter(|id| !id.is_empty());
+
+    // Subscribe before reading the backlog so nothing inserted in between is lost
+    let receiver = hub.subscribe();
+
+    let unread_count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read = false AND archived_at IS NULL",
+    )
+    .bind(&user_id)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let mut pending = VecDeque::new();
+    pending.push_back(
+        Event::default()
+            .event("ready")
+            .retry(std::time::Duration::from_millis(RECONNECT_DELAY_MS))
+            .data(json!({ "userId": user_id, "unreadCount": unread_count }).to_string()),
+    );
+
+    let mut replayed = HashSet::new();
+    if let Some(last_event_id) = &last_event_id {
+        match notifications_after(db.as_ref(), &user_id, last_event_id).await? {
+            Some(mut missed) => {
+                let truncated = missed.len() as i64 > RESUME_LIMIT;
+                missed.truncate(RESUME_LIMIT as usize);
+                for notification in &missed {
+                    replayed.insert(notification.id.clone());
+                    pending.push_back(notification_event(notification));
+                }
+                if truncated {
+                    pending.push_back(resync_event("too_many_missed"));
+                }
+            }
+            None => pending.push_back(resync_event("unknown_last_event_id")),
+        }
+    }
+
+    let state = StreamState { db, user_id, receiver, pending, replayed };
+    Ok(Sse::new(stream::unfold(state, next_event)).keep_alive(KeepAlive::default()))
+}
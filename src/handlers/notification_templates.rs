@@ -0,0 +1,600 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{
+        NotificationTemplate, NotificationTemplateQuery, ResetNotificationTemplateRequest,
+        UpdateNotificationTemplateRequest,
+    },
+    utils,
+};
+
+/// Locale every template ships in, and the fallback when a user's locale has no translation.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Days an ungrouped notification stays current unless its template says otherwise.
+const DEFAULT_TTL_DAYS: i32 = 30;
+
+/// Archived notifications are deleted after this many days (NOTIFICATION_ARCHIVE_RETENTION_DAYS).
+const DEFAULT_ARCHIVE_RETENTION_DAYS: i32 = 180;
+
+/// A notification the system sends. The notification type (used for channel preferences)
+/// is the key up to the first `.`, so `shift_swap.claimed` is a `shift_swap` notification.
+pub struct TemplateDefinition {
+    pub key: &'static str,
+    /// Default English text; `{name}` placeholders are filled from the notification's params
+    pub title: &'static str,
+    pub body: &'static str,
+    pub params: &'static [&'static str],
+    /// Repeats about the same entity merge into the user's unread notification
+    pub group: bool,
+    pub ttl_days: i32,
+}
+
+impl TemplateDefinition {
+    pub fn notification_type(&self) -> &'static str {
+        self.key.split('.').next().unwrap_or(self.key)
+    }
+}
+
+pub const TEMPLATES: &[TemplateDefinition] = &[
+    TemplateDefinition {
+        key: "shift_swap.claimed",
+        title: "Shift Swap Claimed",
+        body: "Your {swapType} shift on {startTime} at {clientSite} has been claimed and is awaiting supervisor approval.",
+        params: &["swapType", "startTime", "clientSite"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "shift_swap.awaiting_approval",
+        title: "Shift Swap Awaiting Approval",
+        body: "A {swapType} of the {startTime} shift at {clientSite} needs approval.",
+        params: &["swapType", "startTime", "clientSite"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "shift_swap.cancelled",
+        title: "Shift Swap Cancelled",
+        body: "A shift you claimed was withdrawn from the swap marketplace.",
+        params: &[],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "shift_swap.approved",
+        title: "Shift Swap Approved",
+        body: "The shift swap you were part of was approved by a supervisor.",
+        params: &[],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "shift_swap.rejected",
+        title: "Shift Swap Rejected",
+        body: "The shift swap you were part of was rejected by a supervisor.",
+        params: &[],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "service_request.created",
+        title: "New Service Request",
+        body: "{requestType} ({priority} priority): {title}",
+        params: &["requestType", "priority", "title"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "service_request.updated",
+        title: "Service Request Update",
+        body: "Your request \"{title}\" is now {status}.",
+        params: &["title", "status"],
+        group: true,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "overdue_allocation.guard",
+        title: "Overdue Firearm Return",
+        body: "Your {firearmModel} (S/N {serialNumber}) was due back on {dueAt}. Please return it to the armory.",
+        params: &["firearmModel", "serialNumber", "dueAt"],
+        group: true,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "overdue_allocation.supervisor",
+        title: "Overdue Firearm Return",
+        body: "{guardName} has not returned {firearmModel} (S/N {serialNumber}), due {dueAt}.",
+        params: &["guardName", "firearmModel", "serialNumber", "dueAt"],
+        group: true,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "timesheet_review.approved",
+        title: "Timesheet Approved",
+        body: "Your timesheet for {periodStart} to {periodEnd} was approved.",
+        params: &["periodStart", "periodEnd"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "timesheet_review.rejected",
+        title: "Timesheet Rejected",
+        body: "Your timesheet for {periodStart} to {periodEnd} was rejected.",
+        params: &["periodStart", "periodEnd"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "permit_revoked",
+        title: "Firearm Permit Revoked",
+        body: "Your firearm permit has been revoked. You cannot be issued a firearm until a new permit is active.",
+        params: &[],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "evaluation_dispute.filed",
+        title: "Evaluation Disputed",
+        body: "A guard disputed a {rating}-star rating from {evaluatorName}.",
+        params: &["rating", "evaluatorName"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "evaluation_dispute.accepted",
+        title: "Evaluation Dispute Resolved",
+        body: "Your dispute was accepted and the rating no longer counts toward your merit score.",
+        params: &[],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "evaluation_dispute.rejected",
+        title: "Evaluation Dispute Resolved",
+        body: "Your dispute was reviewed and the rating stands.",
+        params: &[],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "replacement_request",
+        title: "Replacement Needed - Urgent",
+        body: "Guard no-show at {clientSite}. Can you cover this shift from {startTime} to {endTime}? This offer expires at {expiresAt}.",
+        params: &["clientSite", "startTime", "endTime", "expiresAt"],
+        group: false,
+        ttl_days: 2,
+    },
+    TemplateDefinition {
+        key: "replacement_escalated",
+        title: "Replacement Escalated",
+        body: "No replacement accepted the shift at {clientSite} ({startTime} to {endTime}) after {waves} offer waves. Manual assignment needed.",
+        params: &["clientSite", "startTime", "endTime", "waves"],
+        group: true,
+        ttl_days: 7,
+    },
+    TemplateDefinition {
+        key: "leave_review.approved",
+        title: "Leave Request Approved",
+        body: "Your {leaveType} leave from {startDate} to {endDate} was approved.",
+        params: &["leaveType", "startDate", "endDate"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "leave_review.rejected",
+        title: "Leave Request Rejected",
+        body: "Your {leaveType} leave from {startDate} to {endDate} was rejected.",
+        params: &["leaveType", "startDate", "endDate"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "training.seat_confirmed",
+        title: "Training Seat Confirmed",
+        body: "A seat opened up and you are now enrolled in {courseName}.",
+        params: &["courseName"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "training.renewal_enrolled",
+        title: "Training Renewal Scheduled",
+        body: "Your {trainingType} training expires on {expiryDate}. You have been enrolled in {courseName} on {startTime}.",
+        params: &["trainingType", "expiryDate", "courseName", "startTime"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "training.renewal_waitlisted",
+        title: "Training Renewal Scheduled",
+        body: "Your {trainingType} training expires on {expiryDate}. You have been waitlisted in {courseName} on {startTime}.",
+        params: &["trainingType", "expiryDate", "courseName", "startTime"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "training_expiring",
+        title: "Training Expiring: {trainingType}",
+        body: "Your {trainingType} training expires on {expiryDate} and no renewal session is currently available. Please contact your supervisor.",
+        params: &["trainingType", "expiryDate"],
+        group: true,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "disciplinary.suspension",
+        title: "Suspension Issued",
+        body: "You are suspended {period} for: {infraction}. You will not be scheduled or issued firearms during this time.",
+        params: &["period", "infraction"],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "disciplinary.warning",
+        title: "Disciplinary Warning",
+        body: "A {actionType} was recorded for: {infraction}. It stays on your record {period}.",
+        params: &["actionType", "infraction", "period"],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "disciplinary.suspension_lifted",
+        title: "Suspension Lifted",
+        body: "Your suspension has been lifted. You can be scheduled and issued firearms again.",
+        params: &[],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "disciplinary_appeal.filed",
+        title: "Disciplinary Appeal",
+        body: "A guard appealed a {actionType} for: {infraction}.",
+        params: &["actionType", "infraction"],
+        group: false,
+        ttl_days: DEFAULT_TTL_DAYS,
+    },
+    TemplateDefinition {
+        key: "disciplinary_appeal.overturned",
+        title: "Disciplinary Appeal Resolved",
+        body: "Your appeal was accepted and the record has been overturned.",
+        params: &[],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "disciplinary_appeal.upheld",
+        title: "Disciplinary Appeal Resolved",
+        body: "Your appeal was reviewed and the record stands.",
+        params: &[],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "commendation.commendation",
+        title: "Commendation Received",
+        body: "You were recognised for: {title}.",
+        params: &["title"],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "commendation.award",
+        title: "Award Received",
+        body: "You were recognised for: {title}.",
+        params: &["title"],
+        group: false,
+        ttl_days: 90,
+    },
+    TemplateDefinition {
+        key: "trip_alert.route_deviation",
+        title: "Route Deviation: {plate}",
+        body: "{plate} on the trip to {destination} is {distance} m outside its planned route corridor.",
+        params: &["plate", "destination", "distance"],
+        group: false,
+        ttl_days: 7,
+    },
+    TemplateDefinition {
+        key: "trip_alert.long_stop",
+        title: "Unscheduled Stop: {plate}",
+        body: "{plate} on the trip to {destination} has been stopped for {minutes} min ({place}).",
+        params: &["plate", "destination", "minutes", "place"],
+        group: false,
+        ttl_days: 7,
+    },
+    TemplateDefinition {
+        key: "trip_alert.late_arrival",
+        title: "Late Arrival: {plate}",
+        body: "{plate} has not arrived at {destination}; it was due at {plannedArrival}.",
+        params: &["plate", "destination", "plannedArrival"],
+        group: false,
+        ttl_days: 7,
+    },
+    TemplateDefinition {
+        key: "sos.raised",
+        title: "SOS: {guardName}",
+        body: "{guardName} raised an SOS ({context}) at {location}. Acknowledge it now.",
+        params: &["guardName", "context", "location"],
+        group: false,
+        ttl_days: 30,
+    },
+    TemplateDefinition {
+        key: "sos.escalated",
+        title: "SOS Unacknowledged: {guardName}",
+        body: "{guardName}'s SOS ({context}) at {location} has not been acknowledged after {seconds} seconds.",
+        params: &["guardName", "context", "location", "seconds"],
+        group: false,
+        ttl_days: 30,
+    },
+    TemplateDefinition {
+        key: "sos.acknowledged",
+        title: "SOS Acknowledged",
+        body: "{acknowledgedBy} acknowledged your SOS. Help is on the way.",
+        params: &["acknowledgedBy"],
+        group: false,
+        ttl_days: 30,
+    },
+    TemplateDefinition {
+        key: "vehicle_document.expiring",
+        title: "Vehicle {document} Expiring: {plate}",
+        body: "The {document} of {plate} expires on {expiryDate} ({days} days). Renew it to keep the vehicle in service.",
+        params: &["plate", "document", "expiryDate", "days"],
+        group: true,
+        ttl_days: 30,
+    },
+    TemplateDefinition {
+        key: "vehicle_document.expired",
+        title: "Vehicle {document} Expired: {plate}",
+        body: "The {document} of {plate} expired on {expiryDate}. It cannot be issued or dispatched without a supervisor override.",
+        params: &["plate", "document", "expiryDate"],
+        group: true,
+        ttl_days: 30,
+    },
+    TemplateDefinition {
+        key: "vehicle_document.override",
+        title: "Dispatch Override: {plate}",
+        body: "{approvedBy} allowed {plate} to be dispatched with a lapsed {document} until {validUntil}. Reason: {reason}",
+        params: &["plate", "document", "approvedBy", "validUntil", "reason"],
+        group: false,
+        ttl_days: 30,
+    },
+    TemplateDefinition {
+        key: "consignment.seal_breach",
+        title: "Seal Breach: {reference}",
+        body: "The seal check at {stage} of consignment {reference} failed: {details}",
+        params: &["reference", "stage", "details"],
+        group: false,
+        ttl_days: 30,
+    },
+];
+
+/// The registered template for a key.
+pub fn template(key: &str) -> AppResult<&'static TemplateDefinition> {
+    TEMPLATES
+        .iter()
+        .find(|t| t.key == key)
+        .ok_or_else(|| AppError::InternalServerError(format!("Unknown notification template '{}'", key)))
+}
+
+/// Names between `{` and `}` in a template string.
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start + 1..].find('}') else { break };
+        names.push(&rest[start + 1..start + 1 + len]);
+        rest = &rest[start + 1 + len + 1..];
+    }
+    names
+}
+
+pub fn validate_locale(locale: &str) -> AppResult<()> {
+    // "en", "fil", "en-PH"
+    let mut parts = locale.splitn(2, '-');
+    let language = parts.next().unwrap_or_default();
+    let region_ok = parts
+        .next()
+        .is_none_or(|region| region.len() == 2 && region.chars().all(|c| c.is_ascii_uppercase()));
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_lowercase()) || !region_ok {
+        return Err(AppError::ValidationError(format!("Invalid locale '{}'", locale)));
+    }
+    Ok(())
+}
+
+/// Store the built-in English text for every template. Text a supervisor has edited is kept.
+pub async fn register_templates(db: &PgPool) -> AppResult<()> {
+    for definition in TEMPLATES {
+        sqlx::query(
+            "INSERT INTO notification_templates (template_key, locale, title, body)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (template_key, locale) DO UPDATE
+             SET title = EXCLUDED.title, body = EXCLUDED.body, updated_at = CURRENT_TIMESTAMP
+             WHERE notification_templates.customized = false
+               AND (notification_templates.title, notification_templates.body) IS DISTINCT FROM (EXCLUDED.title, EXCLUDED.body)",
+        )
+        .bind(definition.key)
+        .bind(DEFAULT_LOCALE)
+        .bind(definition.title)
+        .bind(definition.body)
+        .execute(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to register template '{}': {}", definition.key, e)))?;
+    }
+
+    Ok(())
+}
+
+fn definition_json(definition: &TemplateDefinition, translations: Vec<&NotificationTemplate>) -> serde_json::Value {
+    json!({
+        "key": definition.key,
+        "notificationType": definition.notification_type(),
+        "params": definition.params,
+        "grouped": definition.group,
+        "ttlDays": definition.ttl_days,
+        "translations": translations,
+    })
+}
+
+// List the template registry with its text per locale (optionally one locale)
+pub async fn get_notification_templates(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<NotificationTemplateQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let rows = sqlx::query_as::<_, NotificationTemplate>(
+        "SELECT template_key, locale, title, body, customized, updated_by, updated_at
+         FROM notification_templates
+         WHERE $1::varchar IS NULL OR locale = $1
+         ORDER BY template_key, locale",
+    )
+    .bind(&query.locale)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let templates: Vec<serde_json::Value> = TEMPLATES
+        .iter()
+        .map(|definition| {
+            definition_json(definition, rows.iter().filter(|row| row.template_key == definition.key).collect())
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "total": templates.len(),
+        "defaultLocale": DEFAULT_LOCALE,
+        "templates": templates
+    })))
+}
+
+// Set a template's text for a locale (adds a translation, or overrides the built-in English)
+pub async fn update_notification_template(
+    State(db): State<Arc<PgPool>>,
+    Path((template_key, locale)): Path<(String, String)>,
+    Json(payload): Json<UpdateNotificationTemplateRequest>,
+) -> AppResult<Json<NotificationTemplate>> {
+    let definition = TEMPLATES
+        .iter()
+        .find(|t| t.key == template_key)
+        .ok_or_else(|| AppError::NotFound("Notification template not found".to_string()))?;
+    validate_locale(&locale)?;
+    if payload.title.trim().is_empty() || payload.body.trim().is_empty() {
+        return Err(AppError::ValidationError("title and body are required".to_string()));
+    }
+    if let Some(unknown) = placeholders(&payload.title)
+        .into_iter()
+        .chain(placeholders(&payload.body))
+        .find(|name| !definition.params.contains(name))
+    {
+        return Err(AppError::ValidationError(format!(
+            "Unknown placeholder {{{}}}; this template takes: {}",
+            unknown,
+            definition.params.join(", ")
+        )));
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.updated_by, "edit notification templates").await?;
+
+    let template = sqlx::query_as::<_, NotificationTemplate>(
+        "INSERT INTO notification_templates (template_key, locale, title, body, customized, updated_by)
+         VALUES ($1, $2, $3, $4, true, $5)
+         ON CONFLICT (template_key, locale) DO UPDATE
+         SET title = EXCLUDED.title, body = EXCLUDED.body, customized = true,
+             updated_by = EXCLUDED.updated_by, updated_at = CURRENT_TIMESTAMP
+         RETURNING template_key, locale, title, body, customized, updated_by, updated_at",
+    )
+    .bind(&template_key)
+    .bind(&locale)
+    .bind(payload.title.trim())
+    .bind(payload.body.trim())
+    .bind(&payload.updated_by)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to save template: {}", e)))?;
+
+    Ok(Json(template))
+}
+
+// Undo edits: English goes back to the built-in text, other locales are removed
+pub async fn reset_notification_template(
+    State(db): State<Arc<PgPool>>,
+    Path((template_key, locale)): Path<(String, String)>,
+    Json(payload): Json<ResetNotificationTemplateRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let definition = TEMPLATES
+        .iter()
+        .find(|t| t.key == template_key)
+        .ok_or_else(|| AppError::NotFound("Notification template not found".to_string()))?;
+    utils::ensure_supervisor(db.as_ref(), &payload.updated_by, "edit notification templates").await?;
+
+    let result = if locale == DEFAULT_LOCALE {
+        sqlx::query(
+            "UPDATE notification_templates
+             SET title = $3, body = $4, customized = false, updated_by = $5, updated_at = CURRENT_TIMESTAMP
+             WHERE template_key = $1 AND locale = $2",
+        )
+        .bind(definition.key)
+        .bind(&locale)
+        .bind(definition.title)
+        .bind(definition.body)
+        .bind(&payload.updated_by)
+        .execute(db.as_ref())
+        .await
+    } else {
+        sqlx::query("DELETE FROM notification_templates WHERE template_key = $1 AND locale = $2")
+            .bind(definition.key)
+            .bind(&locale)
+            .execute(db.as_ref())
+            .await
+    }
+    .map_err(|e| AppError::DatabaseError(format!("Failed to reset template: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("No '{}' text for this template", locale)));
+    }
+
+    Ok(Json(json!({
+        "message": "Notification template reset",
+        "templateKey": definition.key,
+        "locale": locale
+    })))
+}
+
+/// Archive notifications past their expiry and delete those archived longer than the
+/// retention period. Shared by the scheduler job.
+pub async fn archive_expired_notifications(db: &PgPool) -> AppResult<serde_json::Value> {
+    let retention_days = std::env::var("NOTIFICATION_ARCHIVE_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok())
+        .unwrap_or(DEFAULT_ARCHIVE_RETENTION_DAYS);
+
+    let archived = sqlx::query(
+        "UPDATE notifications SET archived_at = CURRENT_TIMESTAMP
+         WHERE archived_at IS NULL AND expires_at <= CURRENT_TIMESTAMP",
+    )
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to archive notifications: {}", e)))?
+    .rows_affected();
+
+    let purged = sqlx::query(
+        "DELETE FROM notifications
+         WHERE archived_at IS NOT NULL AND archived_at <= CURRENT_TIMESTAMP - make_interval(days => $1)",
+    )
+    .bind(retention_days)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to purge notifications: {}", e)))?
+    .rows_affected();
+
+    tracing::info!("Notifications archived: {}, purged: {}", archived, purged);
+
+    Ok(json!({
+        "archived": archived,
+        "purged": purged,
+        "retentionDays": retention_days
+    }))
+}
@@ -1,31 +1,101 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
 use serde_json::json;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use std::sync::Arc;
 
 use crate::{
     error::{AppError, AppResult},
-    models::{CreateNotificationRequest, Notification, MarkNotificationReadRequest},
+    handlers::notification_templates,
+    models::{CreateNotificationRequest, Notification, MarkNotificationReadRequest, NotificationListQuery},
     utils,
 };
 
+/// Columns selected into `Notification`.
+pub const NOTIFICATION_COLUMNS: &str = "id, user_id, title, message, type as notification_type, related_shift_id, read, \
+     template_key, entity_type, entity_id, params::text AS params, group_count, expires_at, archived_at, created_at, updated_at";
+
+/// Notify a user from a registered template, as a side effect of another workflow.
+/// The text is rendered in the user's locale (falling back to English) from `params`, a JSON
+/// object of strings. `entity` is what the notification is about, e.g. `("shift", id)`.
+/// For grouped templates a repeat about the same entity updates the user's unread
+/// notification instead of adding another. Returns the notification id.
+pub async fn notify_user(
+    db: impl PgExecutor<'_>,
+    user_id: &str,
+    template_key: &str,
+    entity: Option<(&str, &str)>,
+    params: serde_json::Value,
+) -> AppResult<String> {
+    let definition = notification_templates::template(template_key)?;
+    let (entity_type, entity_id) = entity.unzip();
+    let group_key = definition.group.then(|| match entity {
+        Some((entity_type, entity_id)) => format!("{}:{}:{}", template_key, entity_type, entity_id),
+        None => template_key.to_string(),
+    });
+
+    sqlx::query_scalar::<_, String>(
+        r#"
+        WITH template AS (
+            SELECT title, body FROM notification_templates
+            WHERE template_key = $3
+              AND locale IN ($9, COALESCE((SELECT locale FROM notification_settings WHERE user_id = $2), $9))
+            ORDER BY locale = $9
+            LIMIT 1
+        )
+        INSERT INTO notifications (id, user_id, title, message, type, related_shift_id, read,
+                                   template_key, entity_type, entity_id, params, group_key, expires_at)
+        SELECT $1, $2,
+               render_notification_template(COALESCE(template.title, $10), $7::jsonb),
+               render_notification_template(COALESCE(template.body, $11), $7::jsonb),
+               $4, CASE WHEN $5::varchar = 'shift' THEN $6::varchar END, false,
+               $3, $5::varchar, $6::varchar, $7::jsonb, $8,
+               CURRENT_TIMESTAMP + make_interval(days => $12)
+        FROM (SELECT 1) AS base LEFT JOIN template ON true
+        ON CONFLICT (user_id, group_key) WHERE read = false AND archived_at IS NULL AND group_key IS NOT NULL
+        DO UPDATE SET title = EXCLUDED.title, message = EXCLUDED.message, params = EXCLUDED.params,
+            group_count = notifications.group_count + 1, seq = DEFAULT,
+            expires_at = EXCLUDED.expires_at, updated_at = CURRENT_TIMESTAMP
+        RETURNING id
+        "#,
+    )
+    .bind(utils::generate_id())
+    .bind(user_id)
+    .bind(template_key)
+    .bind(definition.notification_type())
+    .bind(entity_type)
+    .bind(entity_id)
+    .bind(params.to_string())
+    .bind(group_key)
+    .bind(notification_templates::DEFAULT_LOCALE)
+    .bind(definition.title)
+    .bind(definition.body)
+    .bind(definition.ttl_days)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create notification: {}", e)))
+}
+
 // Get all notifications for a user
 pub async fn get_user_notifications(
     State(db): State<Arc<PgPool>>,
     Path(user_id): Path<String>,
+    Query(query): Query<NotificationListQuery>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let notifications = sqlx::query_as::<_, Notification>(
-        "SELECT id, user_id, title, message, type as notification_type, related_shift_id, read, created_at, updated_at 
+    // `seq` is bumped when a repeat is grouped in, so updated groups move to the top
+    let notifications = sqlx::query_as::<_, Notification>(&format!(
+        "SELECT {} 
          FROM notifications 
-         WHERE user_id = $1 
-         ORDER BY created_at DESC 
+         WHERE user_id = $1 AND (archived_at IS NOT NULL) = $2
+         ORDER BY seq DESC 
          LIMIT 50",
-    )
+        NOTIFICATION_COLUMNS
+    ))
     .bind(&user_id)
+    .bind(query.archived.unwrap_or(false))
     .fetch_all(db.as_ref())
     .await
     .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
@@ -45,7 +115,7 @@ pub async fn get_unread_count(
     Path(user_id): Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
     let count = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read = false",
+        "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read = false AND archived_at IS NULL",
     )
     .bind(&user_id)
     .fetch_one(db.as_ref())
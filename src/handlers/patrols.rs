@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{CreatePatrolRoundRequest, PatrolRound},
+    utils,
+};
+
+// Log a completed patrol round and how many of its checkpoints were scanned
+pub async fn create_patrol_round(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreatePatrolRoundRequest>,
+) -> AppResult<(StatusCode, Json<PatrolRound>)> {
+    if payload.checkpoints_expected <= 0 {
+        return Err(AppError::ValidationError("checkpointsExpected must be positive".to_string()));
+    }
+    if !(0..=payload.checkpoints_expected).contains(&payload.checkpoints_completed) {
+        return Err(AppError::ValidationError(
+            "checkpointsCompleted must be between 0 and checkpointsExpected".to_string(),
+        ));
+    }
+    if payload.completed_at.is_some_and(|completed| completed < payload.started_at) {
+        return Err(AppError::ValidationError("completedAt must be after startedAt".to_string()));
+    }
+
+    if let Some(shift_id) = &payload.shift_id {
+        let shift_guard = sqlx::query_scalar::<_, String>("SELECT guard_id FROM shifts WHERE id = $1")
+            .bind(shift_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+        if shift_guard != payload.guard_id {
+            return Err(AppError::BadRequest("Shift is not assigned to this guard".to_string()));
+        }
+    }
+
+    let round = sqlx::query_as::<_, PatrolRound>(
+        "INSERT INTO patrol_rounds
+         (id, guard_id, shift_id, checkpoints_expected, checkpoints_completed, started_at, completed_at, notes)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.guard_id)
+    .bind(&payload.shift_id)
+    .bind(payload.checkpoints_expected)
+    .bind(payload.checkpoints_completed)
+    .bind(payload.started_at)
+    .bind(payload.completed_at)
+    .bind(&payload.notes)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record patrol round: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(round)))
+}
+
+// Patrol rounds logged by a guard, newest first
+pub async fn get_guard_patrol_rounds(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let rounds = sqlx::query_as::<_, PatrolRound>(
+        "SELECT * FROM patrol_rounds WHERE guard_id = $1 ORDER BY started_at DESC",
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": rounds.len(),
+        "rounds": rounds
+    })))
+}
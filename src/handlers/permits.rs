@@ -9,6 +9,7 @@ use std::sync::Arc;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::notifications::notify_user,
     models::{CreateGuardFirearmPermitRequest, GuardFirearmPermit},
     utils,
 };
@@ -109,35 +110,47 @@ pub async fn revoke_permit(
     State(db): State<Arc<PgPool>>,
     Path(permit_id): Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let result = sqlx::query(
-        "UPDATE guard_firearm_permits SET status = 'revoked', updated_at = NOW() WHERE id = $1",
+    let guard_id = sqlx::query_scalar::<_, String>(
+        "UPDATE guard_firearm_permits SET status = 'revoked', updated_at = NOW() WHERE id = $1 RETURNING guard_id",
     )
     .bind(&permit_id)
-    .execute(db.as_ref())
+    .fetch_optional(db.as_ref())
     .await
-    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
-
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound("Permit not found".to_string()));
-    }
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Permit not found".to_string()))?;
+
+    notify_user(
+        db.as_ref(),
+        &guard_id,
+        "permit_revoked",
+        Some(("firearm_permit", &permit_id)),
+        json!({}),
+    )
+    .await?;
 
     Ok(Json(json!({ "message": "Permit revoked successfully" })))
 }
 
-/// POST /api/guard-firearm-permits/auto-expire  — batch expire all past-due permits
-pub async fn auto_expire_permits(
-    State(db): State<Arc<PgPool>>,
-) -> AppResult<Json<serde_json::Value>> {
+/// Mark every active permit past its expiry date as expired. Returns the number expired.
+pub async fn expire_permits(db: &PgPool) -> AppResult<u64> {
     let result = sqlx::query(
         "UPDATE guard_firearm_permits SET status = 'expired', updated_at = NOW() WHERE status = 'active' AND expiry_date < NOW()",
     )
-    .execute(db.as_ref())
+    .execute(db)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Batch expire error: {}", e)))?;
 
+    Ok(result.rows_affected())
+}
+
+/// POST /api/guard-firearm-permits/auto-expire  — batch expire all past-due permits
+pub async fn auto_expire_permits(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let expired = expire_permits(db.as_ref()).await?;
+
     Ok(Json(json!({
         "message": "Auto-expire completed",
-        "expiredCount": result.rows_affected()
+        "expiredCount": expired
     })))
 }
-
@@ -0,0 +1,566 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{availability, notifications::notify_user},
+    models::{ReplacementOffer, RespondReplacementOfferRequest},
+    utils,
+};
+
+/// Guards offered a shift per wave.
+const WAVE_SIZE: i64 = 5;
+/// How long a guard has to accept an offer before it expires.
+const OFFER_TTL_MINUTES: i64 = 10;
+/// Waves sent before the shift is escalated to supervisors.
+const MAX_WAVES: i32 = 3;
+/// Merit points subtracted per km between a guard's home and the site.
+const DISTANCE_PENALTY_PER_KM: f64 = 0.5;
+/// Distance assumed when the guard or site has no coordinates.
+const UNKNOWN_DISTANCE_KM: f64 = 25.0;
+
+#[derive(sqlx::FromRow)]
+struct ReplacementShift {
+    id: String,
+    guard_id: String,
+    original_guard_id: Option<String>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    client_site: String,
+    replacement_status: Option<String>,
+    replacement_wave: Option<i32>,
+}
+
+#[derive(sqlx::FromRow)]
+struct OfferCandidate {
+    guard_id: String,
+    merit_score: Option<f64>,
+    distance_km: Option<f64>,
+}
+
+enum WaveOutcome {
+    Sent,
+    NotSearching,
+    OffersPending,
+    Exhausted,
+}
+
+/// Move a searching shift forward: send the next wave of offers, or escalate
+/// once every wave has been used or no eligible guard is left.
+/// Does nothing while offers from the current wave are still pending.
+pub async fn advance_replacement(db: &PgPool, shift_id: &str) -> AppResult<&'static str> {
+    match send_offer_wave(db, shift_id).await? {
+        WaveOutcome::Sent => Ok("wave_sent"),
+        WaveOutcome::NotSearching | WaveOutcome::OffersPending => Ok("unchanged"),
+        WaveOutcome::Exhausted => {
+            escalate_replacement(db, shift_id).await?;
+            Ok("escalated")
+        }
+    }
+}
+
+async fn send_offer_wave(db: &PgPool, shift_id: &str) -> AppResult<WaveOutcome> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    // Lock the shift so concurrent sweeps and declines cannot send the same wave twice
+    let shift = sqlx::query_as::<_, ReplacementShift>(
+        "SELECT id, guard_id, original_guard_id, start_time, end_time, client_site,
+                replacement_status, replacement_wave
+         FROM shifts WHERE id = $1 FOR UPDATE",
+    )
+    .bind(shift_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+    if shift.replacement_status.as_deref() != Some("searching") {
+        return Ok(WaveOutcome::NotSearching);
+    }
+
+    let pending = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM replacement_offers WHERE shift_id = $1 AND status = 'pending'",
+    )
+    .bind(&shift.id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if pending > 0 {
+        return Ok(WaveOutcome::OffersPending);
+    }
+
+    let wave = shift.replacement_wave.unwrap_or(0) + 1;
+    if wave > MAX_WAVES {
+        return Ok(WaveOutcome::Exhausted);
+    }
+
+    // Rank eligible guards by merit, penalised by haversine distance from home to site.
+    // Guards who are unavailable or already had an offer for this shift are skipped.
+    let candidates = sqlx::query_as::<_, OfferCandidate>(
+        r#"
+        SELECT guard_id, merit_score, distance_km FROM (
+            SELECT u.id AS guard_id,
+                   gms.overall_score AS merit_score,
+                   CASE WHEN u.home_latitude IS NOT NULL AND u.home_longitude IS NOT NULL
+                             AND cs.latitude IS NOT NULL AND cs.longitude IS NOT NULL
+                   THEN 6371 * 2 * ASIN(SQRT(
+                            POWER(SIN(RADIANS(cs.latitude - u.home_latitude) / 2), 2)
+                            + COS(RADIANS(u.home_latitude)) * COS(RADIANS(cs.latitude))
+                            * POWER(SIN(RADIANS(cs.longitude - u.home_longitude) / 2), 2)))
+                   END AS distance_km
+            FROM users u
+            LEFT JOIN client_sites cs ON cs.name = $1
+            LEFT JOIN guard_merit_scores gms ON gms.guard_id = u.id
+            WHERE u.role = 'user'
+              AND u.verified = true
+              AND u.id != $2
+              AND u.id != COALESCE($3, $2)
+              AND guard_is_available(u.id, $6, $5, $10)
+              AND NOT EXISTS (
+                  SELECT 1 FROM replacement_offers ro
+                  WHERE ro.shift_id = $4 AND ro.guard_id = u.id
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM shifts s2
+                  WHERE s2.guard_id = u.id
+                  AND s2.status IN ('scheduled', 'in_progress')
+                  AND s2.start_time <= $5
+                  AND s2.end_time >= $6
+              )
+        ) c
+        ORDER BY COALESCE(c.merit_score, 0) - $7 * COALESCE(c.distance_km, $8) DESC
+        LIMIT $9
+        "#,
+    )
+    .bind(&shift.client_site)
+    .bind(&shift.guard_id)
+    .bind(&shift.original_guard_id)
+    .bind(&shift.id)
+    .bind(shift.end_time)
+    .bind(shift.start_time)
+    .bind(DISTANCE_PENALTY_PER_KM)
+    .bind(UNKNOWN_DISTANCE_KM)
+    .bind(WAVE_SIZE)
+    .bind(availability::schedule_timezone())
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to find replacement candidates: {}", e)))?;
+
+    if candidates.is_empty() {
+        return Ok(WaveOutcome::Exhausted);
+    }
+
+    let expires_at = Utc::now() + chrono::Duration::minutes(OFFER_TTL_MINUTES);
+    let params = json!({
+        "clientSite": shift.client_site,
+        "startTime": shift.start_time.format("%I:%M %p").to_string(),
+        "endTime": shift.end_time.format("%I:%M %p").to_string(),
+        "expiresAt": expires_at.format("%I:%M %p").to_string()
+    });
+
+    for (rank, candidate) in candidates.iter().enumerate() {
+        let notification_id = notify_user(
+            &mut *tx,
+            &candidate.guard_id,
+            "replacement_request",
+            Some(("shift", &shift.id)),
+            params.clone(),
+        )
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO replacement_offers
+             (id, shift_id, guard_id, wave, rank_in_wave, merit_score, distance_km, status, notification_id, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, 'pending', $8, $9)",
+        )
+        .bind(utils::generate_id())
+        .bind(&shift.id)
+        .bind(&candidate.guard_id)
+        .bind(wave)
+        .bind(rank as i32 + 1)
+        .bind(candidate.merit_score)
+        .bind(candidate.distance_km)
+        .bind(&notification_id)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to create replacement offer: {}", e)))?;
+    }
+
+    sqlx::query(
+        "UPDATE shifts SET replacement_wave = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2",
+    )
+    .bind(wave)
+    .bind(&shift.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update shift: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    tracing::info!(
+        "Replacement wave {} for shift {}: offered to {} guards",
+        wave, shift.id, candidates.len()
+    );
+
+    Ok(WaveOutcome::Sent)
+}
+
+/// Hand an uncovered shift to supervisors: admins plus the site's supervisor, if set.
+async fn escalate_replacement(db: &PgPool, shift_id: &str) -> AppResult<()> {
+    let escalated: Option<(String, DateTime<Utc>, DateTime<Utc>, i32)> = sqlx::query_as(
+        "UPDATE shifts
+         SET replacement_status = 'escalated', replacement_escalated_at = CURRENT_TIMESTAMP,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND replacement_status = 'searching'
+         RETURNING client_site, start_time, end_time, COALESCE(replacement_wave, 0)",
+    )
+    .bind(shift_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to escalate shift: {}", e)))?;
+
+    // Waves actually sent; fewer than MAX_WAVES when eligible guards ran out first
+    let Some((client_site, start_time, end_time, waves)) = escalated else {
+        return Ok(());
+    };
+
+    let supervisors: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM users WHERE role IN ('admin', 'superadmin')
+         UNION
+         SELECT supervisor_id FROM client_sites WHERE name = $1 AND supervisor_id IS NOT NULL",
+    )
+    .bind(&client_site)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch supervisors: {}", e)))?;
+
+    let params = json!({
+        "clientSite": client_site,
+        "startTime": start_time.format("%Y-%m-%d %I:%M %p").to_string(),
+        "endTime": end_time.format("%I:%M %p").to_string(),
+        "waves": waves.to_string()
+    });
+
+    for supervisor_id in &supervisors {
+        notify_user(
+            db,
+            supervisor_id,
+            "replacement_escalated",
+            Some(("shift", shift_id)),
+            params.clone(),
+        )
+        .await?;
+    }
+
+    tracing::warn!("Replacement for shift {} escalated to {} supervisors", shift_id, supervisors.len());
+
+    Ok(())
+}
+
+/// Accept an offer. The shift row is locked first, so only the first
+/// acceptance wins; later ones see the shift is no longer searching.
+pub async fn accept_offer(db: &PgPool, offer_id: &str, guard_id: &str) -> AppResult<ReplacementOffer> {
+    let shift_id: String = sqlx::query_scalar("SELECT shift_id FROM replacement_offers WHERE id = $1")
+        .bind(offer_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Replacement offer not found".to_string()))?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let shift = sqlx::query_as::<_, ReplacementShift>(
+        "SELECT id, guard_id, original_guard_id, start_time, end_time, client_site,
+                replacement_status, replacement_wave
+         FROM shifts WHERE id = $1 FOR UPDATE",
+    )
+    .bind(&shift_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+    let offer = sqlx::query_as::<_, ReplacementOffer>(
+        "SELECT * FROM replacement_offers WHERE id = $1 FOR UPDATE",
+    )
+    .bind(offer_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if offer.guard_id != guard_id {
+        return Err(AppError::Forbidden("This offer was made to another guard".to_string()));
+    }
+
+    match shift.replacement_status.as_deref() {
+        Some("searching") => {}
+        Some("accepted") => {
+            return Err(AppError::Conflict("Shift has already been covered".to_string()));
+        }
+        _ => {
+            return Err(AppError::Conflict("Shift is no longer open for replacement".to_string()));
+        }
+    }
+
+    if offer.status != "pending" {
+        return Err(AppError::Conflict(format!("Offer is {}", offer.status)));
+    }
+    if offer.expires_at <= Utc::now() {
+        return Err(AppError::Conflict("Offer has expired".to_string()));
+    }
+
+    let overlapping = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(
+             SELECT 1 FROM shifts
+             WHERE guard_id = $1 AND id != $2
+             AND status IN ('scheduled', 'in_progress')
+             AND start_time <= $3 AND end_time >= $4
+         )",
+    )
+    .bind(guard_id)
+    .bind(&shift.id)
+    .bind(shift.end_time)
+    .bind(shift.start_time)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if overlapping {
+        return Err(AppError::Conflict("Guard already has an overlapping shift".to_string()));
+    }
+
+    availability::ensure_guard_available(&mut *tx, guard_id, shift.start_time, shift.end_time).await?;
+
+    sqlx::query(
+        "UPDATE shifts
+         SET guard_id = $1, replacement_status = 'accepted', updated_at = CURRENT_TIMESTAMP
+         WHERE id = $2",
+    )
+    .bind(guard_id)
+    .bind(&shift.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to accept replacement: {}", e)))?;
+
+    let offer = sqlx::query_as::<_, ReplacementOffer>(
+        "UPDATE replacement_offers
+         SET status = 'accepted', responded_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(offer_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update offer: {}", e)))?;
+
+    sqlx::query(
+        "UPDATE replacement_offers
+         SET status = 'superseded', updated_at = CURRENT_TIMESTAMP
+         WHERE shift_id = $1 AND status = 'pending'",
+    )
+    .bind(&shift.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to close other offers: {}", e)))?;
+
+    // Clear the offer notifications so other guards are not left with a dead link
+    sqlx::query(
+        "UPDATE notifications
+         SET read = true, updated_at = CURRENT_TIMESTAMP
+         WHERE related_shift_id = $1 AND type = 'replacement_request' AND read = false",
+    )
+    .bind(&shift.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update notifications: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    tracing::info!("Replacement accepted: Guard {} accepted shift {}", guard_id, shift.id);
+
+    Ok(offer)
+}
+
+/// Expire lapsed offers and advance every searching shift that has no pending offers left.
+/// Run by the scheduler and the process endpoint.
+pub async fn process_replacement_offers(db: &PgPool) -> AppResult<serde_json::Value> {
+    let expired = sqlx::query(
+        "UPDATE replacement_offers
+         SET status = 'expired', updated_at = CURRENT_TIMESTAMP
+         WHERE status = 'pending' AND expires_at <= CURRENT_TIMESTAMP",
+    )
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to expire offers: {}", e)))?
+    .rows_affected();
+
+    let shift_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT s.id FROM shifts s
+         WHERE s.replacement_status = 'searching'
+         AND NOT EXISTS (
+             SELECT 1 FROM replacement_offers ro
+             WHERE ro.shift_id = s.id AND ro.status = 'pending'
+         )",
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch searching shifts: {}", e)))?;
+
+    let mut waves_sent = 0;
+    let mut escalated = Vec::new();
+    for shift_id in &shift_ids {
+        // One failing shift must not hold up the rest of the sweep
+        match advance_replacement(db, shift_id).await {
+            Ok("wave_sent") => waves_sent += 1,
+            Ok("escalated") => escalated.push(shift_id.clone()),
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to advance replacement for shift {}: {}", shift_id, e),
+        }
+    }
+
+    Ok(json!({
+        "expiredOffers": expired,
+        "wavesSent": waves_sent,
+        "escalatedShiftIds": escalated
+    }))
+}
+
+// Accept a replacement offer (first acceptance wins)
+pub async fn accept_replacement_offer(
+    State(db): State<Arc<PgPool>>,
+    Path(offer_id): Path<String>,
+    Json(payload): Json<RespondReplacementOfferRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let offer = accept_offer(db.as_ref(), &offer_id, &payload.guard_id).await?;
+
+    Ok(Json(json!({
+        "message": "Replacement shift accepted successfully",
+        "offer": offer
+    })))
+}
+
+// Decline a replacement offer; the next wave goes out once no offers are pending
+pub async fn decline_replacement_offer(
+    State(db): State<Arc<PgPool>>,
+    Path(offer_id): Path<String>,
+    Json(payload): Json<RespondReplacementOfferRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let offer = sqlx::query_as::<_, ReplacementOffer>("SELECT * FROM replacement_offers WHERE id = $1")
+        .bind(&offer_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Replacement offer not found".to_string()))?;
+
+    if offer.guard_id != payload.guard_id {
+        return Err(AppError::Forbidden("This offer was made to another guard".to_string()));
+    }
+
+    let offer = sqlx::query_as::<_, ReplacementOffer>(
+        "UPDATE replacement_offers
+         SET status = 'declined', responded_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND status = 'pending'
+         RETURNING *",
+    )
+    .bind(&offer_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to decline offer: {}", e)))?
+    .ok_or_else(|| AppError::Conflict(format!("Offer is {}", offer.status)))?;
+
+    if let Some(notification_id) = &offer.notification_id {
+        sqlx::query("UPDATE notifications SET read = true, updated_at = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(notification_id)
+            .execute(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update notification: {}", e)))?;
+    }
+
+    let outcome = advance_replacement(db.as_ref(), &offer.shift_id).await?;
+
+    Ok(Json(json!({
+        "message": "Replacement offer declined",
+        "offer": offer,
+        "replacementOutcome": outcome
+    })))
+}
+
+// Get all offers made for a shift, by wave
+pub async fn get_shift_replacement_offers(
+    State(db): State<Arc<PgPool>>,
+    Path(shift_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let shift: (Option<String>, Option<i32>, Option<String>) = sqlx::query_as(
+        "SELECT replacement_status, replacement_wave, original_guard_id FROM shifts WHERE id = $1",
+    )
+    .bind(&shift_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+    let offers = sqlx::query_as::<_, ReplacementOffer>(
+        "SELECT * FROM replacement_offers WHERE shift_id = $1 ORDER BY wave, rank_in_wave",
+    )
+    .bind(&shift_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "shiftId": shift_id,
+        "replacementStatus": shift.0,
+        "currentWave": shift.1.unwrap_or(0),
+        "originalGuardId": shift.2,
+        "total": offers.len(),
+        "offers": offers
+    })))
+}
+
+// Get a guard's open replacement offers
+pub async fn get_guard_replacement_offers(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let offers = sqlx::query_as::<_, ReplacementOffer>(
+        "SELECT * FROM replacement_offers
+         WHERE guard_id = $1 AND status = 'pending' AND expires_at > CURRENT_TIMESTAMP
+         ORDER BY expires_at",
+    )
+    .bind(&guard_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": offers.len(),
+        "offers": offers
+    })))
+}
+
+// Expire lapsed offers and send follow-up waves or escalate
+pub async fn run_replacement_offer_processing(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    let summary = process_replacement_offers(db.as_ref()).await?;
+    Ok((StatusCode::OK, Json(summary)))
+}
@@ -0,0 +1,126 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{ScheduledJob, ScheduledJobRun, UpdateScheduledJobRequest},
+    scheduler,
+};
+
+// List all background jobs with their last outcome and next run time
+pub async fn get_scheduled_jobs(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let jobs = sqlx::query_as::<_, ScheduledJob>("SELECT * FROM scheduled_jobs ORDER BY name")
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let failing = jobs.iter().filter(|j| j.consecutive_failures > 0).count();
+
+    Ok(Json(json!({
+        "total": jobs.len(),
+        "failing": failing,
+        "instanceId": scheduler::instance_id(),
+        "jobs": jobs
+    })))
+}
+
+// Get recent run history for a job
+pub async fn get_job_runs(
+    State(db): State<Arc<PgPool>>,
+    Path(name): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let job = sqlx::query_as::<_, ScheduledJob>("SELECT * FROM scheduled_jobs WHERE name = $1")
+        .bind(&name)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound(format!("Job '{}' not found", name)))?;
+
+    let runs = sqlx::query_as::<_, ScheduledJobRun>(
+        "SELECT * FROM scheduled_job_runs WHERE job_name = $1 ORDER BY started_at DESC LIMIT 50",
+    )
+    .bind(&name)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "job": job,
+        "total": runs.len(),
+        "runs": runs
+    })))
+}
+
+// Get failed runs across all jobs, most recent first
+pub async fn get_failed_job_runs(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let runs = sqlx::query_as::<_, ScheduledJobRun>(
+        "SELECT * FROM scheduled_job_runs WHERE status = 'failed' ORDER BY started_at DESC LIMIT 100",
+    )
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": runs.len(),
+        "runs": runs
+    })))
+}
+
+// Run a job immediately, regardless of its schedule or enabled flag
+pub async fn trigger_job(
+    State(db): State<Arc<PgPool>>,
+    Path(name): Path<String>,
+) -> AppResult<(StatusCode, Json<ScheduledJobRun>)> {
+    let run = scheduler::run_job(db.as_ref(), &name, "manual").await?;
+    Ok((StatusCode::OK, Json(run)))
+}
+
+// Change a job's interval or enable/disable it
+pub async fn update_scheduled_job(
+    State(db): State<Arc<PgPool>>,
+    Path(name): Path<String>,
+    Json(payload): Json<UpdateScheduledJobRequest>,
+) -> AppResult<Json<ScheduledJob>> {
+    if let Some(interval) = payload.interval_seconds {
+        if interval < 10 {
+            return Err(AppError::ValidationError(
+                "intervalSeconds must be at least 10".to_string(),
+            ));
+        }
+    }
+
+    // A shorter interval pulls the next run forward instead of waiting out the old one
+    let job = sqlx::query_as::<_, ScheduledJob>(
+        r#"
+        UPDATE scheduled_jobs
+        SET interval_seconds = COALESCE($2, interval_seconds),
+            enabled = COALESCE($3, enabled),
+            next_run_at = CASE
+                WHEN $2::int IS NULL THEN next_run_at
+                ELSE LEAST(next_run_at, COALESCE(last_finished_at, NOW()) + $2::int * INTERVAL '1 second')
+            END,
+            updated_at = NOW()
+        WHERE name = $1
+        RETURNING *
+        "#,
+    )
+    .bind(&name)
+    .bind(payload.interval_seconds)
+    .bind(payload.enabled)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update job: {}", e)))?
+    .ok_or_else(|| AppError::NotFound(format!("Job '{}' not found", name)))?;
+
+    Ok(Json(job))
+}
@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::notifications::notify_user,
+    models::{DispatchServiceRequest, ServiceRequest, ServiceRequestQuery},
+    utils,
+};
+
+/// Statuses a dispatcher may move a request to from its current status.
+fn allowed_transitions(status: &str) -> &'static [&'static str] {
+    match status {
+        "pending" => &["acknowledged", "scheduled", "rejected"],
+        "acknowledged" => &["scheduled", "rejected"],
+        "scheduled" => &["completed", "cancelled"],
+        _ => &[],
+    }
+}
+
+// Dispatcher queue: open requests first by priority, then by requested start
+pub async fn get_dispatch_queue(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<ServiceRequestQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let requests = sqlx::query_as::<_, ServiceRequest>(
+        "SELECT * FROM service_requests
+         WHERE ($1::varchar IS NULL AND status IN ('pending', 'acknowledged')) OR status = $1
+         ORDER BY CASE priority WHEN 'urgent' THEN 0 WHEN 'high' THEN 1 WHEN 'normal' THEN 2 ELSE 3 END,
+                  requested_start",
+    )
+    .bind(&query.status)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": requests.len(),
+        "requests": requests
+    })))
+}
+
+// Dispatcher acknowledges, schedules, rejects or completes a service request
+pub async fn dispatch_service_request(
+    State(db): State<Arc<PgPool>>,
+    Path(request_id): Path<String>,
+    Json(payload): Json<DispatchServiceRequest>,
+) -> AppResult<Json<ServiceRequest>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.dispatcher_id, "update service requests").await?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let current = sqlx::query_scalar::<_, String>("SELECT status FROM service_requests WHERE id = $1 FOR UPDATE")
+        .bind(&request_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Service request not found".to_string()))?;
+
+    if !allowed_transitions(&current).contains(&payload.status.as_str()) {
+        return Err(AppError::Conflict(format!(
+            "Cannot move a {} request to {}",
+            current, payload.status
+        )));
+    }
+
+    let request = sqlx::query_as::<_, ServiceRequest>(
+        "UPDATE service_requests
+         SET status = $2, dispatcher_id = $3, dispatcher_notes = COALESCE($4, dispatcher_notes),
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&request_id)
+    .bind(&payload.status)
+    .bind(&payload.dispatcher_id)
+    .bind(&payload.notes)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update service request: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    notify_user(
+        db.as_ref(),
+        &request.requested_by,
+        "service_request.updated",
+        Some(("service_request", &request.id)),
+        json!({ "title": request.title, "status": request.status }),
+    )
+    .await?;
+
+    Ok(Json(request))
+}
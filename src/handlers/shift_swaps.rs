@@ -0,0 +1,534 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{availability, notifications::notify_user},
+    models::{
+        ClaimShiftSwapRequest, CreateShiftSwapRequest, ReviewShiftSwapRequest, ShiftSwap,
+        ShiftSwapActionRequest, ShiftSwapEvent, ShiftSwapQuery,
+    },
+    utils,
+};
+
+const SWAP_TYPES: &[&str] = &["giveaway", "swap"];
+
+#[derive(sqlx::FromRow)]
+struct SwapShift {
+    id: String,
+    guard_id: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    client_site: String,
+    status: String,
+}
+
+async fn fetch_swap_shift(db: impl PgExecutor<'_>, shift_id: &str, lock: bool) -> AppResult<SwapShift> {
+    let sql = if lock {
+        "SELECT id, guard_id, start_time, end_time, client_site, status FROM shifts WHERE id = $1 FOR UPDATE"
+    } else {
+        "SELECT id, guard_id, start_time, end_time, client_site, status FROM shifts WHERE id = $1"
+    };
+
+    sqlx::query_as::<_, SwapShift>(sql)
+        .bind(shift_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))
+}
+
+async fn record_event(
+    db: impl PgExecutor<'_>,
+    swap_id: &str,
+    actor_id: &str,
+    action: &str,
+    details: Option<&str>,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO shift_swap_events (id, swap_id, actor_id, action, details) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(utils::generate_id())
+    .bind(swap_id)
+    .bind(actor_id)
+    .bind(action)
+    .bind(details)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record swap event: {}", e)))?;
+
+    Ok(())
+}
+
+fn ensure_tradeable(shift: &SwapShift) -> AppResult<()> {
+    if shift.status != "scheduled" {
+        return Err(AppError::Conflict(format!("Shift is {}", shift.status)));
+    }
+    if shift.start_time <= Utc::now() {
+        return Err(AppError::Conflict("Shift has already started".to_string()));
+    }
+    Ok(())
+}
+
+/// Check a guard may take over `shift`: an active verified guard, available per
+/// their calendar, and with no overlapping shift other than those in `released`
+/// (shifts the guard gives up in the same trade).
+async fn ensure_guard_can_take(
+    conn: &mut PgConnection,
+    guard_id: &str,
+    shift: &SwapShift,
+    released: &[&str],
+) -> AppResult<()> {
+    let (role, verified): (String, Option<bool>) =
+        sqlx::query_as("SELECT role, verified FROM users WHERE id = $1")
+            .bind(guard_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+
+    if role != "user" || verified != Some(true) {
+        return Err(AppError::Forbidden("Only verified guards can take shifts".to_string()));
+    }
+
+    availability::ensure_guard_available(&mut *conn, guard_id, shift.start_time, shift.end_time).await?;
+
+    let released: Vec<String> = released.iter().map(|id| id.to_string()).collect();
+    let overlapping = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(
+             SELECT 1 FROM shifts
+             WHERE guard_id = $1 AND id != $2 AND NOT (id = ANY($3))
+             AND status IN ('scheduled', 'in_progress')
+             AND start_time < $4 AND end_time > $5
+         )",
+    )
+    .bind(guard_id)
+    .bind(&shift.id)
+    .bind(&released)
+    .bind(shift.end_time)
+    .bind(shift.start_time)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if overlapping {
+        return Err(AppError::Conflict(format!(
+            "Guard has an overlapping shift between {} and {}",
+            shift.start_time.format("%Y-%m-%d %H:%M"),
+            shift.end_time.format("%H:%M")
+        )));
+    }
+
+    Ok(())
+}
+
+async fn fetch_swap(db: impl PgExecutor<'_>, swap_id: &str, lock: bool) -> AppResult<ShiftSwap> {
+    let sql = if lock {
+        "SELECT * FROM shift_swaps WHERE id = $1 FOR UPDATE"
+    } else {
+        "SELECT * FROM shift_swaps WHERE id = $1"
+    };
+
+    sqlx::query_as::<_, ShiftSwap>(sql)
+        .bind(swap_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Shift swap not found".to_string()))
+}
+
+// Post a shift to the swap marketplace
+pub async fn create_shift_swap(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateShiftSwapRequest>,
+) -> AppResult<(StatusCode, Json<ShiftSwap>)> {
+    if !SWAP_TYPES.contains(&payload.swap_type.as_str()) {
+        return Err(AppError::ValidationError("swapType must be 'giveaway' or 'swap'".to_string()));
+    }
+
+    let shift = fetch_swap_shift(db.as_ref(), &payload.shift_id, false).await?;
+    if shift.guard_id != payload.guard_id {
+        return Err(AppError::Forbidden("Only the assigned guard can post this shift".to_string()));
+    }
+    ensure_tradeable(&shift)?;
+
+    let already_posted = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM shift_swaps WHERE shift_id = $1 AND status IN ('open', 'claimed'))",
+    )
+    .bind(&shift.id)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if already_posted {
+        return Err(AppError::Conflict("Shift is already posted for swap".to_string()));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let swap = sqlx::query_as::<_, ShiftSwap>(
+        "INSERT INTO shift_swaps (id, shift_id, posted_by, swap_type, status, notes)
+         VALUES ($1, $2, $3, $4, 'open', $5)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&shift.id)
+    .bind(&payload.guard_id)
+    .bind(&payload.swap_type)
+    .bind(&payload.notes)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to post shift swap: {}", e)))?;
+
+    record_event(&mut *tx, &swap.id, &payload.guard_id, "posted", Some(&payload.swap_type)).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(swap)))
+}
+
+// List swaps; defaults to the open marketplace
+pub async fn get_shift_swaps(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<ShiftSwapQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    #[derive(sqlx::FromRow, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SwapListing {
+        #[sqlx(flatten)]
+        #[serde(flatten)]
+        swap: ShiftSwap,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        client_site: String,
+        posted_by_name: Option<String>,
+    }
+
+    let status = query.status.unwrap_or_else(|| "open".to_string());
+
+    let swaps = sqlx::query_as::<_, SwapListing>(
+        "SELECT sw.*, s.start_time, s.end_time, s.client_site, u.full_name AS posted_by_name
+         FROM shift_swaps sw
+         JOIN shifts s ON s.id = sw.shift_id
+         JOIN users u ON u.id = sw.posted_by
+         WHERE sw.status = $1
+         AND ($1 != 'open' OR s.start_time > CURRENT_TIMESTAMP)
+         ORDER BY s.start_time",
+    )
+    .bind(&status)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": swaps.len(),
+        "status": status,
+        "swaps": swaps
+    })))
+}
+
+// Get a swap with its audit trail
+pub async fn get_shift_swap(
+    State(db): State<Arc<PgPool>>,
+    Path(swap_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let swap = fetch_swap(db.as_ref(), &swap_id, false).await?;
+
+    let events = sqlx::query_as::<_, ShiftSwapEvent>(
+        "SELECT * FROM shift_swap_events WHERE swap_id = $1 ORDER BY created_at",
+    )
+    .bind(&swap_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "swap": swap,
+        "events": events
+    })))
+}
+
+// Claim an open swap; it then waits for supervisor approval
+pub async fn claim_shift_swap(
+    State(db): State<Arc<PgPool>>,
+    Path(swap_id): Path<String>,
+    Json(payload): Json<ClaimShiftSwapRequest>,
+) -> AppResult<Json<ShiftSwap>> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let swap = fetch_swap(&mut *tx, &swap_id, true).await?;
+    if swap.status != "open" {
+        return Err(AppError::Conflict(format!("Swap is {}", swap.status)));
+    }
+    if swap.posted_by == payload.guard_id {
+        return Err(AppError::BadRequest("You cannot claim your own shift".to_string()));
+    }
+
+    let shift = fetch_swap_shift(&mut *tx, &swap.shift_id, false).await?;
+    ensure_tradeable(&shift)?;
+
+    let claim_shift_id = match swap.swap_type.as_str() {
+        "swap" => {
+            let claim_shift_id = payload.claim_shift_id.as_deref().ok_or_else(|| {
+                AppError::BadRequest("claimShiftId is required to claim a swap".to_string())
+            })?;
+            let claim_shift = fetch_swap_shift(&mut *tx, claim_shift_id, false).await?;
+            if claim_shift.guard_id != payload.guard_id {
+                return Err(AppError::Forbidden("Offered shift belongs to another guard".to_string()));
+            }
+            ensure_tradeable(&claim_shift)?;
+            ensure_guard_can_take(&mut tx, &payload.guard_id, &shift, &[&claim_shift.id]).await?;
+            ensure_guard_can_take(&mut tx, &swap.posted_by, &claim_shift, &[&shift.id]).await?;
+            Some(claim_shift.id)
+        }
+        _ => {
+            ensure_guard_can_take(&mut tx, &payload.guard_id, &shift, &[]).await?;
+            None
+        }
+    };
+
+    let swap = sqlx::query_as::<_, ShiftSwap>(
+        "UPDATE shift_swaps
+         SET status = 'claimed', claimed_by = $2, claim_shift_id = $3, claimed_at = CURRENT_TIMESTAMP,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&swap_id)
+    .bind(&payload.guard_id)
+    .bind(&claim_shift_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to claim swap: {}", e)))?;
+
+    record_event(&mut *tx, &swap.id, &payload.guard_id, "claimed", claim_shift_id.as_deref()).await?;
+
+    let params = json!({
+        "swapType": swap.swap_type,
+        "startTime": shift.start_time.format("%Y-%m-%d %I:%M %p").to_string(),
+        "clientSite": shift.client_site
+    });
+    notify_user(&mut *tx, &swap.posted_by, "shift_swap.claimed", Some(("shift", &shift.id)), params.clone()).await?;
+
+    let supervisors: Vec<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE role IN ('admin', 'superadmin')")
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch supervisors: {}", e)))?;
+
+    for supervisor_id in &supervisors {
+        notify_user(
+            &mut *tx,
+            supervisor_id,
+            "shift_swap.awaiting_approval",
+            Some(("shift_swap", &swap.id)),
+            params.clone(),
+        )
+        .await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(swap))
+}
+
+// Withdraw a claim before it is reviewed; the swap goes back on the marketplace
+pub async fn withdraw_swap_claim(
+    State(db): State<Arc<PgPool>>,
+    Path(swap_id): Path<String>,
+    Json(payload): Json<ShiftSwapActionRequest>,
+) -> AppResult<Json<ShiftSwap>> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let swap = fetch_swap(&mut *tx, &swap_id, true).await?;
+    if swap.status != "claimed" || swap.claimed_by.as_deref() != Some(payload.guard_id.as_str()) {
+        return Err(AppError::Conflict("You have no pending claim on this swap".to_string()));
+    }
+
+    let swap = sqlx::query_as::<_, ShiftSwap>(
+        "UPDATE shift_swaps
+         SET status = 'open', claimed_by = NULL, claim_shift_id = NULL, claimed_at = NULL,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&swap_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to withdraw claim: {}", e)))?;
+
+    record_event(&mut *tx, &swap.id, &payload.guard_id, "claim_withdrawn", None).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(swap))
+}
+
+// Take a swap off the marketplace (poster only, before approval)
+pub async fn cancel_shift_swap(
+    State(db): State<Arc<PgPool>>,
+    Path(swap_id): Path<String>,
+    Json(payload): Json<ShiftSwapActionRequest>,
+) -> AppResult<Json<ShiftSwap>> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let swap = fetch_swap(&mut *tx, &swap_id, true).await?;
+    if swap.posted_by != payload.guard_id {
+        return Err(AppError::Forbidden("Only the guard who posted the swap can cancel it".to_string()));
+    }
+    if !matches!(swap.status.as_str(), "open" | "claimed") {
+        return Err(AppError::Conflict(format!("Swap is {}", swap.status)));
+    }
+
+    let swap = sqlx::query_as::<_, ShiftSwap>(
+        "UPDATE shift_swaps SET status = 'cancelled', updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+    )
+    .bind(&swap_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to cancel swap: {}", e)))?;
+
+    record_event(&mut *tx, &swap.id, &payload.guard_id, "cancelled", None).await?;
+
+    if let Some(claimed_by) = &swap.claimed_by {
+        notify_user(
+            &mut *tx,
+            claimed_by,
+            "shift_swap.cancelled",
+            Some(("shift", &swap.shift_id)),
+            json!({}),
+        )
+        .await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(swap))
+}
+
+// Supervisor approves or rejects a claimed swap. Approval re-checks compliance and
+// overlaps under row locks, then reassigns the shifts.
+pub async fn review_shift_swap(
+    State(db): State<Arc<PgPool>>,
+    Path(swap_id): Path<String>,
+    Json(payload): Json<ReviewShiftSwapRequest>,
+) -> AppResult<Json<ShiftSwap>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.reviewer_id, "review shift swaps").await?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let swap = fetch_swap(&mut *tx, &swap_id, true).await?;
+    if swap.status != "claimed" {
+        return Err(AppError::Conflict(format!("Swap is {}", swap.status)));
+    }
+    let claimed_by = swap
+        .claimed_by
+        .clone()
+        .ok_or_else(|| AppError::InternalServerError("Claimed swap has no claimant".to_string()))?;
+
+    if payload.approved {
+        let shift = fetch_swap_shift(&mut *tx, &swap.shift_id, true).await?;
+        if shift.guard_id != swap.posted_by {
+            return Err(AppError::Conflict("Shift has been reassigned since it was posted".to_string()));
+        }
+        ensure_tradeable(&shift)?;
+
+        let claim_shift = match &swap.claim_shift_id {
+            Some(claim_shift_id) => {
+                let claim_shift = fetch_swap_shift(&mut *tx, claim_shift_id, true).await?;
+                if claim_shift.guard_id != claimed_by {
+                    return Err(AppError::Conflict("Offered shift has been reassigned".to_string()));
+                }
+                ensure_tradeable(&claim_shift)?;
+                Some(claim_shift)
+            }
+            None => None,
+        };
+
+        match &claim_shift {
+            Some(claim_shift) => {
+                ensure_guard_can_take(&mut tx, &claimed_by, &shift, &[&claim_shift.id]).await?;
+                ensure_guard_can_take(&mut tx, &swap.posted_by, claim_shift, &[&shift.id]).await?;
+            }
+            None => ensure_guard_can_take(&mut tx, &claimed_by, &shift, &[]).await?,
+        }
+
+        sqlx::query("UPDATE shifts SET guard_id = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
+            .bind(&claimed_by)
+            .bind(&shift.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to reassign shift: {}", e)))?;
+
+        if let Some(claim_shift) = &claim_shift {
+            sqlx::query("UPDATE shifts SET guard_id = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
+                .bind(&swap.posted_by)
+                .bind(&claim_shift.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Failed to reassign shift: {}", e)))?;
+        }
+    }
+
+    let status = if payload.approved { "approved" } else { "rejected" };
+
+    let swap = sqlx::query_as::<_, ShiftSwap>(
+        "UPDATE shift_swaps
+         SET status = $2, reviewed_by = $3, reviewed_at = CURRENT_TIMESTAMP, review_notes = $4,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&swap_id)
+    .bind(status)
+    .bind(&payload.reviewer_id)
+    .bind(&payload.notes)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to review swap: {}", e)))?;
+
+    record_event(&mut *tx, &swap.id, &payload.reviewer_id, status, payload.notes.as_deref()).await?;
+
+    let template_key = if payload.approved { "shift_swap.approved" } else { "shift_swap.rejected" };
+    for guard_id in [&swap.posted_by, &claimed_by] {
+        notify_user(&mut *tx, guard_id, template_key, Some(("shift", &swap.shift_id)), json!({})).await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    tracing::info!("Shift swap {} {} by {}", swap.id, status, payload.reviewer_id);
+
+    Ok(Json(swap))
+}
@@ -0,0 +1,494 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{incidents::record_event, notifications::notify_user},
+    models::{AcknowledgeSosRequest, Incident, RaiseSosRequest, SosLocationRequest},
+    utils,
+};
+
+/// Levels of the escalation chain:
+/// 1. admins and the site's supervisor, as soon as the SOS is raised;
+/// 2. superadmins;
+/// 3. every admin, superadmin and the site supervisor again (last call).
+const MAX_ESCALATION_LEVEL: i32 = 3;
+/// Seconds an SOS may stay unacknowledged before the next level is notified (SOS_ESCALATION_SECS).
+const DEFAULT_ESCALATION_SECS: i64 = 60;
+
+fn escalation_secs() -> i64 {
+    std::env::var("SOS_ESCALATION_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_ESCALATION_SECS)
+}
+
+fn valid_coordinates(lat: f64, lon: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)
+}
+
+async fn fetch_sos(db: &PgPool, incident_id: &str) -> AppResult<Incident> {
+    sqlx::query_as::<_, Incident>("SELECT * FROM incidents WHERE id = $1 AND incident_type = 'sos'")
+        .bind(incident_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("SOS not found".to_string()))
+}
+
+/// Who is notified at a level of the escalation chain.
+async fn escalation_recipients(db: &PgPool, level: i32, client_site: Option<&str>) -> AppResult<Vec<String>> {
+    let roles: &[&str] = match level {
+        1 => &["admin"],
+        2 => &["superadmin"],
+        _ => &["admin", "superadmin"],
+    };
+    let include_site_supervisor = level != 2;
+
+    sqlx::query_scalar(
+        "SELECT id FROM users WHERE role = ANY($1)
+         UNION
+         SELECT supervisor_id FROM client_sites
+         WHERE $3 AND name = $2 AND supervisor_id IS NOT NULL",
+    )
+    .bind(roles)
+    .bind(client_site)
+    .bind(include_site_supervisor)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch SOS recipients: {}", e)))
+}
+
+/// Template parameters describing who raised the SOS, what they were on and where they are.
+async fn sos_params(db: &PgPool, incident: &Incident) -> AppResult<serde_json::Value> {
+    let (guard_name, plate): (String, Option<String>) = sqlx::query_as(
+        "SELECT u.full_name,
+                (SELECT ac.license_plate FROM trips t JOIN armored_cars ac ON ac.id = t.car_id WHERE t.id = $2)
+         FROM users u WHERE u.id = $1",
+    )
+    .bind(&incident.reported_by)
+    .bind(&incident.trip_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let context = match (&incident.client_site, plate) {
+        (Some(site), Some(plate)) => format!("on shift at {}, in vehicle {}", site, plate),
+        (Some(site), None) => format!("on shift at {}", site),
+        (None, Some(plate)) => format!("on a trip in vehicle {}", plate),
+        (None, None) => "off duty".to_string(),
+    };
+    let location = match (incident.latitude, incident.longitude) {
+        (Some(lat), Some(lon)) => format!("{:.5}, {:.5}", lat, lon),
+        _ => "an unknown location".to_string(),
+    };
+
+    Ok(json!({
+        "guardName": guard_name,
+        "context": context,
+        "location": location,
+        "seconds": (Utc::now() - incident.occurred_at).num_seconds().to_string()
+    }))
+}
+
+/// Notify one level of the chain and log it on the timeline.
+async fn notify_level(db: &PgPool, incident: &Incident, level: i32) -> AppResult<usize> {
+    let recipients = escalation_recipients(db, level, incident.client_site.as_deref()).await?;
+    let params = sos_params(db, incident).await?;
+    let template_key = if level == 1 { "sos.raised" } else { "sos.escalated" };
+
+    for recipient_id in &recipients {
+        notify_user(db, recipient_id, template_key, Some(("incident", &incident.id)), params.clone()).await?;
+    }
+
+    record_event(
+        db,
+        &incident.id,
+        "notified",
+        None,
+        Some(level),
+        None,
+        Some(&format!("{} recipients", recipients.len())),
+    )
+    .await?;
+
+    tracing::warn!(
+        "SOS {} from {}: level {} sent to {} recipients",
+        incident.id, incident.reported_by, level, recipients.len()
+    );
+    Ok(recipients.len())
+}
+
+/// Move an unacknowledged SOS to the next level once its escalation is due.
+/// Returns false when there was nothing to do (acknowledged, resolved, not yet due or already moved).
+async fn escalate_sos(db: &PgPool, incident_id: &str) -> AppResult<bool> {
+    let escalated = sqlx::query_as::<_, Incident>(
+        "UPDATE incidents
+         SET escalation_level = escalation_level + 1,
+             next_escalation_at = CASE WHEN escalation_level + 1 < $2
+                                       THEN CURRENT_TIMESTAMP + make_interval(secs => $3) END,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND incident_type = 'sos' AND acknowledged_at IS NULL AND status <> 'resolved'
+           AND next_escalation_at <= CURRENT_TIMESTAMP
+         RETURNING *",
+    )
+    .bind(incident_id)
+    .bind(MAX_ESCALATION_LEVEL)
+    .bind(escalation_secs() as f64)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to escalate SOS: {}", e)))?;
+
+    let Some(incident) = escalated else {
+        return Ok(false);
+    };
+
+    record_event(
+        db,
+        &incident.id,
+        "escalated",
+        None,
+        Some(incident.escalation_level),
+        None,
+        Some("Not acknowledged in time"),
+    )
+    .await?;
+    notify_level(db, &incident, incident.escalation_level).await?;
+
+    Ok(true)
+}
+
+/// Escalate an SOS as soon as each level falls due. The `sos_escalation` job catches any
+/// escalation this timer misses (for example after a restart).
+fn schedule_escalation(db: Arc<PgPool>, incident_id: String) {
+    tokio::spawn(async move {
+        loop {
+            let next = sqlx::query_scalar::<_, Option<chrono::DateTime<Utc>>>(
+                "SELECT next_escalation_at FROM incidents
+                 WHERE id = $1 AND acknowledged_at IS NULL AND status <> 'resolved'",
+            )
+            .bind(&incident_id)
+            .fetch_optional(db.as_ref())
+            .await;
+
+            let due = match next {
+                Ok(Some(Some(due))) => due,
+                Ok(_) => break,
+                Err(e) => {
+                    tracing::error!("Failed to read SOS {} escalation: {}", incident_id, e);
+                    break;
+                }
+            };
+
+            let wait = (due - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+
+            match escalate_sos(db.as_ref(), &incident_id).await {
+                Ok(true) => {}
+                // Not due yet by the database clock, or taken by the job meanwhile
+                Ok(false) => tokio::time::sleep(std::time::Duration::from_secs(1)).await,
+                Err(e) => {
+                    tracing::error!("Failed to escalate SOS {}: {}", incident_id, e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Escalate every SOS whose escalation is overdue. Shared by the scheduler job.
+pub async fn escalate_due_sos(db: &PgPool) -> AppResult<serde_json::Value> {
+    let due: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM incidents
+         WHERE incident_type = 'sos' AND acknowledged_at IS NULL AND status <> 'resolved'
+           AND next_escalation_at <= CURRENT_TIMESTAMP
+         ORDER BY next_escalation_at",
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch due SOS escalations: {}", e)))?;
+
+    let mut escalated = Vec::new();
+    for incident_id in due {
+        if escalate_sos(db, &incident_id).await? {
+            escalated.push(incident_id);
+        }
+    }
+
+    Ok(json!({ "escalated": escalated }))
+}
+
+// Raise an SOS: record it as a critical incident and alert the first level of the chain
+pub async fn raise_sos(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<RaiseSosRequest>,
+) -> AppResult<(StatusCode, Json<Incident>)> {
+    let position = match (payload.lat, payload.lon) {
+        (Some(lat), Some(lon)) if valid_coordinates(lat, lon) => Some((lat, lon)),
+        (None, None) => None,
+        _ => return Err(AppError::ValidationError("lat and lon must be given together and in range".to_string())),
+    };
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    // Locking the guard serialises a double tap, so only the first press opens an incident
+    let guard_name = sqlx::query_scalar::<_, String>("SELECT full_name FROM users WHERE id = $1 FOR UPDATE")
+        .bind(&payload.guard_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    // Pressing SOS again while one is still open only updates it
+    let open = sqlx::query_as::<_, Incident>(
+        "SELECT * FROM incidents
+         WHERE incident_type = 'sos' AND reported_by = $1 AND status <> 'resolved'
+         ORDER BY occurred_at DESC LIMIT 1",
+    )
+    .bind(&payload.guard_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if let Some(open) = open {
+        let incident = sqlx::query_as::<_, Incident>(
+            "UPDATE incidents
+             SET latitude = COALESCE($2, latitude), longitude = COALESCE($3, longitude), updated_at = CURRENT_TIMESTAMP
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(&open.id)
+        .bind(position.map(|p| p.0))
+        .bind(position.map(|p| p.1))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update SOS: {}", e)))?;
+
+        record_event(
+            &mut *tx,
+            &incident.id,
+            "sos_repeated",
+            Some(&payload.guard_id),
+            None,
+            position,
+            payload.message.as_deref(),
+        )
+        .await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+        return Ok((StatusCode::OK, Json(incident)));
+    }
+
+    let shift: Option<(String, String)> = sqlx::query_as(
+        "SELECT id, client_site FROM shifts
+         WHERE guard_id = $1 AND status IN ('scheduled', 'in_progress')
+           AND start_time <= CURRENT_TIMESTAMP AND end_time > CURRENT_TIMESTAMP
+         ORDER BY start_time DESC LIMIT 1",
+    )
+    .bind(&payload.guard_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    // On a trip the guard crews without a phone fix, the vehicle's tracker gives the position
+    let trip: Option<(String, Option<f64>, Option<f64>)> = sqlx::query_as(
+        "SELECT t.id, lp.latitude, lp.longitude
+         FROM trips t
+         LEFT JOIN vehicle_live_positions lp ON lp.car_id = t.car_id AND lp.recorded_at >= t.start_time
+         WHERE EXISTS (SELECT 1 FROM trip_crew tc WHERE tc.trip_id = t.id AND tc.user_id = $1)
+           AND t.status IN ('in_transit', 'in_progress')
+         ORDER BY t.start_time DESC LIMIT 1",
+    )
+    .bind(&payload.guard_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let position = position.or(match &trip {
+        Some((_, Some(lat), Some(lon))) => Some((*lat, *lon)),
+        _ => None,
+    });
+
+    let incident = sqlx::query_as::<_, Incident>(
+        "INSERT INTO incidents (id, client_site, shift_id, trip_id, reported_by, severity, title, description,
+                                occurred_at, incident_type, latitude, longitude, escalation_level, next_escalation_at)
+         VALUES ($1, $2, $3, $4, $5, 'critical', $6, $7, CURRENT_TIMESTAMP, 'sos', $8, $9, 1,
+                 CURRENT_TIMESTAMP + make_interval(secs => $10))
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(shift.as_ref().map(|s| &s.1))
+    .bind(shift.as_ref().map(|s| &s.0))
+    .bind(trip.as_ref().map(|t| &t.0))
+    .bind(&payload.guard_id)
+    .bind(format!("SOS: {}", guard_name))
+    .bind(&payload.message)
+    .bind(position.map(|p| p.0))
+    .bind(position.map(|p| p.1))
+    .bind(escalation_secs() as f64)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record SOS: {}", e)))?;
+
+    record_event(
+        &mut *tx,
+        &incident.id,
+        "sos_raised",
+        Some(&payload.guard_id),
+        None,
+        position,
+        payload.message.as_deref(),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    // The escalation timer is armed first so a failed alert still escalates to the next level
+    schedule_escalation(db.clone(), incident.id.clone());
+    if let Err(e) = notify_level(db.as_ref(), &incident, 1).await {
+        tracing::error!("Failed to alert level 1 for SOS {}: {}", incident.id, e);
+    }
+
+    Ok((StatusCode::CREATED, Json(incident)))
+}
+
+// The guard's device reports a new position while the SOS is open
+pub async fn update_sos_location(
+    State(db): State<Arc<PgPool>>,
+    Path(incident_id): Path<String>,
+    Json(payload): Json<SosLocationRequest>,
+) -> AppResult<Json<Incident>> {
+    if !valid_coordinates(payload.lat, payload.lon) {
+        return Err(AppError::ValidationError("lat/lon out of range".to_string()));
+    }
+
+    let incident = fetch_sos(db.as_ref(), &incident_id).await?;
+    if incident.reported_by != payload.guard_id {
+        return Err(AppError::Forbidden("Only the guard who raised the SOS can update its location".to_string()));
+    }
+    if incident.status == "resolved" {
+        return Err(AppError::Conflict("SOS is already resolved".to_string()));
+    }
+
+    let incident = sqlx::query_as::<_, Incident>(
+        "UPDATE incidents SET latitude = $2, longitude = $3, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&incident_id)
+    .bind(payload.lat)
+    .bind(payload.lon)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update SOS location: {}", e)))?;
+
+    record_event(
+        db.as_ref(),
+        &incident_id,
+        "location_update",
+        Some(&payload.guard_id),
+        None,
+        Some((payload.lat, payload.lon)),
+        None,
+    )
+    .await?;
+
+    Ok(Json(incident))
+}
+
+// A supervisor takes the SOS: escalation stops and the guard is told help is coming
+pub async fn acknowledge_sos(
+    State(db): State<Arc<PgPool>>,
+    Path(incident_id): Path<String>,
+    Json(payload): Json<AcknowledgeSosRequest>,
+) -> AppResult<Json<Incident>> {
+    let incident = fetch_sos(db.as_ref(), &incident_id).await?;
+
+    let (role, name) = sqlx::query_as::<_, (String, String)>("SELECT role, full_name FROM users WHERE id = $1")
+        .bind(&payload.user_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let is_site_supervisor = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM client_sites WHERE name = $1 AND supervisor_id = $2)",
+    )
+    .bind(&incident.client_site)
+    .bind(&payload.user_id)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if !matches!(role.as_str(), "admin" | "superadmin") && !is_site_supervisor {
+        return Err(AppError::Forbidden("Only supervisors can acknowledge an SOS".to_string()));
+    }
+
+    let incident = sqlx::query_as::<_, Incident>(
+        "UPDATE incidents
+         SET acknowledged_by = $2, acknowledged_at = CURRENT_TIMESTAMP, next_escalation_at = NULL,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND acknowledged_at IS NULL
+         RETURNING *",
+    )
+    .bind(&incident_id)
+    .bind(&payload.user_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to acknowledge SOS: {}", e)))?
+    .ok_or_else(|| AppError::Conflict("SOS is already acknowledged".to_string()))?;
+
+    record_event(
+        db.as_ref(),
+        &incident_id,
+        "acknowledged",
+        Some(&payload.user_id),
+        Some(incident.escalation_level),
+        None,
+        payload.notes.as_deref(),
+    )
+    .await?;
+
+    notify_user(
+        db.as_ref(),
+        &incident.reported_by,
+        "sos.acknowledged",
+        Some(("incident", &incident_id)),
+        json!({ "acknowledgedBy": name }),
+    )
+    .await?;
+
+    Ok(Json(incident))
+}
+
+// Unresolved SOS alerts, unacknowledged first
+pub async fn get_active_sos(State(db): State<Arc<PgPool>>) -> AppResult<Json<serde_json::Value>> {
+    let alerts = sqlx::query_as::<_, Incident>(
+        "SELECT * FROM incidents
+         WHERE incident_type = 'sos' AND status <> 'resolved'
+         ORDER BY acknowledged_at IS NOT NULL, occurred_at DESC",
+    )
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": alerts.len(),
+        "alerts": alerts
+    })))
+}
@@ -0,0 +1,333 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::trip_monitoring,
+    models::{IngestTelemetryRequest, LiveTripPosition, TripTrackQuery, VehiclePosition},
+};
+
+/// Most points accepted in one ingest request.
+const MAX_BATCH: usize = 1000;
+/// Points stamped further ahead than this are rejected (tracker clock drift).
+const MAX_CLOCK_SKEW_SECS: i64 = 300;
+/// A live position older than this (TELEMETRY_STALE_SECS) is flagged stale.
+const DEFAULT_STALE_SECS: f64 = 120.0;
+const DEFAULT_TRACK_POINTS: i64 = 2000;
+const MAX_TRACK_POINTS: i64 = 10000;
+
+fn stale_after_secs() -> f64 {
+    std::env::var("TELEMETRY_STALE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_STALE_SECS)
+}
+
+/// Trackers authenticate with the shared TELEMETRY_INGEST_KEY in `X-Tracker-Key`.
+fn verify_tracker_key(headers: &HeaderMap) -> AppResult<()> {
+    let expected = std::env::var("TELEMETRY_INGEST_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| AppError::InternalServerError("Telemetry ingestion is not configured".to_string()))?;
+    let provided = headers
+        .get("x-tracker-key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("A tracker key is required".to_string()))?;
+
+    // Compare without returning early, so timing does not reveal the key
+    let matches = provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if !matches {
+        return Err(AppError::Unauthorized("Invalid tracker key".to_string()));
+    }
+    Ok(())
+}
+
+fn point_error(lat: f64, lon: f64, speed: Option<f64>, heading: Option<f64>, recorded_at: DateTime<Utc>) -> Option<&'static str> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Some("lat/lon out of range");
+    }
+    if speed.is_some_and(|s| !s.is_finite() || s < 0.0) {
+        return Some("speedKph must be zero or more");
+    }
+    if heading.is_some_and(|h| !(0.0..=360.0).contains(&h)) {
+        return Some("heading must be between 0 and 360");
+    }
+    if recorded_at > Utc::now() + Duration::seconds(MAX_CLOCK_SKEW_SECS) {
+        return Some("recordedAt is in the future");
+    }
+    None
+}
+
+/// Create the monthly partition that holds `ts` if it does not exist yet.
+/// Concurrent batches may race to create it; the loser retries once and finds it there.
+pub async fn ensure_partition(db: &PgPool, ts: DateTime<Utc>) -> AppResult<()> {
+    let create = || sqlx::query("SELECT ensure_vehicle_position_partition($1)").bind(ts).execute(db);
+    if create().await.is_err() {
+        create()
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to create telemetry partition: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Create this month's and next month's partitions ahead of time. Shared by the scheduler job.
+pub async fn prepare_partitions(db: &PgPool) -> AppResult<serde_json::Value> {
+    let now = Utc::now();
+    let partitions: Vec<String> = sqlx::query_scalar(
+        "SELECT ensure_vehicle_position_partition(ts)
+         FROM unnest(ARRAY[$1, $1 + INTERVAL '1 month']) AS ts",
+    )
+    .bind(now)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create telemetry partitions: {}", e)))?;
+
+    Ok(json!({ "partitions": partitions }))
+}
+
+// Batch of GPS points from a vehicle tracker. Points are attached to the trip the vehicle was on
+// when they were recorded; invalid points are reported back, repeats of stored points are ignored.
+pub async fn ingest_telemetry(
+    State(db): State<Arc<PgPool>>,
+    headers: HeaderMap,
+    Json(payload): Json<IngestTelemetryRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    verify_tracker_key(&headers)?;
+    if payload.points.is_empty() || payload.points.len() > MAX_BATCH {
+        return Err(AppError::ValidationError(format!(
+            "A batch must contain 1 to {} points",
+            MAX_BATCH
+        )));
+    }
+
+    let car_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM armored_cars WHERE id = $1)")
+        .bind(&payload.car_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    if !car_exists {
+        return Err(AppError::NotFound("Armored car not found".to_string()));
+    }
+
+    let mut rejected = Vec::new();
+    let (mut recorded_at, mut lat, mut lon, mut speed, mut heading) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (index, point) in payload.points.iter().enumerate() {
+        match point_error(point.lat, point.lon, point.speed_kph, point.heading, point.recorded_at) {
+            Some(reason) => rejected.push(json!({ "index": index, "reason": reason })),
+            None => {
+                recorded_at.push(point.recorded_at);
+                lat.push(point.lat);
+                lon.push(point.lon);
+                speed.push(point.speed_kph);
+                heading.push(point.heading.map(|h| h % 360.0));
+            }
+        }
+    }
+
+    // One point per month the batch touches
+    let mut months = BTreeMap::new();
+    for ts in &recorded_at {
+        months.entry((ts.year(), ts.month())).or_insert(*ts);
+    }
+    for ts in months.values() {
+        ensure_partition(db.as_ref(), *ts).await?;
+    }
+
+    // A point belongs to the trip under way at the time, or to a completed trip it falls within
+    // (a tracker may upload late); scheduled and cancelled trips never ran and collect nothing
+    let accepted = if recorded_at.is_empty() {
+        0
+    } else {
+        sqlx::query(
+            r#"
+            INSERT INTO vehicle_positions (car_id, trip_id, recorded_at, latitude, longitude, speed_kph, heading)
+            SELECT $1,
+                   (SELECT t.id FROM trips t
+                    WHERE t.car_id = $1 AND t.start_time <= p.recorded_at
+                      AND (t.end_time IS NULL OR t.end_time >= p.recorded_at)
+                      AND (t.status IN ('in_progress', 'in_transit') OR (t.status = 'completed' AND t.end_time IS NOT NULL))
+                    ORDER BY t.start_time DESC
+                    LIMIT 1),
+                   p.recorded_at, p.latitude, p.longitude, p.speed_kph, p.heading
+            FROM UNNEST($2::timestamptz[], $3::float8[], $4::float8[], $5::float8[], $6::float8[])
+                 AS p(recorded_at, latitude, longitude, speed_kph, heading)
+            ON CONFLICT (car_id, recorded_at) DO NOTHING
+            "#,
+        )
+        .bind(&payload.car_id)
+        .bind(&recorded_at)
+        .bind(&lat)
+        .bind(&lon)
+        .bind(&speed)
+        .bind(&heading)
+        .execute(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to store telemetry: {}", e)))?
+        .rows_affected()
+    };
+
+    // Move the live position forward (late batches never move it back)
+    sqlx::query(
+        r#"
+        INSERT INTO vehicle_live_positions (car_id, trip_id, recorded_at, latitude, longitude, speed_kph, heading)
+        SELECT car_id, trip_id, recorded_at, latitude, longitude, speed_kph, heading
+        FROM vehicle_positions
+        WHERE car_id = $1 AND recorded_at = (SELECT MAX(ts) FROM UNNEST($2::timestamptz[]) AS ts)
+        ON CONFLICT (car_id) DO UPDATE
+        SET trip_id = EXCLUDED.trip_id, recorded_at = EXCLUDED.recorded_at, latitude = EXCLUDED.latitude,
+            longitude = EXCLUDED.longitude, speed_kph = EXCLUDED.speed_kph, heading = EXCLUDED.heading,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE vehicle_live_positions.recorded_at < EXCLUDED.recorded_at
+        "#,
+    )
+    .bind(&payload.car_id)
+    .bind(&recorded_at)
+    .execute(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update live position: {}", e)))?;
+
+    // Route monitoring must not make the tracker resend its batch
+    if accepted > 0 {
+        if let Err(e) = trip_monitoring::evaluate_car(db.as_ref(), &payload.car_id).await {
+            tracing::warn!("Trip monitoring failed for car {}: {:?}", payload.car_id, e);
+        }
+    }
+
+    Ok(Json(json!({
+        "accepted": accepted,
+        "duplicates": recorded_at.len() as u64 - accepted,
+        "rejected": rejected
+    })))
+}
+
+// Latest position of every vehicle on an active trip
+pub async fn get_live_positions(State(db): State<Arc<PgPool>>) -> AppResult<Json<serde_json::Value>> {
+    let positions = sqlx::query_as::<_, LiveTripPosition>(
+        r#"
+        SELECT t.id AS trip_id, t.car_id, ac.license_plate, t.driver_id, u.full_name AS driver_name,
+               t.status AS trip_status, t.destination, t.start_time,
+               lp.recorded_at, lp.latitude, lp.longitude, lp.speed_kph, lp.heading,
+               (lp.recorded_at IS NULL OR lp.recorded_at < NOW() - make_interval(secs => $1)) AS stale
+        FROM trips t
+        LEFT JOIN armored_cars ac ON ac.id = t.car_id
+        LEFT JOIN users u ON u.id = t.driver_id
+        LEFT JOIN vehicle_live_positions lp ON lp.car_id = t.car_id AND lp.recorded_at >= t.start_time
+        WHERE t.status IN ('in_transit', 'in_progress')
+        ORDER BY t.start_time ASC
+        "#,
+    )
+    .bind(stale_after_secs())
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch live positions: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": positions.len(),
+        "positions": positions
+    })))
+}
+
+// Recorded track of a trip for replay, oldest first, thinned evenly to `maxPoints`
+pub async fn get_trip_track(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+    Query(query): Query<TripTrackQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let max_points = query.max_points.unwrap_or(DEFAULT_TRACK_POINTS);
+    if !(2..=MAX_TRACK_POINTS).contains(&max_points) {
+        return Err(AppError::ValidationError(format!(
+            "maxPoints must be between 2 and {}",
+            MAX_TRACK_POINTS
+        )));
+    }
+
+    let (start_time, end_time): (DateTime<Utc>, Option<DateTime<Utc>>) =
+        sqlx::query_as("SELECT start_time, end_time FROM trips WHERE id = $1")
+            .bind(&trip_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+
+    // Bounding by the trip's own times lets Postgres skip other months' partitions
+    let from = query.from.map_or(start_time, |from| from.max(start_time));
+    let to = match (query.to, end_time) {
+        (Some(to), Some(end)) => to.min(end),
+        (Some(to), None) => to,
+        (None, end) => end.unwrap_or_else(|| Utc::now() + Duration::seconds(MAX_CLOCK_SKEW_SECS)),
+    };
+
+    #[derive(sqlx::FromRow)]
+    struct TrackSummary {
+        total_points: i64,
+        distance_km: Option<f64>,
+        max_speed_kph: Option<f64>,
+        first_recorded_at: Option<DateTime<Utc>>,
+        last_recorded_at: Option<DateTime<Utc>>,
+    }
+
+    let summary = sqlx::query_as::<_, TrackSummary>(
+        r#"
+        SELECT COUNT(*) AS total_points, SUM(step_km) AS distance_km, MAX(speed_kph) AS max_speed_kph,
+               MIN(recorded_at) AS first_recorded_at, MAX(recorded_at) AS last_recorded_at
+        FROM (
+            SELECT recorded_at, speed_kph,
+                   haversine_km(LAG(latitude) OVER w, LAG(longitude) OVER w, latitude, longitude) AS step_km
+            FROM vehicle_positions
+            WHERE trip_id = $1 AND recorded_at BETWEEN $2 AND $3
+            WINDOW w AS (ORDER BY recorded_at)
+        ) steps
+        "#,
+    )
+    .bind(&trip_id)
+    .bind(from)
+    .bind(to)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to summarise track: {}", e)))?;
+
+    let points = sqlx::query_as::<_, VehiclePosition>(
+        r#"
+        SELECT car_id, trip_id, recorded_at, latitude, longitude, speed_kph, heading
+        FROM (
+            SELECT *, ROW_NUMBER() OVER (ORDER BY recorded_at) AS n
+            FROM vehicle_positions
+            WHERE trip_id = $1 AND recorded_at BETWEEN $2 AND $3
+        ) track
+        WHERE $4 <= $5 OR (n - 1) % CEIL(($4 - 1)::float8 / ($5 - 1))::bigint = 0 OR n = $4
+        ORDER BY recorded_at
+        "#,
+    )
+    .bind(&trip_id)
+    .bind(from)
+    .bind(to)
+    .bind(summary.total_points)
+    .bind(max_points)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch track: {}", e)))?;
+
+    Ok(Json(json!({
+        "tripId": trip_id,
+        "totalPoints": summary.total_points,
+        "distanceKm": summary.distance_km.unwrap_or(0.0),
+        "maxSpeedKph": summary.max_speed_kph,
+        "firstRecordedAt": summary.first_recorded_at,
+        "lastRecordedAt": summary.last_recorded_at,
+        "total": points.len(),
+        "points": points
+    })))
+}
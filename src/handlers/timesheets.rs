@@ -0,0 +1,783 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{availability::schedule_timezone, notifications::notify_user},
+    models::{
+        CreateHolidayRequest, CreatePayRateRuleRequest, GenerateTimesheetsRequest, Holiday,
+        PayRateRule, ReviewTimesheetRequest, Timesheet, TimesheetEntry, TimesheetQuery,
+    },
+    utils,
+};
+
+/// Version of the payroll JSON export; bump when the documented shape changes.
+const PAYROLL_EXPORT_VERSION: i32 = 1;
+
+#[derive(sqlx::FromRow)]
+struct WorkedAttendance {
+    id: String,
+    guard_id: String,
+    shift_id: String,
+    check_in: NaiveDateTime,
+    check_out: NaiveDateTime,
+}
+
+struct EntryHours {
+    work_date: NaiveDate,
+    hours: f64,
+    regular: f64,
+    overtime: f64,
+    night_diff: f64,
+    holiday: f64,
+    holiday_type: Option<String>,
+}
+
+#[derive(Default)]
+struct PayTotals {
+    total_hours: f64,
+    regular_hours: f64,
+    overtime_hours: f64,
+    night_diff_hours: f64,
+    holiday_hours: f64,
+    regular_pay: f64,
+    overtime_pay: f64,
+    night_diff_pay: f64,
+    holiday_pay: f64,
+}
+
+pub(crate) fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn overlap_hours(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    window_start: NaiveDateTime,
+    window_end: NaiveDateTime,
+) -> f64 {
+    let from = start.max(window_start);
+    let to = end.min(window_end);
+    if to > from {
+        (to - from).num_seconds() as f64 / 3600.0
+    } else {
+        0.0
+    }
+}
+
+/// Hours of [start, end) falling inside a daily window; the window wraps past
+/// midnight when `window_end <= window_start`.
+pub(crate) fn daily_window_hours(
+    window_start: NaiveTime,
+    window_end: NaiveTime,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> f64 {
+    let overnight = window_end <= window_start;
+    let mut day = start.date() - Duration::days(1);
+    let mut total = 0.0;
+    while day <= end.date() {
+        let from = day.and_time(window_start);
+        let to = if overnight {
+            (day + Duration::days(1)).and_time(window_end)
+        } else {
+            day.and_time(window_end)
+        };
+        total += overlap_hours(start, end, from, to);
+        day += Duration::days(1);
+    }
+    total
+}
+
+/// Hours of [start, end) falling on holidays, with the type of the first holiday touched.
+pub(crate) fn holiday_hours(
+    holidays: &HashMap<NaiveDate, String>,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> (f64, Option<String>) {
+    let mut hours = 0.0;
+    let mut holiday_type = None;
+    let mut day = start.date();
+    while day <= end.date() {
+        if let Some(kind) = holidays.get(&day) {
+            let from = day.and_time(NaiveTime::MIN);
+            hours += overlap_hours(start, end, from, from + Duration::days(1));
+            holiday_type.get_or_insert_with(|| kind.clone());
+        }
+        day += Duration::days(1);
+    }
+    (hours, holiday_type)
+}
+
+/// Holidays between `from` and `to` inclusive, keyed by date.
+pub(crate) async fn fetch_holidays(
+    db: impl PgExecutor<'_>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> AppResult<HashMap<NaiveDate, String>> {
+    let rows = sqlx::query_as::<_, (NaiveDate, String)>(
+        "SELECT holiday_date, holiday_type FROM holidays WHERE holiday_date BETWEEN $1 AND $2",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch holidays: {}", e)))?;
+
+    Ok(rows.into_iter().collect())
+}
+
+/// Split one attendance record into pay categories. `worked_today` is the number of
+/// hours already worked on the same local day, so overtime accrues per day.
+fn compute_entry(
+    rule: &PayRateRule,
+    holidays: &HashMap<NaiveDate, String>,
+    attendance: &WorkedAttendance,
+    worked_today: f64,
+) -> EntryHours {
+    let (start, end) = (attendance.check_in, attendance.check_out);
+    let hours = (end - start).num_seconds().max(0) as f64 / 3600.0;
+    let regular = (rule.regular_hours_per_day - worked_today).clamp(0.0, hours);
+    let (holiday, holiday_type) = holiday_hours(holidays, start, end);
+
+    EntryHours {
+        work_date: start.date(),
+        hours,
+        regular,
+        overtime: hours - regular,
+        night_diff: daily_window_hours(rule.night_diff_start, rule.night_diff_end, start, end),
+        holiday,
+        holiday_type,
+    }
+}
+
+/// Newest active pay rule.
+pub async fn active_rate_rule(db: impl PgExecutor<'_>) -> AppResult<PayRateRule> {
+    sqlx::query_as::<_, PayRateRule>(
+        "SELECT * FROM pay_rate_rules WHERE active = true ORDER BY created_at DESC LIMIT 1",
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch pay rule: {}", e)))?
+    .ok_or_else(|| AppError::BadRequest("No active pay rate rule configured".to_string()))
+}
+
+// Get the active pay rate rule and its history
+pub async fn get_rate_rules(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let rules = sqlx::query_as::<_, PayRateRule>("SELECT * FROM pay_rate_rules ORDER BY created_at DESC")
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let active = rules.iter().find(|r| r.active);
+
+    Ok(Json(json!({
+        "active": active,
+        "total": rules.len(),
+        "rules": rules
+    })))
+}
+
+// Create a new pay rate rule; it replaces the current active rule
+pub async fn create_rate_rule(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreatePayRateRuleRequest>,
+) -> AppResult<(StatusCode, Json<PayRateRule>)> {
+    let parse_time = |value: &Option<String>, default: &str| -> AppResult<NaiveTime> {
+        let value = value.as_deref().unwrap_or(default);
+        NaiveTime::parse_from_str(value, "%H:%M")
+            .map_err(|_| AppError::ValidationError(format!("Invalid time '{}', expected HH:MM", value)))
+    };
+
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Rule name is required".to_string()));
+    }
+    if payload.base_hourly_rate < 0.0 {
+        return Err(AppError::ValidationError("baseHourlyRate cannot be negative".to_string()));
+    }
+    let night_start = parse_time(&payload.night_diff_start, "22:00")?;
+    let night_end = parse_time(&payload.night_diff_end, "06:00")?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    sqlx::query("UPDATE pay_rate_rules SET active = false WHERE active = true")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to deactivate pay rules: {}", e)))?;
+
+    let rule = sqlx::query_as::<_, PayRateRule>(
+        "INSERT INTO pay_rate_rules
+         (id, name, base_hourly_rate, currency, regular_hours_per_day, overtime_multiplier,
+          night_diff_start, night_diff_end, night_diff_premium,
+          regular_holiday_multiplier, special_holiday_multiplier, active, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, true, $12)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(payload.name.trim())
+    .bind(payload.base_hourly_rate)
+    .bind(payload.currency.as_deref().unwrap_or("PHP"))
+    .bind(payload.regular_hours_per_day.unwrap_or(8.0))
+    .bind(payload.overtime_multiplier.unwrap_or(1.25))
+    .bind(night_start)
+    .bind(night_end)
+    .bind(payload.night_diff_premium.unwrap_or(0.10))
+    .bind(payload.regular_holiday_multiplier.unwrap_or(2.0))
+    .bind(payload.special_holiday_multiplier.unwrap_or(1.3))
+    .bind(&payload.created_by)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create pay rule: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+// Add a holiday to the payroll calendar
+pub async fn create_holiday(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateHolidayRequest>,
+) -> AppResult<(StatusCode, Json<Holiday>)> {
+    if !matches!(payload.holiday_type.as_str(), "regular" | "special") {
+        return Err(AppError::ValidationError("holidayType must be 'regular' or 'special'".to_string()));
+    }
+
+    let holiday = sqlx::query_as::<_, Holiday>(
+        "INSERT INTO holidays (id, holiday_date, name, holiday_type)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (holiday_date) DO NOTHING
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(payload.holiday_date)
+    .bind(&payload.name)
+    .bind(&payload.holiday_type)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create holiday: {}", e)))?
+    .ok_or_else(|| AppError::Conflict(format!("A holiday already exists on {}", payload.holiday_date)))?;
+
+    Ok((StatusCode::CREATED, Json(holiday)))
+}
+
+// List holidays
+pub async fn get_holidays(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let holidays = sqlx::query_as::<_, Holiday>("SELECT * FROM holidays ORDER BY holiday_date")
+        .fetch_all(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": holidays.len(),
+        "holidays": holidays
+    })))
+}
+
+// Remove a holiday
+pub async fn delete_holiday(
+    State(db): State<Arc<PgPool>>,
+    Path(holiday_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM holidays WHERE id = $1")
+        .bind(&holiday_id)
+        .execute(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to delete holiday: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Holiday not found".to_string()));
+    }
+
+    Ok(Json(json!({
+        "message": "Holiday deleted successfully"
+    })))
+}
+
+/// Build (or rebuild) timesheets for a pay period from completed attendance.
+/// Approved timesheets are never overwritten.
+pub async fn generate_timesheets_for_period(
+    db: &PgPool,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    guard_id: Option<&str>,
+) -> AppResult<serde_json::Value> {
+    if period_end < period_start {
+        return Err(AppError::ValidationError("periodEnd must not be before periodStart".to_string()));
+    }
+
+    let rule = active_rate_rule(db).await?;
+    let tz = schedule_timezone();
+
+    let holidays = fetch_holidays(db, period_start, period_end + Duration::days(1)).await?;
+
+    let attendance = sqlx::query_as::<_, WorkedAttendance>(
+        "SELECT a.id, a.guard_id, a.shift_id,
+                (a.check_in_time AT TIME ZONE $3) AS check_in,
+                (a.check_out_time AT TIME ZONE $3) AS check_out
+         FROM attendance a
+         WHERE a.check_out_time IS NOT NULL
+         AND a.check_out_time > a.check_in_time
+         AND (a.check_in_time AT TIME ZONE $3)::date BETWEEN $1 AND $2
+         AND ($4::varchar IS NULL OR a.guard_id = $4)
+         ORDER BY a.guard_id, a.check_in_time",
+    )
+    .bind(period_start)
+    .bind(period_end)
+    .bind(tz)
+    .bind(guard_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch attendance: {}", e)))?;
+
+    let mut by_guard: Vec<(String, Vec<WorkedAttendance>)> = Vec::new();
+    for record in attendance {
+        match by_guard.last_mut() {
+            Some((id, records)) if *id == record.guard_id => records.push(record),
+            _ => by_guard.push((record.guard_id.clone(), vec![record])),
+        }
+    }
+
+    let mut generated = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (guard_id, records) in &by_guard {
+        let mut tx = db
+            .begin()
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+        let existing: Option<(String, String)> = sqlx::query_as(
+            "SELECT id, status FROM timesheets
+             WHERE guard_id = $1 AND period_start = $2 AND period_end = $3
+             FOR UPDATE",
+        )
+        .bind(guard_id)
+        .bind(period_start)
+        .bind(period_end)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+        if let Some((_, status)) = &existing {
+            if status == "approved" {
+                skipped.push(json!({ "guardId": guard_id, "reason": "already approved" }));
+                continue;
+            }
+        }
+
+        let hourly_rate = sqlx::query_scalar::<_, Option<f64>>("SELECT hourly_rate FROM users WHERE id = $1")
+            .bind(guard_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .unwrap_or(rule.base_hourly_rate);
+
+        let mut worked_per_day: HashMap<NaiveDate, f64> = HashMap::new();
+        let mut totals = PayTotals::default();
+        let mut entries = Vec::with_capacity(records.len());
+
+        for record in records {
+            let worked_today = worked_per_day.entry(record.check_in.date()).or_insert(0.0);
+            let entry = compute_entry(&rule, &holidays, record, *worked_today);
+            *worked_today += entry.hours;
+
+            let holiday_multiplier = match entry.holiday_type.as_deref() {
+                Some("special") => rule.special_holiday_multiplier,
+                _ => rule.regular_holiday_multiplier,
+            };
+
+            totals.total_hours += entry.hours;
+            totals.regular_hours += entry.regular;
+            totals.overtime_hours += entry.overtime;
+            totals.night_diff_hours += entry.night_diff;
+            totals.holiday_hours += entry.holiday;
+            totals.regular_pay += entry.regular * hourly_rate;
+            totals.overtime_pay += entry.overtime * hourly_rate * rule.overtime_multiplier;
+            // Night differential and holiday pay are premiums on top of hours already paid above
+            totals.night_diff_pay += entry.night_diff * hourly_rate * rule.night_diff_premium;
+            totals.holiday_pay += entry.holiday * hourly_rate * (holiday_multiplier - 1.0);
+
+            entries.push((record, entry));
+        }
+
+        let gross = totals.regular_pay + totals.overtime_pay + totals.night_diff_pay + totals.holiday_pay;
+
+        let timesheet_id = existing
+            .map(|(id, _)| id)
+            .unwrap_or_else(utils::generate_id);
+
+        sqlx::query("DELETE FROM timesheet_entries WHERE timesheet_id = $1")
+            .bind(&timesheet_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to clear timesheet entries: {}", e)))?;
+
+        let timesheet = sqlx::query_as::<_, Timesheet>(
+            "INSERT INTO timesheets
+             (id, guard_id, period_start, period_end, rate_rule_id, hourly_rate,
+              total_hours, regular_hours, overtime_hours, night_diff_hours, holiday_hours,
+              regular_pay, overtime_pay, night_diff_pay, holiday_pay, gross_pay, status, generated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16,
+                     'pending_approval', CURRENT_TIMESTAMP)
+             ON CONFLICT (id) DO UPDATE SET
+                rate_rule_id = EXCLUDED.rate_rule_id, hourly_rate = EXCLUDED.hourly_rate,
+                total_hours = EXCLUDED.total_hours, regular_hours = EXCLUDED.regular_hours,
+                overtime_hours = EXCLUDED.overtime_hours, night_diff_hours = EXCLUDED.night_diff_hours,
+                holiday_hours = EXCLUDED.holiday_hours, regular_pay = EXCLUDED.regular_pay,
+                overtime_pay = EXCLUDED.overtime_pay, night_diff_pay = EXCLUDED.night_diff_pay,
+                holiday_pay = EXCLUDED.holiday_pay, gross_pay = EXCLUDED.gross_pay,
+                status = 'pending_approval', reviewed_by = NULL, reviewed_at = NULL, review_notes = NULL,
+                generated_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+             RETURNING *",
+        )
+        .bind(&timesheet_id)
+        .bind(guard_id)
+        .bind(period_start)
+        .bind(period_end)
+        .bind(&rule.id)
+        .bind(hourly_rate)
+        .bind(round2(totals.total_hours))
+        .bind(round2(totals.regular_hours))
+        .bind(round2(totals.overtime_hours))
+        .bind(round2(totals.night_diff_hours))
+        .bind(round2(totals.holiday_hours))
+        .bind(round2(totals.regular_pay))
+        .bind(round2(totals.overtime_pay))
+        .bind(round2(totals.night_diff_pay))
+        .bind(round2(totals.holiday_pay))
+        .bind(round2(gross))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save timesheet: {}", e)))?;
+
+        for (record, entry) in &entries {
+            sqlx::query(
+                "INSERT INTO timesheet_entries
+                 (id, timesheet_id, attendance_id, shift_id, work_date, check_in, check_out,
+                  hours, regular_hours, overtime_hours, night_diff_hours, holiday_hours, holiday_type)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+            )
+            .bind(utils::generate_id())
+            .bind(&timesheet.id)
+            .bind(&record.id)
+            .bind(&record.shift_id)
+            .bind(entry.work_date)
+            .bind(record.check_in)
+            .bind(record.check_out)
+            .bind(round2(entry.hours))
+            .bind(round2(entry.regular))
+            .bind(round2(entry.overtime))
+            .bind(round2(entry.night_diff))
+            .bind(round2(entry.holiday))
+            .bind(&entry.holiday_type)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save timesheet entry: {}", e)))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+        generated.push(timesheet);
+    }
+
+    Ok(json!({
+        "periodStart": period_start,
+        "periodEnd": period_end,
+        "rateRuleId": rule.id,
+        "generated": generated.len(),
+        "skipped": skipped,
+        "timesheets": generated
+    }))
+}
+
+// Generate timesheets for a pay period
+pub async fn generate_timesheets(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<GenerateTimesheetsRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let summary = generate_timesheets_for_period(
+        db.as_ref(),
+        payload.period_start,
+        payload.period_end,
+        payload.guard_id.as_deref(),
+    )
+    .await?;
+
+    Ok(Json(summary))
+}
+
+async fn query_timesheets(db: &PgPool, query: &TimesheetQuery) -> AppResult<Vec<Timesheet>> {
+    sqlx::query_as::<_, Timesheet>(
+        "SELECT * FROM timesheets
+         WHERE ($1::date IS NULL OR period_start >= $1)
+         AND ($2::date IS NULL OR period_end <= $2)
+         AND ($3::varchar IS NULL OR status = $3)
+         AND ($4::varchar IS NULL OR guard_id = $4)
+         ORDER BY period_start DESC, guard_id",
+    )
+    .bind(query.period_start)
+    .bind(query.period_end)
+    .bind(&query.status)
+    .bind(&query.guard_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))
+}
+
+// List timesheets, filterable by period, status and guard
+pub async fn get_timesheets(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<TimesheetQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let timesheets = query_timesheets(db.as_ref(), &query).await?;
+
+    Ok(Json(json!({
+        "total": timesheets.len(),
+        "timesheets": timesheets
+    })))
+}
+
+// Get a timesheet with its per-attendance entries
+pub async fn get_timesheet(
+    State(db): State<Arc<PgPool>>,
+    Path(timesheet_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let timesheet = sqlx::query_as::<_, Timesheet>("SELECT * FROM timesheets WHERE id = $1")
+        .bind(&timesheet_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Timesheet not found".to_string()))?;
+
+    let entries = sqlx::query_as::<_, TimesheetEntry>(
+        "SELECT * FROM timesheet_entries WHERE timesheet_id = $1 ORDER BY check_in",
+    )
+    .bind(&timesheet_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "timesheet": timesheet,
+        "entries": entries
+    })))
+}
+
+// Supervisor approves or rejects a timesheet
+pub async fn review_timesheet(
+    State(db): State<Arc<PgPool>>,
+    Path(timesheet_id): Path<String>,
+    Json(payload): Json<ReviewTimesheetRequest>,
+) -> AppResult<Json<Timesheet>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.reviewer_id, "review timesheets").await?;
+
+    let status = if payload.approved { "approved" } else { "rejected" };
+
+    let timesheet = sqlx::query_as::<_, Timesheet>(
+        "UPDATE timesheets
+         SET status = $2, reviewed_by = $3, reviewed_at = CURRENT_TIMESTAMP, review_notes = $4,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND status = 'pending_approval'
+         RETURNING *",
+    )
+    .bind(&timesheet_id)
+    .bind(status)
+    .bind(&payload.reviewer_id)
+    .bind(&payload.notes)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to review timesheet: {}", e)))?;
+
+    let timesheet = match timesheet {
+        Some(timesheet) => timesheet,
+        None => {
+            let current = sqlx::query_scalar::<_, String>("SELECT status FROM timesheets WHERE id = $1")
+                .bind(&timesheet_id)
+                .fetch_optional(db.as_ref())
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+                .ok_or_else(|| AppError::NotFound("Timesheet not found".to_string()))?;
+            return Err(AppError::Conflict(format!("Timesheet is already {}", current)));
+        }
+    };
+
+    notify_user(
+        db.as_ref(),
+        &timesheet.guard_id,
+        if payload.approved { "timesheet_review.approved" } else { "timesheet_review.rejected" },
+        Some(("timesheet", &timesheet.id)),
+        json!({
+            "periodStart": timesheet.period_start.to_string(),
+            "periodEnd": timesheet.period_end.to_string()
+        }),
+    )
+    .await?;
+
+    Ok(Json(timesheet))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ExportRow {
+    #[sqlx(flatten)]
+    timesheet: Timesheet,
+    guard_name: String,
+    guard_email: String,
+    currency: String,
+}
+
+async fn fetch_export_rows(db: &PgPool, query: &TimesheetQuery) -> AppResult<Vec<ExportRow>> {
+    sqlx::query_as::<_, ExportRow>(
+        "SELECT t.*, u.full_name AS guard_name, u.email AS guard_email, r.currency
+         FROM timesheets t
+         JOIN users u ON u.id = t.guard_id
+         JOIN pay_rate_rules r ON r.id = t.rate_rule_id
+         WHERE ($1::date IS NULL OR t.period_start >= $1)
+         AND ($2::date IS NULL OR t.period_end <= $2)
+         AND t.status = COALESCE($3, 'approved')
+         AND ($4::varchar IS NULL OR t.guard_id = $4)
+         ORDER BY t.period_start, u.full_name",
+    )
+    .bind(query.period_start)
+    .bind(query.period_end)
+    .bind(&query.status)
+    .bind(&query.guard_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch timesheets for export: {}", e)))
+}
+
+// Export timesheets as CSV (approved only unless ?status= is given)
+pub async fn export_timesheets_csv(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<TimesheetQuery>,
+) -> AppResult<impl IntoResponse> {
+    let rows = fetch_export_rows(db.as_ref(), &query).await?;
+
+    let mut csv = String::from(
+        "timesheet_id,guard_id,guard_name,period_start,period_end,status,hourly_rate,currency,\
+         total_hours,regular_hours,overtime_hours,night_diff_hours,holiday_hours,\
+         regular_pay,overtime_pay,night_diff_pay,holiday_pay,gross_pay\n",
+    );
+    for row in &rows {
+        let t = &row.timesheet;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{:.2},{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2}\n",
+            t.id,
+            t.guard_id,
+            csv_field(&row.guard_name),
+            t.period_start,
+            t.period_end,
+            t.status,
+            t.hourly_rate,
+            row.currency,
+            t.total_hours,
+            t.regular_hours,
+            t.overtime_hours,
+            t.night_diff_hours,
+            t.holiday_hours,
+            t.regular_pay,
+            t.overtime_pay,
+            t.night_diff_pay,
+            t.holiday_pay,
+            t.gross_pay,
+        ));
+    }
+
+    let filename = format!(
+        "timesheets_{}_{}.csv",
+        query.period_start.map(|d| d.to_string()).unwrap_or_else(|| "all".to_string()),
+        query.period_end.map(|d| d.to_string()).unwrap_or_else(|| "all".to_string()),
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        csv,
+    ))
+}
+
+// Export timesheets in the payroll JSON format (see README "Payroll export format")
+pub async fn export_timesheets_payroll(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<TimesheetQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let rows = fetch_export_rows(db.as_ref(), &query).await?;
+
+    let mut gross_total = 0.0;
+    let employees: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|row| {
+            let t = &row.timesheet;
+            gross_total += t.gross_pay;
+            json!({
+                "timesheetId": t.id,
+                "guardId": t.guard_id,
+                "name": row.guard_name,
+                "email": row.guard_email,
+                "periodStart": t.period_start,
+                "periodEnd": t.period_end,
+                "currency": row.currency,
+                "hourlyRate": t.hourly_rate,
+                "hours": {
+                    "total": t.total_hours,
+                    "regular": t.regular_hours,
+                    "overtime": t.overtime_hours,
+                    "nightDifferential": t.night_diff_hours,
+                    "holiday": t.holiday_hours
+                },
+                "earnings": {
+                    "regular": t.regular_pay,
+                    "overtime": t.overtime_pay,
+                    "nightDifferential": t.night_diff_pay,
+                    "holiday": t.holiday_pay,
+                    "gross": t.gross_pay
+                },
+                "status": t.status,
+                "approvedBy": t.reviewed_by,
+                "approvedAt": t.reviewed_at
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "format": "dasia-payroll",
+        "version": PAYROLL_EXPORT_VERSION,
+        "generatedAt": Utc::now(),
+        "periodStart": query.period_start,
+        "periodEnd": query.period_end,
+        "employeeCount": employees.len(),
+        "grossTotal": round2(gross_total),
+        "employees": employees
+    })))
+}
@@ -45,23 +45,30 @@ pub async fn create_training_record(
     Ok((StatusCode::CREATED, Json(rec)))
 }
 
-/// GET /api/training-records/:guard_id
-pub async fn get_guard_training(
-    State(db): State<Arc<PgPool>>,
-    Path(guard_id): Path<String>,
-) -> AppResult<Json<Vec<TrainingRecord>>> {
-    // Auto-expire any records past their expiry_date first
-    sqlx::query(
+/// Mark every valid training record past its expiry date as expired. Returns the number expired.
+pub async fn expire_training_records(db: &PgPool) -> AppResult<u64> {
+    let result = sqlx::query(
         "UPDATE training_records
          SET status = 'expired', updated_at = NOW()
          WHERE status = 'valid'
            AND expiry_date IS NOT NULL
            AND expiry_date < NOW()",
     )
-    .execute(db.as_ref())
+    .execute(db)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Auto-expire error: {}", e)))?;
 
+    Ok(result.rows_affected())
+}
+
+/// GET /api/training-records/:guard_id
+pub async fn get_guard_training(
+    State(db): State<Arc<PgPool>>,
+    Path(guard_id): Path<String>,
+) -> AppResult<Json<Vec<TrainingRecord>>> {
+    // Auto-expire any records past their expiry_date first
+    expire_training_records(db.as_ref()).await?;
+
     let recs = sqlx::query_as::<_, TrainingRecord>(
         "SELECT * FROM training_records WHERE guard_id = $1 ORDER BY completed_date DESC",
     )
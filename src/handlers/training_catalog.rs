@@ -0,0 +1,824 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Months, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::notifications::notify_user,
+    models::{
+        CreateTrainingCourseRequest, CreateTrainingSessionRequest, EnrollGuardRequest,
+        MarkTrainingAttendanceRequest, TrainingCourse, TrainingEnrollment, TrainingSession,
+        UpdateTrainingCourseRequest,
+    },
+    utils,
+};
+
+/// Expiry of a training completed at `completed` for a course valid `validity_months`.
+fn compute_expiry(completed: DateTime<Utc>, validity_months: Option<i32>) -> Option<DateTime<Utc>> {
+    validity_months
+        .filter(|m| *m > 0)
+        .and_then(|m| completed.checked_add_months(Months::new(m as u32)))
+}
+
+// ========== Course Catalog ==========
+
+/// POST /api/training-courses
+pub async fn create_training_course(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateTrainingCourseRequest>,
+) -> AppResult<(StatusCode, Json<TrainingCourse>)> {
+    if payload.code.is_empty() || payload.name.is_empty() || payload.training_type.is_empty() {
+        return Err(AppError::BadRequest(
+            "Code, name, and training type are required".to_string(),
+        ));
+    }
+    if payload.validity_months.is_some_and(|m| m <= 0) {
+        return Err(AppError::ValidationError(
+            "Validity months must be greater than 0".to_string(),
+        ));
+    }
+
+    let existing = sqlx::query("SELECT id FROM training_courses WHERE code = $1")
+        .bind(&payload.code)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if existing.is_some() {
+        return Err(AppError::Conflict("A course with this code already exists".to_string()));
+    }
+
+    let course = sqlx::query_as::<_, TrainingCourse>(
+        r#"
+        INSERT INTO training_courses
+            (id, code, name, description, training_type, validity_months, duration_hours, is_active)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, true)
+        RETURNING *
+        "#,
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.code)
+    .bind(&payload.name)
+    .bind(payload.description.as_deref())
+    .bind(&payload.training_type)
+    .bind(payload.validity_months)
+    .bind(payload.duration_hours)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create training course: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(course)))
+}
+
+/// GET /api/training-courses
+pub async fn get_training_courses(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let courses = sqlx::query_as::<_, TrainingCourse>(
+        "SELECT * FROM training_courses ORDER BY is_active DESC, name ASC",
+    )
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": courses.len(),
+        "courses": courses
+    })))
+}
+
+/// PUT /api/training-courses/:course_id
+pub async fn update_training_course(
+    State(db): State<Arc<PgPool>>,
+    Path(course_id): Path<String>,
+    Json(payload): Json<UpdateTrainingCourseRequest>,
+) -> AppResult<Json<TrainingCourse>> {
+    if payload.validity_months.is_some_and(|m| m <= 0) {
+        return Err(AppError::ValidationError(
+            "Validity months must be greater than 0".to_string(),
+        ));
+    }
+
+    let course = sqlx::query_as::<_, TrainingCourse>(
+        r#"
+        UPDATE training_courses
+        SET name = COALESCE($1, name),
+            description = COALESCE($2, description),
+            validity_months = COALESCE($3, validity_months),
+            duration_hours = COALESCE($4, duration_hours),
+            is_active = COALESCE($5, is_active),
+            updated_at = NOW()
+        WHERE id = $6
+        RETURNING *
+        "#,
+    )
+    .bind(payload.name.as_deref())
+    .bind(payload.description.as_deref())
+    .bind(payload.validity_months)
+    .bind(payload.duration_hours)
+    .bind(payload.is_active)
+    .bind(&course_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update training course: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Training course not found".to_string()))?;
+
+    Ok(Json(course))
+}
+
+// ========== Sessions ==========
+
+/// POST /api/training-sessions
+pub async fn create_training_session(
+    State(db): State<Arc<PgPool>>,
+    Json(payload): Json<CreateTrainingSessionRequest>,
+) -> AppResult<(StatusCode, Json<TrainingSession>)> {
+    if payload.capacity <= 0 {
+        return Err(AppError::ValidationError("Capacity must be greater than 0".to_string()));
+    }
+    if payload.end_time <= payload.start_time {
+        return Err(AppError::ValidationError("End time must be after start time".to_string()));
+    }
+
+    let active: bool = sqlx::query_scalar("SELECT is_active FROM training_courses WHERE id = $1")
+        .bind(&payload.course_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Training course not found".to_string()))?;
+
+    if !active {
+        return Err(AppError::BadRequest(
+            "Cannot schedule a session for an inactive course".to_string(),
+        ));
+    }
+
+    if let Some(instructor_id) = payload.instructor_id.as_deref() {
+        sqlx::query("SELECT id FROM users WHERE id = $1")
+            .bind(instructor_id)
+            .fetch_optional(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Instructor not found".to_string()))?;
+    }
+
+    let session = sqlx::query_as::<_, TrainingSession>(
+        r#"
+        INSERT INTO training_sessions
+            (id, course_id, instructor_id, instructor_name, location, start_time, end_time, capacity, status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'scheduled')
+        RETURNING *
+        "#,
+    )
+    .bind(utils::generate_id())
+    .bind(&payload.course_id)
+    .bind(payload.instructor_id.as_deref())
+    .bind(payload.instructor_name.as_deref())
+    .bind(payload.location.as_deref())
+    .bind(payload.start_time)
+    .bind(payload.end_time)
+    .bind(payload.capacity)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create training session: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// GET /api/training-sessions  — upcoming and in-progress sessions with seat counts
+pub async fn get_training_sessions(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    #[derive(sqlx::FromRow, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SessionSummary {
+        id: String,
+        course_id: String,
+        course_name: String,
+        training_type: String,
+        instructor_name: Option<String>,
+        location: Option<String>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        capacity: i32,
+        status: String,
+        enrolled_count: i64,
+        waitlist_count: i64,
+    }
+
+    let sessions = sqlx::query_as::<_, SessionSummary>(
+        r#"
+        SELECT ts.id, ts.course_id, tc.name AS course_name, tc.training_type,
+               COALESCE(ts.instructor_name, u.full_name) AS instructor_name,
+               ts.location, ts.start_time, ts.end_time, ts.capacity, ts.status,
+               COUNT(te.id) FILTER (WHERE te.status IN ('enrolled', 'attended')) AS enrolled_count,
+               COUNT(te.id) FILTER (WHERE te.status = 'waitlisted') AS waitlist_count
+        FROM training_sessions ts
+        JOIN training_courses tc ON tc.id = ts.course_id
+        LEFT JOIN users u ON u.id = ts.instructor_id
+        LEFT JOIN training_enrollments te ON te.session_id = ts.id
+        WHERE ts.status = 'scheduled' AND ts.end_time >= NOW()
+        GROUP BY ts.id, tc.name, tc.training_type, u.full_name
+        ORDER BY ts.start_time ASC
+        "#,
+    )
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": sessions.len(),
+        "sessions": sessions
+    })))
+}
+
+/// GET /api/training-sessions/:session_id
+pub async fn get_training_session_details(
+    State(db): State<Arc<PgPool>>,
+    Path(session_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let session = sqlx::query_as::<_, TrainingSession>("SELECT * FROM training_sessions WHERE id = $1")
+        .bind(&session_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Training session not found".to_string()))?;
+
+    let course = sqlx::query_as::<_, TrainingCourse>("SELECT * FROM training_courses WHERE id = $1")
+        .bind(&session.course_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let enrollments = sqlx::query_as::<_, TrainingEnrollment>(
+        r#"
+        SELECT * FROM training_enrollments
+        WHERE session_id = $1
+        ORDER BY CASE status WHEN 'waitlisted' THEN 1 WHEN 'cancelled' THEN 2 ELSE 0 END,
+                 waitlist_position ASC NULLS FIRST, created_at ASC
+        "#,
+    )
+    .bind(&session_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "session": session,
+        "course": course,
+        "total": enrollments.len(),
+        "enrollments": enrollments
+    })))
+}
+
+// ========== Enrolment & Waitlist ==========
+
+/// Enrol a guard in a session, placing them on the waitlist when the session is full.
+/// The session row is locked so concurrent enrolments cannot oversubscribe it.
+pub async fn enroll_in_session(
+    db: &PgPool,
+    session_id: &str,
+    guard_id: &str,
+    source: &str,
+) -> AppResult<TrainingEnrollment> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let session = sqlx::query_as::<_, TrainingSession>(
+        "SELECT * FROM training_sessions WHERE id = $1 FOR UPDATE",
+    )
+    .bind(session_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Training session not found".to_string()))?;
+
+    if session.status != "scheduled" || session.end_time < Utc::now() {
+        return Err(AppError::BadRequest(
+            "Enrolment is closed for this session".to_string(),
+        ));
+    }
+
+    sqlx::query("SELECT id FROM users WHERE id = $1")
+        .bind(guard_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+
+    let existing: Option<String> = sqlx::query_scalar(
+        "SELECT status FROM training_enrollments WHERE session_id = $1 AND guard_id = $2",
+    )
+    .bind(session_id)
+    .bind(guard_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if existing.as_deref().is_some_and(|s| s != "cancelled") {
+        return Err(AppError::Conflict(
+            "Guard is already enrolled or waitlisted for this session".to_string(),
+        ));
+    }
+
+    let (enrolled_count, max_position): (i64, Option<i32>) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE status IN ('enrolled', 'attended')),
+               MAX(waitlist_position) FILTER (WHERE status = 'waitlisted')
+        FROM training_enrollments
+        WHERE session_id = $1
+        "#,
+    )
+    .bind(session_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let (status, waitlist_position) = if enrolled_count < session.capacity as i64 {
+        ("enrolled", None)
+    } else {
+        ("waitlisted", Some(max_position.unwrap_or(0) + 1))
+    };
+
+    // A previously cancelled enrolment is reactivated in place (one row per guard per session)
+    let enrollment = sqlx::query_as::<_, TrainingEnrollment>(
+        r#"
+        INSERT INTO training_enrollments (id, session_id, guard_id, status, waitlist_position, source)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (session_id, guard_id) DO UPDATE
+        SET status = EXCLUDED.status,
+            waitlist_position = EXCLUDED.waitlist_position,
+            source = EXCLUDED.source,
+            attendance_marked_at = NULL,
+            updated_at = NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(utils::generate_id())
+    .bind(session_id)
+    .bind(guard_id)
+    .bind(status)
+    .bind(waitlist_position)
+    .bind(source)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to enrol guard: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit enrolment: {}", e)))?;
+
+    Ok(enrollment)
+}
+
+/// POST /api/training-sessions/:session_id/enroll
+pub async fn enroll_guard(
+    State(db): State<Arc<PgPool>>,
+    Path(session_id): Path<String>,
+    Json(payload): Json<EnrollGuardRequest>,
+) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    if payload.guard_id.is_empty() {
+        return Err(AppError::BadRequest("Guard ID is required".to_string()));
+    }
+
+    let enrollment = enroll_in_session(db.as_ref(), &session_id, &payload.guard_id, "manual").await?;
+
+    let message = if enrollment.status == "waitlisted" {
+        "Session is full; guard added to the waitlist"
+    } else {
+        "Guard enrolled successfully"
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "message": message,
+            "enrollment": enrollment
+        })),
+    ))
+}
+
+/// POST /api/training-sessions/:session_id/cancel-enrollment
+/// Cancels a guard's seat and promotes the next guard on the waitlist.
+pub async fn cancel_enrollment(
+    State(db): State<Arc<PgPool>>,
+    Path(session_id): Path<String>,
+    Json(payload): Json<EnrollGuardRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let course_name: String = sqlx::query_scalar(
+        r#"
+        SELECT tc.name FROM training_sessions ts
+        JOIN training_courses tc ON tc.id = ts.course_id
+        WHERE ts.id = $1
+        FOR UPDATE OF ts
+        "#,
+    )
+    .bind(&session_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Training session not found".to_string()))?;
+
+    let previous_status: String = sqlx::query_scalar(
+        r#"
+        SELECT status FROM training_enrollments
+        WHERE session_id = $1 AND guard_id = $2 AND status IN ('enrolled', 'waitlisted')
+        "#,
+    )
+    .bind(&session_id)
+    .bind(&payload.guard_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("No active enrolment for this guard".to_string()))?;
+
+    sqlx::query(
+        r#"
+        UPDATE training_enrollments
+        SET status = 'cancelled', waitlist_position = NULL, updated_at = NOW()
+        WHERE session_id = $1 AND guard_id = $2
+        "#,
+    )
+    .bind(&session_id)
+    .bind(&payload.guard_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to cancel enrolment: {}", e)))?;
+
+    let mut promoted_guard_id: Option<String> = None;
+    if previous_status == "enrolled" {
+        promoted_guard_id = sqlx::query_scalar(
+            r#"
+            UPDATE training_enrollments
+            SET status = 'enrolled', waitlist_position = NULL, updated_at = NOW()
+            WHERE id = (
+                SELECT id FROM training_enrollments
+                WHERE session_id = $1 AND status = 'waitlisted'
+                ORDER BY waitlist_position ASC, created_at ASC
+                LIMIT 1
+            )
+            RETURNING guard_id
+            "#,
+        )
+        .bind(&session_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to promote waitlist: {}", e)))?;
+
+        if let Some(guard_id) = promoted_guard_id.as_deref() {
+            notify_user(
+                &mut *tx,
+                guard_id,
+                "training.seat_confirmed",
+                Some(("training_session", &session_id)),
+                json!({ "courseName": course_name }),
+            )
+            .await?;
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit cancellation: {}", e)))?;
+
+    Ok(Json(json!({
+        "message": "Enrolment cancelled",
+        "promotedGuardId": promoted_guard_id
+    })))
+}
+
+/// POST /api/training-sessions/:session_id/attendance
+pub async fn mark_attendance(
+    State(db): State<Arc<PgPool>>,
+    Path(session_id): Path<String>,
+    Json(payload): Json<MarkTrainingAttendanceRequest>,
+) -> AppResult<Json<TrainingEnrollment>> {
+    let session_status: String = sqlx::query_scalar("SELECT status FROM training_sessions WHERE id = $1")
+        .bind(&session_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Training session not found".to_string()))?;
+
+    if session_status != "scheduled" {
+        return Err(AppError::BadRequest(format!(
+            "Attendance cannot be changed for a {} session",
+            session_status
+        )));
+    }
+
+    let status = if payload.attended { "attended" } else { "absent" };
+
+    let enrollment = sqlx::query_as::<_, TrainingEnrollment>(
+        r#"
+        UPDATE training_enrollments
+        SET status = $1, attendance_marked_at = NOW(), updated_at = NOW()
+        WHERE session_id = $2 AND guard_id = $3
+          AND status IN ('enrolled', 'attended', 'absent')
+        RETURNING *
+        "#,
+    )
+    .bind(status)
+    .bind(&session_id)
+    .bind(&payload.guard_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to mark attendance: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Guard is not enrolled in this session".to_string()))?;
+
+    Ok(Json(enrollment))
+}
+
+/// POST /api/training-sessions/:session_id/complete
+/// Closes the session and issues training_records for every guard marked as attended.
+pub async fn complete_training_session(
+    State(db): State<Arc<PgPool>>,
+    Path(session_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let session = sqlx::query_as::<_, TrainingSession>(
+        "SELECT * FROM training_sessions WHERE id = $1 FOR UPDATE",
+    )
+    .bind(&session_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Training session not found".to_string()))?;
+
+    if session.status != "scheduled" {
+        return Err(AppError::Conflict(format!(
+            "Session is already {}",
+            session.status
+        )));
+    }
+
+    let course = sqlx::query_as::<_, TrainingCourse>("SELECT * FROM training_courses WHERE id = $1")
+        .bind(&session.course_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let attendees: Vec<(String, String)> = sqlx::query_as(
+        "SELECT id, guard_id FROM training_enrollments WHERE session_id = $1 AND status = 'attended'",
+    )
+    .bind(&session_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let completed_date = session.end_time;
+    let expiry_date = compute_expiry(completed_date, course.validity_months);
+    let mut issued = Vec::new();
+
+    for (enrollment_id, guard_id) in &attendees {
+        let record_id = utils::generate_id();
+        sqlx::query(
+            r#"
+            INSERT INTO training_records
+                (id, guard_id, training_type, completed_date, expiry_date,
+                 certificate_number, status, notes)
+            VALUES ($1, $2, $3, $4, $5, $6, 'valid', $7)
+            "#,
+        )
+        .bind(&record_id)
+        .bind(guard_id)
+        .bind(&course.training_type)
+        .bind(completed_date)
+        .bind(expiry_date)
+        .bind(format!("{}-{}", course.code, record_id.split('-').next().unwrap_or("0")))
+        .bind(format!("Completed {} (session {})", course.name, session.id))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to create training record: {}", e)))?;
+
+        sqlx::query(
+            "UPDATE training_enrollments SET status = 'completed', training_record_id = $1, updated_at = NOW() WHERE id = $2",
+        )
+        .bind(&record_id)
+        .bind(enrollment_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update enrolment: {}", e)))?;
+
+        issued.push(json!({
+            "guardId": guard_id,
+            "trainingRecordId": record_id
+        }));
+    }
+
+    // Anyone still merely enrolled never had attendance marked; waitlisted guards never got a seat
+    let absent = sqlx::query(
+        "UPDATE training_enrollments SET status = 'absent', updated_at = NOW() WHERE session_id = $1 AND status = 'enrolled'",
+    )
+    .bind(&session_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update enrolments: {}", e)))?
+    .rows_affected();
+
+    sqlx::query(
+        "UPDATE training_enrollments SET status = 'cancelled', waitlist_position = NULL, updated_at = NOW() WHERE session_id = $1 AND status = 'waitlisted'",
+    )
+    .bind(&session_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update enrolments: {}", e)))?;
+
+    sqlx::query(
+        "UPDATE training_sessions SET status = 'completed', completed_at = NOW(), updated_at = NOW() WHERE id = $1",
+    )
+    .bind(&session_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to complete session: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit session completion: {}", e)))?;
+
+    Ok(Json(json!({
+        "message": "Training session completed",
+        "expiryDate": expiry_date,
+        "recordsIssued": issued.len(),
+        "absentCount": absent,
+        "records": issued
+    })))
+}
+
+// ========== Renewals ==========
+
+/// Auto-enrol guards whose training expires within 30 days into the next
+/// session with a free seat, or notify them when no such session exists.
+pub async fn process_training_renewals(db: &PgPool) -> AppResult<serde_json::Value> {
+    #[derive(sqlx::FromRow)]
+    struct ExpiringTraining {
+        guard_id: String,
+        training_type: String,
+        expiry_date: DateTime<Utc>,
+    }
+
+    // Only the guard's latest valid record per type counts (a non-expiring record
+    // means nothing is due), and guards already
+    // holding a seat (or waitlist spot) in an upcoming session are skipped.
+    let expiring = sqlx::query_as::<_, ExpiringTraining>(
+        r#"
+        SELECT tr.guard_id, tr.training_type, MAX(tr.expiry_date) AS expiry_date
+        FROM training_records tr
+        WHERE tr.status = 'valid'
+        GROUP BY tr.guard_id, tr.training_type
+        HAVING NOT bool_or(tr.expiry_date IS NULL)
+           AND MAX(tr.expiry_date) BETWEEN NOW() AND NOW() + INTERVAL '30 days'
+           AND NOT EXISTS (
+               SELECT 1 FROM training_enrollments te
+               JOIN training_sessions ts ON ts.id = te.session_id
+               JOIN training_courses tc ON tc.id = ts.course_id
+               WHERE te.guard_id = tr.guard_id
+                 AND tc.training_type = tr.training_type
+                 AND ts.status = 'scheduled'
+                 AND ts.start_time > NOW()
+                 AND te.status IN ('enrolled', 'waitlisted')
+           )
+        "#,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to query expiring training: {}", e)))?;
+
+    let mut enrolled = Vec::new();
+    let mut notified = Vec::new();
+
+    for item in &expiring {
+        let session: Option<(String, String, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT ts.id, tc.name, ts.start_time
+            FROM training_sessions ts
+            JOIN training_courses tc ON tc.id = ts.course_id
+            WHERE tc.training_type = $1
+              AND tc.is_active = true
+              AND ts.status = 'scheduled'
+              AND ts.start_time > NOW()
+              AND ts.capacity > (
+                  SELECT COUNT(*) FROM training_enrollments te
+                  WHERE te.session_id = ts.id AND te.status IN ('enrolled', 'attended')
+              )
+            ORDER BY ts.start_time ASC
+            LIMIT 1
+            "#,
+        )
+        .bind(&item.training_type)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to find renewal session: {}", e)))?;
+
+        if let Some((session_id, course_name, start_time)) = session {
+            match enroll_in_session(db, &session_id, &item.guard_id, "auto_renewal").await {
+                Ok(enrollment) => {
+                    notify_user(
+                        db,
+                        &item.guard_id,
+                        if enrollment.status == "waitlisted" {
+                            "training.renewal_waitlisted"
+                        } else {
+                            "training.renewal_enrolled"
+                        },
+                        Some(("training_session", &session_id)),
+                        json!({
+                            "trainingType": item.training_type,
+                            "expiryDate": item.expiry_date.format("%Y-%m-%d").to_string(),
+                            "courseName": course_name,
+                            "startTime": start_time.format("%Y-%m-%d %I:%M %p").to_string()
+                        }),
+                    )
+                    .await?;
+
+                    enrolled.push(json!({
+                        "guardId": item.guard_id,
+                        "trainingType": item.training_type,
+                        "sessionId": session_id,
+                        "status": enrollment.status
+                    }));
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Auto-enrolment of guard {} in session {} failed: {}",
+                        item.guard_id, session_id, e
+                    );
+                }
+            }
+        }
+
+        // No seat available: remind the guard, at most once a week per training type
+        let recently_notified = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM notifications
+                WHERE user_id = $1 AND template_key = 'training_expiring'
+                  AND entity_type = 'training_type' AND entity_id = $2
+                  AND updated_at > NOW() - INTERVAL '7 days'
+            )
+            "#,
+        )
+        .bind(&item.guard_id)
+        .bind(&item.training_type)
+        .fetch_one(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+        if !recently_notified {
+            notify_user(
+                db,
+                &item.guard_id,
+                "training_expiring",
+                Some(("training_type", &item.training_type)),
+                json!({
+                    "trainingType": item.training_type,
+                    "expiryDate": item.expiry_date.format("%Y-%m-%d").to_string()
+                }),
+            )
+            .await?;
+
+            notified.push(json!({
+                "guardId": item.guard_id,
+                "trainingType": item.training_type,
+                "expiryDate": item.expiry_date
+            }));
+        }
+    }
+
+    tracing::info!(
+        "Training renewals processed: {} expiring, {} auto-enrolled, {} notified",
+        expiring.len(), enrolled.len(), notified.len()
+    );
+
+    Ok(json!({
+        "expiringCount": expiring.len(),
+        "autoEnrolled": enrolled,
+        "notified": notified
+    }))
+}
+
+/// POST /api/training-sessions/process-renewals
+pub async fn run_training_renewals(
+    State(db): State<Arc<PgPool>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let summary = process_training_renewals(db.as_ref()).await?;
+    Ok(Json(summary))
+}
@@ -0,0 +1,305 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::driver_qualifications,
+    models::{CrewEscortInput, SetTripCrewRequest, TripCrewMember},
+    utils,
+};
+
+/// Seats assumed for a car with no `passenger_capacity` (the column default).
+const DEFAULT_PASSENGER_CAPACITY: i32 = 4;
+
+const CREW_SQL: &str = r#"
+    SELECT tc.id, tc.trip_id, tc.user_id, u.full_name, tc.crew_role, tc.firearm_allocation_id,
+           fa.firearm_id, f.serial_number AS firearm_serial_number, tc.created_at
+    FROM trip_crew tc
+    JOIN users u ON u.id = tc.user_id
+    LEFT JOIN firearm_allocations fa ON fa.id = tc.firearm_allocation_id
+    LEFT JOIN firearms f ON f.id = fa.firearm_id
+    WHERE tc.trip_id = $1
+    ORDER BY CASE tc.crew_role WHEN 'driver' THEN 0 WHEN 'custodian' THEN 1 ELSE 2 END, u.full_name
+"#;
+
+/// Who rides on a trip: one driver, any escorts with the weapons they carry, and at most one
+/// custodian responsible for the cargo.
+pub struct CrewManifest<'a> {
+    pub driver_id: &'a str,
+    pub escorts: &'a [CrewEscortInput],
+    pub custodian_id: Option<&'a str>,
+}
+
+impl CrewManifest<'_> {
+    fn member_ids(&self) -> Vec<&str> {
+        std::iter::once(self.driver_id)
+            .chain(self.escorts.iter().map(|escort| escort.user_id.as_str()))
+            .chain(self.custodian_id)
+            .collect()
+    }
+}
+
+/// Reject a driver who may not drive this car on `trip_id` (None for a trip not yet created):
+/// not licensed and certified for it, not assigned to the car, or short of rest.
+pub async fn ensure_driver_qualified(
+    conn: &mut PgConnection,
+    driver_id: &str,
+    car_id: &str,
+    trip_id: Option<&str>,
+) -> AppResult<()> {
+    driver_qualifications::ensure_licensed(conn, driver_id, car_id).await?;
+
+    let (name, assigned): (String, bool) = sqlx::query_as(
+        "SELECT u.full_name,
+                EXISTS(SELECT 1 FROM driver_assignments WHERE car_id = $2 AND guard_id = u.id AND status = 'active')
+         FROM users u WHERE u.id = $1",
+    )
+    .bind(driver_id)
+    .bind(car_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    if !assigned {
+        return Err(AppError::Conflict(format!("{} is not an assigned driver of this car", name)));
+    }
+
+    driver_qualifications::ensure_rested(conn, driver_id, trip_id).await
+}
+
+/// Check a manifest for `trip_id` (None for a trip not yet created) against the car's seats, the
+/// driver's qualifications and each escort's firearm allocation.
+pub async fn validate_crew(
+    conn: &mut PgConnection,
+    car_id: &str,
+    trip_id: Option<&str>,
+    crew: &CrewManifest<'_>,
+) -> AppResult<()> {
+    let members = crew.member_ids();
+    let unique: HashSet<&str> = members.iter().copied().collect();
+    if unique.len() != members.len() {
+        return Err(AppError::ValidationError("A person can hold only one place on the crew".to_string()));
+    }
+
+    let (plate, capacity): (String, Option<i32>) =
+        sqlx::query_as("SELECT license_plate, passenger_capacity FROM armored_cars WHERE id = $1")
+            .bind(car_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Armored car not found".to_string()))?;
+    let capacity = capacity.unwrap_or(DEFAULT_PASSENGER_CAPACITY);
+    if members.len() > capacity as usize {
+        return Err(AppError::ValidationError(format!(
+            "A crew of {} does not fit the {} seats in {}",
+            members.len(),
+            capacity,
+            plate
+        )));
+    }
+
+    let missing = sqlx::query_scalar::<_, String>(
+        "SELECT m.member_id FROM unnest($1::varchar[]) AS m(member_id)
+         WHERE NOT EXISTS(SELECT 1 FROM users u WHERE u.id = m.member_id)",
+    )
+    .bind(&members)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    if !missing.is_empty() {
+        return Err(AppError::NotFound(format!("Crew member not found: {}", missing.join(", "))));
+    }
+
+    ensure_driver_qualified(conn, crew.driver_id, car_id, trip_id).await?;
+
+    for escort in crew.escorts {
+        let Some(allocation_id) = &escort.firearm_allocation_id else { continue };
+        let held = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM firearm_allocations WHERE id = $1 AND guard_id = $2 AND status = 'active')",
+        )
+        .bind(allocation_id)
+        .bind(&escort.user_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+        if !held {
+            return Err(AppError::ValidationError(format!(
+                "Firearm allocation {} is not an active allocation to escort {}",
+                allocation_id, escort.user_id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace a trip's manifest. Validate it first with [`validate_crew`].
+pub async fn write_crew(conn: &mut PgConnection, trip_id: &str, crew: &CrewManifest<'_>) -> AppResult<()> {
+    sqlx::query("DELETE FROM trip_crew WHERE trip_id = $1")
+        .bind(trip_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to clear trip crew: {}", e)))?;
+
+    let rows = std::iter::once((crew.driver_id, "driver", None))
+        .chain(
+            crew.escorts
+                .iter()
+                .map(|escort| (escort.user_id.as_str(), "escort", escort.firearm_allocation_id.as_deref())),
+        )
+        .chain(crew.custodian_id.map(|id| (id, "custodian", None)));
+
+    for (user_id, role, allocation_id) in rows {
+        sqlx::query(
+            "INSERT INTO trip_crew (id, trip_id, user_id, crew_role, firearm_allocation_id) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(utils::generate_id())
+        .bind(trip_id)
+        .bind(user_id)
+        .bind(role)
+        .bind(allocation_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save trip crew: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// Validate a trip's manifest again and freeze it as the trip starts. A trip with no manifest
+/// gets one holding just its driver. Does nothing if the manifest is already frozen.
+pub async fn freeze_crew(conn: &mut PgConnection, trip_id: &str) -> AppResult<()> {
+    let (car_id, driver_id, frozen_at): (String, String, Option<DateTime<Utc>>) =
+        sqlx::query_as("SELECT car_id, driver_id, crew_frozen_at FROM trips WHERE id = $1 FOR UPDATE")
+            .bind(trip_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+    if frozen_at.is_some() {
+        return Ok(());
+    }
+
+    let rows: Vec<(String, String, Option<String>)> =
+        sqlx::query_as("SELECT user_id, crew_role, firearm_allocation_id FROM trip_crew WHERE trip_id = $1")
+            .bind(trip_id)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let driver_id = rows
+        .iter()
+        .find(|(_, role, _)| role == "driver")
+        .map_or(driver_id, |(user_id, _, _)| user_id.clone());
+    let escorts: Vec<CrewEscortInput> = rows
+        .iter()
+        .filter(|(_, role, _)| role == "escort")
+        .map(|(user_id, _, allocation_id)| CrewEscortInput {
+            user_id: user_id.clone(),
+            firearm_allocation_id: allocation_id.clone(),
+        })
+        .collect();
+    let custodian_id = rows.iter().find(|(_, role, _)| role == "custodian").map(|(user_id, _, _)| user_id.as_str());
+
+    let crew = CrewManifest { driver_id: &driver_id, escorts: &escorts, custodian_id };
+    validate_crew(conn, &car_id, Some(trip_id), &crew).await?;
+    write_crew(conn, trip_id, &crew).await?;
+
+    sqlx::query("UPDATE trips SET crew_frozen_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(trip_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to freeze trip crew: {}", e)))?;
+
+    Ok(())
+}
+
+/// A trip's manifest, driver first.
+pub async fn crew_members(db: &PgPool, trip_id: &str) -> AppResult<Vec<TripCrewMember>> {
+    sqlx::query_as::<_, TripCrewMember>(CREW_SQL)
+        .bind(trip_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to fetch trip crew: {}", e)))
+}
+
+/// PUT /api/trips/:trip_id/crew  — replace the manifest of a trip that has not started
+pub async fn set_trip_crew(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+    Json(payload): Json<SetTripCrewRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.updated_by, "set a trip's crew").await?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let (car_id, status, frozen_at): (String, String, Option<DateTime<Utc>>) =
+        sqlx::query_as("SELECT car_id, status, crew_frozen_at FROM trips WHERE id = $1 FOR UPDATE")
+            .bind(&trip_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+
+    if frozen_at.is_some() || matches!(status.as_str(), "completed" | "cancelled") {
+        return Err(AppError::Conflict("The crew of a trip cannot change once it has started".to_string()));
+    }
+
+    let crew = CrewManifest {
+        driver_id: &payload.driver_id,
+        escorts: &payload.escorts,
+        custodian_id: payload.custodian_id.as_deref(),
+    };
+    validate_crew(&mut tx, &car_id, Some(&trip_id), &crew).await?;
+    write_crew(&mut tx, &trip_id, &crew).await?;
+
+    sqlx::query("UPDATE trips SET driver_id = $1, crew_updated_by = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $3")
+        .bind(&payload.driver_id)
+        .bind(&payload.updated_by)
+        .bind(&trip_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update trip: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    let crew = crew_members(db.as_ref(), &trip_id).await?;
+    Ok(Json(json!({
+        "tripId": trip_id,
+        "total": crew.len(),
+        "crew": crew
+    })))
+}
+
+/// GET /api/trips/:trip_id/crew
+pub async fn get_trip_crew(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let frozen_at = sqlx::query_scalar::<_, Option<DateTime<Utc>>>("SELECT crew_frozen_at FROM trips WHERE id = $1")
+        .bind(&trip_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+
+    let crew = crew_members(db.as_ref(), &trip_id).await?;
+    Ok(Json(json!({
+        "tripId": trip_id,
+        "frozenAt": frozen_at,
+        "total": crew.len(),
+        "crew": crew
+    })))
+}
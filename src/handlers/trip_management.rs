@@ -3,16 +3,75 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
+    handlers::{trip_crew, vehicle_compliance, vehicle_status},
     utils,
 };
 
+/// Every status a trip may move to from each status through a status update. Completed and
+/// cancelled trips are final.
+const TRIP_TRANSITIONS: &[(&str, &[&str])] = &[
+    ("scheduled", &["in_progress", "in_transit", "cancelled"]),
+    ("in_progress", &["completed", "cancelled"]),
+    ("in_transit", &["completed", "cancelled"]),
+    ("completed", &[]),
+    ("cancelled", &[]),
+];
+
+/// Whether a status update may move a trip from `from` to `to`.
+pub fn can_transition_trip(from: &str, to: &str) -> bool {
+    TRIP_TRANSITIONS
+        .iter()
+        .any(|(status, next)| *status == from && next.contains(&to))
+}
+
+/// Statuses a trip may move to `to` from.
+fn trip_statuses_into(to: &str) -> Vec<&'static str> {
+    TRIP_TRANSITIONS
+        .iter()
+        .map(|(status, _)| *status)
+        .filter(|status| can_transition_trip(status, to))
+        .collect()
+}
+
+/// Move a trip to a new status if the transition table allows it, stamping its end time once it
+/// is over. Returns the trip's car and driver.
+pub async fn set_trip_status(conn: &mut PgConnection, trip_id: &str, to: &str) -> AppResult<(String, String)> {
+    if !TRIP_TRANSITIONS.iter().any(|(status, _)| *status == to) {
+        return Err(AppError::ValidationError(format!("Unknown trip status '{}'", to)));
+    }
+
+    let updated = sqlx::query_as::<_, (String, String)>(
+        "UPDATE trips SET status = $1,
+                end_time = CASE WHEN $1 IN ('completed', 'cancelled') THEN COALESCE(end_time, CURRENT_TIMESTAMP) ELSE end_time END,
+                updated_at = CURRENT_TIMESTAMP
+         WHERE id = $2 AND status = ANY($3) RETURNING car_id, driver_id"
+    )
+    .bind(to)
+    .bind(trip_id)
+    .bind(trip_statuses_into(to))
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to update trip: {}", e)))?;
+    if let Some(updated) = updated {
+        return Ok(updated);
+    }
+
+    let from = sqlx::query_scalar::<_, String>("SELECT status FROM trips WHERE id = $1")
+        .bind(trip_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+    Err(AppError::Conflict(format!("Trip cannot go from {} to {}", from, to)))
+}
+
 #[derive(Debug, Serialize, sqlx::FromRow)]
 pub struct TripDetails {
     pub id: String,
@@ -44,6 +103,7 @@ pub struct GuardInfo {
     pub id: String,
     pub name: Option<String>,
     pub username: String,
+    pub crew_role: Option<String>,
 }
 
 // Get all active trips with details
@@ -93,13 +153,15 @@ pub async fn get_trip_details(
     .map_err(|e| AppError::DatabaseError(format!("Failed to fetch trip: {}", e)))?
     .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
 
-    // Get assigned guards for this trip
+    // Guards and firearms come from the trip's crew manifest
+    let crew = trip_crew::crew_members(db.as_ref(), &trip_id).await?;
+
     let guards = sqlx::query_as::<_, GuardInfo>(
-        "SELECT DISTINCT u.id, u.full_name as name, u.username
-         FROM users u
-         JOIN shifts s ON u.id = s.guard_id
-         JOIN trips t ON DATE(s.start_time) = DATE(t.start_time)
-         WHERE t.id = $1 AND s.status IN ('scheduled', 'in_progress')"
+        "SELECT u.id, u.full_name as name, u.username, tc.crew_role
+         FROM trip_crew tc
+         JOIN users u ON u.id = tc.user_id
+         WHERE tc.trip_id = $1
+         ORDER BY CASE tc.crew_role WHEN 'driver' THEN 0 WHEN 'custodian' THEN 1 ELSE 2 END, u.full_name"
     )
     .bind(&trip_id)
     .fetch_all(db.as_ref())
@@ -116,12 +178,11 @@ pub async fn get_trip_details(
     }
 
     let firearms = sqlx::query_as::<_, FirearmInfo>(
-        "SELECT DISTINCT f.id, f.name, f.model, f.serial_number
-         FROM firearms f
-         JOIN firearm_allocations fa ON f.id = fa.firearm_id
-         JOIN shifts s ON fa.guard_id = s.guard_id
-         JOIN trips t ON DATE(s.start_time) = DATE(t.start_time)
-         WHERE t.id = $1 AND fa.status = 'active'"
+        "SELECT f.id, f.name, f.model, f.serial_number
+         FROM trip_crew tc
+         JOIN firearm_allocations fa ON fa.id = tc.firearm_allocation_id
+         JOIN firearms f ON f.id = fa.firearm_id
+         WHERE tc.trip_id = $1"
     )
     .bind(&trip_id)
     .fetch_all(db.as_ref())
@@ -130,6 +191,7 @@ pub async fn get_trip_details(
 
     Ok(Json(json!({
         "trip": trip,
+        "crew": crew,
         "guards": guards,
         "firearms": firearms,
         "guard_count": guards.len(),
@@ -142,29 +204,55 @@ pub async fn assign_driver_to_trip(
     State(db): State<Arc<PgPool>>,
     Json(payload): Json<AssignDriverRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify driver exists and is verified
-    let driver_exists = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM users WHERE id = $1 AND verified = true)"
-    )
-    .bind(&payload.driver_id)
-    .fetch_one(db.as_ref())
-    .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to verify driver: {}", e)))?;
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
 
-    if !driver_exists {
-        return Err(AppError::BadRequest("Driver not found or not verified".to_string()));
+    let (car_id, frozen_at): (String, Option<chrono::DateTime<chrono::Utc>>) =
+        sqlx::query_as("SELECT car_id, crew_frozen_at FROM trips WHERE id = $1 FOR UPDATE")
+            .bind(&payload.trip_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+            .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+
+    if frozen_at.is_some() {
+        return Err(AppError::Conflict("The crew of a trip cannot change once it has started".to_string()));
     }
 
+    trip_crew::ensure_driver_qualified(&mut tx, &payload.driver_id, &car_id, Some(&payload.trip_id)).await?;
+
+    // The new driver takes the driver's place in the manifest, leaving any other place they held
+    sqlx::query("DELETE FROM trip_crew WHERE trip_id = $1 AND (crew_role = 'driver' OR user_id = $2)")
+        .bind(&payload.trip_id)
+        .bind(&payload.driver_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update trip crew: {}", e)))?;
+
+    sqlx::query("INSERT INTO trip_crew (id, trip_id, user_id, crew_role) VALUES ($1, $2, $3, 'driver')")
+        .bind(utils::generate_id())
+        .bind(&payload.trip_id)
+        .bind(&payload.driver_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update trip crew: {}", e)))?;
+
     // Update trip with driver
     sqlx::query(
         "UPDATE trips SET driver_id = $1 WHERE id = $2"
     )
     .bind(&payload.driver_id)
     .bind(&payload.trip_id)
-    .execute(db.as_ref())
+    .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(format!("Failed to assign driver: {}", e)))?;
 
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
     Ok(Json(json!({
         "success": true,
         "message": "Driver assigned successfully"
@@ -177,35 +265,30 @@ pub async fn update_trip_status(
     Path(trip_id): Path<String>,
     Json(payload): Json<UpdateTripStatusRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
-    sqlx::query(
-        "UPDATE trips SET status = $1 WHERE id = $2"
-    )
-    .bind(&payload.status)
-    .bind(&trip_id)
-    .execute(db.as_ref())
-    .await
-    .map_err(|e| AppError::DatabaseError(format!("Failed to update trip: {}", e)))?;
-
-    // If completed, update vehicle status
-    if payload.status == "completed" {
-        sqlx::query(
-            "UPDATE armored_cars SET status = 'operational' 
-             WHERE id IN (SELECT car_id FROM trips WHERE id = $1)"
-        )
-        .bind(&trip_id)
-        .execute(db.as_ref())
-        .await
-        .ok();
-    } else if payload.status == "in_progress" {
-        sqlx::query(
-            "UPDATE armored_cars SET status = 'deployed' 
-             WHERE id IN (SELECT car_id FROM trips WHERE id = $1)"
-        )
-        .bind(&trip_id)
-        .execute(db.as_ref())
+    let mut tx = db
+        .begin()
         .await
-        .ok();
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let (car_id, driver_id) = set_trip_status(&mut tx, &trip_id, &payload.status).await?;
+
+    // The vehicle follows its trip: deployed while under way, released once it is over
+    if matches!(payload.status.as_str(), "in_progress" | "in_transit") {
+        trip_crew::freeze_crew(&mut tx, &trip_id).await?;
     }
+    let overridden = vehicle_status::follow_trip(&mut tx, &car_id, &trip_id, &payload.status).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    vehicle_compliance::record_override_use(
+        db.as_ref(),
+        &overridden,
+        Some(&driver_id),
+        &format!("Dispatched on trip {}", trip_id),
+    )
+    .await?;
 
     Ok(Json(json!({
         "success": true,
@@ -255,3 +338,49 @@ pub struct AssignDriverRequest {
 pub struct UpdateTripStatusRequest {
     pub status: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_scheduled_trip_starts_or_is_cancelled() {
+        assert!(can_transition_trip("scheduled", "in_progress"));
+        assert!(can_transition_trip("scheduled", "in_transit"));
+        assert!(can_transition_trip("scheduled", "cancelled"));
+        assert!(!can_transition_trip("scheduled", "completed"));
+    }
+
+    #[test]
+    fn a_trip_under_way_completes_or_is_cancelled() {
+        for from in ["in_progress", "in_transit"] {
+            assert!(can_transition_trip(from, "completed"), "{}", from);
+            assert!(can_transition_trip(from, "cancelled"), "{}", from);
+            assert!(!can_transition_trip(from, "scheduled"), "{}", from);
+        }
+    }
+
+    #[test]
+    fn completed_and_cancelled_trips_are_final() {
+        for from in ["completed", "cancelled"] {
+            for (to, _) in TRIP_TRANSITIONS {
+                assert!(!can_transition_trip(from, to), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn no_status_moves_to_itself_or_to_an_unknown_status() {
+        for (status, _) in TRIP_TRANSITIONS {
+            assert!(!can_transition_trip(status, status), "{}", status);
+            assert!(!can_transition_trip(status, "in_transti"), "{}", status);
+        }
+    }
+
+    #[test]
+    fn statuses_into_follow_the_table() {
+        assert_eq!(trip_statuses_into("completed"), vec!["in_progress", "in_transit"]);
+        assert_eq!(trip_statuses_into("in_transit"), vec!["scheduled"]);
+        assert!(trip_statuses_into("scheduled").is_empty());
+    }
+}
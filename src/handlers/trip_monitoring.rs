@@ -0,0 +1,688 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::notifications::notify_user,
+    models::{
+        AcknowledgeTripAlertRequest, ReviewTripAlertRequest, RouteWaypoint, SetTripRouteRequest, TripAlert,
+        TripAlertQuery, TripAllowedStop, TripRoute,
+    },
+    utils,
+};
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+/// Points within this distance of the latest one count as the vehicle standing still.
+const STOP_RADIUS_M: f64 = 50.0;
+/// How far back a stop is traced.
+const STOP_LOOKBACK_HOURS: i64 = 6;
+/// Minutes past the planned arrival before a trip is reported late.
+const LATE_GRACE_MINUTES: i64 = 10;
+
+fn haversine_m(a: RouteWaypoint, b: RouteWaypoint) -> f64 {
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let h = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((b.lon - a.lon).to_radians() / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Shortest distance in metres from a point to the planned route. Each segment is measured on a
+/// flat projection around the point, which is accurate at corridor scale.
+fn distance_to_route_m(point: RouteWaypoint, route: &[RouteWaypoint]) -> f64 {
+    let scale_x = point.lat.to_radians().cos() * EARTH_RADIUS_M;
+    let project = |w: RouteWaypoint| {
+        (
+            (w.lon - point.lon).to_radians() * scale_x,
+            (w.lat - point.lat).to_radians() * EARTH_RADIUS_M,
+        )
+    };
+
+    match route {
+        [] => f64::INFINITY,
+        [only] => haversine_m(point, *only),
+        _ => route
+            .windows(2)
+            .map(|segment| {
+                let (ax, ay) = project(segment[0]);
+                let (bx, by) = project(segment[1]);
+                let (dx, dy) = (bx - ax, by - ay);
+                let length_sq = dx * dx + dy * dy;
+                let t = if length_sq == 0.0 {
+                    0.0
+                } else {
+                    (-(ax * dx + ay * dy) / length_sq).clamp(0.0, 1.0)
+                };
+                (ax + t * dx).hypot(ay + t * dy)
+            })
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+fn valid_coordinates(lat: f64, lon: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)
+}
+
+async fn route_response(db: &PgPool, trip_id: &str) -> AppResult<serde_json::Value> {
+    let route = sqlx::query_as::<_, TripRoute>("SELECT * FROM trip_routes WHERE trip_id = $1")
+        .bind(trip_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("No route planned for this trip".to_string()))?;
+
+    let stops = sqlx::query_as::<_, TripAllowedStop>(
+        "SELECT id, trip_id, name, latitude, longitude, radius_meters, max_minutes
+         FROM trip_allowed_stops WHERE trip_id = $1 ORDER BY created_at, name",
+    )
+    .bind(trip_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(json!({
+        "route": route,
+        "waypoints": route.waypoints(),
+        "allowedStops": stops
+    }))
+}
+
+// Plan (or re-plan) a trip's route, corridor, stop limit, arrival time and allowed stops
+pub async fn set_trip_route(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+    Json(payload): Json<SetTripRouteRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    if payload.waypoints.len() < 2 {
+        return Err(AppError::ValidationError("A route needs at least two waypoints".to_string()));
+    }
+    if payload.waypoints.iter().any(|w| !valid_coordinates(w.lat, w.lon)) {
+        return Err(AppError::ValidationError("Waypoint lat/lon out of range".to_string()));
+    }
+    let corridor = payload.corridor_meters.unwrap_or(500.0);
+    let max_stop_minutes = payload.max_stop_minutes.unwrap_or(10);
+    let arrival_radius = payload.arrival_radius_meters.unwrap_or(200.0);
+    if !(50.0..=5000.0).contains(&corridor) {
+        return Err(AppError::ValidationError("corridorMeters must be between 50 and 5000".to_string()));
+    }
+    if !(1..=240).contains(&max_stop_minutes) {
+        return Err(AppError::ValidationError("maxStopMinutes must be between 1 and 240".to_string()));
+    }
+    if !(20.0..=2000.0).contains(&arrival_radius) {
+        return Err(AppError::ValidationError("arrivalRadiusMeters must be between 20 and 2000".to_string()));
+    }
+    for stop in &payload.allowed_stops {
+        if stop.name.trim().is_empty() || !valid_coordinates(stop.lat, stop.lon) {
+            return Err(AppError::ValidationError("Allowed stops need a name and a valid lat/lon".to_string()));
+        }
+        if stop.radius_meters.is_some_and(|r| !(10.0..=1000.0).contains(&r))
+            || stop.max_minutes.is_some_and(|m| !(1..=480).contains(&m))
+        {
+            return Err(AppError::ValidationError(
+                "Allowed stop radiusMeters must be 10-1000 and maxMinutes 1-480".to_string(),
+            ));
+        }
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.planned_by, "plan trip routes").await?;
+
+    let status = sqlx::query_scalar::<_, String>("SELECT status FROM trips WHERE id = $1")
+        .bind(&trip_id)
+        .fetch_optional(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Trip not found".to_string()))?;
+    if matches!(status.as_str(), "completed" | "cancelled") {
+        return Err(AppError::Conflict(format!("Trip is already {}", status)));
+    }
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let (latitudes, longitudes): (Vec<f64>, Vec<f64>) = payload.waypoints.iter().map(|w| (w.lat, w.lon)).unzip();
+    sqlx::query(
+        "INSERT INTO trip_routes (trip_id, waypoint_latitudes, waypoint_longitudes, corridor_meters,
+                                  max_stop_minutes, arrival_radius_meters, planned_arrival_at, planned_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (trip_id) DO UPDATE
+         SET waypoint_latitudes = EXCLUDED.waypoint_latitudes, waypoint_longitudes = EXCLUDED.waypoint_longitudes,
+             corridor_meters = EXCLUDED.corridor_meters, max_stop_minutes = EXCLUDED.max_stop_minutes,
+             arrival_radius_meters = EXCLUDED.arrival_radius_meters, planned_arrival_at = EXCLUDED.planned_arrival_at,
+             arrived_at = NULL, planned_by = EXCLUDED.planned_by, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(&trip_id)
+    .bind(&latitudes)
+    .bind(&longitudes)
+    .bind(corridor)
+    .bind(max_stop_minutes)
+    .bind(arrival_radius)
+    .bind(payload.planned_arrival_at)
+    .bind(&payload.planned_by)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to save route: {}", e)))?;
+
+    sqlx::query("DELETE FROM trip_allowed_stops WHERE trip_id = $1")
+        .bind(&trip_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to replace allowed stops: {}", e)))?;
+
+    for stop in &payload.allowed_stops {
+        sqlx::query(
+            "INSERT INTO trip_allowed_stops (id, trip_id, name, latitude, longitude, radius_meters, max_minutes)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(utils::generate_id())
+        .bind(&trip_id)
+        .bind(stop.name.trim())
+        .bind(stop.lat)
+        .bind(stop.lon)
+        .bind(stop.radius_meters.unwrap_or(150.0))
+        .bind(stop.max_minutes.unwrap_or(30))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save allowed stop: {}", e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(route_response(db.as_ref(), &trip_id).await?))
+}
+
+// Planned route and allowed stops of a trip
+pub async fn get_trip_route(
+    State(db): State<Arc<PgPool>>,
+    Path(trip_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    Ok(Json(route_response(db.as_ref(), &trip_id).await?))
+}
+
+#[derive(sqlx::FromRow)]
+struct MonitoredTrip {
+    id: String,
+    car_id: String,
+    start_time: DateTime<Utc>,
+    license_plate: String,
+    destination: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct TrackedPoint {
+    recorded_at: DateTime<Utc>,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl TrackedPoint {
+    fn position(&self) -> RouteWaypoint {
+        RouteWaypoint { lat: self.latitude, lon: self.longitude }
+    }
+}
+
+struct AlertDraft<'a> {
+    alert_type: &'static str,
+    priority: &'static str,
+    started_at: DateTime<Utc>,
+    position: Option<RouteWaypoint>,
+    distance_meters: Option<f64>,
+    duration_minutes: Option<f64>,
+    details: String,
+    params: serde_json::Value,
+    trip: &'a MonitoredTrip,
+}
+
+/// Open an alert, or update the one of this type already ongoing for the trip.
+/// Dispatch is notified only when the alert is new.
+async fn raise_alert(db: &PgPool, draft: AlertDraft<'_>) -> AppResult<Option<String>> {
+    let (alert_id, inserted): (String, bool) = sqlx::query_as(
+        "INSERT INTO trip_alerts (id, trip_id, car_id, alert_type, priority, started_at, latitude, longitude,
+                                  distance_meters, duration_minutes, details)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         ON CONFLICT (trip_id, alert_type) WHERE ended_at IS NULL DO UPDATE
+         SET distance_meters = GREATEST(trip_alerts.distance_meters, EXCLUDED.distance_meters),
+             duration_minutes = GREATEST(trip_alerts.duration_minutes, EXCLUDED.duration_minutes),
+             details = EXCLUDED.details, updated_at = CURRENT_TIMESTAMP
+         RETURNING id, (xmax = 0) AS inserted",
+    )
+    .bind(utils::generate_id())
+    .bind(&draft.trip.id)
+    .bind(&draft.trip.car_id)
+    .bind(draft.alert_type)
+    .bind(draft.priority)
+    .bind(draft.started_at)
+    .bind(draft.position.map(|p| p.lat))
+    .bind(draft.position.map(|p| p.lon))
+    .bind(draft.distance_meters)
+    .bind(draft.duration_minutes)
+    .bind(&draft.details)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record trip alert: {}", e)))?;
+
+    if !inserted {
+        return Ok(None);
+    }
+
+    let dispatchers: Vec<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE role IN ('admin', 'superadmin')")
+            .fetch_all(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch dispatchers: {}", e)))?;
+
+    let template_key = format!("trip_alert.{}", draft.alert_type);
+    for dispatcher_id in &dispatchers {
+        notify_user(db, dispatcher_id, &template_key, Some(("trip", &draft.trip.id)), draft.params.clone()).await?;
+    }
+
+    tracing::warn!(
+        "Trip alert {} for trip {} ({}): {}",
+        draft.alert_type, draft.trip.id, draft.trip.license_plate, draft.details
+    );
+    Ok(Some(alert_id))
+}
+
+async fn end_alert(db: &PgPool, trip_id: &str, alert_type: &str, ended_at: DateTime<Utc>) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE trip_alerts SET ended_at = GREATEST($3, started_at), updated_at = CURRENT_TIMESTAMP
+         WHERE trip_id = $1 AND alert_type = $2 AND ended_at IS NULL",
+    )
+    .bind(trip_id)
+    .bind(alert_type)
+    .bind(ended_at)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to end trip alert: {}", e)))?;
+    Ok(())
+}
+
+/// Check one active trip against its planned route: corridor deviation, long stops outside
+/// (or overstaying) allowed stops, and late arrival. Returns the ids of alerts newly raised.
+async fn evaluate_trip(db: &PgPool, trip: &MonitoredTrip) -> AppResult<Vec<String>> {
+    let Some(mut route) = sqlx::query_as::<_, TripRoute>("SELECT * FROM trip_routes WHERE trip_id = $1")
+        .bind(&trip.id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    else {
+        return Ok(Vec::new());
+    };
+    let waypoints = route.waypoints();
+    let Some(&destination) = waypoints.last() else { return Ok(Vec::new()) };
+    let mut raised = Vec::new();
+
+    let latest = sqlx::query_as::<_, TrackedPoint>(
+        "SELECT recorded_at, latitude, longitude FROM vehicle_live_positions
+         WHERE car_id = $1 AND recorded_at >= $2",
+    )
+    .bind(&trip.car_id)
+    .bind(trip.start_time)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if let Some(latest) = &latest {
+        let position = latest.position();
+
+        // Arrival ends any lateness and any waiting at the destination is expected
+        if route.arrived_at.is_none() && haversine_m(position, destination) <= route.arrival_radius_meters {
+            sqlx::query("UPDATE trip_routes SET arrived_at = $2, updated_at = CURRENT_TIMESTAMP WHERE trip_id = $1 AND arrived_at IS NULL")
+                .bind(&trip.id)
+                .bind(latest.recorded_at)
+                .execute(db)
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Failed to record arrival: {}", e)))?;
+            route.arrived_at = Some(latest.recorded_at);
+            end_alert(db, &trip.id, "late_arrival", latest.recorded_at).await?;
+        }
+
+        // ── Corridor deviation
+        let off_route = distance_to_route_m(position, &waypoints);
+        if off_route > route.corridor_meters {
+            raised.extend(
+                raise_alert(
+                    db,
+                    AlertDraft {
+                        alert_type: "route_deviation",
+                        priority: "critical",
+                        started_at: latest.recorded_at,
+                        position: Some(position),
+                        distance_meters: Some(off_route),
+                        duration_minutes: None,
+                        details: format!(
+                            "{:.0} m from the planned route (corridor {:.0} m)",
+                            off_route, route.corridor_meters
+                        ),
+                        params: json!({
+                            "plate": trip.license_plate,
+                            "destination": trip.destination,
+                            "distance": format!("{:.0}", off_route)
+                        }),
+                        trip,
+                    },
+                )
+                .await?,
+            );
+        } else {
+            end_alert(db, &trip.id, "route_deviation", latest.recorded_at).await?;
+        }
+
+        // ── Long stops: trace back while the vehicle stayed within STOP_RADIUS_M of where it is now
+        if route.arrived_at.is_some() {
+            end_alert(db, &trip.id, "long_stop", latest.recorded_at).await?;
+        } else {
+            let recent = sqlx::query_as::<_, TrackedPoint>(
+                "SELECT recorded_at, latitude, longitude FROM vehicle_positions
+                 WHERE car_id = $1 AND recorded_at BETWEEN $2 AND $3
+                 ORDER BY recorded_at DESC",
+            )
+            .bind(&trip.car_id)
+            .bind(trip.start_time.max(latest.recorded_at - Duration::hours(STOP_LOOKBACK_HOURS)))
+            .bind(latest.recorded_at)
+            .fetch_all(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Failed to load recent positions: {}", e)))?;
+
+            let stopped_since = recent
+                .iter()
+                .take_while(|p| haversine_m(p.position(), position) <= STOP_RADIUS_M)
+                .last()
+                .map_or(latest.recorded_at, |p| p.recorded_at);
+            let stopped_minutes = (latest.recorded_at - stopped_since).num_seconds() as f64 / 60.0;
+
+            let allowed_stop = sqlx::query_as::<_, TripAllowedStop>(
+                "SELECT id, trip_id, name, latitude, longitude, radius_meters, max_minutes
+                 FROM trip_allowed_stops
+                 WHERE trip_id = $1 AND haversine_km(latitude, longitude, $2, $3) * 1000 <= radius_meters
+                 ORDER BY haversine_km(latitude, longitude, $2, $3)
+                 LIMIT 1",
+            )
+            .bind(&trip.id)
+            .bind(position.lat)
+            .bind(position.lon)
+            .fetch_optional(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+            let (limit, place) = match &allowed_stop {
+                Some(stop) => (stop.max_minutes, format!("at {}, allowed {} min", stop.name, stop.max_minutes)),
+                None => (route.max_stop_minutes, "outside planned stops".to_string()),
+            };
+
+            if stopped_minutes > limit as f64 {
+                raised.extend(
+                    raise_alert(
+                        db,
+                        AlertDraft {
+                            alert_type: "long_stop",
+                            priority: "high",
+                            started_at: stopped_since,
+                            position: Some(position),
+                            distance_meters: None,
+                            duration_minutes: Some(stopped_minutes),
+                            details: format!("Stopped {:.0} min {}", stopped_minutes, place),
+                            params: json!({
+                                "plate": trip.license_plate,
+                                "destination": trip.destination,
+                                "minutes": format!("{:.0}", stopped_minutes),
+                                "place": place
+                            }),
+                            trip,
+                        },
+                    )
+                    .await?,
+                );
+            } else {
+                end_alert(db, &trip.id, "long_stop", latest.recorded_at).await?;
+            }
+        }
+    }
+
+    // ── Late arrival
+    if let (Some(planned), None) = (route.planned_arrival_at, route.arrived_at) {
+        let now = Utc::now();
+        if now > planned + Duration::minutes(LATE_GRACE_MINUTES) {
+            let minutes_late = (now - planned).num_seconds() as f64 / 60.0;
+            raised.extend(
+                raise_alert(
+                    db,
+                    AlertDraft {
+                        alert_type: "late_arrival",
+                        priority: "high",
+                        started_at: planned,
+                        position: latest.as_ref().map(TrackedPoint::position),
+                        distance_meters: latest.as_ref().map(|p| haversine_m(p.position(), destination)),
+                        duration_minutes: Some(minutes_late),
+                        details: format!("Not arrived {:.0} min after the planned arrival", minutes_late),
+                        params: json!({
+                            "plate": trip.license_plate,
+                            "destination": trip.destination,
+                            "plannedArrival": planned.format("%Y-%m-%d %H:%M UTC").to_string()
+                        }),
+                        trip,
+                    },
+                )
+                .await?,
+            );
+        }
+    }
+
+    Ok(raised)
+}
+
+const MONITORED_TRIPS_SQL: &str = "
+    SELECT t.id, t.car_id, t.start_time, ac.license_plate,
+           COALESCE(t.destination, t.end_location, 'its destination') AS destination
+    FROM trips t
+    JOIN armored_cars ac ON ac.id = t.car_id
+    JOIN trip_routes r ON r.trip_id = t.id
+    WHERE t.status IN ('in_transit', 'in_progress')";
+
+/// Evaluate the active routed trip of one vehicle, right after its tracker reports.
+pub async fn evaluate_car(db: &PgPool, car_id: &str) -> AppResult<Vec<String>> {
+    let trips = sqlx::query_as::<_, MonitoredTrip>(&format!("{} AND t.car_id = $1", MONITORED_TRIPS_SQL))
+        .bind(car_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let mut raised = Vec::new();
+    for trip in &trips {
+        raised.extend(evaluate_trip(db, trip).await?);
+    }
+    Ok(raised)
+}
+
+/// Evaluate every active trip with a planned route (late arrivals need no new positions), and
+/// close alerts left open on trips that have ended. Shared by the scheduler job.
+pub async fn monitor_active_trips(db: &PgPool) -> AppResult<serde_json::Value> {
+    let closed = sqlx::query(
+        "UPDATE trip_alerts a
+         SET ended_at = GREATEST(COALESCE(t.end_time, CURRENT_TIMESTAMP), a.started_at), updated_at = CURRENT_TIMESTAMP
+         FROM trips t
+         WHERE t.id = a.trip_id AND a.ended_at IS NULL AND t.status NOT IN ('in_transit', 'in_progress')",
+    )
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to close alerts of ended trips: {}", e)))?
+    .rows_affected();
+
+    let trips = sqlx::query_as::<_, MonitoredTrip>(MONITORED_TRIPS_SQL)
+        .fetch_all(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to fetch monitored trips: {}", e)))?;
+
+    let mut raised = Vec::new();
+    for trip in &trips {
+        match evaluate_trip(db, trip).await {
+            Ok(ids) => raised.extend(ids),
+            Err(e) => tracing::error!("Trip monitoring failed for trip {}: {:?}", trip.id, e),
+        }
+    }
+
+    Ok(json!({
+        "tripsMonitored": trips.len(),
+        "alertsRaised": raised,
+        "alertsClosed": closed
+    }))
+}
+
+// Trip alerts for the dispatch board or a post-mission review, newest first
+pub async fn get_trip_alerts(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<TripAlertQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let alerts = sqlx::query_as::<_, TripAlert>(
+        "SELECT * FROM trip_alerts
+         WHERE ($1::varchar IS NULL OR trip_id = $1)
+           AND ($2::varchar IS NULL OR alert_type = $2)
+           AND (NOT $3 OR ended_at IS NULL)
+           AND (NOT $4 OR acknowledged_at IS NULL)
+         ORDER BY started_at DESC
+         LIMIT 500",
+    )
+    .bind(&query.trip_id)
+    .bind(&query.alert_type)
+    .bind(query.open_only.unwrap_or(false))
+    .bind(query.unacknowledged.unwrap_or(false))
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": alerts.len(),
+        "alerts": alerts
+    })))
+}
+
+// Dispatcher acknowledges an alert
+pub async fn acknowledge_trip_alert(
+    State(db): State<Arc<PgPool>>,
+    Path(alert_id): Path<String>,
+    Json(payload): Json<AcknowledgeTripAlertRequest>,
+) -> AppResult<Json<TripAlert>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.user_id, "acknowledge trip alerts").await?;
+
+    let alert = sqlx::query_as::<_, TripAlert>(
+        "UPDATE trip_alerts SET acknowledged_by = $2, acknowledged_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND acknowledged_at IS NULL
+         RETURNING *",
+    )
+    .bind(&alert_id)
+    .bind(&payload.user_id)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to acknowledge alert: {}", e)))?;
+
+    match alert {
+        Some(alert) => Ok(Json(alert)),
+        None => {
+            let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM trip_alerts WHERE id = $1)")
+                .bind(&alert_id)
+                .fetch_one(db.as_ref())
+                .await
+                .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+            Err(if exists {
+                AppError::Conflict("Alert is already acknowledged".to_string())
+            } else {
+                AppError::NotFound("Trip alert not found".to_string())
+            })
+        }
+    }
+}
+
+// Record the post-mission review outcome of an alert
+pub async fn review_trip_alert(
+    State(db): State<Arc<PgPool>>,
+    Path(alert_id): Path<String>,
+    Json(payload): Json<ReviewTripAlertRequest>,
+) -> AppResult<Json<TripAlert>> {
+    if payload.notes.trim().is_empty() {
+        return Err(AppError::ValidationError("notes are required".to_string()));
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.reviewed_by, "review trip alerts").await?;
+
+    sqlx::query_as::<_, TripAlert>(
+        "UPDATE trip_alerts SET review_notes = $2, reviewed_by = $3, reviewed_at = CURRENT_TIMESTAMP,
+                updated_at = CURRENT_TIMESTAMP
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(&alert_id)
+    .bind(payload.notes.trim())
+    .bind(&payload.reviewed_by)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to review alert: {}", e)))?
+    .map(Json)
+    .ok_or_else(|| AppError::NotFound("Trip alert not found".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Metres in one degree along a great circle.
+    const DEGREE_M: f64 = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+
+    fn point(lat: f64, lon: f64) -> RouteWaypoint {
+        RouteWaypoint { lat, lon }
+    }
+
+    #[test]
+    fn haversine_matches_known_distances() {
+        assert!((haversine_m(point(0.0, 0.0), point(1.0, 0.0)) - DEGREE_M).abs() < 1.0);
+        assert!((haversine_m(point(0.0, 0.0), point(0.0, 1.0)) - DEGREE_M).abs() < 1.0);
+        // London to Paris, about 343.5 km on a 6371 km sphere
+        let london_paris = haversine_m(point(51.5074, -0.1278), point(48.8566, 2.3522));
+        assert!((london_paris - 343_500.0).abs() < 1_000.0, "{}", london_paris);
+        assert_eq!(haversine_m(point(14.6, 121.0), point(14.6, 121.0)), 0.0);
+    }
+
+    #[test]
+    fn point_on_a_segment_is_on_route() {
+        let route = [point(0.0, 0.0), point(0.0, 1.0)];
+        assert!(distance_to_route_m(point(0.0, 0.5), &route) < 1.0);
+        assert!(distance_to_route_m(point(0.0, 1.0), &route) < 1.0);
+    }
+
+    #[test]
+    fn point_beside_a_segment_is_measured_square_to_it() {
+        let route = [point(0.0, 0.0), point(0.0, 1.0)];
+        let distance = distance_to_route_m(point(0.01, 0.5), &route);
+        assert!((distance - 0.01 * DEGREE_M).abs() < 1.0, "{}", distance);
+    }
+
+    #[test]
+    fn point_past_a_segment_end_is_measured_to_the_end() {
+        let route = [point(0.0, 0.0), point(0.0, 1.0)];
+        let distance = distance_to_route_m(point(0.0, 1.5), &route);
+        assert!((distance - 0.5 * DEGREE_M).abs() < 5.0, "{}", distance);
+
+        let before = distance_to_route_m(point(0.003, -0.004), &route);
+        assert!((before - 0.005 * DEGREE_M).abs() < 1.0, "{}", before);
+    }
+
+    #[test]
+    fn nearest_segment_wins_on_a_bent_route() {
+        let route = [point(0.0, 0.0), point(0.0, 1.0), point(1.0, 1.0)];
+        let distance = distance_to_route_m(point(0.5, 1.002), &route);
+        assert!((distance - 0.002 * DEGREE_M).abs() < 1.0, "{}", distance);
+    }
+
+    #[test]
+    fn single_point_route_uses_straight_distance() {
+        let only = point(14.5995, 120.9842);
+        let position = point(14.6095, 120.9942);
+        assert_eq!(distance_to_route_m(position, &[only]), haversine_m(position, only));
+        assert_eq!(distance_to_route_m(position, &[]), f64::INFINITY);
+    }
+}
@@ -9,7 +9,7 @@ use serde_json::json;
 
 use crate::{
     error::{AppError, AppResult},
-    models::{UserResponse, User},
+    models::{UserResponse, User, UpdateGuardLocationRequest},
 };
 
 pub async fn get_all_users(
@@ -220,3 +220,32 @@ pub async fn delete_profile_photo(
     })))
 }
 
+
+// Set a guard's home coordinates, used to rank replacement offers by distance
+pub async fn update_home_location(
+    State(db): State<Arc<PgPool>>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateGuardLocationRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    if !(-90.0..=90.0).contains(&payload.latitude) || !(-180.0..=180.0).contains(&payload.longitude) {
+        return Err(AppError::ValidationError("Coordinates out of range".to_string()));
+    }
+
+    let result = sqlx::query(
+        "UPDATE users SET home_latitude = $1, home_longitude = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $3"
+    )
+    .bind(payload.latitude)
+    .bind(payload.longitude)
+    .bind(&id)
+    .execute(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    Ok(Json(json!({
+        "message": "Home location updated successfully"
+    })))
+}
@@ -0,0 +1,423 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::notifications::notify_user,
+    models::{
+        CreateComplianceOverrideRequest, RevokeComplianceOverrideRequest, VehicleComplianceEvent,
+        VehicleComplianceOverride, VehicleDocumentExpiry, VehicleExpiryQuery,
+    },
+    utils,
+};
+
+const DOCUMENTS: &[&str] = &["registration", "insurance"];
+/// Expiring documents are listed and notified this many days ahead (VEHICLE_DOCUMENT_NOTICE_DAYS).
+const DEFAULT_NOTICE_DAYS: i32 = 30;
+/// Longest an override may run; a lapsed document should be renewed, not overridden indefinitely.
+const MAX_OVERRIDE_DAYS: i64 = 7;
+/// A fleet manager is reminded about the same document at most once in this many days.
+const REMINDER_INTERVAL_DAYS: i32 = 7;
+
+/// One row per car and dated document, with the active override for it, if any.
+const DOCUMENT_EXPIRY_SQL: &str = r#"
+    SELECT c.id AS car_id, c.license_plate, d.document, d.expiry_date,
+           (d.expiry_date::date - CURRENT_DATE) AS days_remaining,
+           c.fleet_manager_id, o.id AS override_id, o.valid_until AS override_valid_until
+    FROM armored_cars c
+    CROSS JOIN LATERAL (
+        VALUES ('registration', c.registration_expiry), ('insurance', c.insurance_expiry)
+    ) AS d(document, expiry_date)
+    LEFT JOIN LATERAL (
+        SELECT id, valid_until FROM vehicle_compliance_overrides
+        WHERE car_id = c.id AND document = d.document AND revoked_at IS NULL AND valid_until > CURRENT_TIMESTAMP
+        ORDER BY valid_until DESC
+        LIMIT 1
+    ) o ON true
+    WHERE d.expiry_date IS NOT NULL
+"#;
+
+fn notice_days() -> i32 {
+    std::env::var("VEHICLE_DOCUMENT_NOTICE_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|days| (1..=365).contains(days))
+        .unwrap_or(DEFAULT_NOTICE_DAYS)
+}
+
+async fn record_event(
+    db: &PgPool,
+    car_id: &str,
+    override_id: Option<&str>,
+    action: &str,
+    actor_id: Option<&str>,
+    details: &str,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO vehicle_compliance_events (id, car_id, override_id, action, actor_id, details)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(utils::generate_id())
+    .bind(car_id)
+    .bind(override_id)
+    .bind(action)
+    .bind(actor_id)
+    .bind(details)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record compliance event: {}", e)))?;
+
+    Ok(())
+}
+
+/// Fleet manager of a car, or every admin when it has none.
+async fn fleet_managers(db: &PgPool, fleet_manager_id: Option<&str>) -> AppResult<Vec<String>> {
+    if let Some(manager_id) = fleet_manager_id {
+        return Ok(vec![manager_id.to_string()]);
+    }
+    sqlx::query_scalar("SELECT id FROM users WHERE role IN ('admin', 'superadmin')")
+        .fetch_all(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to fetch fleet managers: {}", e)))
+}
+
+/// Reject dispatching a car whose registration or insurance has lapsed, unless a supervisor
+/// override covers it. Returns the overridden documents so the caller can audit their use
+/// with [`record_override_use`] once the dispatch is saved.
+pub async fn ensure_documents_valid(db: impl PgExecutor<'_>, car_id: &str) -> AppResult<Vec<VehicleDocumentExpiry>> {
+    let lapsed = sqlx::query_as::<_, VehicleDocumentExpiry>(&format!(
+        "{} AND c.id = $1 AND d.expiry_date < CURRENT_TIMESTAMP",
+        DOCUMENT_EXPIRY_SQL
+    ))
+    .bind(car_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let blocking: Vec<String> = lapsed
+        .iter()
+        .filter(|doc| doc.override_id.is_none())
+        .map(|doc| format!("{} expired on {}", doc.document, doc.expiry_date.format("%Y-%m-%d")))
+        .collect();
+
+    if let Some(first) = lapsed.first().filter(|_| !blocking.is_empty()) {
+        return Err(AppError::Conflict(format!(
+            "{} cannot be dispatched: {}; renew it or ask a supervisor for an override",
+            first.license_plate,
+            blocking.join(", ")
+        )));
+    }
+
+    Ok(lapsed)
+}
+
+/// Audit each override that allowed a dispatch.
+pub async fn record_override_use(
+    db: &PgPool,
+    overridden: &[VehicleDocumentExpiry],
+    actor_id: Option<&str>,
+    dispatch: &str,
+) -> AppResult<()> {
+    for doc in overridden {
+        record_event(
+            db,
+            &doc.car_id,
+            doc.override_id.as_deref(),
+            "override_used",
+            actor_id,
+            &format!("{} despite {} expired on {}", dispatch, doc.document, doc.expiry_date.format("%Y-%m-%d")),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Remind fleet managers about documents expiring within the notice period or already lapsed.
+/// Shared by the scheduler job.
+pub async fn notify_document_expiry(db: &PgPool) -> AppResult<serde_json::Value> {
+    let documents = sqlx::query_as::<_, VehicleDocumentExpiry>(&format!(
+        "{} AND d.expiry_date < CURRENT_TIMESTAMP + make_interval(days => $1) ORDER BY d.expiry_date",
+        DOCUMENT_EXPIRY_SQL
+    ))
+    .bind(notice_days())
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to fetch expiring vehicle documents: {}", e)))?;
+
+    let now = Utc::now();
+    let mut notified = Vec::new();
+    for doc in &documents {
+        let expired = doc.expiry_date < now;
+        let template_key = if expired { "vehicle_document.expired" } else { "vehicle_document.expiring" };
+        let entity_type = format!("vehicle_{}", doc.document);
+
+        for manager_id in fleet_managers(db, doc.fleet_manager_id.as_deref()).await? {
+            let recently_notified = sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(
+                     SELECT 1 FROM notifications
+                     WHERE user_id = $1 AND template_key = $2 AND entity_type = $3 AND entity_id = $4
+                       AND created_at > CURRENT_TIMESTAMP - make_interval(days => $5)
+                 )",
+            )
+            .bind(&manager_id)
+            .bind(template_key)
+            .bind(&entity_type)
+            .bind(&doc.car_id)
+            .bind(REMINDER_INTERVAL_DAYS)
+            .fetch_one(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+            if recently_notified {
+                continue;
+            }
+
+            notify_user(
+                db,
+                &manager_id,
+                template_key,
+                Some((&entity_type, &doc.car_id)),
+                json!({
+                    "plate": doc.license_plate,
+                    "document": doc.document,
+                    "expiryDate": doc.expiry_date.format("%Y-%m-%d").to_string(),
+                    "days": doc.days_remaining.to_string()
+                }),
+            )
+            .await?;
+
+            notified.push(json!({
+                "userId": manager_id,
+                "carId": doc.car_id,
+                "document": doc.document,
+                "expired": expired
+            }));
+        }
+    }
+
+    tracing::info!(
+        "Vehicle document expiry: {} expiring or lapsed, {} reminders sent",
+        documents.len(), notified.len()
+    );
+
+    Ok(json!({
+        "documents": documents.len(),
+        "notified": notified
+    }))
+}
+
+/// GET /api/armored-cars/expiring  — registration or insurance expiring within `days`
+pub async fn get_expiring_vehicle_documents(
+    State(db): State<Arc<PgPool>>,
+    Query(query): Query<VehicleExpiryQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let days = query.days.unwrap_or_else(notice_days);
+    if !(1..=365).contains(&days) {
+        return Err(AppError::ValidationError("days must be between 1 and 365".to_string()));
+    }
+
+    let documents = sqlx::query_as::<_, VehicleDocumentExpiry>(&format!(
+        "{} AND d.expiry_date BETWEEN CURRENT_TIMESTAMP AND CURRENT_TIMESTAMP + make_interval(days => $1)
+         ORDER BY d.expiry_date ASC",
+        DOCUMENT_EXPIRY_SQL
+    ))
+    .bind(days)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": documents.len(),
+        "expiringDocuments": documents
+    })))
+}
+
+/// GET /api/armored-cars/expired  — lapsed registration or insurance, with any active override
+pub async fn get_expired_vehicle_documents(State(db): State<Arc<PgPool>>) -> AppResult<Json<serde_json::Value>> {
+    let documents = sqlx::query_as::<_, VehicleDocumentExpiry>(&format!(
+        "{} AND d.expiry_date < CURRENT_TIMESTAMP ORDER BY d.expiry_date ASC",
+        DOCUMENT_EXPIRY_SQL
+    ))
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let blocked = documents.iter().filter(|doc| doc.override_id.is_none()).count();
+    Ok(Json(json!({
+        "total": documents.len(),
+        "blocked": blocked,
+        "expiredDocuments": documents
+    })))
+}
+
+/// POST /api/armored-cars/:id/compliance-overrides  — allow dispatch despite a lapsed document
+pub async fn create_compliance_override(
+    State(db): State<Arc<PgPool>>,
+    Path(car_id): Path<String>,
+    Json(payload): Json<CreateComplianceOverrideRequest>,
+) -> AppResult<(StatusCode, Json<VehicleComplianceOverride>)> {
+    if !DOCUMENTS.contains(&payload.document.as_str()) {
+        return Err(AppError::ValidationError(format!("document must be one of: {}", DOCUMENTS.join(", "))));
+    }
+    if payload.reason.trim().is_empty() {
+        return Err(AppError::ValidationError("reason is required".to_string()));
+    }
+    let now = Utc::now();
+    let valid_until = payload.valid_until.unwrap_or(now + Duration::hours(24));
+    if valid_until <= now || valid_until > now + Duration::days(MAX_OVERRIDE_DAYS) {
+        return Err(AppError::ValidationError(format!(
+            "validUntil must be in the next {} days",
+            MAX_OVERRIDE_DAYS
+        )));
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.approved_by, "override vehicle document checks").await?;
+
+    let (plate, fleet_manager_id, approver_name): (String, Option<String>, String) = sqlx::query_as(
+        "SELECT c.license_plate, c.fleet_manager_id, (SELECT full_name FROM users WHERE id = $2)
+         FROM armored_cars c WHERE c.id = $1",
+    )
+    .bind(&car_id)
+    .bind(&payload.approved_by)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Armored car not found".to_string()))?;
+
+    let record = sqlx::query_as::<_, VehicleComplianceOverride>(
+        "INSERT INTO vehicle_compliance_overrides (id, car_id, document, reason, approved_by, valid_until)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&car_id)
+    .bind(&payload.document)
+    .bind(payload.reason.trim())
+    .bind(&payload.approved_by)
+    .bind(valid_until)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to create override: {}", e)))?;
+
+    record_event(
+        db.as_ref(),
+        &car_id,
+        Some(&record.id),
+        "override_granted",
+        Some(&payload.approved_by),
+        &format!(
+            "{} override until {}: {}",
+            record.document,
+            valid_until.format("%Y-%m-%d %H:%M UTC"),
+            record.reason
+        ),
+    )
+    .await?;
+
+    for manager_id in fleet_managers(db.as_ref(), fleet_manager_id.as_deref()).await? {
+        if manager_id == payload.approved_by {
+            continue;
+        }
+        notify_user(
+            db.as_ref(),
+            &manager_id,
+            "vehicle_document.override",
+            Some(("armored_car", &car_id)),
+            json!({
+                "plate": plate,
+                "document": record.document,
+                "approvedBy": approver_name,
+                "validUntil": valid_until.format("%Y-%m-%d %H:%M UTC").to_string(),
+                "reason": record.reason
+            }),
+        )
+        .await?;
+    }
+
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+/// PUT /api/vehicle-compliance-overrides/:override_id/revoke
+pub async fn revoke_compliance_override(
+    State(db): State<Arc<PgPool>>,
+    Path(override_id): Path<String>,
+    Json(payload): Json<RevokeComplianceOverrideRequest>,
+) -> AppResult<Json<VehicleComplianceOverride>> {
+    utils::ensure_supervisor(db.as_ref(), &payload.revoked_by, "revoke vehicle document overrides").await?;
+
+    let record = sqlx::query_as::<_, VehicleComplianceOverride>(
+        "UPDATE vehicle_compliance_overrides SET revoked_by = $2, revoked_at = CURRENT_TIMESTAMP
+         WHERE id = $1 AND revoked_at IS NULL
+         RETURNING *",
+    )
+    .bind(&override_id)
+    .bind(&payload.revoked_by)
+    .fetch_optional(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to revoke override: {}", e)))?
+    .ok_or_else(|| AppError::NotFound("Active override not found".to_string()))?;
+
+    record_event(
+        db.as_ref(),
+        &record.car_id,
+        Some(&record.id),
+        "override_revoked",
+        Some(&payload.revoked_by),
+        &format!("{} override revoked", record.document),
+    )
+    .await?;
+
+    Ok(Json(record))
+}
+
+/// GET /api/armored-cars/:id/compliance  — document expiry, overrides and their audit trail
+pub async fn get_car_compliance(
+    State(db): State<Arc<PgPool>>,
+    Path(car_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let car_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM armored_cars WHERE id = $1)")
+        .bind(&car_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    if !car_exists {
+        return Err(AppError::NotFound("Armored car not found".to_string()));
+    }
+
+    let documents = sqlx::query_as::<_, VehicleDocumentExpiry>(&format!(
+        "{} AND c.id = $1 ORDER BY d.expiry_date",
+        DOCUMENT_EXPIRY_SQL
+    ))
+    .bind(&car_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let overrides = sqlx::query_as::<_, VehicleComplianceOverride>(
+        "SELECT * FROM vehicle_compliance_overrides WHERE car_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(&car_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let events = sqlx::query_as::<_, VehicleComplianceEvent>(
+        "SELECT * FROM vehicle_compliance_events WHERE car_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(&car_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "documents": documents,
+        "overrides": overrides,
+        "events": events
+    })))
+}
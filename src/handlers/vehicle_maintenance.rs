@@ -0,0 +1,283 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{UpsertServicePolicyRequest, VehicleServicePolicy, VehicleServiceStatus},
+    utils,
+};
+
+/// Policy used for car models that have none of their own.
+pub const DEFAULT_POLICY_MODEL: &str = "*";
+
+/// Service state of each car under its model's policy (or the '*' policy). Interval
+/// kilometres count from the odometer at the last service; months from the last service
+/// date, or from when the car was added if it was never serviced.
+const SERVICE_STATUS_SQL: &str = r#"
+    WITH s AS (
+        SELECT c.id AS car_id, c.license_plate, c.model, COALESCE(c.mileage, 0) AS mileage,
+               c.last_maintenance_date, c.last_maintenance_mileage,
+               p.id AS policy_id, p.interval_km, p.interval_months, p.lead_km, p.lead_days,
+               COALESCE(c.last_maintenance_mileage, 0) + p.interval_km AS next_due_mileage,
+               COALESCE(c.last_maintenance_date, c.created_at) + make_interval(months => p.interval_months) AS next_due_date
+        FROM armored_cars c
+        LEFT JOIN LATERAL (
+            SELECT * FROM vehicle_service_policies
+            WHERE model IN (c.model, '*')
+            ORDER BY model = '*'
+            LIMIT 1
+        ) p ON true
+    )
+    SELECT s.car_id, s.license_plate, s.model, s.mileage, s.last_maintenance_date, s.last_maintenance_mileage,
+           s.policy_id, s.interval_km, s.interval_months, s.next_due_mileage, s.next_due_date,
+           CASE
+               WHEN s.policy_id IS NULL THEN 'no_policy'
+               WHEN s.mileage >= s.next_due_mileage OR CURRENT_TIMESTAMP >= s.next_due_date THEN 'overdue'
+               WHEN s.mileage >= s.next_due_mileage - s.lead_km
+                    OR CURRENT_TIMESTAMP >= s.next_due_date - make_interval(days => s.lead_days) THEN 'due_soon'
+               ELSE 'ok'
+           END AS service_state,
+           (SELECT m.id FROM car_maintenance m
+            WHERE m.car_id = s.car_id AND m.auto_generated AND m.status = 'scheduled') AS open_maintenance_id
+    FROM s
+"#;
+
+async fn service_status(db: impl PgExecutor<'_>, car_id: &str) -> AppResult<VehicleServiceStatus> {
+    sqlx::query_as::<_, VehicleServiceStatus>(&format!("{} WHERE s.car_id = $1", SERVICE_STATUS_SQL))
+        .bind(car_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Armored car not found".to_string()))
+}
+
+/// Reject issuing or dispatching a car that is past its service interval.
+pub async fn ensure_serviceable(db: impl PgExecutor<'_>, car_id: &str) -> AppResult<()> {
+    let status = service_status(db, car_id).await?;
+    if status.service_state != "overdue" {
+        return Ok(());
+    }
+
+    let mut reasons = Vec::new();
+    if let Some(due) = status.next_due_mileage.filter(|due| status.mileage >= *due) {
+        reasons.push(format!("odometer {} km, service due at {} km", status.mileage, due));
+    }
+    if let Some(due) = status.next_due_date.filter(|due| Utc::now() >= *due) {
+        reasons.push(format!("service due since {}", due.format("%Y-%m-%d")));
+    }
+
+    Err(AppError::Conflict(format!(
+        "{} is overdue for service ({}); complete its maintenance first",
+        status.license_plate,
+        reasons.join("; ")
+    )))
+}
+
+/// Create a scheduled `car_maintenance` row for every car (or the given car) whose service is
+/// due soon or overdue and has none planned yet. Returns the ids of the rows created.
+pub async fn plan_maintenance(db: &PgPool, car_id: Option<&str>) -> AppResult<Vec<String>> {
+    let due = sqlx::query_as::<_, VehicleServiceStatus>(&format!(
+        "{} WHERE ($1::varchar IS NULL OR s.car_id = $1)",
+        SERVICE_STATUS_SQL
+    ))
+    .bind(car_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to compute service status: {}", e)))?
+    .into_iter()
+    .filter(|s| matches!(s.service_state.as_str(), "due_soon" | "overdue") && s.open_maintenance_id.is_none());
+
+    let now = Utc::now();
+    let mut created = Vec::new();
+    for status in due {
+        let Some(policy_id) = &status.policy_id else { continue };
+        let (maintenance_type, description, lead_km, lead_days): (String, Option<String>, i32, i32) = sqlx::query_as(
+            "SELECT maintenance_type, description, lead_km, lead_days FROM vehicle_service_policies WHERE id = $1",
+        )
+        .bind(policy_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+        let interval = match (status.interval_km, status.interval_months) {
+            (Some(km), Some(months)) => format!("every {} km or {} months", km, months),
+            (Some(km), None) => format!("every {} km", km),
+            (None, Some(months)) => format!("every {} months", months),
+            (None, None) => continue,
+        };
+        // Services brought on by the calendar are booked for their due date; by mileage, as soon as possible
+        let mileage_due = status.next_due_mileage.is_some_and(|km| status.mileage >= km - lead_km);
+        let scheduled_date = status
+            .next_due_date
+            .filter(|due| !mileage_due && *due > now && *due <= now + Duration::days(lead_days as i64))
+            .unwrap_or(now);
+
+        let id = utils::generate_id();
+        let inserted = sqlx::query(
+            "INSERT INTO car_maintenance (id, car_id, maintenance_type, description, scheduled_date, status,
+                                          policy_id, due_mileage, auto_generated)
+             VALUES ($1, $2, $3, $4, $5, 'scheduled', $6, $7, true)
+             ON CONFLICT (car_id) WHERE auto_generated AND status = 'scheduled' DO NOTHING",
+        )
+        .bind(&id)
+        .bind(&status.car_id)
+        .bind(&maintenance_type)
+        .bind(description.unwrap_or_else(|| format!("{} service ({})", status.model, interval)))
+        .bind(scheduled_date)
+        .bind(policy_id)
+        .bind(status.next_due_mileage)
+        .execute(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to plan maintenance: {}", e)))?
+        .rows_affected();
+
+        if inserted > 0 {
+            tracing::info!(
+                "Planned {} for {} ({}, odometer {} km)",
+                maintenance_type, status.license_plate, status.service_state, status.mileage
+            );
+            created.push(id);
+        }
+    }
+
+    Ok(created)
+}
+
+/// Daily pass over the fleet so date-based intervals come due without a trip. Shared by the scheduler job.
+pub async fn plan_fleet_maintenance(db: &PgPool) -> AppResult<serde_json::Value> {
+    let created = plan_maintenance(db, None).await?;
+    Ok(json!({ "planned": created }))
+}
+
+// List service policies
+pub async fn get_service_policies(State(db): State<Arc<PgPool>>) -> AppResult<Json<serde_json::Value>> {
+    let policies = sqlx::query_as::<_, VehicleServicePolicy>(
+        "SELECT * FROM vehicle_service_policies ORDER BY model = '*', model",
+    )
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": policies.len(),
+        "policies": policies
+    })))
+}
+
+// Create or replace the service policy for a car model ('*' for the fleet default)
+pub async fn upsert_service_policy(
+    State(db): State<Arc<PgPool>>,
+    Path(model): Path<String>,
+    Json(payload): Json<UpsertServicePolicyRequest>,
+) -> AppResult<Json<VehicleServicePolicy>> {
+    let model = model.trim().to_string();
+    if model.is_empty() {
+        return Err(AppError::ValidationError("model is required".to_string()));
+    }
+    if payload.interval_km.is_none() && payload.interval_months.is_none() {
+        return Err(AppError::ValidationError("Set intervalKm, intervalMonths or both".to_string()));
+    }
+    if payload.interval_km.is_some_and(|km| km <= 0) || payload.interval_months.is_some_and(|m| !(1..=120).contains(&m)) {
+        return Err(AppError::ValidationError(
+            "intervalKm must be positive and intervalMonths between 1 and 120".to_string(),
+        ));
+    }
+    let lead_km = payload.lead_km.unwrap_or(500);
+    let lead_days = payload.lead_days.unwrap_or(14);
+    if lead_km < 0 || lead_days < 0 {
+        return Err(AppError::ValidationError("leadKm and leadDays cannot be negative".to_string()));
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.updated_by, "manage service policies").await?;
+
+    if model != DEFAULT_POLICY_MODEL {
+        let model_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM armored_cars WHERE model = $1)")
+            .bind(&model)
+            .fetch_one(db.as_ref())
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+        if !model_exists {
+            return Err(AppError::NotFound(format!("No armored car of model '{}'", model)));
+        }
+    }
+
+    let policy = sqlx::query_as::<_, VehicleServicePolicy>(
+        "INSERT INTO vehicle_service_policies (id, model, interval_km, interval_months, lead_km, lead_days,
+                                               maintenance_type, description, updated_by)
+         VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, 'routine_service'), $8, $9)
+         ON CONFLICT (model) DO UPDATE
+         SET interval_km = EXCLUDED.interval_km, interval_months = EXCLUDED.interval_months,
+             lead_km = EXCLUDED.lead_km, lead_days = EXCLUDED.lead_days,
+             maintenance_type = EXCLUDED.maintenance_type, description = EXCLUDED.description,
+             updated_by = EXCLUDED.updated_by, updated_at = CURRENT_TIMESTAMP
+         RETURNING *",
+    )
+    .bind(utils::generate_id())
+    .bind(&model)
+    .bind(payload.interval_km)
+    .bind(payload.interval_months)
+    .bind(lead_km)
+    .bind(lead_days)
+    .bind(&payload.maintenance_type)
+    .bind(&payload.description)
+    .bind(&payload.updated_by)
+    .fetch_one(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to save service policy: {}", e)))?;
+
+    plan_maintenance(db.as_ref(), None).await?;
+
+    Ok(Json(policy))
+}
+
+pub async fn delete_service_policy(
+    State(db): State<Arc<PgPool>>,
+    Path(model): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM vehicle_service_policies WHERE model = $1")
+        .bind(&model)
+        .execute(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to delete service policy: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Service policy not found".to_string()));
+    }
+
+    Ok(Json(json!({
+        "message": "Service policy deleted successfully"
+    })))
+}
+
+// Service state of one car
+pub async fn get_car_service_status(
+    State(db): State<Arc<PgPool>>,
+    Path(car_id): Path<String>,
+) -> AppResult<Json<VehicleServiceStatus>> {
+    Ok(Json(service_status(db.as_ref(), &car_id).await?))
+}
+
+// Cars due for service soon or overdue, overdue first
+pub async fn get_service_due(State(db): State<Arc<PgPool>>) -> AppResult<Json<serde_json::Value>> {
+    let cars = sqlx::query_as::<_, VehicleServiceStatus>(&format!(
+        "SELECT * FROM ({}) due
+         WHERE service_state IN ('due_soon', 'overdue')
+         ORDER BY service_state = 'due_soon', next_due_date NULLS LAST",
+        SERVICE_STATUS_SQL
+    ))
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let overdue = cars.iter().filter(|c| c.service_state == "overdue").count();
+    Ok(Json(json!({
+        "total": cars.len(),
+        "overdue": overdue,
+        "cars": cars
+    })))
+}
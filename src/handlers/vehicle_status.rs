@@ -0,0 +1,311 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{vehicle_compliance, vehicle_maintenance},
+    models::{SetVehicleStatusRequest, VehicleDocumentExpiry, VehicleStatus, VehicleStatusChange},
+    utils,
+};
+
+use VehicleStatus::*;
+
+/// Every status an armored car may move to from each status. Anything else is a conflict.
+const TRANSITIONS: &[(VehicleStatus, &[VehicleStatus])] = &[
+    (Available, &[Allocated, Deployed, Maintenance, OutOfService]),
+    (Allocated, &[Available, Deployed, Maintenance]),
+    (Deployed, &[Available, Allocated, Maintenance]),
+    (Maintenance, &[Available, OutOfService]),
+    (OutOfService, &[Available, Maintenance]),
+];
+
+/// Why a car's status changed, stored with the history row.
+#[derive(Default)]
+pub struct StatusChange<'a> {
+    pub reason: &'a str,
+    pub changed_by: Option<&'a str>,
+    pub trip_id: Option<&'a str>,
+    pub allocation_id: Option<&'a str>,
+}
+
+pub fn can_transition(from: VehicleStatus, to: VehicleStatus) -> bool {
+    TRANSITIONS
+        .iter()
+        .any(|(status, next)| *status == from && next.contains(&to))
+}
+
+/// Current status of a car, locked until the caller's transaction ends.
+pub async fn current_status(conn: &mut PgConnection, car_id: &str) -> AppResult<VehicleStatus> {
+    let status = sqlx::query_scalar::<_, String>("SELECT status FROM armored_cars WHERE id = $1 FOR UPDATE")
+        .bind(car_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("Armored car not found".to_string()))?;
+
+    VehicleStatus::parse(&status)
+        .ok_or_else(|| AppError::InternalServerError(format!("Unknown armored car status '{}'", status)))
+}
+
+/// Append a row to the car's status history.
+pub async fn record_change(
+    conn: &mut PgConnection,
+    car_id: &str,
+    from: Option<VehicleStatus>,
+    to: VehicleStatus,
+    change: &StatusChange<'_>,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO vehicle_status_history (id, car_id, from_status, to_status, reason, changed_by, trip_id, allocation_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(utils::generate_id())
+    .bind(car_id)
+    .bind(from.map(|status| status.as_str()))
+    .bind(to.as_str())
+    .bind(change.reason)
+    .bind(change.changed_by)
+    .bind(change.trip_id)
+    .bind(change.allocation_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record status change: {}", e)))?;
+
+    Ok(())
+}
+
+/// Move a car to a new status if the transition table allows it, and record the change.
+/// Returns the status it left. Run inside the transaction that makes the change happen.
+pub async fn transition(
+    conn: &mut PgConnection,
+    car_id: &str,
+    to: VehicleStatus,
+    change: &StatusChange<'_>,
+) -> AppResult<VehicleStatus> {
+    let from = current_status(conn, car_id).await?;
+    if from == to {
+        return Err(AppError::Conflict(format!("Armored car status is already {}", to)));
+    }
+    if !can_transition(from, to) {
+        return Err(AppError::Conflict(format!("Armored car cannot go from {} to {}", from, to)));
+    }
+
+    sqlx::query("UPDATE armored_cars SET status = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
+        .bind(to.as_str())
+        .bind(car_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update car status: {}", e)))?;
+
+    record_change(conn, car_id, Some(from), to, change).await?;
+    Ok(from)
+}
+
+/// Move a car from `from` to `to` only if that is its current status. Returns whether it moved.
+pub async fn transition_if(
+    conn: &mut PgConnection,
+    car_id: &str,
+    from: VehicleStatus,
+    to: VehicleStatus,
+    change: &StatusChange<'_>,
+) -> AppResult<bool> {
+    if current_status(conn, car_id).await? != from {
+        return Ok(false);
+    }
+    transition(conn, car_id, to, change).await?;
+    Ok(true)
+}
+
+/// Mark a car deployed for a trip unless it already is. A car overdue for service or with lapsed
+/// documents is not deployed; returns the overridden documents for the caller to audit.
+pub async fn deploy_for_trip(
+    conn: &mut PgConnection,
+    car_id: &str,
+    change: &StatusChange<'_>,
+) -> AppResult<Vec<VehicleDocumentExpiry>> {
+    if current_status(conn, car_id).await? == Deployed {
+        return Ok(Vec::new());
+    }
+    vehicle_maintenance::ensure_serviceable(&mut *conn, car_id).await?;
+    let overridden = vehicle_compliance::ensure_documents_valid(&mut *conn, car_id).await?;
+    transition(conn, car_id, Deployed, change).await?;
+    Ok(overridden)
+}
+
+/// Return a deployed or reserved car to its client allocation, to another mission trip it is
+/// reserved for, or to the pool, when its trip ends. A car that is neither (sent to maintenance
+/// mid-trip, say) keeps its status.
+pub async fn release_after_trip(conn: &mut PgConnection, car_id: &str, change: &StatusChange<'_>) -> AppResult<()> {
+    let from = current_status(conn, car_id).await?;
+    if !matches!(from, Deployed | Allocated) {
+        return Ok(());
+    }
+
+    let allocated = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM car_allocations WHERE car_id = $1 AND status = 'active')
+             OR EXISTS(SELECT 1 FROM trips WHERE car_id = $1 AND status = 'scheduled' AND ($2::varchar IS NULL OR id <> $2))",
+    )
+    .bind(car_id)
+    .bind(change.trip_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let to = if allocated { Allocated } else { Available };
+    if to != from {
+        transition(conn, car_id, to, change).await?;
+    }
+    Ok(())
+}
+
+/// Keep a car's status in step with a trip status change: deployed while the trip is under way,
+/// released when it is completed or cancelled. Returns the document overrides a deployment relied on.
+pub async fn follow_trip(
+    conn: &mut PgConnection,
+    car_id: &str,
+    trip_id: &str,
+    trip_status: &str,
+) -> AppResult<Vec<VehicleDocumentExpiry>> {
+    match trip_status {
+        "in_progress" | "in_transit" => {
+            deploy_for_trip(conn, car_id, &StatusChange { reason: "Trip started", trip_id: Some(trip_id), ..Default::default() }).await
+        }
+        "completed" | "cancelled" => {
+            let reason = if trip_status == "completed" { "Trip completed" } else { "Trip cancelled" };
+            release_after_trip(conn, car_id, &StatusChange { reason, trip_id: Some(trip_id), ..Default::default() }).await?;
+            Ok(Vec::new())
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// GET /api/vehicle-statuses  — the transition table
+pub async fn get_vehicle_statuses() -> Json<serde_json::Value> {
+    let statuses: Vec<_> = TRANSITIONS
+        .iter()
+        .map(|(status, next)| json!({ "status": status, "next": next }))
+        .collect();
+
+    Json(json!({ "statuses": statuses }))
+}
+
+/// PUT /api/armored-cars/:id/status  — take a car out of service, into maintenance or back
+pub async fn set_car_status(
+    State(db): State<Arc<PgPool>>,
+    Path(car_id): Path<String>,
+    Json(payload): Json<SetVehicleStatusRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    if matches!(payload.status, Allocated | Deployed) {
+        return Err(AppError::ValidationError(
+            "allocated and deployed are set by issuing the car and starting a trip".to_string(),
+        ));
+    }
+    if payload.reason.trim().is_empty() {
+        return Err(AppError::ValidationError("reason is required".to_string()));
+    }
+    utils::ensure_supervisor(db.as_ref(), &payload.changed_by, "change a vehicle's status").await?;
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+
+    let from = transition(
+        &mut tx,
+        &car_id,
+        payload.status,
+        &StatusChange {
+            reason: payload.reason.trim(),
+            changed_by: Some(&payload.changed_by),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(Json(json!({
+        "carId": car_id,
+        "fromStatus": from,
+        "status": payload.status
+    })))
+}
+
+/// GET /api/armored-cars/:id/status-history  — newest first
+pub async fn get_car_status_history(
+    State(db): State<Arc<PgPool>>,
+    Path(car_id): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let car_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM armored_cars WHERE id = $1)")
+        .bind(&car_id)
+        .fetch_one(db.as_ref())
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+    if !car_exists {
+        return Err(AppError::NotFound("Armored car not found".to_string()));
+    }
+
+    let history = sqlx::query_as::<_, VehicleStatusChange>(
+        "SELECT * FROM vehicle_status_history WHERE car_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(&car_id)
+    .fetch_all(db.as_ref())
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({
+        "total": history.len(),
+        "history": history
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_status_has_one_row() {
+        for status in VehicleStatus::ALL {
+            assert_eq!(TRANSITIONS.iter().filter(|(from, _)| *from == status).count(), 1, "{}", status);
+        }
+    }
+
+    #[test]
+    fn no_status_moves_to_itself() {
+        for status in VehicleStatus::ALL {
+            assert!(!can_transition(status, status), "{}", status);
+        }
+    }
+
+    #[test]
+    fn trips_deploy_and_release_cars() {
+        assert!(can_transition(Available, Deployed));
+        assert!(can_transition(Allocated, Deployed));
+        assert!(can_transition(Deployed, Available));
+        assert!(can_transition(Deployed, Allocated));
+        assert!(can_transition(Available, Allocated));
+        assert!(can_transition(Allocated, Available));
+    }
+
+    #[test]
+    fn cars_off_the_road_cannot_be_dispatched() {
+        for status in [Maintenance, OutOfService] {
+            assert!(!can_transition(status, Deployed), "{}", status);
+            assert!(!can_transition(status, Allocated), "{}", status);
+            assert!(can_transition(status, Available), "{}", status);
+        }
+    }
+
+    #[test]
+    fn a_deployed_car_is_not_taken_out_of_service_mid_trip() {
+        assert!(!can_transition(Deployed, OutOfService));
+        assert!(can_transition(Deployed, Maintenance));
+    }
+}
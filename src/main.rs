@@ -5,11 +5,12 @@ mod routes;
 mod utils;
 mod error;
 mod config;
+mod scheduler;
 
 use axum::{
     extract::DefaultBodyLimit,
     routing::{get, post, put, delete},
-    Router,
+    Extension, Router,
 };
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
@@ -35,6 +36,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let db = Arc::new(db_pool);
 
+    // Register background jobs and start the scheduler loop
+    scheduler::register_jobs(db.as_ref()).await?;
+    handlers::notification_templates::register_templates(db.as_ref()).await?;
+    if config.scheduler_enabled {
+        scheduler::start(db.clone(), config.scheduler_tick_secs);
+    } else {
+        tracing::info!("Scheduler disabled (SCHEDULER_ENABLED=false)");
+    }
+
+    // Forward inserted notifications (LISTEN/NOTIFY) to open notification streams
+    let notification_hub = handlers::notification_stream::start_listener(db.clone());
+    handlers::notification_dispatch::start(db.clone(), &notification_hub);
+
     // CORS configuration — allow all origins (no credentials, pure JWT via header)
     // Set CORS_ORIGIN env var in Railway to restrict to a specific frontend domain.
     let cors_layer = if let Ok(origin) = std::env::var("CORS_ORIGIN") {
@@ -79,6 +93,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/users/:id", delete(handlers::users::delete_user))
         .route("/api/users/:id/profile-photo", put(handlers::users::update_profile_photo))
         .route("/api/users/:id/profile-photo", delete(handlers::users::delete_profile_photo))
+        .route("/api/users/:id/location", put(handlers::users::update_home_location))
         
         // Firearm routes
         .route("/api/firearms", post(handlers::firearms::add_firearm))
@@ -114,6 +129,94 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/guard-replacement/accept-replacement", post(handlers::guard_replacement::accept_replacement))
         .route("/api/guard-replacement/set-availability", post(handlers::guard_replacement::set_availability))
         .route("/api/guard-replacement/availability/:guard_id", get(handlers::guard_replacement::get_guard_availability))
+        .route("/api/guard-replacement/shifts/:shift_id/offers", get(handlers::replacement_offers::get_shift_replacement_offers))
+        .route("/api/guard-replacement/guard/:guard_id/offers", get(handlers::replacement_offers::get_guard_replacement_offers))
+        .route("/api/guard-replacement/process-offers", post(handlers::replacement_offers::run_replacement_offer_processing))
+        .route("/api/guard-replacement/guard/:guard_id/weekly-availability", put(handlers::availability::set_weekly_availability))
+        .route("/api/guard-replacement/guard/:guard_id/weekly-availability", get(handlers::availability::get_weekly_availability))
+        .route("/api/guard-replacement/guard/:guard_id/blackout-dates", post(handlers::availability::create_blackout_date))
+        .route("/api/guard-replacement/guard/:guard_id/blackout-dates", get(handlers::availability::get_blackout_dates))
+        .route("/api/guard-replacement/blackout-dates/:blackout_id", delete(handlers::availability::delete_blackout_date))
+        .route("/api/guard-replacement/guard/:guard_id/leave-requests", get(handlers::availability::get_guard_leave_requests))
+        .route("/api/guard-replacement/guard/:guard_id/calendar", get(handlers::availability::get_guard_calendar))
+        .route("/api/guard-replacement/guard/:guard_id/availability-check", get(handlers::availability::check_guard_availability))
+        .route("/api/leave-requests", post(handlers::availability::create_leave_request))
+        .route("/api/leave-requests", get(handlers::availability::get_leave_requests))
+        .route("/api/leave-requests/:leave_id/review", put(handlers::availability::review_leave_request))
+        .route("/api/leave-requests/:leave_id/cancel", post(handlers::availability::cancel_leave_request))
+
+        // Shift swap marketplace routes
+        .route("/api/shift-swaps", post(handlers::shift_swaps::create_shift_swap))
+        .route("/api/shift-swaps", get(handlers::shift_swaps::get_shift_swaps))
+        .route("/api/shift-swaps/:swap_id", get(handlers::shift_swaps::get_shift_swap))
+        .route("/api/shift-swaps/:swap_id/claim", post(handlers::shift_swaps::claim_shift_swap))
+        .route("/api/shift-swaps/:swap_id/withdraw-claim", post(handlers::shift_swaps::withdraw_swap_claim))
+        .route("/api/shift-swaps/:swap_id/cancel", post(handlers::shift_swaps::cancel_shift_swap))
+        .route("/api/shift-swaps/:swap_id/review", put(handlers::shift_swaps::review_shift_swap))
+        .route("/api/replacement-offers/:offer_id/accept", post(handlers::replacement_offers::accept_replacement_offer))
+        .route("/api/replacement-offers/:offer_id/decline", post(handlers::replacement_offers::decline_replacement_offer))
+
+        // Timesheet & payroll routes
+        .route("/api/payroll/rate-rules", get(handlers::timesheets::get_rate_rules))
+        .route("/api/payroll/rate-rules", post(handlers::timesheets::create_rate_rule))
+        .route("/api/payroll/holidays", get(handlers::timesheets::get_holidays))
+        .route("/api/payroll/holidays", post(handlers::timesheets::create_holiday))
+        .route("/api/payroll/holidays/:holiday_id", delete(handlers::timesheets::delete_holiday))
+        .route("/api/timesheets/generate", post(handlers::timesheets::generate_timesheets))
+        .route("/api/timesheets", get(handlers::timesheets::get_timesheets))
+        .route("/api/timesheets/export/csv", get(handlers::timesheets::export_timesheets_csv))
+        .route("/api/timesheets/export/payroll", get(handlers::timesheets::export_timesheets_payroll))
+        .route("/api/timesheets/:timesheet_id", get(handlers::timesheets::get_timesheet))
+        .route("/api/timesheets/:timesheet_id/review", put(handlers::timesheets::review_timesheet))
+
+        // Client site routes
+        .route("/api/client-sites", post(handlers::client_sites::create_client_site))
+        .route("/api/client-sites", get(handlers::client_sites::get_client_sites))
+        .route("/api/client-sites/:site_id", put(handlers::client_sites::update_client_site))
+
+        // Client billing routes
+        .route("/api/clients", post(handlers::clients::create_client))
+        .route("/api/clients", get(handlers::clients::get_clients))
+        .route("/api/clients/:client_id", get(handlers::clients::get_client))
+        .route("/api/clients/:client_id", put(handlers::clients::update_client))
+        .route("/api/clients/:client_id/contracts", post(handlers::clients::create_contract))
+        .route("/api/clients/:client_id/contracts", get(handlers::clients::get_client_contracts))
+        .route("/api/clients/:client_id/users", post(handlers::clients::create_client_user))
+        .route("/api/clients/:client_id/users", get(handlers::clients::get_client_users))
+        .route("/api/contracts/:contract_id", put(handlers::clients::update_contract))
+        .route("/api/invoices/generate", post(handlers::invoices::generate_invoice))
+        .route("/api/invoices", get(handlers::invoices::get_invoices))
+        .route("/api/invoices/:invoice_id", get(handlers::invoices::get_invoice))
+        .route("/api/invoices/:invoice_id", delete(handlers::invoices::delete_invoice))
+        .route("/api/invoices/:invoice_id/issue", post(handlers::invoices::issue_invoice))
+        .route("/api/invoices/:invoice_id/pay", post(handlers::invoices::mark_invoice_paid))
+        .route("/api/invoices/:invoice_id/html", get(handlers::invoices::render_invoice_html))
+
+        // Incident routes
+        .route("/api/incidents", post(handlers::incidents::create_incident))
+        .route("/api/incidents", get(handlers::incidents::get_incidents))
+        .route("/api/incidents/:incident_id/status", put(handlers::incidents::update_incident_status))
+        .route("/api/incidents/:incident_id/timeline", get(handlers::incidents::get_incident_timeline))
+        // SOS routes
+        .route("/api/sos", post(handlers::sos::raise_sos))
+        .route("/api/sos/active", get(handlers::sos::get_active_sos))
+        .route("/api/sos/:incident_id/location", post(handlers::sos::update_sos_location))
+        .route("/api/sos/:incident_id/acknowledge", put(handlers::sos::acknowledge_sos))
+
+        // Client portal routes (scoped to the portal user's client)
+        .route("/api/client-portal/:user_id/sites", get(handlers::client_portal::get_portal_sites))
+        .route("/api/client-portal/:user_id/shifts", get(handlers::client_portal::get_portal_shifts))
+        .route("/api/client-portal/:user_id/guards", get(handlers::client_portal::get_portal_guards))
+        .route("/api/client-portal/:user_id/incidents", get(handlers::client_portal::get_portal_incidents))
+        .route("/api/client-portal/:user_id/trips", get(handlers::client_portal::get_portal_trips))
+        .route("/api/client-portal/:user_id/evaluations", post(handlers::client_portal::submit_portal_evaluation))
+        .route("/api/client-portal/:user_id/service-requests", post(handlers::client_portal::create_service_request))
+        .route("/api/client-portal/:user_id/service-requests", get(handlers::client_portal::get_portal_service_requests))
+        .route("/api/client-portal/:user_id/service-requests/:request_id/cancel", post(handlers::client_portal::cancel_service_request))
+
+        // Dispatcher queue routes
+        .route("/api/dispatch/service-requests", get(handlers::service_requests::get_dispatch_queue))
+        .route("/api/dispatch/service-requests/:request_id", put(handlers::service_requests::dispatch_service_request))
         
         // Notification routes (restructured to avoid route conflicts)
         .route("/api/notifications", post(handlers::notifications::create_notification))
@@ -122,6 +225,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/users/:user_id/notifications/mark-all-read", put(handlers::notifications::mark_all_read))
         .route("/api/notifications/:notification_id/read", put(handlers::notifications::mark_notification_read))
         .route("/api/notifications/:notification_id", delete(handlers::notifications::delete_notification))
+        .route("/api/notifications/stream", get(handlers::notification_stream::stream_notifications))
+        .route("/api/notifications/:notification_id/deliveries", get(handlers::notification_dispatch::get_notification_deliveries))
+        .route("/api/users/:user_id/notification-preferences", get(handlers::notification_dispatch::get_notification_preferences))
+        .route("/api/users/:user_id/notification-preferences", put(handlers::notification_dispatch::update_notification_preferences))
+        .route("/api/users/:user_id/push-devices", post(handlers::notification_dispatch::register_push_device))
+        .route("/api/users/:user_id/push-devices/:device_id", delete(handlers::notification_dispatch::delete_push_device))
+        .route("/api/notification-templates", get(handlers::notification_templates::get_notification_templates))
+        .route("/api/notification-templates/:template_key/:locale", put(handlers::notification_templates::update_notification_template))
+        .route("/api/notification-templates/:template_key/:locale/reset", post(handlers::notification_templates::reset_notification_template))
 
         // Mission assignment routes (Integrated Workflow)
         .route("/api/missions/assign", post(handlers::missions::assign_mission))
@@ -146,6 +258,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/training-records/expiring", get(handlers::training::get_expiring_training))
         .route("/api/training-records/:guard_id", get(handlers::training::get_guard_training))
 
+        // Training catalog, sessions and enrolment
+        .route("/api/training-courses", post(handlers::training_catalog::create_training_course))
+        .route("/api/training-courses", get(handlers::training_catalog::get_training_courses))
+        .route("/api/training-courses/:course_id", put(handlers::training_catalog::update_training_course))
+        .route("/api/training-sessions", post(handlers::training_catalog::create_training_session))
+        .route("/api/training-sessions", get(handlers::training_catalog::get_training_sessions))
+        .route("/api/training-sessions/process-renewals", post(handlers::training_catalog::run_training_renewals))
+        .route("/api/training-sessions/:session_id", get(handlers::training_catalog::get_training_session_details))
+        .route("/api/training-sessions/:session_id/enroll", post(handlers::training_catalog::enroll_guard))
+        .route("/api/training-sessions/:session_id/cancel-enrollment", post(handlers::training_catalog::cancel_enrollment))
+        .route("/api/training-sessions/:session_id/attendance", post(handlers::training_catalog::mark_attendance))
+        .route("/api/training-sessions/:session_id/complete", post(handlers::training_catalog::complete_training_session))
+
         // Overdue allocations (Requirement 3)
         .route("/api/firearm-allocations/overdue", get(handlers::firearm_allocation::get_overdue_allocations))
 
@@ -160,6 +285,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/merit/evaluations/submit", post(handlers::merit::submit_client_evaluation))
         .route("/api/merit/evaluations/:guard_id", get(handlers::merit::get_guard_evaluations))
         .route("/api/merit/overtime-candidates", get(handlers::merit::get_overtime_candidates))
+        .route("/api/merit/:guard_id/history", get(handlers::merit::get_merit_history))
+        .route("/api/merit/recalculations", post(handlers::merit_batch::start_batch_recalculation))
+        .route("/api/merit/recalculations", get(handlers::merit_batch::get_recalculation_runs))
+        .route("/api/merit/recalculations/:run_id", get(handlers::merit_batch::get_recalculation_run))
+        .route("/api/merit/models", get(handlers::merit_models::get_merit_models))
+        .route("/api/merit/models", post(handlers::merit_models::create_merit_model))
+        .route("/api/merit/models/what-if", post(handlers::merit_models::merit_what_if))
+        .route("/api/merit/models/:model_id/activate", put(handlers::merit_models::activate_merit_model))
+
+        // Patrol rounds
+        .route("/api/patrol-rounds", post(handlers::patrols::create_patrol_round))
+        .route("/api/patrol-rounds/:guard_id", get(handlers::patrols::get_guard_patrol_rounds))
+
+        // Evaluation links & disputes
+        .route("/api/evaluation-links", post(handlers::evaluations::create_evaluation_link))
+        .route("/api/evaluation-links/submit", post(handlers::evaluations::submit_link_evaluation))
+        .route("/api/evaluation-links/:token", get(handlers::evaluations::get_evaluation_link))
+        .route("/api/evaluations/:evaluation_id/disputes", post(handlers::evaluations::create_evaluation_dispute))
+        .route("/api/evaluation-disputes", get(handlers::evaluations::get_evaluation_disputes))
+        .route("/api/evaluation-disputes/:dispute_id/resolve", put(handlers::evaluations::resolve_evaluation_dispute))
+
+        // Discipline & commendations
+        .route("/api/disciplinary-records", post(handlers::disciplinary::create_disciplinary_record))
+        .route("/api/disciplinary-records", get(handlers::disciplinary::get_disciplinary_records))
+        .route("/api/disciplinary-records/:record_id/appeal", post(handlers::disciplinary::appeal_disciplinary_record))
+        .route("/api/disciplinary-records/:record_id/appeal/resolve", put(handlers::disciplinary::resolve_disciplinary_appeal))
+        .route("/api/disciplinary-records/:record_id/lift", put(handlers::disciplinary::lift_disciplinary_record))
+        .route("/api/commendations", post(handlers::disciplinary::create_commendation))
+        .route("/api/commendations", get(handlers::disciplinary::get_commendations))
         
         // Armored car routes
         .route("/api/armored-cars", post(handlers::armored_cars::add_armored_car))
@@ -178,6 +332,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/car-maintenance/schedule", post(handlers::armored_cars::schedule_maintenance))
         .route("/api/car-maintenance/:maintenance_id/complete", post(handlers::armored_cars::complete_maintenance))
         .route("/api/car-maintenance/:car_id", get(handlers::armored_cars::get_car_maintenance_records))
+
+        // Vehicle service policy routes
+        .route("/api/vehicle-service-policies", get(handlers::vehicle_maintenance::get_service_policies))
+        .route("/api/vehicle-service-policies/:model", put(handlers::vehicle_maintenance::upsert_service_policy))
+        .route("/api/vehicle-service-policies/:model", delete(handlers::vehicle_maintenance::delete_service_policy))
+        .route("/api/vehicle-service/due", get(handlers::vehicle_maintenance::get_service_due))
+        .route("/api/armored-cars/:id/service-status", get(handlers::vehicle_maintenance::get_car_service_status))
+
+        // Vehicle status routes
+        .route("/api/vehicle-statuses", get(handlers::vehicle_status::get_vehicle_statuses))
+        .route("/api/armored-cars/:id/status", put(handlers::vehicle_status::set_car_status))
+        .route("/api/armored-cars/:id/status-history", get(handlers::vehicle_status::get_car_status_history))
+
+        // Vehicle registration and insurance compliance routes
+        .route("/api/armored-cars/expiring", get(handlers::vehicle_compliance::get_expiring_vehicle_documents))
+        .route("/api/armored-cars/expired", get(handlers::vehicle_compliance::get_expired_vehicle_documents))
+        .route("/api/armored-cars/:id/compliance", get(handlers::vehicle_compliance::get_car_compliance))
+        .route("/api/armored-cars/:id/compliance-overrides", post(handlers::vehicle_compliance::create_compliance_override))
+        .route("/api/vehicle-compliance-overrides/:override_id/revoke", put(handlers::vehicle_compliance::revoke_compliance_override))
         
         // Driver assignment routes
         .route("/api/driver-assignment/assign", post(handlers::armored_cars::assign_driver))
@@ -196,15 +369,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/trip-management/:trip_id/status", put(handlers::trip_management::update_trip_status))
         .route("/api/trip-management/assign-driver", post(handlers::trip_management::assign_driver_to_trip))
         .route("/api/trip-management/driver-assignments", get(handlers::trip_management::get_driver_assignments))
+
+        // Vehicle telemetry routes
+        .route("/api/telemetry/ingest", post(handlers::telemetry::ingest_telemetry))
+        .route("/api/telemetry/live", get(handlers::telemetry::get_live_positions))
+        .route("/api/telemetry/trips/:trip_id/track", get(handlers::telemetry::get_trip_track))
+        // Trip crew routes
+        .route("/api/trips/:trip_id/crew", put(handlers::trip_crew::set_trip_crew))
+        .route("/api/trips/:trip_id/crew", get(handlers::trip_crew::get_trip_crew))
+        // Driver qualification routes
+        .route("/api/users/:user_id/driver-qualifications", get(handlers::driver_qualifications::get_driver_qualifications))
+        .route("/api/users/:user_id/driver-qualifications", put(handlers::driver_qualifications::set_driver_qualifications))
+        // Consignment routes
+        .route("/api/trips/:trip_id/consignments", post(handlers::consignments::create_consignment))
+        .route("/api/trips/:trip_id/consignments", get(handlers::consignments::get_trip_consignments))
+        .route("/api/trips/:trip_id/proof-of-delivery", get(handlers::consignments::get_proof_of_delivery))
+        .route("/api/consignments/:consignment_id", get(handlers::consignments::get_consignment))
+        .route("/api/consignments/:consignment_id/handovers", post(handlers::consignments::record_handover))
+
+        // Trip route and alert routes
+        .route("/api/trips/:trip_id/route", put(handlers::trip_monitoring::set_trip_route))
+        .route("/api/trips/:trip_id/route", get(handlers::trip_monitoring::get_trip_route))
+        .route("/api/trip-alerts", get(handlers::trip_monitoring::get_trip_alerts))
+        .route("/api/trip-alerts/:alert_id/acknowledge", put(handlers::trip_monitoring::acknowledge_trip_alert))
+        .route("/api/trip-alerts/:alert_id/review", put(handlers::trip_monitoring::review_trip_alert))
         
         // Analytics routes
         .route("/api/analytics", get(handlers::analytics::get_analytics))
         .route("/api/analytics/trends", get(handlers::analytics::get_performance_trends))
         .route("/api/analytics/mission-status", put(handlers::analytics::update_mission_status))
         
+        // Background job scheduler routes
+        .route("/api/admin/jobs", get(handlers::scheduled_jobs::get_scheduled_jobs))
+        .route("/api/admin/jobs/failures", get(handlers::scheduled_jobs::get_failed_job_runs))
+        .route("/api/admin/jobs/:name", put(handlers::scheduled_jobs::update_scheduled_job))
+        .route("/api/admin/jobs/:name/runs", get(handlers::scheduled_jobs::get_job_runs))
+        .route("/api/admin/jobs/:name/run", post(handlers::scheduled_jobs::trigger_job))
+        
         // Health check
         .route("/api/health", get(handlers::health::health_check))
         
+        .layer(Extension(notification_hub))
         .layer(cors_layer)
         .layer(TraceLayer::new_for_http())
         .layer(DefaultBodyLimit::max(1024 * 1024)) // 1MB limit
@@ -270,6 +270,47 @@ pub struct SetAvailabilityRequest {
     pub available_to: Option<DateTime<Utc>>,
     pub notes: Option<String>,
 }
+// Armored car status enum; transitions are enforced in handlers::vehicle_status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VehicleStatus {
+    Available,
+    Allocated,
+    Deployed,
+    Maintenance,
+    OutOfService,
+}
+
+impl VehicleStatus {
+    pub const ALL: [VehicleStatus; 5] = [
+        VehicleStatus::Available,
+        VehicleStatus::Allocated,
+        VehicleStatus::Deployed,
+        VehicleStatus::Maintenance,
+        VehicleStatus::OutOfService,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VehicleStatus::Available => "available",
+            VehicleStatus::Allocated => "allocated",
+            VehicleStatus::Deployed => "deployed",
+            VehicleStatus::Maintenance => "maintenance",
+            VehicleStatus::OutOfService => "out_of_service",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<VehicleStatus> {
+        VehicleStatus::ALL.into_iter().find(|status| status.as_str() == value)
+    }
+}
+
+impl std::fmt::Display for VehicleStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 // Armored Car models
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct ArmoredCar {
@@ -286,6 +327,10 @@ pub struct ArmoredCar {
     pub mileage: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Notified about registration and insurance renewals (admins when unset)
+    pub fleet_manager_id: Option<String>,
+    /// License class a driver must hold to drive the car (any license when unset)
+    pub required_license_class: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -298,15 +343,21 @@ pub struct CreateArmoredCarRequest {
     pub capacity_kg: i32,
     pub registration_expiry: Option<DateTime<Utc>>,
     pub insurance_expiry: Option<DateTime<Utc>>,
+    pub required_license_class: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateArmoredCarRequest {
-    pub status: Option<String>,
+    /// A status change needs `changed_by` (a supervisor) and `reason`, as on PUT /api/armored-cars/:id/status
+    pub status: Option<VehicleStatus>,
+    pub changed_by: Option<String>,
+    pub reason: Option<String>,
     pub mileage: Option<i32>,
     pub registration_expiry: Option<DateTime<Utc>>,
     pub insurance_expiry: Option<DateTime<Utc>>,
+    pub fleet_manager_id: Option<String>,
+    pub required_license_class: Option<String>,
 }
 
 // Car Allocation model
@@ -329,6 +380,7 @@ pub struct CarAllocation {
 pub struct IssueCarRequest {
     pub car_id: String,
     pub client_id: String,
+    pub issued_by: Option<String>,
     pub expected_return_date: Option<DateTime<Utc>>,
     pub notes: Option<String>,
 }
@@ -353,6 +405,10 @@ pub struct CarMaintenance {
     pub notes: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
+    /// Service policy that generated this row (auto-generated rows only)
+    pub policy_id: Option<String>,
+    pub due_mileage: Option<i32>,
+    pub auto_generated: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -412,6 +468,10 @@ pub struct CreateTripRequest {
     pub allocation_id: Option<String>,
     pub start_location: String,
     pub mission_details: Option<String>,
+    /// The trip starts at once, so its crew manifest is given here and frozen
+    #[serde(default)]
+    pub escorts: Vec<CrewEscortInput>,
+    pub custodian_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -495,10 +555,45 @@ pub struct Notification {
     pub notification_type: String,
     pub related_shift_id: Option<String>,
     pub read: bool,
+    pub template_key: Option<String>,
+    /// What the notification is about, e.g. `shift` / `disciplinary_record`
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    /// Template parameters (a JSON object), selected as text
+    #[serde(serialize_with = "serialize_json_text")]
+    pub params: Option<String>,
+    /// How many repeats were merged into this notification
+    pub group_count: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub archived_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Emit a JSON column read as text as the JSON value itself.
+fn serialize_json_text<S: serde::Serializer>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    let parsed = value
+        .as_deref()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(text).ok());
+    serde::Serialize::serialize(&parsed, serializer)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationListQuery {
+    /// List archived notifications instead of current ones
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationStreamQuery {
+    /// Stream token from login; EventSource cannot send an Authorization header
+    pub token: Option<String>,
+    /// Resume after this notification id (the `Last-Event-ID` header takes precedence)
+    pub last_event_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateNotificationRequest {
@@ -562,6 +657,12 @@ pub struct GuardMeritScore {
     pub last_calculated_at: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
+    pub incident_score: Option<f64>,
+    pub patrol_score: Option<f64>,
+    pub training_score: Option<f64>,
+    /// Merit model version the score was calculated with
+    pub model_version: Option<i32>,
+    pub conduct_adjustment: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -586,21 +687,25 @@ pub struct ClientEvaluation {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
+    /// Set when submitted through the client portal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_user_id: Option<String>,
+    /// True for evaluations submitted by a client account or evaluation link
+    pub verified: bool,
+    /// 'active', 'disputed', 'overturned'
+    pub status: String,
 }
 
+/// Evaluations must come from a client portal account; clients without an
+/// account use a one-time evaluation link instead.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateClientEvaluationRequest {
-    pub guard_id: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_user_id: Option<String>,
     pub shift_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Trip id of a completed armored mission
     pub mission_id: Option<String>,
-    pub evaluator_name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub evaluator_role: Option<String>,
     pub rating: f64,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
 }
 
@@ -629,7 +734,43 @@ pub struct MeritScoreResponse {
     pub attendance_score: f64,
     pub punctuality_score: f64,
     pub client_rating: f64,
+    pub incident_score: f64,
+    pub patrol_score: f64,
+    pub training_score: f64,
+    /// Commendation bonus minus disciplinary penalties, already included in overall_score
+    pub conduct_adjustment: f64,
+    pub model_version: Option<i32>,
     pub stats: MeritStats,
+    /// Rolling 30/90/365-day scores with trend against the preceding window
+    pub windows: Vec<MeritWindowScore>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritWindowScore {
+    pub window_days: i32,
+    pub model_version: i32,
+    pub overall_score: f64,
+    pub rank: String,
+    pub punctuality_score: f64,
+    pub average_client_rating: Option<f64>,
+    /// Shifts, punctuality records, evaluations and patrol rounds inside the window
+    pub activity_count: i32,
+    pub previous_score: Option<f64>,
+    /// 'up', 'down' or 'steady'; unset when the preceding window had no activity
+    pub trend: Option<String>,
+    pub calculated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritRankingQuery {
+    /// 30, 90 or 365; all-time scores when omitted
+    pub window: Option<i32>,
+    /// Client site name the guard has worked at
+    pub site: Option<String>,
+    /// Gold, Silver, Bronze or Standard
+    pub tier: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -653,6 +794,8 @@ pub struct RankedGuardResponse {
     pub merit_rank: Option<String>,
     pub on_time_percentage: f64,
     pub client_rating: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trend: Option<String>,
 }
 
 // ── Requirement 3: Firearm Maintenance ──────────────────────────────────────
@@ -723,3 +866,1835 @@ pub struct CreateTrainingRecordRequest {
 }
 
 
+
+// ── Training Catalog, Sessions & Enrolment ──────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TrainingCourse {
+    pub id: String,
+    pub code: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Matches `training_records.training_type` (e.g. 'firearms_handling')
+    pub training_type: String,
+    /// How long a completion stays valid; NULL means it never expires
+    pub validity_months: Option<i32>,
+    pub duration_hours: Option<f64>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTrainingCourseRequest {
+    pub code: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub training_type: String,
+    pub validity_months: Option<i32>,
+    pub duration_hours: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateTrainingCourseRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub validity_months: Option<i32>,
+    pub duration_hours: Option<f64>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TrainingSession {
+    pub id: String,
+    pub course_id: String,
+    pub instructor_id: Option<String>,
+    pub instructor_name: Option<String>,
+    pub location: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub capacity: i32,
+    pub status: String,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTrainingSessionRequest {
+    pub course_id: String,
+    pub instructor_id: Option<String>,
+    pub instructor_name: Option<String>,
+    pub location: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub capacity: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TrainingEnrollment {
+    pub id: String,
+    pub session_id: String,
+    pub guard_id: String,
+    /// 'enrolled', 'waitlisted', 'cancelled', 'attended', 'absent', 'completed'
+    pub status: String,
+    pub waitlist_position: Option<i32>,
+    /// 'manual' or 'auto_renewal'
+    pub source: String,
+    pub attendance_marked_at: Option<DateTime<Utc>>,
+    pub training_record_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnrollGuardRequest {
+    pub guard_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkTrainingAttendanceRequest {
+    pub guard_id: String,
+    pub attended: bool,
+}
+
+// ── Background Job Scheduler ────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledJob {
+    pub name: String,
+    pub description: Option<String>,
+    pub interval_seconds: i32,
+    pub enabled: bool,
+    pub next_run_at: DateTime<Utc>,
+    pub locked_by: Option<String>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    pub last_result: Option<String>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledJobRun {
+    pub id: String,
+    pub job_name: String,
+    /// 'schedule' or 'manual'
+    pub trigger: String,
+    pub instance_id: String,
+    /// 'running', 'succeeded', 'failed'
+    pub status: String,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateScheduledJobRequest {
+    pub interval_seconds: Option<i32>,
+    pub enabled: Option<bool>,
+}
+
+// ── Client Sites ────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientSite {
+    pub id: String,
+    /// Matches `shifts.client_site`
+    pub name: String,
+    pub address: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub supervisor_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Billing client that owns the site
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClientSiteRequest {
+    pub name: String,
+    pub address: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub supervisor_id: Option<String>,
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateClientSiteRequest {
+    pub address: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub supervisor_id: Option<String>,
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateGuardLocationRequest {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+// ── Replacement Offers ──────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacementOffer {
+    pub id: String,
+    pub shift_id: String,
+    pub guard_id: String,
+    pub wave: i32,
+    pub rank_in_wave: i32,
+    pub merit_score: Option<f64>,
+    pub distance_km: Option<f64>,
+    /// 'pending', 'accepted', 'declined', 'expired', 'superseded'
+    pub status: String,
+    pub notification_id: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RespondReplacementOfferRequest {
+    pub guard_id: String,
+}
+
+// ── Availability Calendar ───────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyAvailabilityWindow {
+    pub id: String,
+    pub guard_id: String,
+    /// 0 = Sunday … 6 = Saturday
+    pub day_of_week: i16,
+    pub start_time: chrono::NaiveTime,
+    /// Earlier than or equal to `start_time` for overnight windows
+    pub end_time: chrono::NaiveTime,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyAvailabilityWindowInput {
+    pub day_of_week: i16,
+    /// "HH:MM"
+    pub start_time: String,
+    pub end_time: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetWeeklyAvailabilityRequest {
+    pub windows: Vec<WeeklyAvailabilityWindowInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaveRequest {
+    pub id: String,
+    pub guard_id: String,
+    /// 'vacation', 'sick', 'emergency', 'other'
+    pub leave_type: String,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub reason: Option<String>,
+    /// 'pending', 'approved', 'rejected', 'cancelled'
+    pub status: String,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateLeaveRequest {
+    pub guard_id: String,
+    pub leave_type: String,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewLeaveRequest {
+    pub reviewer_id: String,
+    pub approved: bool,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelLeaveRequest {
+    pub guard_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct BlackoutDate {
+    pub id: String,
+    pub guard_id: String,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateBlackoutDateRequest {
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarRangeQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+// ── Shift Swaps ─────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ShiftSwap {
+    pub id: String,
+    pub shift_id: String,
+    pub posted_by: String,
+    /// 'giveaway' or 'swap'
+    pub swap_type: String,
+    /// 'open', 'claimed', 'approved', 'rejected', 'cancelled'
+    pub status: String,
+    pub notes: Option<String>,
+    pub claimed_by: Option<String>,
+    /// For swaps, the claimant's shift handed to the poster in exchange
+    pub claim_shift_id: Option<String>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ShiftSwapEvent {
+    pub id: String,
+    pub swap_id: String,
+    pub actor_id: String,
+    /// 'posted', 'claimed', 'claim_withdrawn', 'approved', 'rejected', 'cancelled'
+    pub action: String,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateShiftSwapRequest {
+    pub shift_id: String,
+    pub guard_id: String,
+    pub swap_type: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimShiftSwapRequest {
+    pub guard_id: String,
+    /// Required for 'swap' posts
+    pub claim_shift_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShiftSwapActionRequest {
+    pub guard_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewShiftSwapRequest {
+    pub reviewer_id: String,
+    pub approved: bool,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShiftSwapQuery {
+    pub status: Option<String>,
+}
+
+// ── Timesheets & Payroll ────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PayRateRule {
+    pub id: String,
+    pub name: String,
+    /// Used when the guard has no `users.hourly_rate`
+    pub base_hourly_rate: f64,
+    pub currency: String,
+    /// Hours per work day paid at the regular rate; the rest is overtime
+    pub regular_hours_per_day: f64,
+    pub overtime_multiplier: f64,
+    pub night_diff_start: chrono::NaiveTime,
+    pub night_diff_end: chrono::NaiveTime,
+    /// Premium on top of the hourly rate, e.g. 0.10 = +10%
+    pub night_diff_premium: f64,
+    pub regular_holiday_multiplier: f64,
+    pub special_holiday_multiplier: f64,
+    pub active: bool,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePayRateRuleRequest {
+    pub name: String,
+    pub base_hourly_rate: f64,
+    pub currency: Option<String>,
+    pub regular_hours_per_day: Option<f64>,
+    pub overtime_multiplier: Option<f64>,
+    /// "HH:MM"
+    pub night_diff_start: Option<String>,
+    pub night_diff_end: Option<String>,
+    pub night_diff_premium: Option<f64>,
+    pub regular_holiday_multiplier: Option<f64>,
+    pub special_holiday_multiplier: Option<f64>,
+    pub created_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Holiday {
+    pub id: String,
+    pub holiday_date: chrono::NaiveDate,
+    pub name: String,
+    /// 'regular' or 'special'
+    pub holiday_type: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateHolidayRequest {
+    pub holiday_date: chrono::NaiveDate,
+    pub name: String,
+    pub holiday_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Timesheet {
+    pub id: String,
+    pub guard_id: String,
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub rate_rule_id: String,
+    pub hourly_rate: f64,
+    pub total_hours: f64,
+    pub regular_hours: f64,
+    pub overtime_hours: f64,
+    pub night_diff_hours: f64,
+    pub holiday_hours: f64,
+    pub regular_pay: f64,
+    pub overtime_pay: f64,
+    pub night_diff_pay: f64,
+    pub holiday_pay: f64,
+    pub gross_pay: f64,
+    /// 'pending_approval', 'approved', 'rejected'
+    pub status: String,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_notes: Option<String>,
+    pub generated_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetEntry {
+    pub id: String,
+    pub timesheet_id: String,
+    pub attendance_id: String,
+    pub shift_id: String,
+    pub work_date: chrono::NaiveDate,
+    /// Local (scheduling timezone) check-in and check-out
+    pub check_in: chrono::NaiveDateTime,
+    pub check_out: chrono::NaiveDateTime,
+    pub hours: f64,
+    pub regular_hours: f64,
+    pub overtime_hours: f64,
+    pub night_diff_hours: f64,
+    pub holiday_hours: f64,
+    pub holiday_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateTimesheetsRequest {
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub guard_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewTimesheetRequest {
+    pub reviewer_id: String,
+    pub approved: bool,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetQuery {
+    pub period_start: Option<chrono::NaiveDate>,
+    pub period_end: Option<chrono::NaiveDate>,
+    pub status: Option<String>,
+    pub guard_id: Option<String>,
+}
+
+// ── Client Billing ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Client {
+    pub id: String,
+    pub name: String,
+    pub billing_email: Option<String>,
+    pub billing_address: Option<String>,
+    pub contact_phone: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClientRequest {
+    pub name: String,
+    pub billing_email: Option<String>,
+    pub billing_address: Option<String>,
+    pub contact_phone: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateClientRequest {
+    pub billing_email: Option<String>,
+    pub billing_address: Option<String>,
+    pub contact_phone: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientContract {
+    pub id: String,
+    pub client_id: String,
+    pub name: String,
+    pub currency: String,
+    pub guard_hourly_rate: f64,
+    /// Flat charge per completed armored trip
+    pub trip_rate: f64,
+    pub per_km_rate: f64,
+    /// Each attended shift is billed at least this many hours
+    pub minimum_hours_per_shift: f64,
+    /// Invoices below this subtotal get a top-up line
+    pub minimum_invoice_amount: f64,
+    /// Percent added to guard hours worked 22:00-06:00
+    pub night_surcharge_pct: f64,
+    /// Percent added to guard hours worked on holidays
+    pub holiday_surcharge_pct: f64,
+    pub payment_terms_days: i32,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: Option<chrono::NaiveDate>,
+    /// 'active' or 'terminated'
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClientContractRequest {
+    pub name: String,
+    pub currency: Option<String>,
+    pub guard_hourly_rate: f64,
+    pub trip_rate: Option<f64>,
+    pub per_km_rate: Option<f64>,
+    pub minimum_hours_per_shift: Option<f64>,
+    pub minimum_invoice_amount: Option<f64>,
+    pub night_surcharge_pct: Option<f64>,
+    pub holiday_surcharge_pct: Option<f64>,
+    pub payment_terms_days: Option<i32>,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: Option<chrono::NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateClientContractRequest {
+    pub guard_hourly_rate: Option<f64>,
+    pub trip_rate: Option<f64>,
+    pub per_km_rate: Option<f64>,
+    pub minimum_hours_per_shift: Option<f64>,
+    pub minimum_invoice_amount: Option<f64>,
+    pub night_surcharge_pct: Option<f64>,
+    pub holiday_surcharge_pct: Option<f64>,
+    pub payment_terms_days: Option<i32>,
+    pub end_date: Option<chrono::NaiveDate>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Invoice {
+    pub id: String,
+    /// Assigned when the invoice is issued
+    pub invoice_number: Option<String>,
+    pub client_id: String,
+    pub contract_id: String,
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub currency: String,
+    pub subtotal: f64,
+    pub total: f64,
+    /// 'draft', 'issued', 'paid'
+    pub status: String,
+    pub issued_at: Option<DateTime<Utc>>,
+    pub due_date: Option<chrono::NaiveDate>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub payment_reference: Option<String>,
+    pub notes: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct InvoiceLineItem {
+    pub id: String,
+    pub invoice_id: String,
+    /// 'guard_hours', 'night_surcharge', 'holiday_surcharge', 'trip', 'minimum_charge'
+    pub line_type: String,
+    pub description: String,
+    pub service_date: Option<chrono::NaiveDate>,
+    pub quantity: f64,
+    pub unit_price: f64,
+    pub amount: f64,
+    pub attendance_id: Option<String>,
+    pub shift_id: Option<String>,
+    pub trip_id: Option<String>,
+    pub guard_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateInvoiceRequest {
+    pub contract_id: String,
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub created_by: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkInvoicePaidRequest {
+    pub payment_reference: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvoiceQuery {
+    pub client_id: Option<String>,
+    pub status: Option<String>,
+}
+
+// ── Client Portal ───────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClientUserRequest {
+    pub email: String,
+    pub username: String,
+    pub password: String,
+    pub full_name: String,
+    pub phone_number: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientUser {
+    pub id: String,
+    pub email: String,
+    pub username: String,
+    pub full_name: String,
+    pub phone_number: String,
+    pub client_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Incident {
+    pub id: String,
+    /// Matches `client_sites.name`; empty for an SOS raised away from any site
+    pub client_site: Option<String>,
+    pub shift_id: Option<String>,
+    pub reported_by: String,
+    /// 'low', 'medium', 'high', 'critical'
+    pub severity: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// 'open', 'investigating', 'resolved'
+    pub status: String,
+    pub occurred_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// 'report' or 'sos'
+    pub incident_type: String,
+    pub trip_id: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    /// Escalation chain level reached by an SOS (1 = first responders)
+    pub escalation_level: i32,
+    /// When an unacknowledged SOS moves to the next level; null once acknowledged or at the last level
+    pub next_escalation_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct IncidentEvent {
+    pub id: String,
+    pub incident_id: String,
+    /// 'reported', 'sos_raised', 'sos_repeated', 'notified', 'escalated', 'acknowledged',
+    /// 'location_update', 'status_changed'
+    pub event_type: String,
+    pub actor_id: Option<String>,
+    pub escalation_level: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateIncidentRequest {
+    pub client_site: String,
+    pub shift_id: Option<String>,
+    pub reported_by: String,
+    pub severity: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub occurred_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateIncidentStatusRequest {
+    pub status: String,
+    pub updated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncidentQuery {
+    pub client_site: Option<String>,
+    pub status: Option<String>,
+    pub incident_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortalEvaluationRequest {
+    pub shift_id: Option<String>,
+    pub mission_id: Option<String>,
+    pub rating: f64,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceRequest {
+    pub id: String,
+    pub client_id: String,
+    pub requested_by: String,
+    pub client_site_id: Option<String>,
+    /// 'guard_service', 'mission', 'escort', 'other'
+    pub request_type: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub requested_start: DateTime<Utc>,
+    pub requested_end: DateTime<Utc>,
+    pub guards_required: i32,
+    pub vehicles_required: i32,
+    /// 'low', 'normal', 'high', 'urgent'
+    pub priority: String,
+    /// 'pending', 'acknowledged', 'scheduled', 'completed', 'rejected', 'cancelled'
+    pub status: String,
+    pub dispatcher_id: Option<String>,
+    pub dispatcher_notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateServiceRequest {
+    pub client_site_id: Option<String>,
+    pub request_type: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub requested_start: DateTime<Utc>,
+    pub requested_end: DateTime<Utc>,
+    pub guards_required: Option<i32>,
+    pub vehicles_required: Option<i32>,
+    pub priority: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DispatchServiceRequest {
+    pub dispatcher_id: String,
+    pub status: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceRequestQuery {
+    pub status: Option<String>,
+}
+
+// ── Evaluation Links & Disputes ─────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationLink {
+    pub id: String,
+    pub shift_id: Option<String>,
+    pub mission_id: Option<String>,
+    pub client_id: String,
+    pub guard_id: String,
+    pub evaluator_email: Option<String>,
+    pub created_by: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub evaluation_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateEvaluationLinkRequest {
+    pub shift_id: Option<String>,
+    pub mission_id: Option<String>,
+    pub created_by: String,
+    pub evaluator_email: Option<String>,
+    pub expires_in_hours: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitLinkEvaluationRequest {
+    pub token: String,
+    pub rating: f64,
+    pub comment: Option<String>,
+    pub evaluator_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationDispute {
+    pub id: String,
+    pub evaluation_id: String,
+    pub guard_id: String,
+    pub reason: String,
+    /// 'open', 'accepted' (rating removed), 'rejected' (rating stands)
+    pub status: String,
+    pub reviewed_by: Option<String>,
+    pub review_notes: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateEvaluationDisputeRequest {
+    pub guard_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveEvaluationDisputeRequest {
+    pub reviewer_id: String,
+    /// 'accepted' or 'rejected'
+    pub outcome: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationDisputeQuery {
+    pub status: Option<String>,
+}
+
+// ── Merit Models ────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritModel {
+    pub id: String,
+    pub version: i32,
+    pub name: String,
+    pub attendance_weight: f64,
+    pub punctuality_weight: f64,
+    pub client_rating_weight: f64,
+    pub incident_weight: f64,
+    pub patrol_weight: f64,
+    pub training_weight: f64,
+    pub gold_min: f64,
+    pub silver_min: f64,
+    pub bronze_min: f64,
+    /// Events this many days old count half as much; no decay when unset
+    pub decay_half_life_days: Option<f64>,
+    pub is_active: bool,
+    pub notes: Option<String>,
+    pub created_by: Option<String>,
+    pub activated_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl From<&MeritModel> for MeritModelSpec {
+    fn from(model: &MeritModel) -> Self {
+        MeritModelSpec {
+            attendance_weight: model.attendance_weight,
+            punctuality_weight: model.punctuality_weight,
+            client_rating_weight: model.client_rating_weight,
+            incident_weight: model.incident_weight,
+            patrol_weight: model.patrol_weight,
+            training_weight: model.training_weight,
+            gold_min: model.gold_min,
+            silver_min: model.silver_min,
+            bronze_min: model.bronze_min,
+            decay_half_life_days: model.decay_half_life_days,
+        }
+    }
+}
+
+/// Factor weights (percentages summing to 100) and rank cutoffs.
+/// A factor with weight 0 is left out of the score.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritModelSpec {
+    #[serde(default)]
+    pub attendance_weight: f64,
+    #[serde(default)]
+    pub punctuality_weight: f64,
+    #[serde(default)]
+    pub client_rating_weight: f64,
+    #[serde(default)]
+    pub incident_weight: f64,
+    #[serde(default)]
+    pub patrol_weight: f64,
+    #[serde(default)]
+    pub training_weight: f64,
+    pub gold_min: f64,
+    pub silver_min: f64,
+    pub bronze_min: f64,
+    #[serde(default)]
+    pub decay_half_life_days: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMeritModelRequest {
+    pub name: String,
+    pub created_by: String,
+    pub notes: Option<String>,
+    /// Make this version the active model immediately
+    pub activate: Option<bool>,
+    #[serde(flatten)]
+    pub spec: MeritModelSpec,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivateMeritModelRequest {
+    pub activated_by: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritWhatIfRequest {
+    /// Limit to these guards; all guards when omitted
+    pub guard_ids: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub spec: MeritModelSpec,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritScoreSnapshot {
+    pub id: String,
+    pub guard_id: String,
+    pub model_id: String,
+    pub model_version: i32,
+    pub attendance_score: f64,
+    pub punctuality_score: f64,
+    pub client_rating: f64,
+    pub incident_score: f64,
+    pub patrol_score: f64,
+    pub training_score: f64,
+    pub conduct_adjustment: f64,
+    pub overall_score: f64,
+    pub rank: String,
+    pub calculated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PatrolRound {
+    pub id: String,
+    pub guard_id: String,
+    pub shift_id: Option<String>,
+    pub checkpoints_expected: i32,
+    pub checkpoints_completed: i32,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub notes: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePatrolRoundRequest {
+    pub guard_id: String,
+    pub shift_id: Option<String>,
+    pub checkpoints_expected: i32,
+    pub checkpoints_completed: i32,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritRecalculationRun {
+    pub id: String,
+    /// 'manual' or 'schedule'
+    pub trigger: String,
+    pub requested_by: Option<String>,
+    /// 'running', 'completed', 'failed'
+    pub status: String,
+    pub model_version: Option<i32>,
+    pub total_guards: i32,
+    pub processed_guards: i32,
+    pub succeeded: i32,
+    pub failed: i32,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MeritRecalculationError {
+    pub id: String,
+    pub run_id: String,
+    pub guard_id: String,
+    pub error: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartMeritRecalculationRequest {
+    pub requested_by: String,
+}
+
+// ── Discipline & Commendations ──────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DisciplinaryRecord {
+    pub id: String,
+    pub guard_id: String,
+    /// 'verbal_warning', 'written_warning', 'final_warning', 'suspension'
+    pub action_type: String,
+    /// 'low', 'medium', 'high', 'critical'
+    pub severity: String,
+    pub infraction: String,
+    pub description: Option<String>,
+    pub evidence_urls: Vec<String>,
+    pub incident_id: Option<String>,
+    pub issued_by: Option<String>,
+    pub effective_from: DateTime<Utc>,
+    /// Open-ended when unset (indefinite suspension)
+    pub effective_until: Option<DateTime<Utc>>,
+    /// 'none', 'pending', 'upheld', 'overturned'
+    pub appeal_status: String,
+    pub appeal_reason: Option<String>,
+    pub appealed_at: Option<DateTime<Utc>>,
+    pub appeal_reviewed_by: Option<String>,
+    pub appeal_notes: Option<String>,
+    pub appeal_resolved_at: Option<DateTime<Utc>>,
+    pub lifted_by: Option<String>,
+    pub lifted_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateDisciplinaryRecordRequest {
+    pub guard_id: String,
+    pub action_type: String,
+    pub severity: String,
+    pub infraction: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub evidence_urls: Vec<String>,
+    pub incident_id: Option<String>,
+    pub issued_by: String,
+    /// Defaults to now
+    pub effective_from: Option<DateTime<Utc>>,
+    /// Warnings default to 90/180/365 days; suspensions without an end are indefinite
+    pub effective_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisciplinaryRecordQuery {
+    pub guard_id: Option<String>,
+    /// Only records in effect now and not overturned
+    pub active_only: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppealDisciplinaryRecordRequest {
+    pub guard_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveDisciplinaryAppealRequest {
+    pub reviewed_by: String,
+    /// 'upheld' or 'overturned'
+    pub outcome: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiftDisciplinaryRecordRequest {
+    pub lifted_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Commendation {
+    pub id: String,
+    pub guard_id: String,
+    /// 'commendation' or 'award'
+    pub level: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub evidence_urls: Vec<String>,
+    pub awarded_by: Option<String>,
+    pub awarded_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCommendationRequest {
+    pub guard_id: String,
+    pub level: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub evidence_urls: Vec<String>,
+    pub awarded_by: String,
+    /// Defaults to one year after the award
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommendationQuery {
+    pub guard_id: Option<String>,
+}
+
+// ── Notification Delivery ───────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPreference {
+    pub notification_type: String,
+    pub email_enabled: bool,
+    pub sms_enabled: bool,
+    pub push_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateNotificationPreferencesRequest {
+    /// "HH:MM" in `timezone`; both or neither. Omit both to turn quiet hours off
+    pub quiet_hours_start: Option<String>,
+    pub quiet_hours_end: Option<String>,
+    /// IANA name; defaults to the scheduling timezone
+    pub timezone: Option<String>,
+    /// Locale for notification text, e.g. "en" or "fil"; defaults to "en"
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub preferences: Vec<NotificationPreference>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PushDevice {
+    pub id: String,
+    pub user_id: String,
+    pub device_token: String,
+    /// 'web', 'android', 'ios'
+    pub platform: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterPushDeviceRequest {
+    pub device_token: String,
+    pub platform: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationDelivery {
+    pub id: String,
+    pub notification_id: String,
+    pub user_id: String,
+    /// 'email', 'sms', 'push'
+    pub channel: String,
+    pub destination: Option<String>,
+    /// 'pending', 'deferred' (quiet hours), 'sending', 'sent', 'failed', 'skipped'
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub provider: Option<String>,
+    pub provider_message_id: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// ── Notification Templates ──────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationTemplate {
+    pub template_key: String,
+    pub locale: String,
+    pub title: String,
+    pub body: String,
+    /// Edited by a supervisor; startup registration leaves it alone
+    pub customized: bool,
+    pub updated_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationTemplateQuery {
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateNotificationTemplateRequest {
+    /// `{name}` placeholders must be among the template's parameters
+    pub title: String,
+    pub body: String,
+    pub updated_by: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetNotificationTemplateRequest {
+    pub updated_by: String,
+}
+
+// ── Vehicle Telemetry ───────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryPoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub speed_kph: Option<f64>,
+    /// Degrees clockwise from north, 0-360
+    pub heading: Option<f64>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestTelemetryRequest {
+    pub car_id: String,
+    pub points: Vec<TelemetryPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VehiclePosition {
+    pub car_id: String,
+    pub trip_id: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub speed_kph: Option<f64>,
+    pub heading: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveTripPosition {
+    pub trip_id: String,
+    pub car_id: String,
+    pub license_plate: Option<String>,
+    pub driver_id: String,
+    pub driver_name: Option<String>,
+    pub trip_status: String,
+    pub destination: Option<String>,
+    pub start_time: DateTime<Utc>,
+    /// None until the vehicle's tracker reports during this trip
+    pub recorded_at: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub speed_kph: Option<f64>,
+    pub heading: Option<f64>,
+    /// No point within TELEMETRY_STALE_SECS
+    pub stale: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TripTrackQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Evenly thin the track to at most this many points (default 2000)
+    pub max_points: Option<i64>,
+}
+
+// ── Trip Routes & Alerts ────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteWaypoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TripRoute {
+    pub trip_id: String,
+    #[serde(skip)]
+    pub waypoint_latitudes: Vec<f64>,
+    #[serde(skip)]
+    pub waypoint_longitudes: Vec<f64>,
+    /// Allowed distance either side of the planned route
+    pub corridor_meters: f64,
+    /// Longest stop outside an allowed stop before dispatch is alerted
+    pub max_stop_minutes: i32,
+    /// Within this distance of the last waypoint counts as arrived
+    pub arrival_radius_meters: f64,
+    pub planned_arrival_at: Option<DateTime<Utc>>,
+    pub arrived_at: Option<DateTime<Utc>>,
+    pub planned_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl TripRoute {
+    pub fn waypoints(&self) -> Vec<RouteWaypoint> {
+        self.waypoint_latitudes
+            .iter()
+            .zip(&self.waypoint_longitudes)
+            .map(|(&lat, &lon)| RouteWaypoint { lat, lon })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TripAllowedStop {
+    pub id: String,
+    pub trip_id: String,
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_meters: f64,
+    pub max_minutes: i32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllowedStopInput {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_meters: Option<f64>,
+    pub max_minutes: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTripRouteRequest {
+    /// Planned path from origin to destination; the last waypoint is the destination
+    pub waypoints: Vec<RouteWaypoint>,
+    pub corridor_meters: Option<f64>,
+    pub max_stop_minutes: Option<i32>,
+    pub arrival_radius_meters: Option<f64>,
+    pub planned_arrival_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub allowed_stops: Vec<AllowedStopInput>,
+    pub planned_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TripAlert {
+    pub id: String,
+    pub trip_id: String,
+    pub car_id: String,
+    /// route_deviation, long_stop or late_arrival
+    pub alert_type: String,
+    pub priority: String,
+    pub started_at: DateTime<Utc>,
+    /// Set once the vehicle is back in the corridor, moving again or arrived
+    pub ended_at: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Furthest distance from the route (deviations)
+    pub distance_meters: Option<f64>,
+    /// Longest stop or lateness so far
+    pub duration_minutes: Option<f64>,
+    pub details: Option<String>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub review_notes: Option<String>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TripAlertQuery {
+    pub trip_id: Option<String>,
+    pub alert_type: Option<String>,
+    /// Only alerts that are still ongoing
+    pub open_only: Option<bool>,
+    /// Only alerts nobody has acknowledged
+    pub unacknowledged: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcknowledgeTripAlertRequest {
+    pub user_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewTripAlertRequest {
+    pub reviewed_by: String,
+    pub notes: String,
+}
+
+// ── SOS Alerts ──────────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RaiseSosRequest {
+    pub guard_id: String,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SosLocationRequest {
+    pub guard_id: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcknowledgeSosRequest {
+    pub user_id: String,
+    pub notes: Option<String>,
+}
+
+// ── Vehicle Maintenance ─────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleServicePolicy {
+    pub id: String,
+    /// Matches `armored_cars.model`; '*' applies to models without their own policy
+    pub model: String,
+    pub interval_km: Option<i32>,
+    pub interval_months: Option<i32>,
+    /// Service is planned this many km before it is due
+    pub lead_km: i32,
+    /// ...or this many days before it is due
+    pub lead_days: i32,
+    pub maintenance_type: String,
+    pub description: Option<String>,
+    pub updated_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpsertServicePolicyRequest {
+    pub interval_km: Option<i32>,
+    pub interval_months: Option<i32>,
+    pub lead_km: Option<i32>,
+    pub lead_days: Option<i32>,
+    pub maintenance_type: Option<String>,
+    pub description: Option<String>,
+    pub updated_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleServiceStatus {
+    pub car_id: String,
+    pub license_plate: String,
+    pub model: String,
+    pub mileage: i32,
+    pub last_maintenance_date: Option<DateTime<Utc>>,
+    pub last_maintenance_mileage: Option<i32>,
+    pub policy_id: Option<String>,
+    pub interval_km: Option<i32>,
+    pub interval_months: Option<i32>,
+    pub next_due_mileage: Option<i32>,
+    pub next_due_date: Option<DateTime<Utc>>,
+    /// 'ok', 'due_soon', 'overdue' or 'no_policy'
+    pub service_state: String,
+    /// Open policy service, if one is planned
+    pub open_maintenance_id: Option<String>,
+}
+
+// ── Vehicle Compliance ──────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleDocumentExpiry {
+    pub car_id: String,
+    pub license_plate: String,
+    /// 'registration' or 'insurance'
+    pub document: String,
+    pub expiry_date: DateTime<Utc>,
+    /// Negative once lapsed
+    pub days_remaining: i32,
+    pub fleet_manager_id: Option<String>,
+    /// Active override allowing dispatch despite the lapse
+    pub override_id: Option<String>,
+    pub override_valid_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleExpiryQuery {
+    /// Look-ahead for expiring documents (default 30)
+    pub days: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleComplianceOverride {
+    pub id: String,
+    pub car_id: String,
+    pub document: String,
+    pub reason: String,
+    pub approved_by: String,
+    pub valid_until: DateTime<Utc>,
+    pub revoked_by: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateComplianceOverrideRequest {
+    pub document: String,
+    pub reason: String,
+    pub approved_by: String,
+    /// Defaults to 24 hours from now; at most 7 days
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevokeComplianceOverrideRequest {
+    pub revoked_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleComplianceEvent {
+    pub id: String,
+    pub car_id: String,
+    pub override_id: Option<String>,
+    /// 'override_granted', 'override_used', 'override_revoked'
+    pub action: String,
+    pub actor_id: Option<String>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// ── Vehicle Status History ───────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleStatusChange {
+    pub id: String,
+    pub car_id: String,
+    /// None for the row written when the car is added to the fleet
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub reason: String,
+    pub changed_by: Option<String>,
+    pub trip_id: Option<String>,
+    pub allocation_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetVehicleStatusRequest {
+    pub status: VehicleStatus,
+    pub changed_by: String,
+    pub reason: String,
+}
+
+// ── Trip Crew ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TripCrewMember {
+    pub id: String,
+    pub trip_id: String,
+    pub user_id: String,
+    pub full_name: Option<String>,
+    /// 'driver', 'escort' or 'custodian'
+    pub crew_role: String,
+    pub firearm_allocation_id: Option<String>,
+    pub firearm_id: Option<String>,
+    pub firearm_serial_number: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrewEscortInput {
+    pub user_id: String,
+    /// The escort's active firearm allocation carried on the trip
+    pub firearm_allocation_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTripCrewRequest {
+    pub driver_id: String,
+    #[serde(default)]
+    pub escorts: Vec<CrewEscortInput>,
+    pub custodian_id: Option<String>,
+    pub updated_by: String,
+}
+
+// ── Consignments ─────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Consignment {
+    pub id: String,
+    pub trip_id: String,
+    pub reference: String,
+    pub description: Option<String>,
+    pub declared_value: f64,
+    pub currency: String,
+    pub seal_numbers: Vec<String>,
+    pub pickup_party: String,
+    pub pickup_address: Option<String>,
+    pub dropoff_party: String,
+    pub dropoff_address: Option<String>,
+    /// 'registered', 'in_transit', 'delivered'
+    pub status: String,
+    /// Result of the seal check at delivery; None until delivered
+    pub seals_intact: Option<bool>,
+    pub picked_up_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateConsignmentRequest {
+    pub reference: String,
+    pub description: Option<String>,
+    pub declared_value: f64,
+    pub currency: Option<String>,
+    pub seal_numbers: Vec<String>,
+    pub pickup_party: String,
+    pub pickup_address: Option<String>,
+    pub dropoff_party: String,
+    pub dropoff_address: Option<String>,
+    pub created_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsignmentHandover {
+    pub id: String,
+    pub consignment_id: String,
+    /// 'pickup', 'transfer', 'delivery'
+    pub stage: String,
+    pub from_party: String,
+    pub to_party: String,
+    pub signed_by_name: String,
+    /// Typed name or a PNG/JPEG data URL
+    pub signature: String,
+    pub seals_presented: Vec<String>,
+    pub broken_seals: Vec<String>,
+    pub missing_seals: Vec<String>,
+    pub unexpected_seals: Vec<String>,
+    pub seals_intact: bool,
+    pub notes: Option<String>,
+    pub recorded_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordHandoverRequest {
+    pub stage: String,
+    pub from_party: String,
+    pub to_party: String,
+    pub signed_by_name: String,
+    pub signature: String,
+    /// Seal numbers found on the consignment at hand-over
+    pub seal_numbers: Vec<String>,
+    /// Seals present but tampered with or damaged
+    #[serde(default)]
+    pub broken_seals: Vec<String>,
+    pub notes: Option<String>,
+    pub recorded_by: String,
+}
+
+// ── Driver Qualifications ────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DriverQualifications {
+    pub user_id: String,
+    pub full_name: String,
+    pub verified: bool,
+    pub license_number: Option<String>,
+    pub license_expiry_date: Option<DateTime<Utc>>,
+    /// License classes (restriction codes) on the guard's license, e.g. "B", "C"
+    pub license_classes: Vec<String>,
+    pub defensive_driving_cert_number: Option<String>,
+    pub defensive_driving_cert_expiry: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDriverQualificationsRequest {
+    pub license_classes: Option<Vec<String>>,
+    pub defensive_driving_cert_number: Option<String>,
+    pub defensive_driving_cert_expiry: Option<DateTime<Utc>>,
+    pub updated_by: String,
+}
+
+/// Driving time from trip durations, measured against the hours-of-service limits.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoursOfService {
+    pub driver_id: String,
+    pub hours_last_24h: f64,
+    pub hours_last_7_days: f64,
+    pub last_trip_ended_at: Option<DateTime<Utc>>,
+    /// Hours since the last trip ended; None when no trip ended within the last 7 days
+    pub rest_hours: Option<f64>,
+    pub active_trip_id: Option<String>,
+    pub max_daily_hours: f64,
+    pub max_weekly_hours: f64,
+    pub min_rest_hours: f64,
+    /// Planned length of the trip being checked, counted toward the daily and weekly limits
+    pub planned_trip_hours: Option<f64>,
+    /// Rules the driver would break by starting another trip now
+    pub violations: Vec<String>,
+}
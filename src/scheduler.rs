@@ -0,0 +1,329 @@
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers,
+    models::ScheduledJobRun,
+    utils,
+};
+
+/// How long a run holds its job lock. If an instance dies mid-run, another
+/// instance may take the job over once the lease has lapsed.
+const LOCK_LEASE_SECS: i64 = 900;
+
+pub struct JobDefinition {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub default_interval_secs: i32,
+}
+
+/// Every periodic job known to the scheduler. Intervals can be overridden with
+/// `JOB_<NAME>_INTERVAL_SECS` or changed at runtime through the admin API.
+pub const JOBS: &[JobDefinition] = &[
+    JobDefinition {
+        name: "no_show_sweep",
+        description: "Record no-shows past their grace period and start replacement offers",
+        default_interval_secs: 300,
+    },
+    JobDefinition {
+        name: "replacement_offers",
+        description: "Expire lapsed replacement offers, send the next wave or escalate to supervisors",
+        default_interval_secs: 60,
+    },
+    JobDefinition {
+        name: "permit_expiry",
+        description: "Expire firearm permits past their expiry date",
+        default_interval_secs: 3600,
+    },
+    JobDefinition {
+        name: "training_expiry",
+        description: "Expire training records past their expiry date",
+        default_interval_secs: 3600,
+    },
+    JobDefinition {
+        name: "training_renewals",
+        description: "Auto-enrol or notify guards whose training expires within 30 days",
+        default_interval_secs: 86400,
+    },
+    JobDefinition {
+        name: "merit_recalculation",
+        description: "Recalculate merit scores for all guards",
+        default_interval_secs: 86400,
+    },
+    JobDefinition {
+        name: "overdue_allocations",
+        description: "Notify guards and admins about overdue firearm returns",
+        default_interval_secs: 1800,
+    },
+    JobDefinition {
+        name: "notification_delivery",
+        description: "Send email/SMS/push deliveries that are due: retries and messages held for quiet hours",
+        default_interval_secs: 60,
+    },
+    JobDefinition {
+        name: "notification_archival",
+        description: "Archive expired notifications and purge those past the retention period",
+        default_interval_secs: 86400,
+    },
+    JobDefinition {
+        name: "telemetry_partitions",
+        description: "Create this month's and next month's vehicle position partitions",
+        default_interval_secs: 86400,
+    },
+    JobDefinition {
+        name: "trip_monitoring",
+        description: "Check active trips against their planned routes and raise deviation, stop and late-arrival alerts",
+        default_interval_secs: 60,
+    },
+    JobDefinition {
+        name: "sos_escalation",
+        description: "Escalate SOS alerts nobody acknowledged in time (backstop for the per-alert timer)",
+        default_interval_secs: 15,
+    },
+    JobDefinition {
+        name: "vehicle_service_planning",
+        description: "Schedule policy services for cars that are due soon or overdue by date or mileage",
+        default_interval_secs: 86400,
+    },
+    JobDefinition {
+        name: "vehicle_document_expiry",
+        description: "Remind fleet managers about vehicle registration and insurance that is expiring or lapsed",
+        default_interval_secs: 86400,
+    },
+];
+
+/// Identifies this process as a lock holder in `scheduled_jobs.locked_by`.
+pub fn instance_id() -> &'static str {
+    static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+    INSTANCE_ID.get_or_init(|| {
+        let host = std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("RAILWAY_REPLICA_ID"))
+            .unwrap_or_else(|_| "instance".to_string());
+        format!("{}-{}", host, utils::generate_id().split('-').next().unwrap_or("0"))
+    })
+}
+
+/// Ensure a `scheduled_jobs` row exists for every job and apply env interval overrides.
+pub async fn register_jobs(db: &PgPool) -> AppResult<()> {
+    for job in JOBS {
+        let env_key = format!("JOB_{}_INTERVAL_SECS", job.name.to_uppercase());
+        let override_secs = std::env::var(&env_key).ok().and_then(|v| v.parse::<i32>().ok());
+
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_jobs (name, description, interval_seconds, enabled, next_run_at)
+            VALUES ($1, $2, $3, true, NOW())
+            ON CONFLICT (name) DO UPDATE
+            SET description = EXCLUDED.description,
+                interval_seconds = COALESCE($4, scheduled_jobs.interval_seconds),
+                updated_at = NOW()
+            "#,
+        )
+        .bind(job.name)
+        .bind(job.description)
+        .bind(override_secs.unwrap_or(job.default_interval_secs))
+        .bind(override_secs)
+        .execute(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to register job '{}': {}", job.name, e)))?;
+    }
+
+    Ok(())
+}
+
+/// Spawn the scheduler loop. Each tick looks for due jobs and runs each one in
+/// its own task, so a slow job does not delay the others.
+pub fn start(db: Arc<PgPool>, tick_secs: u64) {
+    tokio::spawn(async move {
+        tracing::info!("✓ Scheduler started (instance {}, tick {}s)", instance_id(), tick_secs);
+        let mut ticker = tokio::time::interval(Duration::from_secs(tick_secs.max(1)));
+
+        loop {
+            ticker.tick().await;
+
+            let due: Vec<String> = match sqlx::query_scalar(
+                r#"
+                SELECT name FROM scheduled_jobs
+                WHERE enabled = true
+                  AND next_run_at <= NOW()
+                  AND (locked_until IS NULL OR locked_until < NOW())
+                "#,
+            )
+            .fetch_all(db.as_ref())
+            .await
+            {
+                Ok(names) => names,
+                Err(e) => {
+                    tracing::warn!("Scheduler failed to poll due jobs: {}", e);
+                    continue;
+                }
+            };
+
+            for name in due {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    match run_job(db.as_ref(), &name, "schedule").await {
+                        // Another instance won the lock between the poll and the claim
+                        Err(AppError::Conflict(_)) => {}
+                        Err(e) => tracing::error!("Scheduled job '{}' could not start: {}", name, e),
+                        Ok(_) => {}
+                    }
+                });
+            }
+        }
+    });
+}
+
+/// Claim the job's lock, execute it, and record the run.
+/// `trigger` is 'schedule' (only runs when due) or 'manual' (runs immediately).
+pub async fn run_job(db: &PgPool, name: &str, trigger: &str) -> AppResult<ScheduledJobRun> {
+    let instance = instance_id();
+
+    let claimed: Option<String> = sqlx::query_scalar(
+        r#"
+        UPDATE scheduled_jobs
+        SET locked_by = $2,
+            locked_until = NOW() + $3 * INTERVAL '1 second',
+            last_started_at = NOW(),
+            updated_at = NOW()
+        WHERE name = $1
+          AND (locked_until IS NULL OR locked_until < NOW())
+          AND ($4 = 'manual' OR (enabled = true AND next_run_at <= NOW()))
+        RETURNING name
+        "#,
+    )
+    .bind(name)
+    .bind(instance)
+    .bind(LOCK_LEASE_SECS as f64)
+    .bind(trigger)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to claim job lock: {}", e)))?;
+
+    if claimed.is_none() {
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM scheduled_jobs WHERE name = $1)",
+        )
+        .bind(name)
+        .fetch_one(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+        return Err(if exists {
+            AppError::Conflict(format!("Job '{}' is already running or not due", name))
+        } else {
+            AppError::NotFound(format!("Job '{}' not found", name))
+        });
+    }
+
+    // Holding the lock means any run still marked 'running' was abandoned by a dead instance
+    sqlx::query(
+        r#"
+        UPDATE scheduled_job_runs
+        SET status = 'failed', error = 'Abandoned: lock lease expired', finished_at = NOW()
+        WHERE job_name = $1 AND status = 'running'
+        "#,
+    )
+    .bind(name)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?;
+
+    let run_id = utils::generate_id();
+    sqlx::query(
+        "INSERT INTO scheduled_job_runs (id, job_name, trigger, instance_id, status) VALUES ($1, $2, $3, $4, 'running')",
+    )
+    .bind(&run_id)
+    .bind(name)
+    .bind(trigger)
+    .bind(instance)
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record job run: {}", e)))?;
+
+    let started = Instant::now();
+    let outcome = execute(db, name).await;
+    let duration_ms = started.elapsed().as_millis() as i64;
+
+    let (status, result, error) = match &outcome {
+        Ok(value) => ("succeeded", Some(value.to_string()), None),
+        Err(e) => ("failed", None, Some(e.to_string())),
+    };
+
+    let run = sqlx::query_as::<_, ScheduledJobRun>(
+        r#"
+        UPDATE scheduled_job_runs
+        SET status = $2, result = $3, error = $4, finished_at = NOW(), duration_ms = $5
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(&run_id)
+    .bind(status)
+    .bind(result.as_deref())
+    .bind(error.as_deref())
+    .bind(duration_ms)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to record job result: {}", e)))?;
+
+    sqlx::query(
+        r#"
+        UPDATE scheduled_jobs
+        SET last_finished_at = NOW(),
+            last_status = $3,
+            last_result = COALESCE($4, last_result),
+            last_error = $5,
+            consecutive_failures = CASE WHEN $3 = 'failed' THEN consecutive_failures + 1 ELSE 0 END,
+            next_run_at = NOW() + interval_seconds * INTERVAL '1 second',
+            locked_by = NULL,
+            locked_until = NULL,
+            updated_at = NOW()
+        WHERE name = $1 AND locked_by = $2
+        "#,
+    )
+    .bind(name)
+    .bind(instance)
+    .bind(status)
+    .bind(result.as_deref())
+    .bind(error.as_deref())
+    .execute(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(format!("Failed to release job lock: {}", e)))?;
+
+    match &outcome {
+        Ok(_) => tracing::info!("Job '{}' ({}) succeeded in {}ms", name, trigger, duration_ms),
+        Err(e) => tracing::error!("Job '{}' ({}) failed after {}ms: {}", name, trigger, duration_ms, e),
+    }
+
+    Ok(run)
+}
+
+async fn execute(db: &PgPool, name: &str) -> AppResult<serde_json::Value> {
+    match name {
+        "no_show_sweep" => handlers::guard_replacement::run_no_show_detection(db).await,
+        "replacement_offers" => handlers::replacement_offers::process_replacement_offers(db).await,
+        "permit_expiry" => {
+            let expired = handlers::permits::expire_permits(db).await?;
+            Ok(json!({ "expiredCount": expired }))
+        }
+        "training_expiry" => {
+            let expired = handlers::training::expire_training_records(db).await?;
+            Ok(json!({ "expiredCount": expired }))
+        }
+        "training_renewals" => handlers::training_catalog::process_training_renewals(db).await,
+        "merit_recalculation" => handlers::merit_batch::recalculate_all_merit_scores(db).await,
+        "overdue_allocations" => handlers::firearm_allocation::notify_overdue_allocations(db).await,
+        "notification_delivery" => handlers::notification_dispatch::process_notification_deliveries(db).await,
+        "notification_archival" => handlers::notification_templates::archive_expired_notifications(db).await,
+        "telemetry_partitions" => handlers::telemetry::prepare_partitions(db).await,
+        "trip_monitoring" => handlers::trip_monitoring::monitor_active_trips(db).await,
+        "sos_escalation" => handlers::sos::escalate_due_sos(db).await,
+        "vehicle_service_planning" => handlers::vehicle_maintenance::plan_fleet_maintenance(db).await,
+        "vehicle_document_expiry" => handlers::vehicle_compliance::notify_document_expiry(db).await,
+        _ => Err(AppError::NotFound(format!("No handler registered for job '{}'", name))),
+    }
+}
@@ -1,5 +1,6 @@
 use crate::error::{AppError, AppResult};
 use regex::Regex;
+use sqlx::PgPool;
 
 pub fn generate_confirmation_code() -> String {
     use rand::Rng;
@@ -98,6 +99,36 @@ pub async fn send_confirmation_email(
     }
 }
 
+/// Escape text for an HTML document.
+pub fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 pub fn generate_id() -> String {
     uuid::Uuid::new_v4().to_string()
 }
+
+/// Whether a user is a supervisor (admin or superadmin). 404 for an unknown user.
+pub async fn is_supervisor(db: &PgPool, user_id: &str) -> AppResult<bool> {
+    let role = sqlx::query_scalar::<_, String>("SELECT role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Database error: {}", e)))?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    Ok(matches!(role.as_str(), "admin" | "superadmin"))
+}
+
+/// Reject a user who is not a supervisor with "Only supervisors can {action}".
+pub async fn ensure_supervisor(db: &PgPool, user_id: &str, action: &str) -> AppResult<()> {
+    if !is_supervisor(db, user_id).await? {
+        return Err(AppError::Forbidden(format!("Only supervisors can {}", action)));
+    }
+    Ok(())
+}